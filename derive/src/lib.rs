@@ -0,0 +1,413 @@
+//! Derive macros for `polywrap_msgpack_serde`.
+
+use proc_macro::TokenStream;
+use proc_macro2::Literal;
+use quote::{quote, ToTokens};
+use syn::{
+    parse_macro_input, Attribute, Data, DeriveInput, Fields, GenericArgument,
+    LitStr, PathArguments, Result, Type,
+};
+
+/// Derives `EncodedFields`, precomputing the msgpack encoding of every
+/// serialized field name of a struct.
+///
+/// Honors `#[serde(rename = "...")]`, `#[serde(rename_all = "...")]` and
+/// `#[serde(skip)]`/`#[serde(skip_serializing)]` so the table matches the
+/// keys serde passes to the serializer.
+#[proc_macro_derive(EncodedFields, attributes(serde))]
+pub fn derive_encoded_fields(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn expand(input: DeriveInput) -> Result<proc_macro2::TokenStream> {
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    &input.ident,
+                    "EncodedFields requires a struct with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &input.ident,
+                "EncodedFields can only be derived for structs",
+            ))
+        }
+    };
+
+    let container = SerdeAttrs::parse(&input.attrs)?;
+    let struct_name =
+        container.rename.unwrap_or_else(|| input.ident.to_string());
+
+    let mut entries = vec![];
+    for field in fields {
+        let attrs = SerdeAttrs::parse(&field.attrs)?;
+        if attrs.skip {
+            continue;
+        }
+        let ident = field.ident.as_ref().unwrap().to_string();
+        let ident = ident.strip_prefix("r#").unwrap_or(&ident);
+        let name = match attrs.rename {
+            Some(name) => name,
+            None => rename(ident, container.rename_all.as_deref())?,
+        };
+        let encoded = Literal::byte_string(&encode_str(&name));
+        entries.push(quote! { (#name, #encoded) });
+    }
+
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) =
+        input.generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics ::polywrap_msgpack_serde::EncodedFields
+            for #ident #ty_generics #where_clause
+        {
+            const NAME: &'static str = #struct_name;
+            const ENCODED_FIELDS: &'static [(&'static str, &'static [u8])] =
+                &[#(#entries),*];
+        }
+    })
+}
+
+/// Derives `Fingerprint`, a hash of the type's name and of its fields' (or
+/// variants') names, types and order.
+///
+/// The fingerprint changes whenever the wire schema of the type may have
+/// changed, e.g. a field is renamed, retyped, added, removed or moved.
+/// Field types are compared by the last segment of their paths, so
+/// `std::string::String` and `String` are the same type, though a type
+/// alias isn't the same as the type it names. Serialized names and skipped
+/// fields follow the serde attributes, and the attributes that otherwise
+/// change the wire shape (`flatten`, `with`, `tag`, `untagged`, ...) are
+/// part of the fingerprint too.
+#[proc_macro_derive(Fingerprint, attributes(serde))]
+pub fn derive_fingerprint(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_fingerprint(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn expand_fingerprint(input: DeriveInput) -> Result<proc_macro2::TokenStream> {
+    let container = SerdeAttrs::parse(&input.attrs)?;
+    let name = container
+        .rename
+        .clone()
+        .unwrap_or_else(|| input.ident.to_string());
+
+    let mut schema = match &input.data {
+        Data::Struct(data) => {
+            format!("struct {name}{}", fields_schema(&data.fields, &container)?)
+        }
+        Data::Enum(data) => {
+            let mut variants = vec![];
+            for variant in &data.variants {
+                let attrs = SerdeAttrs::parse(&variant.attrs)?;
+                if attrs.skip {
+                    continue;
+                }
+                let ident = variant.ident.to_string();
+                let variant_name = match &attrs.rename {
+                    Some(name) => name.clone(),
+                    None => {
+                        rename_variant(&ident, container.rename_all.as_deref())?
+                    }
+                };
+                // A variant's own `rename_all` applies to its fields
+                let fields = fields_schema(&variant.fields, &attrs)?;
+                variants.push(format!("{variant_name}{fields}"));
+            }
+            format!("enum {name}{{{}}}", variants.join("|"))
+        }
+        Data::Union(_) => {
+            return Err(syn::Error::new_spanned(
+                &input.ident,
+                "Fingerprint can't be derived for unions",
+            ))
+        }
+    };
+    for (attr, value) in
+        [("tag", &container.tag), ("content", &container.content)]
+    {
+        if let Some(value) = value {
+            schema.push_str(&format!(";{attr}={value}"));
+        }
+    }
+    for (attr, set) in [
+        ("untagged", container.untagged),
+        ("transparent", container.transparent),
+    ] {
+        if set {
+            schema.push_str(&format!(";{attr}"));
+        }
+    }
+    let fingerprint = fnv1a(schema.as_bytes());
+
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) =
+        input.generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics ::polywrap_msgpack_serde::Fingerprint
+            for #ident #ty_generics #where_clause
+        {
+            const FINGERPRINT: u64 = #fingerprint;
+        }
+    })
+}
+
+/// Canonical description of a struct's or variant's fields, e.g.
+/// `{a:u8,b:String}` or `(u8,String)`.
+fn fields_schema(fields: &Fields, container: &SerdeAttrs) -> Result<String> {
+    let mut described = vec![];
+    for field in fields {
+        let attrs = SerdeAttrs::parse(&field.attrs)?;
+        if attrs.skip {
+            continue;
+        }
+        let mut ty = type_schema(&field.ty);
+        if let Some(with) = &attrs.with {
+            ty = format!("{ty} with {with}");
+        }
+        match &field.ident {
+            Some(_) if attrs.flatten => described.push(format!("..{ty}")),
+            Some(ident) => {
+                let ident = ident.to_string();
+                let ident = ident.strip_prefix("r#").unwrap_or(&ident);
+                let name = match attrs.rename {
+                    Some(name) => name,
+                    None => rename(ident, container.rename_all.as_deref())?,
+                };
+                described.push(format!("{name}:{ty}"));
+            }
+            None => described.push(ty),
+        }
+    }
+
+    Ok(match fields {
+        Fields::Named(_) => format!("{{{}}}", described.join(",")),
+        Fields::Unnamed(_) => format!("({})", described.join(",")),
+        Fields::Unit => String::new(),
+    })
+}
+
+/// Canonical description of a type, independent of how its tokens are
+/// spaced and of the paths it's named through: `Vec<u8>` for
+/// `alloc::vec::Vec < u8 >`. References and boxes are left out, as serde
+/// writes them as the value they point to.
+fn type_schema(ty: &Type) -> String {
+    match ty {
+        Type::Path(path) => {
+            let Some(segment) = path.path.segments.last() else {
+                return String::new();
+            };
+            let args = match &segment.arguments {
+                PathArguments::AngleBracketed(args) => args
+                    .args
+                    .iter()
+                    .map(|arg| match arg {
+                        GenericArgument::Type(ty) => type_schema(ty),
+                        other => tokens_schema(other),
+                    })
+                    .collect(),
+                _ => vec![],
+            };
+            match (segment.ident.to_string().as_str(), &args[..]) {
+                ("Box", [inner]) => inner.clone(),
+                (ident, []) => ident.to_string(),
+                (ident, args) => format!("{ident}<{}>", args.join(",")),
+            }
+        }
+        Type::Reference(reference) => type_schema(&reference.elem),
+        Type::Paren(paren) => type_schema(&paren.elem),
+        Type::Group(group) => type_schema(&group.elem),
+        Type::Slice(slice) => format!("[{}]", type_schema(&slice.elem)),
+        Type::Array(array) => format!(
+            "[{};{}]",
+            type_schema(&array.elem),
+            tokens_schema(&array.len)
+        ),
+        Type::Tuple(tuple) => {
+            let elems: Vec<_> = tuple.elems.iter().map(type_schema).collect();
+            format!("({})", elems.join(","))
+        }
+        other => tokens_schema(other),
+    }
+}
+
+/// Tokens with their spacing removed, for what has no canonical form.
+fn tokens_schema(tokens: &impl ToTokens) -> String {
+    let mut schema = tokens.to_token_stream().to_string();
+    schema.retain(|c| !c.is_whitespace());
+    schema
+}
+
+/// 64-bit FNV-1a, chosen for being trivial to reproduce outside Rust.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+#[derive(Default)]
+struct SerdeAttrs {
+    rename: Option<String>,
+    rename_all: Option<String>,
+    skip: bool,
+    flatten: bool,
+    /// The `with` or `serialize_with` path, normalized.
+    with: Option<String>,
+    tag: Option<String>,
+    content: Option<String>,
+    untagged: bool,
+    transparent: bool,
+}
+
+impl SerdeAttrs {
+    fn parse(attrs: &[Attribute]) -> Result<Self> {
+        let mut parsed = SerdeAttrs::default();
+        for attr in attrs.iter().filter(|attr| attr.path().is_ident("serde")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("rename") {
+                    // `rename(serialize = "...", deserialize = "...")` only
+                    // matters here for its `serialize` half.
+                    if meta.input.peek(syn::token::Paren) {
+                        meta.parse_nested_meta(|inner| {
+                            let value: LitStr = inner.value()?.parse()?;
+                            if inner.path.is_ident("serialize") {
+                                parsed.rename = Some(value.value());
+                            }
+                            Ok(())
+                        })?;
+                    } else {
+                        let value: LitStr = meta.value()?.parse()?;
+                        parsed.rename = Some(value.value());
+                    }
+                } else if meta.path.is_ident("rename_all") {
+                    let value: LitStr = meta.value()?.parse()?;
+                    parsed.rename_all = Some(value.value());
+                } else if meta.path.is_ident("skip")
+                    || meta.path.is_ident("skip_serializing")
+                {
+                    parsed.skip = true;
+                } else if meta.path.is_ident("flatten") {
+                    parsed.flatten = true;
+                } else if meta.path.is_ident("untagged") {
+                    parsed.untagged = true;
+                } else if meta.path.is_ident("transparent") {
+                    parsed.transparent = true;
+                } else if meta.path.is_ident("with")
+                    || meta.path.is_ident("serialize_with")
+                {
+                    let value: LitStr = meta.value()?.parse()?;
+                    let path: syn::Path = value.parse()?;
+                    parsed.with = Some(tokens_schema(&path));
+                } else if meta.path.is_ident("tag") {
+                    let value: LitStr = meta.value()?.parse()?;
+                    parsed.tag = Some(value.value());
+                } else if meta.path.is_ident("content") {
+                    let value: LitStr = meta.value()?.parse()?;
+                    parsed.content = Some(value.value());
+                } else if meta.input.peek(syn::Token![=]) {
+                    // Other serde attributes don't affect the wire shape.
+                    meta.value()?.parse::<syn::Expr>()?;
+                } else if meta.input.peek(syn::token::Paren) {
+                    let _content;
+                    syn::parenthesized!(_content in meta.input);
+                }
+                Ok(())
+            })?;
+        }
+        Ok(parsed)
+    }
+}
+
+/// Applies a serde `rename_all` rule to a snake_case field name.
+fn rename(field: &str, rule: Option<&str>) -> Result<String> {
+    let words = field.split('_').filter(|word| !word.is_empty());
+    let capitalize = |word: &str| {
+        let mut chars = word.chars();
+        match chars.next() {
+            Some(first) => first.to_uppercase().chain(chars).collect(),
+            None => String::new(),
+        }
+    };
+
+    Ok(match rule {
+        None => field.to_string(),
+        Some("lowercase") | Some("snake_case") => field.to_lowercase(),
+        Some("UPPERCASE") | Some("SCREAMING_SNAKE_CASE") => {
+            field.to_uppercase()
+        }
+        Some("kebab-case") => field.replace('_', "-"),
+        Some("SCREAMING-KEBAB-CASE") => field.replace('_', "-").to_uppercase(),
+        Some("PascalCase") => words.map(capitalize).collect(),
+        Some("camelCase") => {
+            let pascal: String = words.map(capitalize).collect();
+            let mut chars = pascal.chars();
+            match chars.next() {
+                Some(first) => first.to_lowercase().chain(chars).collect(),
+                None => String::new(),
+            }
+        }
+        Some(other) => {
+            return Err(syn::Error::new(
+                proc_macro2::Span::call_site(),
+                format!("unknown rename_all rule '{other}'"),
+            ))
+        }
+    })
+}
+
+/// Applies a serde `rename_all` rule to a PascalCase variant name.
+fn rename_variant(variant: &str, rule: Option<&str>) -> Result<String> {
+    Ok(match rule {
+        None | Some("PascalCase") => variant.to_string(),
+        Some("lowercase") => variant.to_ascii_lowercase(),
+        Some("UPPERCASE") => variant.to_ascii_uppercase(),
+        Some("camelCase") => {
+            let mut chars = variant.chars();
+            match chars.next() {
+                Some(first) => first.to_lowercase().chain(chars).collect(),
+                None => String::new(),
+            }
+        }
+        Some(rule) => {
+            let mut snake = String::new();
+            for (i, c) in variant.char_indices() {
+                if i > 0 && c.is_uppercase() {
+                    snake.push('_');
+                }
+                snake.extend(c.to_lowercase());
+            }
+            rename(&snake, Some(rule))?
+        }
+    })
+}
+
+/// Encodes a string the way the serializer does: the shortest str header
+/// followed by its UTF-8 bytes.
+fn encode_str(value: &str) -> Vec<u8> {
+    let len = value.len();
+    let mut encoded = Vec::with_capacity(len + 5);
+    if len < 32 {
+        encoded.push(0xa0 | len as u8);
+    } else if len <= u8::MAX as usize {
+        encoded.extend([0xd9, len as u8]);
+    } else if len <= u16::MAX as usize {
+        encoded.push(0xda);
+        encoded.extend((len as u16).to_be_bytes());
+    } else {
+        encoded.push(0xdb);
+        encoded.extend((len as u32).to_be_bytes());
+    }
+    encoded.extend(value.as_bytes());
+    encoded
+}
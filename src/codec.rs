@@ -0,0 +1,203 @@
+//! A [`tokio_util::codec`] codec for msgpack messages framed by their
+//! length, so that a `Framed` socket can send and receive values directly.
+//!
+//! Each frame is a 4-byte big-endian length followed by that many bytes
+//! holding one value.
+
+use core::marker::PhantomData;
+
+use bytes::{Buf, BufMut, BytesMut};
+use serde::{de::DeserializeOwned, Serialize};
+use tokio_util::codec::{Decoder, Encoder};
+
+use crate::{
+    error::{DecodeError, DecodeResult, EncodeError, EncodeResult},
+    from_slice_with_config, to_vec_with_config, DeserializerConfig,
+    SerializerConfig,
+};
+
+/// Length of the prefix holding the length of each frame.
+const LEN_PREFIX: usize = 4;
+
+/// Encodes any serializable value into a frame and decodes frames into
+/// values of type `T`.
+#[derive(Debug)]
+pub struct MsgPackCodec<T> {
+    serializer_config: SerializerConfig,
+    deserializer_config: DeserializerConfig,
+    max_frame_len: usize,
+    item: PhantomData<fn() -> T>,
+}
+
+impl<T> Clone for MsgPackCodec<T> {
+    fn clone(&self) -> Self {
+        Self {
+            serializer_config: self.serializer_config.clone(),
+            deserializer_config: self.deserializer_config.clone(),
+            max_frame_len: self.max_frame_len,
+            item: PhantomData,
+        }
+    }
+}
+
+impl<T> Default for MsgPackCodec<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> MsgPackCodec<T> {
+    pub fn new() -> Self {
+        Self {
+            serializer_config: SerializerConfig::default(),
+            deserializer_config: DeserializerConfig::default(),
+            max_frame_len: 8 * 1024 * 1024,
+            item: PhantomData,
+        }
+    }
+
+    /// Fail on frames longer than `len` bytes, not counting the length
+    /// prefix, whether sent or received. 8 MiB by default.
+    pub fn max_frame_len(mut self, len: usize) -> Self {
+        self.max_frame_len = len;
+        self
+    }
+
+    pub fn serializer_config(mut self, config: SerializerConfig) -> Self {
+        self.serializer_config = config;
+        self
+    }
+
+    pub fn deserializer_config(mut self, config: DeserializerConfig) -> Self {
+        self.deserializer_config = config;
+        self
+    }
+
+    /// Describes why a frame of `len` bytes can't be sent or received.
+    fn frame_len_error(&self, len: usize) -> Option<String> {
+        (len > self.max_frame_len).then(|| {
+            format!(
+                "Frame of {len} bytes exceeds the maximum length of {}",
+                self.max_frame_len
+            )
+        })
+    }
+}
+
+impl<T: DeserializeOwned> Decoder for MsgPackCodec<T> {
+    type Item = T;
+    type Error = DecodeError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> DecodeResult<Option<T>> {
+        let Some(prefix) = src.get(..LEN_PREFIX) else {
+            return Ok(None);
+        };
+        let len = u32::from_be_bytes(prefix.try_into().unwrap()) as usize;
+        if let Some(message) = self.frame_len_error(len) {
+            return Err(DecodeError::Message(message));
+        }
+        if src.len() < LEN_PREFIX + len {
+            src.reserve(LEN_PREFIX + len - src.len());
+            return Ok(None);
+        }
+
+        src.advance(LEN_PREFIX);
+        let frame = src.split_to(len);
+        let value =
+            from_slice_with_config(&frame, self.deserializer_config.clone())?;
+        Ok(Some(value))
+    }
+}
+
+impl<T, U: Serialize> Encoder<U> for MsgPackCodec<T> {
+    type Error = EncodeError;
+
+    fn encode(&mut self, item: U, dst: &mut BytesMut) -> EncodeResult<()> {
+        let bytes = to_vec_with_config(&item, self.serializer_config.clone())?;
+        if let Some(message) = self.frame_len_error(bytes.len()) {
+            return Err(EncodeError::Message(message));
+        }
+        let len = u32::try_from(bytes.len()).map_err(|_| {
+            EncodeError::Message(format!(
+                "Frame of {} bytes doesn't fit a 4-byte length",
+                bytes.len()
+            ))
+        })?;
+
+        dst.reserve(LEN_PREFIX + bytes.len());
+        dst.put_u32(len);
+        dst.extend_from_slice(&bytes);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::BytesMut;
+    use serde_derive::{Deserialize, Serialize};
+    use tokio_util::codec::{Decoder, Encoder};
+
+    use super::MsgPackCodec;
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct Invocation {
+        method: String,
+        args: Vec<u8>,
+    }
+
+    #[test]
+    fn test_codec_round_trips_frames() {
+        let invocations = [
+            Invocation {
+                method: "run".to_string(),
+                args: vec![1, 2, 3],
+            },
+            Invocation {
+                method: "stop".to_string(),
+                args: vec![],
+            },
+        ];
+        let mut codec = MsgPackCodec::<Invocation>::new();
+        let mut frames = BytesMut::new();
+        for invocation in &invocations {
+            codec.encode(invocation, &mut frames).unwrap();
+        }
+        let first_len =
+            u32::from_be_bytes(frames[..4].try_into().unwrap()) as usize;
+        assert_eq!(
+            crate::to_vec(&invocations[0]).unwrap(),
+            &frames[4..4 + first_len]
+        );
+
+        // Frames are decoded only once they've fully arrived
+        let mut src = BytesMut::new();
+        let mut decoded = Vec::new();
+        for byte in frames {
+            src.extend_from_slice(&[byte]);
+            if let Some(invocation) = codec.decode(&mut src).unwrap() {
+                decoded.push(invocation);
+            }
+        }
+        assert_eq!(&invocations[..], &decoded[..]);
+        assert!(src.is_empty());
+        assert_eq!(None, codec.decode_eof(&mut src).unwrap());
+    }
+
+    #[test]
+    fn test_codec_limits_frame_len() {
+        let mut codec = MsgPackCodec::<String>::new().max_frame_len(8);
+        let mut dst = BytesMut::new();
+        assert!(codec.encode("too long a string", &mut dst).is_err());
+        assert!(dst.is_empty());
+
+        // The length is checked before the frame's bytes arrive
+        let mut src = BytesMut::from(&[0, 0, 0, 9][..]);
+        let err = codec.decode(&mut src).unwrap_err();
+        assert!(err.to_string().contains("maximum length of 8"), "{err}");
+
+        // A frame that isn't valid msgpack fails without stalling the stream
+        let mut src = BytesMut::from(&[0, 0, 0, 1, 0xc1, 0, 0, 0, 1, 0xa0][..]);
+        assert!(codec.decode(&mut src).is_err());
+        assert_eq!(Some(String::new()), codec.decode(&mut src).unwrap());
+    }
+}
@@ -0,0 +1,188 @@
+//! Golden-vector conformance checks shared with the other Polywrap msgpack
+//! implementations.
+//!
+//! A vector is a name, the encoded bytes and a JSON description of the
+//! decoded value, using the rendering of `From<Value> for
+//! serde_json::Value`: bytes as arrays of numbers and exts as
+//! `[type, [data...]]`. On disk a vector is a `<name>.msgpack` file next to
+//! a `<name>.json` file.
+//!
+//! ```ignore
+//! for vector in conformance::load_dir("vectors")? {
+//!     conformance::assert_decodes(&vector);
+//! }
+//! ```
+
+use std::{ffi::OsStr, fs, io, path::Path};
+
+use serde_json::Value as Json;
+
+use crate::{
+    from_slice, inspect::inspect, to_vec_with_config, Error, SerializerConfig,
+    Value,
+};
+
+/// One golden vector.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Vector {
+    pub name: String,
+    pub bytes: Vec<u8>,
+    pub description: Json,
+}
+
+impl Vector {
+    pub fn new(
+        name: impl Into<String>,
+        bytes: Vec<u8>,
+        description: Json,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            bytes,
+            description,
+        }
+    }
+}
+
+/// Loads every `<name>.msgpack` and `<name>.json` pair in `dir`, sorted by
+/// name. A `.msgpack` file without its `.json` description is an error.
+pub fn load_dir(dir: impl AsRef<Path>) -> io::Result<Vec<Vector>> {
+    let mut vectors = vec![];
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension() != Some(OsStr::new("msgpack")) {
+            continue;
+        }
+        let name = path
+            .file_stem()
+            .and_then(OsStr::to_str)
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("vector file name {path:?} isn't UTF-8"),
+                )
+            })?
+            .to_string();
+        let bytes = fs::read(&path)?;
+        let description = fs::read(path.with_extension("json"))?;
+        let description =
+            serde_json::from_slice(&description).map_err(|e| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid description of vector `{name}`: {e}"),
+                )
+            })?;
+        vectors.push(Vector::new(name, bytes, description));
+    }
+    vectors.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(vectors)
+}
+
+/// Asserts that the vector's bytes decode to its description.
+pub fn assert_decodes(vector: &Vector) {
+    let value: Value = match from_slice(&vector.bytes) {
+        Ok(value) => value,
+        Err(e) => panic!(
+            "vector `{}` failed to decode: {e}\n{}",
+            vector.name,
+            inspect(&vector.bytes)
+        ),
+    };
+    let decoded = Json::from(value);
+    assert!(
+        decoded == vector.description,
+        "vector `{}` decoded to\n{decoded}\nexpected\n{}\n{}",
+        vector.name,
+        vector.description,
+        inspect(&vector.bytes)
+    );
+}
+
+/// Asserts that encoding the vector's description with `config` produces
+/// exactly its bytes.
+///
+/// Only meaningful for vectors whose description determines the encoding:
+/// JSON can't tell bins and exts from arrays, nor a GenericMap from a plain
+/// map, so those follow `config`.
+pub fn assert_encodes(vector: &Vector, config: SerializerConfig) {
+    let encoded = Value::try_from(vector.description.clone())
+        .map_err(Error::from)
+        .and_then(|value| Ok(to_vec_with_config(&value, config)?));
+    let encoded = match encoded {
+        Ok(encoded) => encoded,
+        Err(e) => panic!("vector `{}` failed to encode: {e}", vector.name),
+    };
+    assert!(
+        encoded == vector.bytes,
+        "vector `{}` encoded to\n{}\nexpected\n{}",
+        vector.name,
+        inspect(&encoded),
+        inspect(&vector.bytes)
+    );
+}
+
+/// Asserts both directions for every vector in `dir`.
+pub fn assert_dir(dir: impl AsRef<Path>, config: SerializerConfig) {
+    let dir = dir.as_ref();
+    let vectors = load_dir(dir)
+        .unwrap_or_else(|e| panic!("failed to load vectors from {dir:?}: {e}"));
+    for vector in &vectors {
+        assert_decodes(vector);
+        assert_encodes(vector, config.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use serde_json::json;
+
+    use super::*;
+    use crate::MapEncoding;
+
+    #[test]
+    fn test_conformance_vectors() {
+        let dir = std::env::temp_dir()
+            .join(format!("polywrap-msgpack-vectors-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let vectors = [
+            ("int", vec![0xcc, 0xc8], json!(200)),
+            (
+                "map",
+                vec![0x81, 0xa1, b'a', 0x92, 0xc0, 0xc3],
+                json!({"a": [null, true]}),
+            ),
+        ];
+        for (name, bytes, description) in &vectors {
+            fs::write(dir.join(format!("{name}.msgpack")), bytes).unwrap();
+            fs::write(
+                dir.join(format!("{name}.json")),
+                description.to_string(),
+            )
+            .unwrap();
+        }
+        fs::write(dir.join("README.md"), "not a vector").unwrap();
+
+        let loaded = load_dir(&dir).unwrap();
+        assert_eq!(
+            vec!["int", "map"],
+            loaded.iter().map(|v| v.name.as_str()).collect::<Vec<_>>()
+        );
+        let plain =
+            SerializerConfig::default().map_encoding(MapEncoding::Plain);
+        assert_dir(&dir, plain);
+
+        // The default configuration wraps the map in the ext
+        let map = &loaded[1];
+        assert_decodes(map);
+        let result = std::panic::catch_unwind(|| {
+            assert_encodes(map, SerializerConfig::default())
+        });
+        assert!(result.is_err());
+
+        fs::remove_file(dir.join("int.json")).unwrap();
+        assert!(load_dir(&dir).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
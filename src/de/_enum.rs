@@ -1,16 +1,26 @@
-use serde::de::{DeserializeSeed, Visitor, EnumAccess, VariantAccess};
+use serde::de::{self, DeserializeSeed, Visitor, EnumAccess, VariantAccess};
 
-use crate::{Deserializer, error::{Result, Error}};
+use crate::{Deserializer, error::{DecodeResult, DecodeError}};
 
-pub struct Enum<'a> {
-  de: &'a mut Deserializer,
+use super::read::Read;
+
+/// Reads a variant written as a single-entry map from its identifier to
+/// its contents.
+pub struct Enum<'a, R> {
+  de: &'a mut Deserializer<R>,
+}
+
+impl<'a, R> Enum<'a, R> {
+  pub fn new(de: &'a mut Deserializer<R>) -> Self {
+    Self { de }
+  }
 }
 
-impl<'de, 'a> EnumAccess<'de> for Enum<'a> {
-  type Error = Error;
+impl<'de, 'a, R: Read<'de>> EnumAccess<'de> for Enum<'a, R> {
+  type Error = DecodeError;
   type Variant = Self;
 
-  fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self::Variant)>
+  fn variant_seed<V>(self, seed: V) -> DecodeResult<(V::Value, Self::Variant)>
   where
       V: DeserializeSeed<'de>,
   {
@@ -19,35 +29,35 @@ impl<'de, 'a> EnumAccess<'de> for Enum<'a> {
   }
 }
 
-impl<'de, 'a> VariantAccess<'de> for Enum<'a> {
-  type Error = Error;
+impl<'de, 'a, R: Read<'de>> VariantAccess<'de> for Enum<'a, R> {
+  type Error = DecodeError;
 
-  fn unit_variant(self) -> Result<()> {
-      Ok(())
+  fn unit_variant(self) -> DecodeResult<()> {
+    de::Deserialize::deserialize(self.de)
   }
 
-  fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value>
+  fn newtype_variant_seed<T>(self, seed: T) -> DecodeResult<T::Value>
   where
       T: DeserializeSeed<'de>,
   {
     seed.deserialize(self.de)
   }
 
-  fn tuple_variant<V>(self, _len: usize, _: V) -> Result<V::Value>
+  fn tuple_variant<V>(self, len: usize, visitor: V) -> DecodeResult<V::Value>
   where
       V: Visitor<'de>,
   {
-    todo!()
+    de::Deserializer::deserialize_tuple(self.de, len, visitor)
   }
 
   fn struct_variant<V>(
       self,
-      _fields: &'static [&'static str],
-      _: V,
-  ) -> Result<V::Value>
+      fields: &'static [&'static str],
+      visitor: V,
+  ) -> DecodeResult<V::Value>
   where
       V: Visitor<'de>,
   {
-    todo!()
+    de::Deserializer::deserialize_struct(self.de, "", fields, visitor)
   }
 }
\ No newline at end of file
@@ -1,15 +1,17 @@
 use serde::de::{SeqAccess, DeserializeSeed};
 
-use crate::{Deserializer, error::{Result, Error}};
+use crate::{Deserializer, error::{DecodeResult, DecodeError}};
 
-pub struct ArrayReadAccess<'a> {
-  deserializer: &'a mut Deserializer,
+use super::read::Read;
+
+pub struct ArrayReadAccess<'a, R> {
+  deserializer: &'a mut Deserializer<R>,
   elements_in_arr: u32,
 }
 
-impl<'a> ArrayReadAccess<'a> {
+impl<'a, R> ArrayReadAccess<'a, R> {
   pub fn new(
-      deserializer: &'a mut Deserializer,
+      deserializer: &'a mut Deserializer<R>,
       elements_in_arr: u32,
   ) -> Self {
       Self {
@@ -19,10 +21,10 @@ impl<'a> ArrayReadAccess<'a> {
   }
 }
 
-impl<'a, 'de> SeqAccess<'de> for ArrayReadAccess<'a> {
-  type Error = Error;
+impl<'a, 'de, R: Read<'de>> SeqAccess<'de> for ArrayReadAccess<'a, R> {
+  type Error = DecodeError;
 
-  fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
+  fn next_element_seed<T>(&mut self, seed: T) -> DecodeResult<Option<T::Value>>
   where
       T: DeserializeSeed<'de>,
   {
@@ -33,4 +35,8 @@ impl<'a, 'de> SeqAccess<'de> for ArrayReadAccess<'a> {
       self.elements_in_arr -= 1;
       seed.deserialize(&mut *self.deserializer).map(Some)
   }
+
+  fn size_hint(&self) -> Option<usize> {
+      Some(self.elements_in_arr as usize)
+  }
 }
\ No newline at end of file
@@ -0,0 +1,99 @@
+use alloc::format;
+
+use serde::{
+    de::{DeserializeSeed, SeqAccess, Visitor},
+    forward_to_deserialize_any,
+};
+
+use crate::{
+    error::{get_error_message, DecodeError, DecodeResult},
+    format::Format,
+};
+
+/// Visits the payload of a bin or string as a sequence of bytes, so that it
+/// can be read in one go rather than byte by byte.
+///
+/// Elements can only be decoded as `u8`: anything else fails as reading an
+/// array from the bin or string would, so that e.g. a `Vec<u32>` isn't silently
+/// filled from a blob. An empty payload has no element to fail on, and so
+/// decodes into any sequence.
+pub(crate) struct ByteSeqAccess<I> {
+    bytes: I,
+    format: Format,
+}
+
+impl<I> ByteSeqAccess<I> {
+    pub fn new(bytes: I, format: Format) -> Self {
+        Self { bytes, format }
+    }
+}
+
+impl<'de, I: ExactSizeIterator<Item = u8>> SeqAccess<'de> for ByteSeqAccess<I> {
+    type Error = DecodeError;
+
+    fn next_element_seed<T>(
+        &mut self,
+        seed: T,
+    ) -> DecodeResult<Option<T::Value>>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        self.bytes
+            .next()
+            .map(|byte| {
+                seed.deserialize(ByteDeserializer {
+                    byte,
+                    format: self.format,
+                })
+            })
+            .transpose()
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.bytes.len())
+    }
+}
+
+/// One byte of a [`ByteSeqAccess`], which only deserializes as a `u8`.
+struct ByteDeserializer {
+    byte: u8,
+    format: Format,
+}
+
+impl<'de> serde::Deserializer<'de> for ByteDeserializer {
+    type Error = DecodeError;
+
+    fn deserialize_any<V>(self, _visitor: V) -> DecodeResult<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        Err(DecodeError::ExpectedArray(format!(
+            "Property must be of type 'array'. {}",
+            get_error_message(self.format)
+        )))
+    }
+
+    fn deserialize_u8<V>(self, visitor: V) -> DecodeResult<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_u8(self.byte)
+    }
+
+    fn deserialize_newtype_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> DecodeResult<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct seq tuple tuple_struct map
+        struct enum identifier ignored_any
+    }
+}
@@ -0,0 +1,247 @@
+use alloc::sync::Arc;
+use core::fmt;
+
+use crate::format::Format;
+
+/// How the [`Deserializer`] decodes a float where an integer is expected.
+///
+/// [`Deserializer`]: crate::Deserializer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FloatToInt {
+    /// Fail on any float.
+    #[default]
+    Reject,
+    /// Accept floats holding a whole number, such as `1.0`.
+    Exact,
+    /// Round floats to the nearest integer, halfway cases away from zero.
+    Round,
+}
+
+/// How the [`Deserializer`] decodes strings that aren't valid UTF-8.
+///
+/// Either way, such fields can be decoded as raw bytes instead: targets
+/// such as `serde_bytes::ByteBuf` and `Vec<u8>` accept strings as well as
+/// bins, whatever the policy.
+///
+/// [`Deserializer`]: crate::Deserializer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Utf8Policy {
+    /// Fail with [`DecodeError::InvalidUtf8`], which holds the offset of the
+    /// first invalid byte.
+    ///
+    /// [`DecodeError::InvalidUtf8`]: crate::DecodeError::InvalidUtf8
+    #[default]
+    Strict,
+    /// Replace invalid sequences with `U+FFFD REPLACEMENT CHARACTER`.
+    Lossy,
+}
+
+pub(crate) type UnknownFieldFn = dyn Fn(&str, &str, &[u8]) + Send + Sync;
+pub(crate) type FieldDecodedFn = dyn Fn(&str, Format, usize) + Send + Sync;
+
+/// A callback installed on a [`DeserializerConfig`].
+pub(crate) struct Observer<F: ?Sized>(pub(crate) Arc<F>);
+
+impl<F: ?Sized> Clone for Observer<F> {
+    fn clone(&self) -> Self {
+        Observer(self.0.clone())
+    }
+}
+
+impl<F: ?Sized> fmt::Debug for Observer<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Observer")
+    }
+}
+
+/// Options controlling how permissive the [`Deserializer`] is.
+///
+/// [`Deserializer`]: crate::Deserializer
+#[derive(Debug, Clone)]
+pub struct DeserializerConfig {
+    pub(crate) numeric_strings: bool,
+    pub(crate) nil_as_default: bool,
+    pub(crate) float_to_int: FloatToInt,
+    pub(crate) utf8: Utf8Policy,
+    pub(crate) int_to_float: bool,
+    pub(crate) intern_strings: bool,
+    pub(crate) deny_unknown_fields: bool,
+    pub(crate) integer_field_keys: bool,
+    pub(crate) scalar_keys_as_strings: bool,
+    pub(crate) allow_trailing_bytes: bool,
+    pub(crate) max_depth: Option<usize>,
+    pub(crate) max_collection_len: Option<u32>,
+    pub(crate) max_string_len: Option<u32>,
+    pub(crate) max_bytes_len: Option<u32>,
+    pub(crate) unknown_field_observer: Option<Observer<UnknownFieldFn>>,
+    pub(crate) field_decoded_observer: Option<Observer<FieldDecodedFn>>,
+}
+
+impl Default for DeserializerConfig {
+    fn default() -> Self {
+        Self {
+            numeric_strings: false,
+            nil_as_default: false,
+            float_to_int: FloatToInt::Reject,
+            utf8: Utf8Policy::Strict,
+            int_to_float: false,
+            intern_strings: false,
+            deny_unknown_fields: false,
+            integer_field_keys: false,
+            scalar_keys_as_strings: false,
+            allow_trailing_bytes: true,
+            max_depth: Some(128),
+            max_collection_len: None,
+            max_string_len: None,
+            max_bytes_len: None,
+            unknown_field_observer: None,
+            field_decoded_observer: None,
+        }
+    }
+}
+
+impl DeserializerConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Accept integers where a float is expected and whole-valued floats
+    /// where an integer is expected (see [`FloatToInt::Exact`]), as
+    /// encoders differ in how they write numbers that happen to be whole.
+    /// Float32 targets also accept float64s that convert without rounding.
+    pub fn lenient_numbers(mut self, enabled: bool) -> Self {
+        self.int_to_float = enabled;
+        self.float_to_int = if enabled {
+            FloatToInt::Exact
+        } else {
+            FloatToInt::Reject
+        };
+        self
+    }
+
+    /// Fail on struct fields that aren't declared, instead of skipping
+    /// them. The error names the offending key.
+    pub fn deny_unknown_fields(mut self, enabled: bool) -> Self {
+        self.deny_unknown_fields = enabled;
+        self
+    }
+
+    /// Accept struct fields keyed by their index in declaration order, as
+    /// written by compact producers, alongside fields keyed by name.
+    /// Otherwise integer keys are rejected, so that a map which merely has
+    /// integer keys isn't mistaken for a struct.
+    pub fn integer_field_keys(mut self, enabled: bool) -> Self {
+        self.integer_field_keys = enabled;
+        self
+    }
+
+    /// Read bool, integer and float map keys where a string key is expected
+    /// as their JSON text, e.g. `1` as `"1"`, so that GenericMaps keyed by
+    /// such values decode into `serde_json::Value` or `HashMap<String, _>`.
+    /// Otherwise they fail like any other mistyped key.
+    pub fn scalar_keys_as_strings(mut self, enabled: bool) -> Self {
+        self.scalar_keys_as_strings = enabled;
+        self
+    }
+
+    /// Sets whether [`from_slice_with_config`] accepts input that continues
+    /// after the decoded value. Allowed by default.
+    ///
+    /// [`from_slice_with_config`]: crate::from_slice_with_config
+    pub fn allow_trailing_bytes(mut self, allowed: bool) -> Self {
+        self.allow_trailing_bytes = allowed;
+        self
+    }
+
+    /// Fail on arrays, maps and exts nested deeper than `depth`, rather
+    /// than recursing as deep as the input asks. 128 by default, as in
+    /// `serde_json`; `usize::MAX` lifts the limit.
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = Some(depth);
+        self
+    }
+
+    /// Fail on arrays and maps declaring more than `len` elements (entries
+    /// for maps), before any of them is decoded. Unlimited by default.
+    pub fn max_collection_len(mut self, len: u32) -> Self {
+        self.max_collection_len = Some(len);
+        self
+    }
+
+    /// Fail on strings declaring more than `len` bytes, before any of them
+    /// is read. Unlimited by default.
+    pub fn max_string_len(mut self, len: u32) -> Self {
+        self.max_string_len = Some(len);
+        self
+    }
+
+    /// Fail on bins declaring more than `len` bytes, before any of them is
+    /// read. Unlimited by default.
+    pub fn max_bytes_len(mut self, len: u32) -> Self {
+        self.max_bytes_len = Some(len);
+        self
+    }
+
+    /// Accept strings holding decimal numbers (e.g. `"42"`) where a numeric
+    /// value is expected. Integer targets only accept integer strings.
+    pub fn numeric_strings(mut self, enabled: bool) -> Self {
+        self.numeric_strings = enabled;
+        self
+    }
+
+    /// Treat nil as the default value of non-optional targets (`0`, `false`,
+    /// empty maps) instead of failing. Strings, bytes and arrays already
+    /// decode nil as empty.
+    pub fn nil_as_default(mut self, enabled: bool) -> Self {
+        self.nil_as_default = enabled;
+        self
+    }
+
+    /// Sets whether floats are accepted where an integer is expected, as
+    /// JavaScript producers often write `1.0` for an integer field. The
+    /// result must still fit the target type.
+    pub fn float_to_int(mut self, policy: FloatToInt) -> Self {
+        self.float_to_int = policy;
+        self
+    }
+
+    /// Sets how strings that aren't valid UTF-8 are decoded. Strict by
+    /// default.
+    pub fn utf8_policy(mut self, policy: Utf8Policy) -> Self {
+        self.utf8 = policy;
+        self
+    }
+
+    /// Decode identical strings targeting [`SharedString`] into one shared
+    /// allocation, for documents repeating the same URIs or type names.
+    /// Without the `std` feature, each string gets its own allocation.
+    ///
+    /// [`SharedString`]: crate::SharedString
+    pub fn intern_strings(mut self, enabled: bool) -> Self {
+        self.intern_strings = enabled;
+        self
+    }
+
+    /// Calls `observer` with the path of the enclosing struct (its field
+    /// names joined by `.`, empty at the root), the key and the raw encoded
+    /// value of every struct field that is skipped for being unknown. The
+    /// value's wire format is `Format::from_u8(raw[0])`.
+    pub fn on_unknown_field<F>(mut self, observer: F) -> Self
+    where
+        F: Fn(&str, &str, &[u8]) + Send + Sync + 'static,
+    {
+        self.unknown_field_observer = Some(Observer(Arc::new(observer)));
+        self
+    }
+
+    /// Calls `observer` with the path (field names joined by `.`), wire
+    /// format and encoded size in bytes of every struct field decoded, e.g.
+    /// to find which field makes a payload large.
+    pub fn on_field_decoded<F>(mut self, observer: F) -> Self
+    where
+        F: Fn(&str, Format, usize) + Send + Sync + 'static,
+    {
+        self.field_decoded_observer = Some(Observer(Arc::new(observer)));
+        self
+    }
+}
@@ -0,0 +1,60 @@
+use alloc::borrow::Cow;
+
+use serde::de::{
+    value::{BorrowedBytesDeserializer, BytesDeserializer},
+    DeserializeSeed, IntoDeserializer, SeqAccess,
+};
+
+use crate::error::{DecodeError, DecodeResult};
+
+/// Presents an ext of a type the crate doesn't interpret as a
+/// `(type, data)` tuple, the shape [`Value::Ext`] is built from.
+///
+/// [`Value::Ext`]: crate::Value::Ext
+pub(crate) struct ExtAccess<'de> {
+    ext_type: i8,
+    data: Cow<'de, [u8]>,
+    index: usize,
+}
+
+impl<'de> ExtAccess<'de> {
+    pub(crate) fn new(ext_type: i8, data: Cow<'de, [u8]>) -> Self {
+        Self {
+            ext_type,
+            data,
+            index: 0,
+        }
+    }
+}
+
+impl<'de> SeqAccess<'de> for ExtAccess<'de> {
+    type Error = DecodeError;
+
+    fn next_element_seed<T>(
+        &mut self,
+        seed: T,
+    ) -> DecodeResult<Option<T::Value>>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        self.index += 1;
+        match self.index {
+            1 => seed
+                .deserialize(self.ext_type.into_deserializer())
+                .map(Some),
+            2 => match self.data {
+                Cow::Borrowed(data) => seed
+                    .deserialize(BorrowedBytesDeserializer::new(data))
+                    .map(Some),
+                Cow::Owned(ref data) => {
+                    seed.deserialize(BytesDeserializer::new(data)).map(Some)
+                }
+            },
+            _ => Ok(None),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(2usize.saturating_sub(self.index))
+    }
+}
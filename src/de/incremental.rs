@@ -0,0 +1,240 @@
+use alloc::{vec, vec::Vec};
+
+use serde::de::Deserialize;
+
+use crate::error::{DecodeError, DecodeResult};
+
+use super::{read::Read, Deserializer, DeserializerConfig};
+
+/// Finds where a value ends in input that arrives a piece at a time, e.g.
+/// from a socket, so that it's decoded only once all of it is buffered.
+///
+/// Scanning suspends when the input runs out and resumes where it stopped
+/// once more has arrived, instead of starting over from the value's first
+/// byte. The value itself is decoded with a regular [`Deserializer`] once
+/// it's complete.
+#[derive(Debug, Clone)]
+pub struct IncrementalDecoder {
+    config: DeserializerConfig,
+    /// Bytes of the current value scanned so far.
+    scanned: usize,
+    /// Values each enclosing array or map still holds, outermost first.
+    pending: Vec<u64>,
+    /// Bytes missing from the item where scanning stopped.
+    needed: usize,
+}
+
+impl Default for IncrementalDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl IncrementalDecoder {
+    pub fn new() -> Self {
+        Self::with_config(DeserializerConfig::default())
+    }
+
+    pub fn with_config(config: DeserializerConfig) -> Self {
+        Self {
+            config,
+            scanned: 0,
+            pending: vec![1],
+            needed: 1,
+        }
+    }
+
+    /// Returns the length of the value at the start of `input` once it's
+    /// complete, or `None` if more bytes are needed.
+    ///
+    /// `input` must hold the bytes passed the last time, followed by any
+    /// that have arrived since. Once a length is returned, the next call
+    /// starts on a new value.
+    pub fn scan(&mut self, input: &[u8]) -> DecodeResult<Option<usize>> {
+        let result = self.resume(input);
+        if !matches!(result, Ok(None)) {
+            self.reset();
+        }
+        result
+    }
+
+    fn resume(&mut self, input: &[u8]) -> DecodeResult<Option<usize>> {
+        let start = self.scanned.min(input.len());
+        let mut skipper = Deserializer::from_slice_with_config(
+            &input[start..],
+            self.config.clone(),
+        );
+        while let Some(remaining) = self.pending.last_mut() {
+            if *remaining == 0 {
+                self.pending.pop();
+                continue;
+            }
+            let item_start = skipper.read.position();
+            let count = match skipper.skip_item() {
+                Ok(count) => count,
+                Err(DecodeError::Eof {
+                    expected,
+                    available,
+                    ..
+                }) => {
+                    // The item ran to the end of the input, so whatever it
+                    // was missing comes straight after it.
+                    self.scanned = start + item_start;
+                    self.needed = (expected - available).max(1);
+                    return Ok(None);
+                }
+                Err(e) => return Err(e),
+            };
+            *remaining -= 1;
+            if let Some(count) = count {
+                skipper.check_depth(self.pending.len() - 1)?;
+                self.pending.push(count);
+            }
+        }
+        Ok(Some(start + skipper.read.position()))
+    }
+
+    /// Decodes the value at the start of `input` once it's complete,
+    /// returning it along with its length, or `None` if more bytes are
+    /// needed.
+    pub fn decode<'de, T>(
+        &mut self,
+        input: &'de [u8],
+    ) -> DecodeResult<Option<(T, usize)>>
+    where
+        T: Deserialize<'de>,
+    {
+        let Some(len) = self.scan(input)? else {
+            return Ok(None);
+        };
+        let mut deserializer = Deserializer::from_slice_with_config(
+            &input[..len],
+            self.config.clone(),
+        );
+        Ok(Some((T::deserialize(&mut deserializer)?, len)))
+    }
+
+    /// The least number of bytes that must be added to the input passed
+    /// last for the scan to make progress. Reading no more than this never
+    /// reads past the end of the value.
+    pub fn bytes_needed(&self) -> usize {
+        self.needed
+    }
+
+    /// Forgets the value being scanned, to start over on a new one.
+    pub fn reset(&mut self) {
+        self.scanned = 0;
+        self.pending.clear();
+        self.pending.push(1);
+        self.needed = 1;
+    }
+}
+
+/// Most bytes async readers ask for at once, so that nothing is allocated
+/// for a declared length before its bytes have arrived.
+#[cfg(any(feature = "tokio", feature = "futures"))]
+const MAX_READ_LEN: usize = 8 * 1024;
+
+/// The state of reading one value from an async reader, shared by the
+/// tokio and futures entry points: they read into [`Self::spare`] and report
+/// how much was read to [`Self::filled`] until [`Self::decode`] returns the
+/// value.
+#[cfg(any(feature = "tokio", feature = "futures"))]
+pub(crate) struct AsyncReadState {
+    decoder: IncrementalDecoder,
+    buffer: Vec<u8>,
+    /// Length of the buffer before [`Self::spare`] grew it.
+    filled: usize,
+}
+
+#[cfg(any(feature = "tokio", feature = "futures"))]
+impl AsyncReadState {
+    pub(crate) fn new(config: DeserializerConfig) -> Self {
+        Self {
+            decoder: IncrementalDecoder::with_config(config),
+            buffer: Vec::new(),
+            filled: 0,
+        }
+    }
+
+    /// Decodes the value if all of it has been read.
+    pub(crate) fn decode<T>(&mut self) -> DecodeResult<Option<T>>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        let value = self.decoder.decode(&self.buffer[..self.filled])?;
+        Ok(value.map(|(value, _)| value))
+    }
+
+    /// Room for the next read: as many bytes as are known to be needed, up
+    /// to [`MAX_READ_LEN`], so that nothing past the value is read.
+    pub(crate) fn spare(&mut self) -> &mut [u8] {
+        let len = self.decoder.bytes_needed().min(MAX_READ_LEN);
+        self.buffer.resize(self.filled + len, 0);
+        &mut self.buffer[self.filled..]
+    }
+
+    /// Records that the last read filled `read` bytes of [`Self::spare`],
+    /// failing if it found the end of the input.
+    pub(crate) fn filled(&mut self, read: usize) -> DecodeResult<()> {
+        if read == 0 {
+            return Err(DecodeError::Eof {
+                expected: self.decoder.bytes_needed(),
+                available: 0,
+                offset: self.filled,
+            });
+        }
+        self.filled += read;
+        self.buffer.truncate(self.filled);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::IncrementalDecoder;
+    use crate::{to_vec, DecodeError, DeserializerConfig};
+
+    #[test]
+    fn test_scan_input_a_byte_at_a_time() {
+        let value = (vec!["uri"; 3], vec![0u8; 40], 1u64 << 40);
+        let mut bytes = to_vec(&value).unwrap();
+        let len = bytes.len();
+        bytes.push(0xc0);
+
+        let mut decoder = IncrementalDecoder::new();
+        for end in 0..len {
+            assert_eq!(None, decoder.scan(&bytes[..end]).unwrap());
+            assert!(end + decoder.bytes_needed() <= len);
+        }
+        assert_eq!(Some(len), decoder.scan(&bytes[..len]).unwrap());
+        assert_eq!(Some(len), decoder.scan(&bytes).unwrap());
+
+        // Header and payload lengths are known once their headers are in
+        let mut decoder = IncrementalDecoder::new();
+        assert_eq!(None, decoder.scan(&[0x93, 0xc4]).unwrap());
+        assert_eq!(1, decoder.bytes_needed());
+        assert_eq!(None, decoder.scan(&[0x93, 0xc4, 3]).unwrap());
+        assert_eq!(3, decoder.bytes_needed());
+
+        let mut decoder = IncrementalDecoder::new();
+        let decoded = decoder.decode(&bytes).unwrap();
+        assert_eq!(Some((value, len)), decoded);
+    }
+
+    #[test]
+    fn test_scan_malformed_input() {
+        let mut decoder = IncrementalDecoder::with_config(
+            DeserializerConfig::new().max_depth(1),
+        );
+        assert!(decoder.scan(&[0x91, 0x91]).is_err());
+
+        // Scanning starts over after an error
+        assert_eq!(Some(2), decoder.scan(&[0x91, 1]).unwrap());
+        assert!(matches!(
+            decoder.scan(&[0x92, 0xc1]),
+            Err(DecodeError::Message(_))
+        ));
+        assert_eq!(None, decoder.scan(&[0x92]).unwrap());
+    }
+}
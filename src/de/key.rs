@@ -0,0 +1,173 @@
+use alloc::string::{String, ToString};
+
+use serde::de::{Deserialize, Visitor};
+
+use crate::{error::DecodeResult, format::Format, Deserializer, Value};
+
+use super::read::Read;
+
+/// Decodes a map key. With [`scalar_keys_as_strings`], bool, integer and
+/// float keys are accepted where a string is expected and their JSON text is
+/// passed instead, e.g. `1` as `"1"`.
+///
+/// GenericMaps may be keyed by any value, so this lets them decode into
+/// `serde_json::Value` or `HashMap<String, _>`, the same way
+/// `From<Value> for serde_json::Value` renders them. Bytes, exts and
+/// collections still fail as string keys.
+///
+/// [`scalar_keys_as_strings`]: crate::DeserializerConfig::scalar_keys_as_strings
+pub(crate) struct MapKey<'a, R> {
+    de: &'a mut Deserializer<R>,
+}
+
+impl<'a, R> MapKey<'a, R> {
+    pub(crate) fn new(de: &'a mut Deserializer<R>) -> Self {
+        Self { de }
+    }
+}
+
+impl<'de, R: Read<'de>> MapKey<'_, R> {
+    /// The JSON text of the next key, if it's a scalar other than a string
+    /// and those are read as strings.
+    fn scalar_key(&mut self) -> DecodeResult<Option<String>> {
+        if !self.de.config.scalar_keys_as_strings {
+            return Ok(None);
+        }
+        match self.de.peek_format()? {
+            Format::False
+            | Format::True
+            | Format::PositiveFixInt(_)
+            | Format::NegativeFixInt(_)
+            | Format::Uint8
+            | Format::Uint16
+            | Format::Uint32
+            | Format::Uint64
+            | Format::Int8
+            | Format::Int16
+            | Format::Int32
+            | Format::Int64
+            | Format::Float32
+            | Format::Float64 => {
+                let key = Value::deserialize(&mut *self.de)?;
+                Ok(Some(serde_json::Value::from(key).to_string()))
+            }
+            _ => Ok(None),
+        }
+    }
+}
+
+macro_rules! forward_to_deserializer {
+    ($($method:ident)*) => {
+        $(
+            fn $method<V>(self, visitor: V) -> DecodeResult<V::Value>
+            where
+                V: Visitor<'de>,
+            {
+                self.de.$method(visitor)
+            }
+        )*
+    };
+}
+
+impl<'de, R: Read<'de>> serde::Deserializer<'de> for MapKey<'_, R> {
+    type Error = crate::DecodeError;
+
+    fn deserialize_str<V>(mut self, visitor: V) -> DecodeResult<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self.scalar_key()? {
+            Some(key) => visitor.visit_string(key),
+            None => self.de.deserialize_str(visitor),
+        }
+    }
+
+    fn deserialize_string<V>(mut self, visitor: V) -> DecodeResult<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self.scalar_key()? {
+            Some(key) => visitor.visit_string(key),
+            None => self.de.deserialize_string(visitor),
+        }
+    }
+
+    forward_to_deserializer! {
+        deserialize_any deserialize_bool deserialize_i8 deserialize_i16
+        deserialize_i32 deserialize_i64 deserialize_i128 deserialize_u8
+        deserialize_u16 deserialize_u32 deserialize_u64 deserialize_u128
+        deserialize_f32 deserialize_f64 deserialize_char deserialize_bytes
+        deserialize_byte_buf deserialize_option deserialize_unit
+        deserialize_seq deserialize_map deserialize_identifier
+        deserialize_ignored_any
+    }
+
+    fn deserialize_unit_struct<V>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> DecodeResult<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.de.deserialize_unit_struct(name, visitor)
+    }
+
+    fn deserialize_newtype_struct<V>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> DecodeResult<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.de.deserialize_newtype_struct(name, visitor)
+    }
+
+    fn deserialize_tuple<V>(
+        self,
+        len: usize,
+        visitor: V,
+    ) -> DecodeResult<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.de.deserialize_tuple(len, visitor)
+    }
+
+    fn deserialize_tuple_struct<V>(
+        self,
+        name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> DecodeResult<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.de.deserialize_tuple_struct(name, len, visitor)
+    }
+
+    fn deserialize_struct<V>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> DecodeResult<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.de.deserialize_struct(name, fields, visitor)
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> DecodeResult<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.de.deserialize_enum(name, variants, visitor)
+    }
+}
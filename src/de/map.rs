@@ -1,28 +1,56 @@
-use serde::de::{DeserializeSeed, MapAccess};
+use alloc::{
+    format,
+    string::{String, ToString},
+};
 
-use crate::{Deserializer, error::{Result, Error}};
+use serde::de::{self, DeserializeSeed, IntoDeserializer, MapAccess};
 
-pub struct MapReadAccess<'a> {
-  deserializer: &'a mut Deserializer,
+use crate::{
+    Deserializer,
+    error::{unknown_name_message, DecodeResult, DecodeError},
+    format::Format,
+};
+
+use super::{key::MapKey, read::Read};
+
+pub struct MapReadAccess<'a, R> {
+  deserializer: &'a mut Deserializer<R>,
   entries_in_map: u32,
+  /// Declared field names, when decoding a struct.
+  fields: Option<&'static [&'static str]>,
+  /// Key of the struct field being decoded, when fields are observed.
+  field: Option<String>,
 }
 
-impl<'a> MapReadAccess<'a> {
+impl<'a, R> MapReadAccess<'a, R> {
   pub fn new(
-      deserializer: &'a mut Deserializer,
+      deserializer: &'a mut Deserializer<R>,
       entries_in_map: u32,
   ) -> Self {
       Self {
           deserializer,
           entries_in_map,
+          fields: None,
+          field: None,
+      }
+  }
+
+  pub fn for_struct(
+      deserializer: &'a mut Deserializer<R>,
+      entries_in_map: u32,
+      fields: &'static [&'static str],
+  ) -> Self {
+      Self {
+          fields: Some(fields),
+          ..Self::new(deserializer, entries_in_map)
       }
   }
 }
 
-impl<'a, 'de> MapAccess<'de> for MapReadAccess<'a> {
-  type Error = Error;
+impl<'a, 'de, R: Read<'de>> MapAccess<'de> for MapReadAccess<'a, R> {
+  type Error = DecodeError;
 
-  fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>>
+  fn next_key_seed<K>(&mut self, seed: K) -> DecodeResult<Option<K::Value>>
   where
       K: DeserializeSeed<'de>,
   {
@@ -30,14 +58,80 @@ impl<'a, 'de> MapAccess<'de> for MapReadAccess<'a> {
           return Ok(None);
       }
 
-      seed.deserialize(&mut *self.deserializer).map(Some)
+      let deny_unknown_fields = self.deserializer.config.deny_unknown_fields;
+      let tracks_keys =
+          deny_unknown_fields || self.deserializer.observes_fields();
+      let integer_keys = self.deserializer.config.integer_field_keys;
+      if let Some(fields) = self.fields {
+          let key = match self.deserializer.peek_format()? {
+              Format::FixStr(_)
+              | Format::Str8
+              | Format::Str16
+              | Format::Str32 if tracks_keys => {
+                  Some(self.deserializer.parse_string()?)
+              }
+              // Fields keyed by their index; ones out of range are unknown
+              Format::PositiveFixInt(_)
+              | Format::Uint8
+              | Format::Uint16
+              | Format::Uint32
+              | Format::Uint64 if integer_keys => {
+                  let index = self.deserializer.parse_unsigned()?;
+                  let name = usize::try_from(index)
+                      .ok()
+                      .and_then(|index| fields.get(index));
+                  Some(match name {
+                      Some(name) => name.to_string(),
+                      None => index.to_string(),
+                  })
+              }
+              Format::PositiveFixInt(_)
+              | Format::Uint8
+              | Format::Uint16
+              | Format::Uint32
+              | Format::Uint64 => {
+                  return Err(DecodeError::Message(
+                      "Found an integer struct field key; field indexes are \
+                       only accepted with `integer_field_keys`"
+                          .to_string(),
+                  ));
+              }
+              _ => None,
+          };
+          if let Some(key) = key {
+              if deny_unknown_fields && !fields.contains(&key.as_str()) {
+                  // Name the enclosing struct when it isn't the root
+                  let path = &self.deserializer.observed.path;
+                  if path.is_empty() {
+                      return Err(de::Error::unknown_field(&key, fields));
+                  }
+                  return Err(DecodeError::Message(format!(
+                      "at `{}`: {}",
+                      path.join("."),
+                      unknown_name_message("field", &key, fields)
+                  )));
+              }
+              let value = seed.deserialize(
+                  IntoDeserializer::<DecodeError>::into_deserializer(
+                      key.as_str(),
+                  ),
+              )?;
+              self.field = Some(key);
+              return Ok(Some(value));
+          }
+      }
+
+      seed.deserialize(MapKey::new(self.deserializer)).map(Some)
   }
 
-  fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value>
+  fn next_value_seed<V>(&mut self, seed: V) -> DecodeResult<V::Value>
   where
       V: DeserializeSeed<'de>,
   {
       self.entries_in_map -= 1;
-      seed.deserialize(&mut *self.deserializer)
+      match self.field.take() {
+          Some(key) => self.deserializer.deserialize_field(key, seed),
+          None => seed.deserialize(&mut *self.deserializer),
+      }
   }
 }
\ No newline at end of file
@@ -1,58 +1,519 @@
 mod _enum;
 mod array;
+mod byte_seq;
+mod config;
+mod ext;
+mod incremental;
+mod key;
 mod map;
+pub mod read;
+mod stream;
 
 use crate::{
-    error::{get_error_message, Error, Result},
+    error::{get_error_message, DecodeError, DecodeResult, ShortRead},
     format::{ExtensionType, Format},
+    io::{self, Read as _, ReadBytesExt},
+    value::EXT_TOKEN,
+    wrappers::shared_string::{self, SHARED_STRING_TOKEN},
 };
-use byteorder::{BigEndian, ReadBytesExt};
-use serde::de::{self, Deserialize, IntoDeserializer, Visitor};
-use std::io::{Cursor, Read};
+use alloc::{
+    borrow::Cow,
+    format,
+    string::{String, ToString},
+    sync::Arc,
+    vec,
+    vec::Vec,
+};
+use byteorder::BigEndian;
+use core::{
+    fmt::Display,
+    ops::Deref,
+    str::{FromStr, Utf8Error},
+};
+#[cfg(not(feature = "std"))]
+use num_traits::float::FloatCore;
+use serde::de::{
+    self,
+    value::SeqAccessDeserializer,
+    Deserialize, DeserializeOwned, IntoDeserializer, Visitor,
+};
+#[cfg(not(feature = "std"))]
+use alloc::collections::BTreeSet as StringSet;
+#[cfg(feature = "std")]
+use std::collections::HashSet as StringSet;
 
+use _enum::Enum;
 use array::ArrayReadAccess;
+use byte_seq::ByteSeqAccess;
+use ext::ExtAccess;
 use map::MapReadAccess;
+use read::{IoRead, Read, SliceRead, SlicesRead};
+
+pub use config::{DeserializerConfig, FloatToInt, Utf8Policy};
+pub use incremental::IncrementalDecoder;
+#[cfg(any(feature = "tokio", feature = "futures"))]
+pub(crate) use incremental::AsyncReadState;
+pub use stream::StreamDeserializer;
+
+pub struct Deserializer<R> {
+    read: R,
+    config: DeserializerConfig,
+    /// Strings decoded into `SharedString`s when `intern_strings` is enabled.
+    interned: StringSet<Arc<str>>,
+    /// State kept only while a field observer is installed.
+    observed: ObservedFields,
+    /// How many arrays, maps and exts enclose the value being decoded.
+    depth: usize,
+}
 
-pub struct Deserializer {
-    pub buffer: Cursor<Vec<u8>>,
+#[derive(Default)]
+struct ObservedFields {
+    /// Keys of the struct fields being decoded, outermost first.
+    path: Vec<String>,
+    /// Where the value of the innermost struct field starts.
+    value_start: Option<usize>,
+    /// Bytes consumed while skipping an unknown field's value.
+    raw_value: Option<Vec<u8>>,
 }
 
-impl Default for Deserializer {
+impl Default for Deserializer<SliceRead<'_>> {
     fn default() -> Self {
-        Self {
-            buffer: Cursor::new(vec![]),
-        }
+        Self::new(SliceRead::new(&[]))
     }
 }
 
-impl Deserializer {
-    #[allow(clippy::should_implement_trait)]
-    pub fn from_slice(buffer: &[u8]) -> Self {
+impl<'de, R: Read<'de>> Deserializer<R> {
+    pub fn new(read: R) -> Self {
+        Self::with_config(read, DeserializerConfig::default())
+    }
+
+    pub fn with_config(read: R, config: DeserializerConfig) -> Self {
         Deserializer {
-            buffer: Cursor::new(buffer.to_vec()),
+            read,
+            config,
+            interned: StringSet::new(),
+            observed: ObservedFields::default(),
+            depth: 0,
         }
     }
+
+    /// Checks that the input has been consumed entirely, for callers
+    /// expecting it to hold a single value.
+    pub fn end(&mut self) -> DecodeResult<()> {
+        match self.read.peek()? {
+            Some(_) => Err(DecodeError::TrailingCharacters),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<'a> Deserializer<SliceRead<'a>> {
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_slice(buffer: &'a [u8]) -> Self {
+        Self::from_slice_with_config(buffer, DeserializerConfig::default())
+    }
+
+    pub fn from_slice_with_config(
+        buffer: &'a [u8],
+        config: DeserializerConfig,
+    ) -> Self {
+        Self::with_config(SliceRead::new(buffer), config)
+    }
+}
+
+impl<'a, S> Deserializer<SlicesRead<'a, S>>
+where
+    S: Deref<Target = [u8]>,
+{
+    pub fn from_slices(slices: &'a [S]) -> Self {
+        Self::new(SlicesRead::new(slices))
+    }
+}
+
+impl<R: io::Read> Deserializer<IoRead<R>> {
+    pub fn from_reader(reader: R) -> Self {
+        Self::new(IoRead::new(reader))
+    }
+}
+
+pub fn from_slice<'a, T>(buffer: &'a [u8]) -> DecodeResult<T>
+where
+    T: Deserialize<'a>,
+{
+    from_slice_with_config(buffer, DeserializerConfig::default())
 }
 
-pub fn from_slice<'a, T>(buffer: &'a [u8]) -> Result<T>
+pub fn from_slice_with_config<'a, T>(
+    buffer: &'a [u8],
+    config: DeserializerConfig,
+) -> DecodeResult<T>
 where
     T: Deserialize<'a>,
 {
-    let mut deserializer = Deserializer::from_slice(buffer);
+    let allow_trailing_bytes = config.allow_trailing_bytes;
+    let mut deserializer = Deserializer::from_slice_with_config(buffer, config);
     let t = T::deserialize(&mut deserializer)?;
+    if !allow_trailing_bytes {
+        deserializer.end()?;
+    }
     Ok(t)
 }
 
-impl Deserializer {
-    fn peek_format(&mut self) -> Result<Format> {
-        let position = self.buffer.position();
-        let format = Format::get_format(self)?;
-        self.buffer.set_position(position);
+/// Deserializes a value split across several buffers (e.g. `&[&[u8]]` or
+/// `&[IoSlice]`) without concatenating them first.
+///
+/// Strings and bytes are borrowed from the slices where they don't straddle
+/// two of them.
+pub fn from_slices<'a, T, S>(slices: &'a [S]) -> DecodeResult<T>
+where
+    T: Deserialize<'a>,
+    S: Deref<Target = [u8]>,
+{
+    let mut deserializer = Deserializer::from_slices(slices);
+    let t = T::deserialize(&mut deserializer)?;
+    Ok(t)
+}
+
+/// Deserializes a value from a `bytes::Bytes` buffer. Fields using
+/// [`shared_bytes`](crate::wrappers::shared_bytes) decode into `Bytes` that
+/// share `input`'s allocation instead of copying out of it.
+#[cfg(feature = "bytes")]
+pub fn from_bytes<'a, T>(input: &'a bytes::Bytes) -> DecodeResult<T>
+where
+    T: Deserialize<'a>,
+{
+    crate::wrappers::shared_bytes::with_source(input, || from_slice(input))
+}
+
+/// Deserializes a value read incrementally from `reader`, e.g. a file or a
+/// network stream, without loading the whole input first.
+///
+/// Only the bytes of the value are consumed, so further values can be read
+/// from the same reader afterwards.
+pub fn from_reader<R, T>(reader: R) -> DecodeResult<T>
+where
+    R: io::Read,
+    T: DeserializeOwned,
+{
+    let mut deserializer = Deserializer::from_reader(reader);
+    let t = T::deserialize(&mut deserializer)?;
+    Ok(t)
+}
+
+impl<'de, R: Read<'de>> Deserializer<R> {
+    fn peek_format(&mut self) -> DecodeResult<Format> {
+        match self.read.peek()? {
+            Some(byte) => Ok(Format::from_u8(byte)),
+            None => Err(self.eof(1, 0)),
+        }
+    }
+
+    /// The error for input ending `available` bytes into a read of
+    /// `expected` bytes at the current position.
+    fn eof(&self, expected: usize, available: usize) -> DecodeError {
+        DecodeError::Eof {
+            expected,
+            available,
+            offset: self.read.position(),
+        }
+    }
+
+    /// Reads a struct written as a map, after any GenericMap ext header.
+    fn read_struct_map<V>(
+        &mut self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> DecodeResult<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let map_len = self.read_map_length()?;
+        self.nested(|de| {
+            visitor.visit_map(MapReadAccess::for_struct(de, map_len, fields))
+        })
+    }
+
+    /// Decodes the contents of an array, map or ext, within the configured
+    /// maximum depth.
+    fn nested<T>(
+        &mut self,
+        f: impl FnOnce(&mut Self) -> DecodeResult<T>,
+    ) -> DecodeResult<T> {
+        self.check_depth(self.depth)?;
+        self.depth += 1;
+        let result = f(self);
+        self.depth -= 1;
+        result
+    }
+
+    /// Checks that a collection at `depth` may hold nested values.
+    fn check_depth(&self, depth: usize) -> DecodeResult<()> {
+        match self.config.max_depth {
+            Some(max_depth) if depth >= max_depth => Err(DecodeError::Message(
+                format!("Nesting exceeds the maximum depth of {max_depth}"),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks a declared array or map length against the configured limit.
+    fn check_collection_len(&self, len: u32) -> DecodeResult<u32> {
+        match self.config.max_collection_len {
+            Some(max_len) if len > max_len => Err(DecodeError::Message(format!(
+                "Collection of {len} elements exceeds the maximum length of \
+                 {max_len}"
+            ))),
+            _ => Ok(len),
+        }
+    }
+
+    /// Checks a declared string or bin length against the configured limit
+    /// and the input left.
+    fn check_payload_len(
+        &self,
+        len: u32,
+        limit: Option<u32>,
+        kind: &str,
+    ) -> DecodeResult<u32> {
+        match limit {
+            Some(max_len) if len > max_len => Err(DecodeError::Message(format!(
+                "{kind} of {len} bytes exceeds the maximum length of {max_len}"
+            ))),
+            _ => self.check_remaining(len, 1),
+        }
+    }
+
+    /// Fails when `len` items of at least `item_size` bytes each can't fit
+    /// in what's left of an input of known size, so that a corrupt or
+    /// malicious length can't make us allocate for data that isn't there.
+    fn check_remaining(&self, len: u32, item_size: usize) -> DecodeResult<u32> {
+        let expected = (len as usize).saturating_mul(item_size);
+        match self.read.remaining() {
+            Some(available) if expected > available => Err(DecodeError::Eof {
+                expected,
+                available,
+                offset: self.read.position(),
+            }),
+            _ => Ok(len),
+        }
+    }
+
+    fn observes_unknown_fields(&self) -> bool {
+        self.config.unknown_field_observer.is_some()
+    }
+
+    /// Whether struct field keys must be tracked for an observer.
+    fn observes_fields(&self) -> bool {
+        self.observes_unknown_fields()
+            || self.config.field_decoded_observer.is_some()
+    }
+
+    /// Decodes the value of the struct field `key`, keeping track of where
+    /// it starts in case it turns out to be unknown, and reporting it to the
+    /// field decoded observer.
+    fn deserialize_field<S>(
+        &mut self,
+        key: String,
+        seed: S,
+    ) -> DecodeResult<S::Value>
+    where
+        S: de::DeserializeSeed<'de>,
+    {
+        let format = self.peek_format()?;
+        let start = self.read.position();
+        self.observed.path.push(key);
+        self.observed.value_start = Some(start);
+        let value = seed.deserialize(&mut *self);
+
+        if let (Ok(_), Some(observer)) =
+            (&value, &self.config.field_decoded_observer)
+        {
+            let size = self.read.position() - start;
+            (observer.0)(&self.observed.path.join("."), format, size);
+        }
+        self.observed.path.pop();
+        value
+    }
+
+    /// Skips an unknown struct field's value and reports it to the observer.
+    fn skip_unknown_field<V>(&mut self, visitor: V) -> DecodeResult<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.observed.raw_value = Some(vec![]);
+        let skipped = self.skip_value();
+        let raw_value = self.observed.raw_value.take().unwrap_or_default();
+        skipped?;
+
+        if let Some(observer) = &self.config.unknown_field_observer {
+            let (key, path) = self.observed.path.split_last().unwrap();
+            (observer.0)(&path.join("."), key, &raw_value);
+        }
+        visitor.visit_unit()
+    }
+
+    /// Consumes the next value without decoding it: only headers are read,
+    /// and payloads are passed over without being copied out.
+    ///
+    /// Nested arrays and maps are tracked on an explicit stack of remaining
+    /// element counts rather than by recursion, so that skipping a deeply
+    /// nested value can't overflow the call stack.
+    fn skip_value(&mut self) -> DecodeResult<()> {
+        let mut pending = vec![1u64];
+        while let Some(remaining) = pending.last_mut() {
+            if *remaining == 0 {
+                pending.pop();
+                continue;
+            }
+            *remaining -= 1;
+            if let Some(count) = self.skip_item()? {
+                self.check_depth(self.depth + pending.len() - 1)?;
+                pending.push(count);
+            }
+        }
+        Ok(())
+    }
+
+    /// Skips a scalar, string, bin or ext, or reads the header of an array or
+    /// map and returns how many values it holds.
+    fn skip_item(&mut self) -> DecodeResult<Option<u64>> {
+        let payload_len = match Format::get_format(self)? {
+            Format::PositiveFixInt(_)
+            | Format::NegativeFixInt(_)
+            | Format::Nil
+            | Format::False
+            | Format::True => 0,
+            Format::Reserved => {
+                return Err(DecodeError::Message(
+                    "Found reserved format byte 0xc1".to_string(),
+                ))
+            }
+            Format::Uint8 | Format::Int8 => 1,
+            Format::Uint16 | Format::Int16 => 2,
+            Format::Uint32 | Format::Int32 | Format::Float32 => 4,
+            Format::Uint64 | Format::Int64 | Format::Float64 => 8,
+            Format::FixStr(len) => len as u64,
+            Format::Str8 | Format::Bin8 => ReadBytesExt::read_u8(self)? as u64,
+            Format::Str16 | Format::Bin16 => {
+                ReadBytesExt::read_u16::<BigEndian>(self)? as u64
+            }
+            Format::Str32 | Format::Bin32 => {
+                ReadBytesExt::read_u32::<BigEndian>(self)? as u64
+            }
+            // Ext payloads are skipped along with their type byte.
+            Format::FixExt1 => 2,
+            Format::FixExt2 => 3,
+            Format::FixExt4 => 5,
+            Format::FixExt8 => 9,
+            Format::FixExt16 => 17,
+            Format::Ext8 => ReadBytesExt::read_u8(self)? as u64 + 1,
+            Format::Ext16 => {
+                ReadBytesExt::read_u16::<BigEndian>(self)? as u64 + 1
+            }
+            Format::Ext32 => {
+                ReadBytesExt::read_u32::<BigEndian>(self)? as u64 + 1
+            }
+            Format::FixArray(len) => return Ok(Some(len as u64)),
+            Format::Array16 => {
+                let len = ReadBytesExt::read_u16::<BigEndian>(self)?;
+                return Ok(Some(len as u64));
+            }
+            Format::Array32 => {
+                let len = ReadBytesExt::read_u32::<BigEndian>(self)?;
+                return Ok(Some(len as u64));
+            }
+            Format::FixMap(len) => return Ok(Some(2 * len as u64)),
+            Format::Map16 => {
+                let len = ReadBytesExt::read_u16::<BigEndian>(self)?;
+                return Ok(Some(2 * len as u64));
+            }
+            Format::Map32 => {
+                let len = ReadBytesExt::read_u32::<BigEndian>(self)?;
+                return Ok(Some(2 * len as u64));
+            }
+        };
+        self.skip_bytes(payload_len)?;
+        Ok(None)
+    }
+
+    fn skip_bytes(&mut self, len: u64) -> DecodeResult<()> {
+        if self.borrow_bytes(len as usize).is_some() {
+            return Ok(());
+        }
+        self.read_chunks(len, |_| {})
+    }
+
+    /// Reads the next `len` bytes a chunk at a time, handing each chunk to
+    /// `f`, so that nothing is allocated for a length the input can't back.
+    fn read_chunks(
+        &mut self,
+        len: u64,
+        mut f: impl FnMut(&[u8]),
+    ) -> DecodeResult<()> {
+        let offset = self.read.position();
+        let mut chunk = [0; 4096];
+        let mut read = 0;
+        while read < len {
+            let n = (len - read).min(chunk.len() as u64) as usize;
+            match io::Read::read(self, &mut chunk[..n]) {
+                Ok(0) => {
+                    return Err(ShortRead {
+                        expected: len as usize,
+                        available: read as usize,
+                        offset,
+                    }
+                    .into())
+                }
+                Ok(n) => {
+                    f(&chunk[..n]);
+                    read += n as u64;
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(())
+    }
+
+    /// Consumes a nil when `nil_as_default` is enabled, in which case the
+    /// caller should visit the default value of its target instead.
+    fn take_nil_as_default(&mut self) -> DecodeResult<bool> {
+        if self.config.nil_as_default && self.peek_format()? == Format::Nil {
+            Format::get_format(self)?;
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    /// Visits the data of an ext whose header has been read as a newtype
+    /// over its `(type, data)` tuple.
+    fn visit_raw_ext<V>(
+        &mut self,
+        len: u32,
+        ext_type: i8,
+        visitor: V,
+    ) -> DecodeResult<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let data = match self.borrow_bytes(len as usize) {
+            Some(data) => Cow::Borrowed(data),
+            None => Cow::Owned(self.get_bytes(len as u64)?),
+        };
+        visitor.visit_newtype_struct(SeqAccessDeserializer::new(
+            ExtAccess::new(ext_type, data),
+        ))
+    }
 
-        Ok(format)
+    fn read_ext_length_and_type(
+        &mut self,
+    ) -> DecodeResult<(u32, ExtensionType)> {
+        let (byte_length, ext_type) = self.read_ext_header()?;
+        Ok((byte_length, (ext_type as u8).try_into()?))
     }
 
-    fn read_ext_length_and_type(&mut self) -> Result<(u32, ExtensionType)> {
+    /// Reads an ext's length and type, whether or not the type is known.
+    fn read_ext_header(&mut self) -> DecodeResult<(u32, i8)> {
         let format = Format::get_format(self)?;
         let byte_length = match format {
             Format::FixExt1 => 1,
@@ -68,133 +529,299 @@ impl Deserializer {
                     "Property must be of type 'ext generic map'. {}",
                     get_error_message(err_f)
                 );
-                return Err(Error::ExpectedExt(formatted_err));
+                return Err(DecodeError::ExpectedExt(formatted_err));
             }
         };
 
-        let ext_type = ReadBytesExt::read_u8(self)?;
+        let ext_type = ReadBytesExt::read_i8(self)?;
+        self.check_remaining(byte_length, 1)?;
 
-        Ok((byte_length, ext_type.try_into()?))
+        Ok((byte_length, ext_type))
     }
 
-    fn read_array_length(&mut self) -> Result<u32> {
-        let next_format = self.peek_format()?;
-
-        if let Format::Nil = next_format {
-            return Ok(0);
-        }
-
-        match Format::get_format(self)? {
-            Format::FixArray(len) => Ok(len as u32),
+    fn read_array_length(&mut self) -> DecodeResult<u32> {
+        let len = match Format::get_format(self)? {
+            Format::FixArray(len) => len as u32,
             Format::Array16 => {
-                Ok(ReadBytesExt::read_u16::<BigEndian>(self)? as u32)
+                ReadBytesExt::read_u16::<BigEndian>(self)? as u32
             }
-            Format::Array32 => Ok(ReadBytesExt::read_u32::<BigEndian>(self)?),
-            Format::Nil => Ok(0),
+            Format::Array32 => ReadBytesExt::read_u32::<BigEndian>(self)?,
+            Format::Nil => 0,
             err_f => {
                 let formatted_err = format!(
                     "Property must be of type 'array'. {}",
                     get_error_message(err_f)
                 );
-                Err(Error::ExpectedArray(formatted_err))
+                return Err(DecodeError::ExpectedArray(formatted_err));
             }
+        };
+        self.check_collection_len(len)?;
+        self.check_remaining(len, 1)
+    }
+
+    fn get_bytes(&mut self, n_bytes_to_read: u64) -> DecodeResult<Vec<u8>> {
+        // Copy straight out of the input when it's in memory, rather than
+        // growing a buffer through `Read`.
+        if let Some(bytes) = usize::try_from(n_bytes_to_read)
+            .ok()
+            .and_then(|len| self.borrow_bytes(len))
+        {
+            return Ok(bytes.to_vec());
         }
-    }
-
-    fn get_bytes(&mut self, n_bytes_to_read: u64) -> Result<Vec<u8>> {
         let mut buf = vec![];
-        let mut chunk = self.take(n_bytes_to_read);
-        match chunk.read_to_end(&mut buf) {
-            Ok(_n) => Ok(buf),
-            Err(e) => Err(Error::Message(e.to_string())),
+        self.read_chunks(n_bytes_to_read, |chunk| {
+            buf.extend_from_slice(chunk)
+        })?;
+        Ok(buf)
+    }
+
+    /// Consumes the next `len` bytes without copying them, if the input
+    /// lives long enough to lend them to the visitor.
+    fn borrow_bytes(&mut self, len: usize) -> Option<&'de [u8]> {
+        let bytes = self.read.borrow(len)?;
+        if let Some(raw_value) = &mut self.observed.raw_value {
+            raw_value.extend_from_slice(bytes);
         }
+        Some(bytes)
     }
 
-    fn read_string_length(&mut self) -> Result<u32> {
-        let next_format = self.peek_format()?;
-
-        if let Format::Nil = next_format {
-            return Ok(0);
-        }
-
-        match Format::get_format(self)? {
-            Format::FixStr(len) => Ok(len as u32),
-            Format::FixArray(len) => Ok(len as u32),
-            Format::Str8 => Ok(ReadBytesExt::read_u8(self)? as u32),
-            Format::Str16 => {
-                Ok(ReadBytesExt::read_u16::<BigEndian>(self)? as u32)
-            }
-            Format::Str32 => Ok(ReadBytesExt::read_u32::<BigEndian>(self)?),
-            Format::Nil => Ok(0),
+    fn read_string_length(&mut self) -> DecodeResult<u32> {
+        let len = match Format::get_format(self)? {
+            Format::FixStr(len) => len as u32,
+            Format::FixArray(len) => len as u32,
+            Format::Str8 => ReadBytesExt::read_u8(self)? as u32,
+            Format::Str16 => ReadBytesExt::read_u16::<BigEndian>(self)? as u32,
+            Format::Str32 => ReadBytesExt::read_u32::<BigEndian>(self)?,
+            Format::Nil => 0,
             err_f => {
                 let formatted_err = format!(
                     "Property must be of type 'string'. {}",
                     get_error_message(err_f)
                 );
-                Err(Error::ExpectedString(formatted_err))
+                return Err(DecodeError::ExpectedString(formatted_err));
             }
-        }
+        };
+        self.check_payload_len(len, self.config.max_string_len, "String")
     }
 
-    fn parse_string(&mut self) -> Result<String> {
+    /// Reads a string, borrowing it from in-memory input after a single
+    /// UTF-8 check rather than copying it out first.
+    fn parse_str(&mut self) -> DecodeResult<Cow<'de, str>> {
         let str_len = self.read_string_length()?;
-        let bytes = self.get_bytes(str_len as u64)?;
+        let start = self.read.position();
+        match self.borrow_bytes(str_len as usize) {
+            Some(bytes) => self.utf8_str(bytes, start),
+            None => {
+                let bytes = self.get_bytes(str_len as u64)?;
+                self.utf8_string(bytes, start).map(Cow::Owned)
+            }
+        }
+    }
+
+    fn parse_string(&mut self) -> DecodeResult<String> {
+        self.parse_str().map(Cow::into_owned)
+    }
+
+    /// Decodes the bytes of a string read from `start` as the UTF-8 policy
+    /// says.
+    fn utf8_str<'b>(
+        &self,
+        bytes: &'b [u8],
+        start: usize,
+    ) -> DecodeResult<Cow<'b, str>> {
+        match str_from_utf8(bytes) {
+            Ok(s) => Ok(Cow::Borrowed(s)),
+            Err(e) => self.invalid_utf8(bytes, e, start),
+        }
+    }
+
+    /// Like [`Self::utf8_str`], reusing `bytes` when they're valid.
+    fn utf8_string(
+        &self,
+        bytes: Vec<u8>,
+        start: usize,
+    ) -> DecodeResult<String> {
+        #[cfg(feature = "simdutf8")]
+        if simdutf8::basic::from_utf8(&bytes).is_ok() {
+            // SAFETY: the bytes were just validated as UTF-8
+            return Ok(unsafe { String::from_utf8_unchecked(bytes) });
+        }
         match String::from_utf8(bytes) {
             Ok(s) => Ok(s),
-            Err(e) => Err(Error::Message(e.to_string())),
+            Err(e) => self
+                .invalid_utf8(e.as_bytes(), e.utf8_error(), start)
+                .map(Cow::into_owned),
+        }
+    }
+
+    fn invalid_utf8<'b>(
+        &self,
+        bytes: &'b [u8],
+        error: Utf8Error,
+        start: usize,
+    ) -> DecodeResult<Cow<'b, str>> {
+        match self.config.utf8 {
+            Utf8Policy::Strict => Err(DecodeError::InvalidUtf8 {
+                offset: start + error.valid_up_to(),
+                source: error,
+            }),
+            Utf8Policy::Lossy => Ok(String::from_utf8_lossy(bytes)),
         }
     }
 
-    fn read_map_length(&mut self) -> Result<u32> {
-        let next_format = self.peek_format()?;
+    /// Reads a 128-bit integer written as a decimal string.
+    fn parse_wide_integer<T>(&mut self) -> DecodeResult<T>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        let str = self.parse_str()?;
+        str.parse().map_err(|e| {
+            DecodeError::ExpectedInteger(format!(
+                "Invalid integer '{str}': {e}"
+            ))
+        })
+    }
 
-        if let Format::Nil = next_format {
-            return Ok(0);
+    /// Reads a string holding a decimal number when lenient numeric strings
+    /// are enabled and the next value is a string.
+    fn parse_numeric_string<T>(&mut self) -> DecodeResult<Option<T>>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        if !self.config.numeric_strings {
+            return Ok(None);
         }
 
-        match Format::get_format(self)? {
-            Format::FixMap(len) => Ok(len as u32),
-            Format::Map16 => {
-                Ok(ReadBytesExt::read_u16::<BigEndian>(self)? as u32)
+        match self.peek_format()? {
+            Format::FixStr(_)
+            | Format::Str8
+            | Format::Str16
+            | Format::Str32 => {
+                let str = self.parse_str()?;
+                match str.trim().parse::<T>() {
+                    Ok(v) => Ok(Some(v)),
+                    Err(e) => Err(DecodeError::Message(format!(
+                        "Invalid numeric string '{str}': {e}"
+                    ))),
+                }
             }
-            Format::Map32 => Ok(ReadBytesExt::read_u32::<BigEndian>(self)?),
-            Format::Nil => Ok(0),
-            err_f => {
-                let formatted_err = format!(
-                    "Property must be of type 'map'. {}",
-                    get_error_message(err_f)
-                );
-                Err(Error::ExpectedMap(formatted_err))
+            _ => Ok(None),
+        }
+    }
+
+    /// Reads a float where an integer is expected, as allowed by the
+    /// `float_to_int` policy.
+    fn parse_float_as_integer(&mut self) -> DecodeResult<Option<f64>> {
+        let v = match (self.config.float_to_int, self.peek_format()?) {
+            (FloatToInt::Reject, _) => return Ok(None),
+            (_, Format::Float32) => {
+                Format::get_format(self)?;
+                ReadBytesExt::read_f32::<BigEndian>(self)? as f64
             }
+            (_, Format::Float64) => {
+                Format::get_format(self)?;
+                ReadBytesExt::read_f64::<BigEndian>(self)?
+            }
+            _ => return Ok(None),
+        };
+
+        let integer = match self.config.float_to_int {
+            FloatToInt::Round => round(v),
+            _ => v,
+        };
+        if !integer.is_finite() || fract(integer) != 0.0 {
+            return Err(DecodeError::ExpectedInteger(format!(
+                "float {v} is not a whole number"
+            )));
         }
+        Ok(Some(integer))
     }
 
-    fn read_bytes_length(&mut self) -> Result<u32> {
-        let next_format = self.peek_format()?;
+    /// Reads an integer where a float is expected, when lenient numbers are
+    /// enabled.
+    fn parse_integer_as_float(&mut self) -> DecodeResult<Option<f64>> {
+        if !self.config.int_to_float {
+            return Ok(None);
+        }
 
-        if let Format::Nil = next_format {
-            return Ok(0);
+        match self.peek_format()? {
+            Format::PositiveFixInt(_)
+            | Format::Uint8
+            | Format::Uint16
+            | Format::Uint32
+            | Format::Uint64 => Ok(Some(self.parse_unsigned()? as f64)),
+            Format::NegativeFixInt(_)
+            | Format::Int8
+            | Format::Int16
+            | Format::Int32
+            | Format::Int64 => Ok(Some(self.parse_signed()? as f64)),
+            _ => Ok(None),
         }
+    }
 
-        match Format::get_format(self)? {
-            Format::FixArray(len) => Ok(len as u32),
-            Format::Bin8 => Ok(ReadBytesExt::read_u8(self)? as u32),
-            Format::Bin16 => {
-                Ok(ReadBytesExt::read_u16::<BigEndian>(self)? as u32)
+    fn read_map_length(&mut self) -> DecodeResult<u32> {
+        let len = match Format::get_format(self)? {
+            Format::FixMap(len) => len as u32,
+            Format::Map16 => {
+                ReadBytesExt::read_u16::<BigEndian>(self)? as u32
+            }
+            Format::Map32 => ReadBytesExt::read_u32::<BigEndian>(self)?,
+            Format::Nil => 0,
+            err_f => {
+                let formatted_err = format!(
+                    "Property must be of type 'map'. {}",
+                    get_error_message(err_f)
+                );
+                return Err(DecodeError::ExpectedMap(formatted_err));
             }
-            Format::Bin32 => Ok(ReadBytesExt::read_u32::<BigEndian>(self)?),
-            Format::Nil => Ok(0),
+        };
+        self.check_collection_len(len)?;
+        self.check_remaining(len, 2)
+    }
+
+    /// Strings are accepted too, whatever the UTF-8 policy, so that fields
+    /// which may hold invalid UTF-8 can be decoded as raw bytes.
+    fn read_bytes_length(&mut self) -> DecodeResult<u32> {
+        let len = match Format::get_format(self)? {
+            Format::FixArray(len) => len as u32,
+            Format::FixStr(len) => len as u32,
+            Format::Str8 => ReadBytesExt::read_u8(self)? as u32,
+            Format::Str16 => ReadBytesExt::read_u16::<BigEndian>(self)? as u32,
+            Format::Str32 => ReadBytesExt::read_u32::<BigEndian>(self)?,
+            Format::Bin8 => ReadBytesExt::read_u8(self)? as u32,
+            Format::Bin16 => ReadBytesExt::read_u16::<BigEndian>(self)? as u32,
+            Format::Bin32 => ReadBytesExt::read_u32::<BigEndian>(self)?,
+            Format::Nil => 0,
             err_f => {
                 let formatted_err = format!(
                     "Property must be of type 'bytes'. {}",
                     get_error_message(err_f)
                 );
-                Err(Error::ExpectedBytes(formatted_err))
+                return Err(DecodeError::ExpectedBytes(formatted_err));
             }
-        }
+        };
+        self.check_payload_len(len, self.config.max_bytes_len, "Bin")
     }
 
-    fn parse_unsigned(&mut self) -> Result<u64> {
+    fn parse_unsigned(&mut self) -> DecodeResult<u64> {
+        if self.take_nil_as_default()? {
+            return Ok(0);
+        }
+        if let Some(v) = self.parse_numeric_string()? {
+            return Ok(v);
+        }
+        if let Some(v) = self.parse_float_as_integer()? {
+            // u64::MAX as f64 rounds up to 2^64, which is out of range
+            if v < 0.0 || v >= u64::MAX as f64 {
+                return Err(DecodeError::ExpectedUInteger(format!(
+                    "float {v} is out of range for an unsigned integer"
+                )));
+            }
+            return Ok(v as u64);
+        }
+
         let f = Format::get_format(self)?;
         match f {
             Format::PositiveFixInt(v) => Ok(v as u64),
@@ -204,7 +831,7 @@ impl Deserializer {
                     get_error_message(f)
                 );
 
-                Err(Error::ExpectedUInteger(formatted_err))
+                Err(DecodeError::ExpectedUInteger(formatted_err))
             }
             Format::Uint8 => Ok(ReadBytesExt::read_u8(self)? as u64),
             Format::Uint16 => {
@@ -225,7 +852,7 @@ impl Deserializer {
                     "unsigned integer cannot be negative. {}",
                     get_error_message(f)
                 );
-                Err(Error::ExpectedUInteger(formatted_err))
+                Err(DecodeError::ExpectedUInteger(formatted_err))
             }
             Format::Int16 => {
                 let int16 = ReadBytesExt::read_i16::<BigEndian>(self)?;
@@ -238,7 +865,7 @@ impl Deserializer {
                     "unsigned integer cannot be negative. {}",
                     get_error_message(f)
                 );
-                Err(Error::ExpectedUInteger(formatted_err))
+                Err(DecodeError::ExpectedUInteger(formatted_err))
             }
             Format::Int32 => {
                 let int32 = ReadBytesExt::read_i32::<BigEndian>(self)?;
@@ -251,7 +878,7 @@ impl Deserializer {
                     "unsigned integer cannot be negative. {}",
                     get_error_message(f)
                 );
-                Err(Error::ExpectedUInteger(formatted_err))
+                Err(DecodeError::ExpectedUInteger(formatted_err))
             }
             Format::Int64 => {
                 let int64 = ReadBytesExt::read_i64::<BigEndian>(self)?;
@@ -264,7 +891,7 @@ impl Deserializer {
                     "unsigned integer cannot be negative. {}",
                     get_error_message(f)
                 );
-                Err(Error::ExpectedUInteger(formatted_err))
+                Err(DecodeError::ExpectedUInteger(formatted_err))
             }
 
             err_f => {
@@ -272,12 +899,28 @@ impl Deserializer {
                     "Property must be of type 'uint'. {}",
                     get_error_message(err_f)
                 );
-                Err(Error::ExpectedUInteger(formatted_err))
+                Err(DecodeError::ExpectedUInteger(formatted_err))
             }
         }
     }
 
-    fn parse_signed(&mut self) -> Result<i64> {
+    fn parse_signed(&mut self) -> DecodeResult<i64> {
+        if self.take_nil_as_default()? {
+            return Ok(0);
+        }
+        if let Some(v) = self.parse_numeric_string()? {
+            return Ok(v);
+        }
+        if let Some(v) = self.parse_float_as_integer()? {
+            // i64::MAX as f64 rounds up to 2^63, which is out of range
+            if v < i64::MIN as f64 || v >= i64::MAX as f64 {
+                return Err(DecodeError::ExpectedInteger(format!(
+                    "float {v} is out of range for an integer"
+                )));
+            }
+            return Ok(v as i64);
+        }
+
         match Format::get_format(self)? {
             Format::PositiveFixInt(v) => Ok(v as i64),
             Format::NegativeFixInt(v) => Ok(v as i64),
@@ -304,7 +947,7 @@ impl Deserializer {
                 } else {
                     let formatted_err =
                         format!("integer overflow: value = {}; bits = 64", v);
-                    Err(Error::Message(formatted_err))
+                    Err(DecodeError::Message(formatted_err))
                 }
             }
             err_f => {
@@ -312,16 +955,16 @@ impl Deserializer {
                     "Property must be of type 'int'. {}",
                     get_error_message(err_f)
                 );
-                Err(Error::ExpectedInteger(formatted_err))
+                Err(DecodeError::ExpectedInteger(formatted_err))
             }
         }
     }
 }
 
-impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer {
-    type Error = Error;
+impl<'de, R: Read<'de>> de::Deserializer<'de> for &mut Deserializer<R> {
+    type Error = DecodeError;
 
-    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
+    fn deserialize_any<V>(self, visitor: V) -> DecodeResult<V::Value>
     where
         V: Visitor<'de>,
     {
@@ -338,9 +981,11 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer {
             Format::FixStr(_)
             | Format::Str8
             | Format::Str16
-            | Format::Str32 => self.deserialize_string(visitor),
+            | Format::Str32 => self.deserialize_str(visitor),
             Format::Nil => self.deserialize_unit(visitor),
-            Format::Reserved => todo!(),
+            Format::Reserved => Err(DecodeError::Message(
+                "Found reserved format byte 0xc1".to_string(),
+            )),
             Format::False | Format::True => self.deserialize_bool(visitor),
             Format::Bin8 | Format::Bin16 | Format::Bin32 => {
                 self.deserialize_bytes(visitor)
@@ -362,19 +1007,26 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer {
             | Format::Ext8
             | Format::Ext16
             | Format::Ext32 => {
-                let (_, ext_type) = self.read_ext_length_and_type()?;
-
-                match ext_type {
-                    ExtensionType::GenericMap => self.deserialize_map(visitor),
+                let (len, ext_type) = self.read_ext_header()?;
+                if ext_type as u8 == u8::from(ExtensionType::GenericMap) {
+                    return self.deserialize_map(visitor);
                 }
+
+                // Exts the crate doesn't interpret are handed over as their
+                // raw type and data, like rmp-serde does.
+                self.visit_raw_ext(len, ext_type, visitor)
             }
         }
     }
 
-    fn deserialize_bool<V>(self, visitor: V) -> Result<V::Value>
+    fn deserialize_bool<V>(self, visitor: V) -> DecodeResult<V::Value>
     where
         V: Visitor<'de>,
     {
+        if self.take_nil_as_default()? {
+            return visitor.visit_bool(false);
+        }
+
         match Format::get_format(self)? {
             Format::True => visitor.visit_bool(true),
             Format::False => visitor.visit_bool(false),
@@ -383,12 +1035,12 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer {
                     "Property must be of type 'bool'. {}",
                     get_error_message(err_f)
                 );
-                Err(Error::ExpectedBoolean(formatted_err))
+                Err(DecodeError::ExpectedBoolean(formatted_err))
             }
         }
     }
 
-    fn deserialize_i8<V>(self, visitor: V) -> Result<V::Value>
+    fn deserialize_i8<V>(self, visitor: V) -> DecodeResult<V::Value>
     where
         V: Visitor<'de>,
     {
@@ -398,11 +1050,11 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer {
         } else {
             let formatted_err =
                 format!("integer overflow: value = {}; bits = 8", v);
-            Err(Error::Message(formatted_err))
+            Err(DecodeError::Message(formatted_err))
         }
     }
 
-    fn deserialize_i16<V>(self, visitor: V) -> Result<V::Value>
+    fn deserialize_i16<V>(self, visitor: V) -> DecodeResult<V::Value>
     where
         V: Visitor<'de>,
     {
@@ -412,11 +1064,11 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer {
         } else {
             let formatted_err =
                 format!("integer overflow: value = {}; bits = 16", v);
-            Err(Error::Message(formatted_err))
+            Err(DecodeError::Message(formatted_err))
         }
     }
 
-    fn deserialize_i32<V>(self, visitor: V) -> Result<V::Value>
+    fn deserialize_i32<V>(self, visitor: V) -> DecodeResult<V::Value>
     where
         V: Visitor<'de>,
     {
@@ -426,18 +1078,36 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer {
         } else {
             let formatted_err =
                 format!("integer overflow: value = {}; bits = 32", v);
-            Err(Error::Message(formatted_err))
+            Err(DecodeError::Message(formatted_err))
         }
     }
 
-    fn deserialize_i64<V>(self, visitor: V) -> Result<V::Value>
+    fn deserialize_i64<V>(self, visitor: V) -> DecodeResult<V::Value>
     where
         V: Visitor<'de>,
     {
         visitor.visit_i64(self.parse_signed()?)
     }
 
-    fn deserialize_u8<V>(self, visitor: V) -> Result<V::Value>
+    /// Accepts any integer format, as well as the decimal string values
+    /// beyond 64 bits are written as.
+    fn deserialize_i128<V>(self, visitor: V) -> DecodeResult<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self.peek_format()? {
+            Format::FixStr(_)
+            | Format::Str8
+            | Format::Str16
+            | Format::Str32 => visitor.visit_i128(self.parse_wide_integer()?),
+            Format::Uint64 => {
+                visitor.visit_i128(self.parse_unsigned()? as i128)
+            }
+            _ => visitor.visit_i128(self.parse_signed()? as i128),
+        }
+    }
+
+    fn deserialize_u8<V>(self, visitor: V) -> DecodeResult<V::Value>
     where
         V: Visitor<'de>,
     {
@@ -448,11 +1118,11 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer {
         } else {
             let formatted_err =
                 format!("unsigned integer overflow: value = {}; bits = 8", v);
-            Err(Error::Message(formatted_err))
+            Err(DecodeError::Message(formatted_err))
         }
     }
 
-    fn deserialize_u16<V>(self, visitor: V) -> Result<V::Value>
+    fn deserialize_u16<V>(self, visitor: V) -> DecodeResult<V::Value>
     where
         V: Visitor<'de>,
     {
@@ -463,11 +1133,11 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer {
         } else {
             let formatted_err =
                 format!("unsigned integer overflow: value = {}; bits = 16", v);
-            Err(Error::Message(formatted_err))
+            Err(DecodeError::Message(formatted_err))
         }
     }
 
-    fn deserialize_u32<V>(self, visitor: V) -> Result<V::Value>
+    fn deserialize_u32<V>(self, visitor: V) -> DecodeResult<V::Value>
     where
         V: Visitor<'de>,
     {
@@ -478,39 +1148,86 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer {
         } else {
             let formatted_err =
                 format!("unsigned integer overflow: value = {}; bits = 32", v);
-            Err(Error::Message(formatted_err))
+            Err(DecodeError::Message(formatted_err))
         }
     }
 
-    fn deserialize_u64<V>(self, visitor: V) -> Result<V::Value>
+    fn deserialize_u64<V>(self, visitor: V) -> DecodeResult<V::Value>
     where
         V: Visitor<'de>,
     {
         visitor.visit_u64(self.parse_unsigned()?)
     }
 
-    fn deserialize_f32<V>(self, visitor: V) -> Result<V::Value>
+    /// Accepts any non-negative integer, as well as the decimal string
+    /// values beyond 64 bits are written as.
+    fn deserialize_u128<V>(self, visitor: V) -> DecodeResult<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self.peek_format()? {
+            Format::FixStr(_)
+            | Format::Str8
+            | Format::Str16
+            | Format::Str32 => visitor.visit_u128(self.parse_wide_integer()?),
+            _ => visitor.visit_u128(self.parse_unsigned()? as u128),
+        }
+    }
+
+    fn deserialize_f32<V>(self, visitor: V) -> DecodeResult<V::Value>
     where
         V: Visitor<'de>,
     {
+        if self.take_nil_as_default()? {
+            return visitor.visit_f32(0.0);
+        }
+
+        if let Some(v) = self.parse_numeric_string()? {
+            return visitor.visit_f32(v);
+        }
+        if let Some(v) = self.parse_integer_as_float()? {
+            return visitor.visit_f32(v as f32);
+        }
+
         match Format::get_format(self)? {
             Format::Float32 => {
                 visitor.visit_f32(ReadBytesExt::read_f32::<BigEndian>(self)?)
             }
+            // Lenient numbers also take float64s that fit without rounding
+            Format::Float64 if self.config.int_to_float => {
+                let v = ReadBytesExt::read_f64::<BigEndian>(self)?;
+                if v.is_finite() && v as f32 as f64 != v {
+                    return Err(DecodeError::ExpectedFloat(format!(
+                        "float64 {v} can't be represented as a float32"
+                    )));
+                }
+                visitor.visit_f32(v as f32)
+            }
             err_f => {
                 let formatted_err = format!(
                     "Property must be of type 'float32'. {}",
                     get_error_message(err_f)
                 );
-                Err(Error::ExpectedFloat(formatted_err))
+                Err(DecodeError::ExpectedFloat(formatted_err))
             }
         }
     }
 
-    fn deserialize_f64<V>(self, visitor: V) -> Result<V::Value>
+    fn deserialize_f64<V>(self, visitor: V) -> DecodeResult<V::Value>
     where
         V: Visitor<'de>,
     {
+        if self.take_nil_as_default()? {
+            return visitor.visit_f64(0.0);
+        }
+
+        if let Some(v) = self.parse_numeric_string()? {
+            return visitor.visit_f64(v);
+        }
+        if let Some(v) = self.parse_integer_as_float()? {
+            return visitor.visit_f64(v);
+        }
+
         match Format::get_format(self)? {
             Format::Float64 => {
                 visitor.visit_f64(ReadBytesExt::read_f64::<BigEndian>(self)?)
@@ -522,52 +1239,81 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer {
                     "Property must be of type 'float64'. {}",
                     get_error_message(err_f)
                 );
-                Err(Error::ExpectedFloat(formatted_err))
+                Err(DecodeError::ExpectedFloat(formatted_err))
             }
         }
     }
 
-    fn deserialize_char<V>(self, visitor: V) -> Result<V::Value>
+    fn deserialize_char<V>(self, visitor: V) -> DecodeResult<V::Value>
     where
         V: Visitor<'de>,
     {
-        // TODO: maybe better implementation
-        let str = self.parse_string()?;
-
-        if str.len() == 1 {
-            visitor.visit_char(str.chars().last().unwrap())
-        } else {
-            Err(Error::ExpectedChar(format!(
+        // A char is any string of exactly one character, which may take up
+        // to four bytes.
+        let str = self.parse_str()?;
+        let mut chars = str.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => visitor.visit_char(c),
+            _ => Err(DecodeError::ExpectedChar(format!(
                 "Expected char, found string: '{}'",
                 str
-            )))
+            ))),
         }
     }
 
-    fn deserialize_str<V>(self, visitor: V) -> Result<V::Value>
+    fn deserialize_str<V>(self, visitor: V) -> DecodeResult<V::Value>
     where
         V: Visitor<'de>,
     {
-        self.deserialize_string(visitor)
-    }
+        // Strings are lent straight out of in-memory input. Otherwise short
+        // strings are read onto the stack and lent to the visitor, so targets
+        // storing them inline (e.g. `CompactString`) never allocate.
+        const INLINE_CAPACITY: usize = 32;
+
+        let str_len = self.read_string_length()? as usize;
+        let start = self.read.position();
+        if let Some(bytes) = self.borrow_bytes(str_len) {
+            return match self.utf8_str(bytes, start)? {
+                Cow::Borrowed(s) => visitor.visit_borrowed_str(s),
+                Cow::Owned(s) => visitor.visit_string(s),
+            };
+        }
+        if str_len > INLINE_CAPACITY {
+            let bytes = self.get_bytes(str_len as u64)?;
+            return visitor.visit_string(self.utf8_string(bytes, start)?);
+        }
 
-    fn deserialize_string<V>(self, visitor: V) -> Result<V::Value>
+        let mut buf = [0; INLINE_CAPACITY];
+        self.read_exact(&mut buf[..str_len])?;
+        match self.utf8_str(&buf[..str_len], start)? {
+            Cow::Borrowed(s) => visitor.visit_str(s),
+            Cow::Owned(s) => visitor.visit_string(s),
+        }
+    }
+
+    fn deserialize_string<V>(self, visitor: V) -> DecodeResult<V::Value>
     where
         V: Visitor<'de>,
     {
-        visitor.visit_string(self.parse_string()?)
+        match self.parse_str()? {
+            Cow::Borrowed(s) => visitor.visit_borrowed_str(s),
+            Cow::Owned(s) => visitor.visit_string(s),
+        }
     }
 
-    fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value>
+    fn deserialize_bytes<V>(self, visitor: V) -> DecodeResult<V::Value>
     where
         V: Visitor<'de>,
     {
         let bytes_len = self.read_bytes_length()?;
+        if let Some(bytes) = self.borrow_bytes(bytes_len as usize) {
+            return visitor.visit_borrowed_bytes(bytes);
+        }
         let bytes = self.get_bytes(bytes_len as u64)?;
         visitor.visit_bytes(&bytes)
     }
 
-    fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value>
+    fn deserialize_byte_buf<V>(self, visitor: V) -> DecodeResult<V::Value>
     where
         V: Visitor<'de>,
     {
@@ -576,7 +1322,7 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer {
         visitor.visit_byte_buf(bytes)
     }
 
-    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value>
+    fn deserialize_option<V>(self, visitor: V) -> DecodeResult<V::Value>
     where
         V: Visitor<'de>,
     {
@@ -589,13 +1335,13 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer {
         }
     }
 
-    fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value>
+    fn deserialize_unit<V>(self, visitor: V) -> DecodeResult<V::Value>
     where
         V: Visitor<'de>,
     {
         match Format::get_format(self)? {
             Format::Nil => visitor.visit_unit(),
-            format => Err(Error::ExpectedNull(format!(
+            format => Err(DecodeError::ExpectedNull(format!(
                 "Expected null, found format: {}",
                 format
             ))),
@@ -606,7 +1352,7 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer {
         self,
         _name: &'static str,
         visitor: V,
-    ) -> Result<V::Value>
+    ) -> DecodeResult<V::Value>
     where
         V: Visitor<'de>,
     {
@@ -615,50 +1361,115 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer {
 
     fn deserialize_newtype_struct<V>(
         self,
-        _name: &'static str,
+        name: &'static str,
         visitor: V,
-    ) -> Result<V::Value>
+    ) -> DecodeResult<V::Value>
     where
         V: Visitor<'de>,
     {
+        if name == SHARED_STRING_TOKEN && self.config.intern_strings {
+            let string = self.parse_str()?;
+            let interned = match self.interned.get(string.as_ref()) {
+                Some(interned) => interned.clone(),
+                None => {
+                    let interned: Arc<str> = string.into();
+                    self.interned.insert(interned.clone());
+                    interned
+                }
+            };
+            return shared_string::with_interned(interned, |v| {
+                visitor.visit_str(v)
+            });
+        }
+
+        // Raw exts are asked for by name, so that GenericMaps can be kept
+        // undecoded too.
+        if name == EXT_TOKEN {
+            let (len, ext_type) = self.read_ext_header()?;
+            return self.visit_raw_ext(len, ext_type, visitor);
+        }
+
         visitor.visit_newtype_struct(self)
     }
 
-    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value>
+    fn deserialize_seq<V>(self, visitor: V) -> DecodeResult<V::Value>
     where
         V: Visitor<'de>,
     {
+        // A bin is visited as a sequence of bytes, read in one go, and so
+        // is a string, whether or not it's valid UTF-8, but only when the
+        // elements are `u8`s
+        let format = self.peek_format()?;
+        if matches!(
+            format,
+            Format::Bin8
+                | Format::Bin16
+                | Format::Bin32
+                | Format::FixStr(_)
+                | Format::Str8
+                | Format::Str16
+                | Format::Str32
+        ) {
+            let len = self.read_bytes_length()?;
+            return match self.borrow_bytes(len as usize) {
+                Some(bytes) => visitor.visit_seq(ByteSeqAccess::new(
+                    bytes.iter().copied(),
+                    format,
+                )),
+                None => visitor.visit_seq(ByteSeqAccess::new(
+                    self.get_bytes(len as u64)?.into_iter(),
+                    format,
+                )),
+            };
+        }
         let arr_len = self.read_array_length()?;
-        visitor.visit_seq(ArrayReadAccess::new(self, arr_len))
+        self.nested(|de| visitor.visit_seq(ArrayReadAccess::new(de, arr_len)))
     }
 
-    fn deserialize_tuple<V>(self, _len: usize, _: V) -> Result<V::Value>
+    fn deserialize_tuple<V>(
+        self,
+        len: usize,
+        visitor: V,
+    ) -> DecodeResult<V::Value>
     where
         V: Visitor<'de>,
     {
-        todo!()
+        let arr_len = self.read_array_length()?;
+        if arr_len as usize != len {
+            return Err(de::Error::invalid_length(
+                arr_len as usize,
+                &format!("a tuple of size {len}").as_str(),
+            ));
+        }
+        self.nested(|de| visitor.visit_seq(ArrayReadAccess::new(de, arr_len)))
     }
 
     fn deserialize_tuple_struct<V>(
         self,
         _name: &'static str,
-        _len: usize,
-        _: V,
-    ) -> Result<V::Value>
+        len: usize,
+        visitor: V,
+    ) -> DecodeResult<V::Value>
     where
         V: Visitor<'de>,
     {
-        todo!()
+        self.deserialize_tuple(len, visitor)
     }
 
-    fn deserialize_map<V>(self, visitor: V) -> Result<V::Value>
+    fn deserialize_map<V>(self, visitor: V) -> DecodeResult<V::Value>
     where
         V: Visitor<'de>,
     {
+        if self.take_nil_as_default()? {
+            return visitor.visit_map(MapReadAccess::new(self, 0));
+        }
+
         match self.peek_format()? {
             Format::FixMap(_) | Format::Map16 | Format::Map32 => {
               let map_len = self.read_map_length()?;
-              return visitor.visit_map(MapReadAccess::new(self, map_len));
+              self.nested(|de| {
+                  visitor.visit_map(MapReadAccess::new(de, map_len))
+              })
             }
             Format::Ext8
             | Format::Ext16
@@ -671,13 +1482,13 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer {
               let (_, ext_type) = self.read_ext_length_and_type()?;
 
               if let ExtensionType::GenericMap = ext_type {
-                self.deserialize_map(visitor)
+                self.nested(|de| de.deserialize_map(visitor))
               } else {
-                Err(Error::ExpectedMap(format!("Expected map or ext type 1 (generic map), but found Ext type '{ext_type:?}'")))
+                Err(DecodeError::ExpectedMap(format!("Expected map or ext type 1 (generic map), but found Ext type '{ext_type:?}'")))
               }
             },
             format => {
-              Err(Error::ExpectedMap(format!("Expected map or ext type 1 (generic map), but found: '{format}'")))
+              Err(DecodeError::ExpectedMap(format!("Expected map or ext type 1 (generic map), but found: '{format}'")))
             }
         }
     }
@@ -685,15 +1496,42 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer {
     fn deserialize_struct<V>(
         self,
         _name: &'static str,
-        _fields: &'static [&'static str],
+        fields: &'static [&'static str],
         visitor: V,
-    ) -> Result<V::Value>
+    ) -> DecodeResult<V::Value>
     where
         V: Visitor<'de>,
     {
-        let map_len = self.read_map_length()?;
+        if let Format::FixArray(_) | Format::Array16 | Format::Array32 =
+            self.peek_format()?
+        {
+            let len = self.read_array_length()?;
+            return self
+                .nested(|de| visitor.visit_seq(ArrayReadAccess::new(de, len)));
+        }
 
-        visitor.visit_map(MapReadAccess::new(self, map_len))
+        // Structs written as a map of a GenericMap ext (e.g. flattened
+        // structs from older encoders) are accepted too.
+        if let Format::FixExt1
+        | Format::FixExt2
+        | Format::FixExt4
+        | Format::FixExt8
+        | Format::FixExt16
+        | Format::Ext8
+        | Format::Ext16
+        | Format::Ext32 = self.peek_format()?
+        {
+            let (_, ext_type) = self.read_ext_length_and_type()?;
+            if !matches!(ext_type, ExtensionType::GenericMap) {
+                return Err(DecodeError::ExpectedMap(format!(
+                    "Expected struct as a map, but found Ext type \
+                     '{ext_type:?}'"
+                )));
+            }
+            // The ext counts as a level of its own, as in `deserialize_map`
+            return self.nested(|de| de.read_struct_map(fields, visitor));
+        }
+        self.read_struct_map(fields, visitor)
     }
 
     fn deserialize_enum<V>(
@@ -701,7 +1539,7 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer {
         _name: &'static str,
         variants: &'static [&'static str],
         visitor: V,
-    ) -> Result<V::Value>
+    ) -> DecodeResult<V::Value>
     where
         V: Visitor<'de>,
     {
@@ -723,7 +1561,7 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer {
                     let variant = variant.to_string();
                     visitor.visit_enum(variant.into_deserializer())
                 } else {
-                    Err(Error::ExpectedUInteger(
+                    Err(DecodeError::ExpectedUInteger(
                       format!("Expected enum variant as an unsigned integer. Could not find varitant with index {index} for enum {_name}")
                     ))
                 }
@@ -732,33 +1570,127 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer {
             | Format::Str16
             | Format::Str32
             | Format::FixStr(_) => {
-                visitor.visit_enum(self.parse_string()?.into_deserializer())
+                visitor.visit_enum(self.parse_str()?.into_deserializer())
+            }
+            Format::FixMap(_) | Format::Map16 | Format::Map32 => {
+                let len = self.read_map_length()?;
+                if len != 1 {
+                    return Err(DecodeError::ExpectedEnum(format!(
+                        "Expected a map holding a single variant of enum \
+                         {_name}, found {len} entries"
+                    )));
+                }
+                self.nested(|de| visitor.visit_enum(Enum::new(de)))
             }
-            format => Err(Error::Message(format!(
+            format => Err(DecodeError::Message(format!(
                 "Expected valid enum variant, found: {}",
                 format
             ))),
         }
     }
 
-    fn deserialize_identifier<V>(self, visitor: V) -> Result<V::Value>
+    fn deserialize_identifier<V>(self, visitor: V) -> DecodeResult<V::Value>
     where
         V: Visitor<'de>,
     {
-        self.deserialize_str(visitor)
+        // Enum variants are written as their index, so identifiers (e.g.
+        // enum map keys) may arrive as integers as well as names.
+        match self.peek_format()? {
+            Format::Uint8
+            | Format::Uint16
+            | Format::Uint32
+            | Format::Uint64
+            | Format::Int8
+            | Format::Int16
+            | Format::Int32
+            | Format::Int64
+            | Format::NegativeFixInt(_)
+            | Format::PositiveFixInt(_) => {
+                visitor.visit_u64(self.parse_unsigned()?)
+            }
+            _ => self.deserialize_str(visitor),
+        }
     }
 
-    fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value>
+    fn deserialize_ignored_any<V>(self, visitor: V) -> DecodeResult<V::Value>
     where
         V: Visitor<'de>,
     {
-        self.deserialize_any(visitor)
+        // Only a struct field's value itself is an unknown field, not
+        // something ignored further inside a known one.
+        if self.observes_unknown_fields()
+            && self.observed.value_start.take() == Some(self.read.position())
+        {
+            return self.skip_unknown_field(visitor);
+        }
+        self.skip_value()?;
+        visitor.visit_unit()
+    }
+}
+
+// Float rounding lives in std; without it, num-traits provides it.
+
+#[cfg(feature = "std")]
+fn round(v: f64) -> f64 {
+    v.round()
+}
+
+#[cfg(not(feature = "std"))]
+fn round(v: f64) -> f64 {
+    FloatCore::round(v)
+}
+
+#[cfg(feature = "std")]
+fn fract(v: f64) -> f64 {
+    v.fract()
+}
+
+#[cfg(not(feature = "std"))]
+fn fract(v: f64) -> f64 {
+    FloatCore::fract(v)
+}
+
+/// Validates `bytes` as UTF-8, with SIMD when the `simdutf8` feature is on.
+/// Invalid input goes through std again to locate the first bad byte.
+fn str_from_utf8(bytes: &[u8]) -> core::result::Result<&str, Utf8Error> {
+    #[cfg(feature = "simdutf8")]
+    if let Ok(s) = simdutf8::basic::from_utf8(bytes) {
+        return Ok(s);
     }
+    core::str::from_utf8(bytes)
 }
 
-impl Read for Deserializer {
-    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
-        self.buffer.read(&mut *buf)
+impl<'de, R: Read<'de>> io::Read for Deserializer<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.read.read(buf)?;
+        if let Some(raw_value) = &mut self.observed.raw_value {
+            raw_value.extend_from_slice(&buf[..n]);
+        }
+        Ok(n)
+    }
+
+    /// Reports a short read as a [`ShortRead`], which converts into
+    /// [`DecodeError::Eof`], so that truncated headers can be told apart from
+    /// other IO failures.
+    fn read_exact(&mut self, buf: &mut [u8]) -> io::Result<()> {
+        let offset = self.read.position();
+        let mut filled = 0;
+        while filled < buf.len() {
+            match self.read(&mut buf[filled..]) {
+                Ok(0) => {
+                    return Err(ShortRead {
+                        expected: buf.len(),
+                        available: filled,
+                        offset,
+                    }
+                    .into())
+                }
+                Ok(n) => filled += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
     }
 }
 
@@ -768,12 +1700,16 @@ impl Read for Deserializer {
 mod tests {
     use std::{
         collections::{BTreeMap, HashMap},
+        io::IoSlice,
         str::FromStr,
     };
 
     use num_bigint::BigInt;
     use serde_derive::Deserialize;
 
+    use super::{
+        from_slice_with_config, from_slices, DeserializerConfig, FloatToInt,
+    };
     use crate::{
         from_slice,
         wrappers::{polywrap_bigint::BigIntWrapper, polywrap_json::JSONString},
@@ -785,6 +1721,171 @@ mod tests {
         assert_eq!("".to_string(), result);
     }
 
+    #[test]
+    fn test_read_char() {
+        assert_eq!('a', from_slice::<char>(&[161, 97]).unwrap());
+        assert_eq!('é', from_slice::<char>(&[162, 0xc3, 0xa9]).unwrap());
+        assert_eq!(
+            '🦀',
+            from_slice::<char>(&[164, 0xf0, 0x9f, 0xa6, 0x80]).unwrap()
+        );
+        assert!(from_slice::<char>(&[160]).is_err());
+        assert!(from_slice::<char>(&[162, 97, 98]).is_err());
+    }
+
+    #[test]
+    fn test_read_invalid_utf8() {
+        use serde_bytes::ByteBuf;
+
+        use super::Utf8Policy;
+        use crate::DecodeError;
+
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Foo {
+            a: String,
+        }
+
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct RawFoo {
+            a: ByteBuf,
+        }
+
+        // {"a": "x\xffy"}
+        let bytes = [129, 161, 97, 163, b'x', 0xff, b'y'];
+        match from_slice::<Foo>(&bytes).unwrap_err() {
+            DecodeError::InvalidUtf8 { offset, .. } => assert_eq!(5, offset),
+            err => panic!("unexpected error {err:?}"),
+        }
+
+        let lossy = DeserializerConfig::new().utf8_policy(Utf8Policy::Lossy);
+        assert_eq!(
+            Foo {
+                a: "x\u{fffd}y".to_string()
+            },
+            from_slice_with_config(&bytes, lossy.clone()).unwrap()
+        );
+        let boxed: Box<str> =
+            from_slice_with_config(&bytes[3..], lossy).unwrap();
+        assert_eq!("x\u{fffd}y", &*boxed);
+
+        assert_eq!(
+            RawFoo {
+                a: ByteBuf::from(vec![b'x', 0xff, b'y'])
+            },
+            from_slice(&bytes).unwrap()
+        );
+        assert_eq!(
+            vec![b'x', 0xff, b'y'],
+            from_slice::<Vec<u8>>(&bytes[3..]).unwrap()
+        );
+
+        // Other sequences aren't read from strings
+        assert!(from_slice::<Vec<u32>>(&[0xa3, b'a', b'b', b'c']).is_err());
+        assert!(from_slice::<Vec<f64>>(&[0xa2, b'a', b'b']).is_err());
+    }
+
+    #[test]
+    fn test_read_bin_as_seq() {
+        use super::from_reader;
+
+        // bin [1, 200], e.g. a `Vec<u8>` written with `byte_seqs_as_bin`
+        let bytes = [0xc4, 2, 1, 200];
+        assert_eq!(vec![1, 200], from_slice::<Vec<u8>>(&bytes).unwrap());
+        let read: Vec<u8> = from_reader(&bytes[..]).unwrap();
+        assert_eq!(vec![1, 200], read);
+
+        // Only byte sequences are read from a bin
+        let err = from_slice::<Vec<u32>>(&bytes).unwrap_err();
+        assert_eq!(
+            "Expected Array: `Property must be of type 'array'. Found \
+             'BIN8'.`",
+            err.to_string()
+        );
+        assert!(from_reader::<_, Vec<u32>>(&bytes[..]).is_err());
+        assert!(from_slice::<Vec<f64>>(&bytes).is_err());
+        assert!(from_slice::<Vec<i8>>(&bytes).is_err());
+        assert!(from_slice::<Vec<crate::Value>>(&bytes).is_err());
+    }
+
+    #[test]
+    fn test_read_long_utf8_strings() {
+        use super::from_reader;
+        use crate::{to_vec, DecodeError};
+
+        // Long enough to go through the SIMD validator's chunked loop
+        let text = "héllo wörld, ".repeat(40);
+        let bytes = to_vec(&text).unwrap();
+        assert_eq!(text, from_slice::<String>(&bytes).unwrap());
+        assert_eq!(text, from_reader::<_, String>(&bytes[..]).unwrap());
+
+        let mut invalid = bytes.clone();
+        let bad = invalid.len() - 7;
+        invalid[bad] = 0xff;
+        for err in [
+            from_slice::<String>(&invalid).unwrap_err(),
+            from_reader::<_, String>(&invalid[..]).unwrap_err(),
+        ] {
+            match err {
+                DecodeError::InvalidUtf8 { offset, .. } => {
+                    assert_eq!(bad, offset)
+                }
+                err => panic!("unexpected error {err:?}"),
+            }
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_read_string_borrowed_from_slice() {
+        use std::io::Cursor;
+
+        use serde::de::{Deserialize, Visitor};
+
+        use super::from_reader;
+
+        /// Records whether a string was lent by the input.
+        #[derive(Debug, PartialEq)]
+        struct Lent(bool);
+
+        impl<'de> Deserialize<'de> for Lent {
+            fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+            where
+                D: serde::Deserializer<'de>,
+            {
+                struct LentVisitor;
+                impl Visitor<'_> for LentVisitor {
+                    type Value = Lent;
+
+                    fn expecting(
+                        &self,
+                        f: &mut core::fmt::Formatter,
+                    ) -> core::fmt::Result {
+                        f.write_str("a string")
+                    }
+
+                    fn visit_borrowed_str<E>(
+                        self,
+                        _: &str,
+                    ) -> Result<Lent, E> {
+                        Ok(Lent(true))
+                    }
+
+                    fn visit_str<E>(self, _: &str) -> Result<Lent, E> {
+                        Ok(Lent(false))
+                    }
+                }
+                deserializer.deserialize_string(LentVisitor)
+            }
+        }
+
+        let bytes = [163, b'a', b'b', b'c'];
+        assert_eq!(Lent(true), from_slice(&bytes).unwrap());
+        assert_eq!(Lent(false), from_reader(Cursor::new(bytes)).unwrap());
+        let split: [&[u8]; 2] = [&bytes[..2], &bytes[2..]];
+        assert_eq!(Lent(false), from_slices(&split).unwrap());
+        assert_eq!('é', from_slice::<char>(&[162, 0xc3, 0xa9]).unwrap());
+    }
+
     #[test]
     fn test_read_string_5char() {
         let result: String =
@@ -900,6 +2001,58 @@ mod tests {
         assert_eq!(result[&2], vec![1, 4, 7]);
     }
 
+    #[test]
+    fn test_read_plain_maps() {
+        let plain: BTreeMap<i32, Vec<i32>> =
+            from_slice(&[130, 1, 147, 3, 5, 9, 2, 147, 1, 4, 7]).unwrap();
+        let ext: BTreeMap<i32, Vec<i32>> =
+            from_slice(&[199, 11, 1, 130, 1, 147, 3, 5, 9, 2, 147, 1, 4, 7])
+                .unwrap();
+        assert_eq!(plain, ext);
+
+        // A plain map nesting an ext-wrapped one: {"a": {"b": 1}}
+        let mixed: HashMap<String, HashMap<String, u8>> =
+            from_slice(&[129, 161, 97, 199, 4, 1, 129, 161, 98, 1]).unwrap();
+        assert_eq!(1, mixed["a"]["b"]);
+
+        let mut hash_map = HashMap::new();
+        for i in 0..20u16 {
+            hash_map.insert(i.to_string(), i * 1000);
+        }
+        let bytes = rmp_serde::to_vec(&hash_map).unwrap();
+        assert_eq!(
+            hash_map,
+            from_slice::<HashMap<String, u16>>(&bytes).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_read_fix_ext_maps() {
+        use crate::{to_vec, Value};
+
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Empty {}
+
+        // Payloads of 1, 4, 8 and 16 bytes, written as FixExt1/4/8/16
+        for (key, marker) in [("a", 0xd6), ("abcde", 0xd7)] {
+            let map = BTreeMap::from([(key.to_string(), 1u8)]);
+            let bytes = to_vec(&map).unwrap();
+            assert_eq!([marker, 1], bytes[..2]);
+            assert_eq!(map, from_slice::<BTreeMap<_, _>>(&bytes).unwrap());
+            let value = from_slice::<Value>(&bytes).unwrap();
+            assert!(matches!(value, Value::Map(_)));
+        }
+        let key = "abcdefghijklm".to_string();
+        let map = HashMap::from([(key, 1u8)]);
+        let bytes = to_vec(&map).unwrap();
+        assert_eq!([0xd8, 1], bytes[..2]);
+        assert_eq!(map, from_slice::<HashMap<_, _>>(&bytes).unwrap());
+
+        let empty = to_vec(&BTreeMap::<String, u8>::new()).unwrap();
+        assert_eq!(vec![0xd4, 1, 0x80], empty);
+        assert_eq!(Empty {}, from_slice(&empty).unwrap());
+    }
+
     #[test]
     fn test_read_bool_true() {
         let result: bool = from_slice(&[195]).unwrap();
@@ -1048,6 +2201,853 @@ mod tests {
         assert_eq!(foo, result);
     }
 
+    #[test]
+    fn test_read_enum_map_keys() {
+        #[derive(Deserialize, PartialEq, Eq, PartialOrd, Ord, Debug)]
+        enum Foo {
+            First,
+            Second,
+        }
+
+        let mut expected = BTreeMap::new();
+        expected.insert(Foo::First, 3u8);
+        expected.insert(Foo::Second, 5u8);
+
+        let cases: [&[u8]; 4] = [
+            // ext generic map, keys as variant indices
+            &[199, 5, 1, 130, 0, 3, 1, 5],
+            // plain map, keys as variant indices
+            &[130, 0, 3, 1, 5],
+            // ext generic map, keys as variant names
+            &[
+                199, 16, 1, 130, 165, 70, 105, 114, 115, 116, 3, 166, 83, 101,
+                99, 111, 110, 100, 5,
+            ],
+            // plain map, keys as variant names
+            &[
+                130, 165, 70, 105, 114, 115, 116, 3, 166, 83, 101, 99, 111,
+                110, 100, 5,
+            ],
+        ];
+
+        for case in cases {
+            let result: BTreeMap<Foo, u8> = from_slice(case).unwrap();
+            assert_eq!(expected, result);
+        }
+    }
+
+    #[test]
+    fn test_read_numeric_strings() {
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Foo {
+            int: u32,
+            neg: i8,
+            float: f64,
+        }
+
+        // {"int": "42", "neg": "-3", "float": "0.5"}
+        let bytes = [
+            131, 163, 105, 110, 116, 162, 52, 50, 163, 110, 101, 103, 162, 45,
+            51, 165, 102, 108, 111, 97, 116, 163, 48, 46, 53,
+        ];
+
+        let config = DeserializerConfig::new().numeric_strings(true);
+        let result: Foo = from_slice_with_config(&bytes, config).unwrap();
+        assert_eq!(
+            Foo {
+                int: 42,
+                neg: -3,
+                float: 0.5
+            },
+            result
+        );
+
+        assert!(from_slice::<Foo>(&bytes).is_err());
+
+        let config = DeserializerConfig::new().numeric_strings(true);
+        let err = from_slice_with_config::<u32>(&[163, 52, 46, 50], config)
+            .unwrap_err();
+        assert!(err.to_string().contains("Invalid numeric string '4.2'"));
+    }
+
+    #[test]
+    fn test_read_float_to_int() {
+        let exact = DeserializerConfig::new().float_to_int(FloatToInt::Exact);
+        let round = DeserializerConfig::new().float_to_int(FloatToInt::Round);
+
+        // 1.0 as Float32 and 2.5 / -2.5 / 1e20 as Float64
+        let one = [202, 63, 128, 0, 0];
+        let two_and_a_half = [203, 64, 4, 0, 0, 0, 0, 0, 0];
+        let minus_two_and_a_half = [203, 192, 4, 0, 0, 0, 0, 0, 0];
+        let huge = [203, 68, 21, 175, 29, 120, 181, 140, 64];
+
+        assert!(from_slice::<u32>(&one).is_err());
+        assert_eq!(
+            1,
+            from_slice_with_config::<u32>(&one, exact.clone()).unwrap()
+        );
+        assert!(
+            from_slice_with_config::<u32>(&two_and_a_half, exact.clone())
+                .is_err()
+        );
+        assert_eq!(
+            3,
+            from_slice_with_config::<u32>(&two_and_a_half, round.clone())
+                .unwrap()
+        );
+        assert_eq!(
+            -3,
+            from_slice_with_config::<i8>(&minus_two_and_a_half, round.clone())
+                .unwrap()
+        );
+        assert!(from_slice_with_config::<u8>(
+            &minus_two_and_a_half,
+            round.clone()
+        )
+        .is_err());
+        assert!(from_slice_with_config::<u32>(&huge, round.clone()).is_err());
+        assert!(from_slice_with_config::<i64>(&huge, round).is_err());
+    }
+
+    #[test]
+    fn test_read_hardened_config() {
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Foo {
+            a: u8,
+        }
+
+        // {"a": 1, "b": 2}
+        let extra_field = [130, 161, 97, 1, 161, 98, 2];
+        assert_eq!(Foo { a: 1 }, from_slice(&extra_field).unwrap());
+        let strict = DeserializerConfig::new().deny_unknown_fields(true);
+        let err = from_slice_with_config::<Foo>(&extra_field, strict.clone())
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("unknown field `b`"), "{err}");
+
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Outer {
+            foo: Foo,
+        }
+
+        // {"foo": {"a": 1, "b": 2}}
+        let nested_extra =
+            [129, 163, 102, 111, 111, 130, 161, 97, 1, 161, 98, 2];
+        let err = from_slice_with_config::<Outer>(&nested_extra, strict)
+            .unwrap_err()
+            .to_string();
+        assert!(err.starts_with("`at `foo`: unknown field `b`"), "{err}");
+
+        // 1 followed by 2
+        let trailing = [1, 2];
+        assert_eq!(1, from_slice::<u8>(&trailing).unwrap());
+        let exact = DeserializerConfig::new().allow_trailing_bytes(false);
+        assert!(
+            from_slice_with_config::<u8>(&trailing, exact.clone()).is_err()
+        );
+        assert_eq!(1, from_slice_with_config::<u8>(&[1], exact).unwrap());
+
+        // [[[1]]]
+        let nested = [145, 145, 145, 1];
+        let shallow = DeserializerConfig::new().max_depth(2);
+        assert!(
+            from_slice_with_config::<Vec<Vec<Vec<u8>>>>(&nested, shallow)
+                .is_err()
+        );
+        let deep_enough = DeserializerConfig::new().max_depth(3);
+        assert_eq!(
+            vec![vec![vec![1]]],
+            from_slice_with_config::<Vec<Vec<Vec<u8>>>>(&nested, deep_enough)
+                .unwrap()
+        );
+
+        // GenericMap ext headers around a map count as nesting too
+        let mut nested = [0xc7, 0, 1].repeat(300_000);
+        nested.push(0x80);
+        let shallow = DeserializerConfig::new().max_depth(8);
+        assert!(from_slice_with_config::<BTreeMap<String, u8>>(
+            &nested, shallow
+        )
+        .is_err());
+
+        // {"a": 1} in a GenericMap ext, as a struct and as a map
+        let wrapped = [0xc7, 4, 1, 0x81, 0xa1, b'a', 1];
+        let shallow = DeserializerConfig::new().max_depth(1);
+        for err in [
+            from_slice_with_config::<Foo>(&wrapped, shallow.clone())
+                .unwrap_err(),
+            from_slice_with_config::<BTreeMap<String, u8>>(&wrapped, shallow)
+                .unwrap_err(),
+        ] {
+            assert_eq!(
+                "`Nesting exceeds the maximum depth of 1`",
+                err.to_string()
+            );
+        }
+        let deep_enough = DeserializerConfig::new().max_depth(2);
+        assert_eq!(
+            Foo { a: 1 },
+            from_slice_with_config(&wrapped, deep_enough).unwrap()
+        );
+
+        // [[[...]]], deeper than the default limit
+        let nested = [0x91; 300_000];
+        assert!(from_slice::<crate::Value>(&nested).is_err());
+
+        // [1, 2, 3]
+        let array = [147, 1, 2, 3];
+        let short = DeserializerConfig::new().max_collection_len(2);
+        assert!(from_slice_with_config::<Vec<u8>>(&array, short).is_err());
+
+        // 5 and 2.0
+        let lenient = DeserializerConfig::new().lenient_numbers(true);
+        assert!(from_slice::<f64>(&[5]).is_err());
+        assert_eq!(
+            5.0,
+            from_slice_with_config::<f64>(&[5], lenient.clone()).unwrap()
+        );
+        assert_eq!(
+            2,
+            from_slice_with_config::<u32>(&[202, 64, 0, 0, 0], lenient.clone())
+                .unwrap()
+        );
+
+        // -3 into f32, 0.5 and 0.1 as float64 into f32
+        assert_eq!(
+            -3.0,
+            from_slice_with_config::<f32>(&[253], lenient.clone()).unwrap()
+        );
+        let half = crate::to_vec(&0.5f64).unwrap();
+        assert!(from_slice::<f32>(&half).is_err());
+        assert_eq!(
+            0.5,
+            from_slice_with_config::<f32>(&half, lenient.clone()).unwrap()
+        );
+        let tenth = crate::to_vec(&0.1f64).unwrap();
+        assert!(
+            from_slice_with_config::<f32>(&tenth, lenient.clone()).is_err()
+        );
+        // -2.0 and 1.5 into integers
+        let minus_two = crate::to_vec(&-2.0f64).unwrap();
+        assert_eq!(
+            -2,
+            from_slice_with_config::<i8>(&minus_two, lenient.clone()).unwrap()
+        );
+        assert!(from_slice_with_config::<u8>(&minus_two, lenient.clone())
+            .is_err());
+        let one_and_half = crate::to_vec(&1.5f64).unwrap();
+        assert!(from_slice_with_config::<i64>(&one_and_half, lenient).is_err());
+    }
+
+    #[test]
+    fn test_read_declared_lengths() {
+        use serde_bytes::ByteBuf;
+
+        use crate::{value::Value, DecodeError};
+
+        // Lengths claiming more than the input holds fail before allocating
+        let huge_bin = [0xc6, 0xff, 0xff, 0xff, 0xff, 1];
+        match from_slice::<ByteBuf>(&huge_bin).unwrap_err() {
+            DecodeError::Eof {
+                expected,
+                available,
+                offset,
+            } => assert_eq!((0xffff_ffff, 1, 5), (expected, available, offset)),
+            err => panic!("unexpected error {err:?}"),
+        }
+        let huge_array = [0xdd, 0xff, 0xff, 0xff, 0xff, 1];
+        let err = from_slice::<Value>(&huge_array).unwrap_err();
+        assert!(matches!(err, DecodeError::Eof { .. }), "{err:?}");
+        let huge_map = [0x83, 1, 2];
+        let err = from_slice::<Value>(&huge_map).unwrap_err();
+        assert!(matches!(err, DecodeError::Eof { .. }), "{err:?}");
+        let huge_ext = [0xc7, 200, 5, 1];
+        let err = from_slice::<Value>(&huge_ext).unwrap_err();
+        assert!(matches!(err, DecodeError::Eof { .. }), "{err:?}");
+
+        // "abc" and b"abc"
+        let string = [0xa3, b'a', b'b', b'c'];
+        let bin = [0xc4, 3, 1, 2, 3];
+        let capped = DeserializerConfig::new()
+            .max_string_len(2)
+            .max_bytes_len(2);
+        let err =
+            from_slice_with_config::<String>(&string, capped.clone())
+                .unwrap_err();
+        assert_eq!(
+            "`String of 3 bytes exceeds the maximum length of 2`",
+            err.to_string()
+        );
+        let err = from_slice_with_config::<ByteBuf>(&bin, capped.clone())
+            .unwrap_err();
+        assert!(err.to_string().contains("Bin of 3 bytes"), "{err}");
+        let roomy = DeserializerConfig::new()
+            .max_string_len(3)
+            .max_bytes_len(3);
+        assert_eq!(
+            "abc",
+            from_slice_with_config::<String>(&string, roomy.clone()).unwrap()
+        );
+        assert_eq!(
+            vec![1, 2, 3],
+            from_slice_with_config::<ByteBuf>(&bin, roomy)
+                .unwrap()
+                .into_vec()
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_read_interned_strings() {
+        use crate::wrappers::shared_string::SharedString;
+        use std::sync::Arc;
+
+        // ["uri", "uri", "other"]
+        let bytes = [
+            147, 163, 117, 114, 105, 163, 117, 114, 105, 165, 111, 116, 104,
+            101, 114,
+        ];
+
+        let interned: Vec<SharedString> = from_slice_with_config(
+            &bytes,
+            DeserializerConfig::new().intern_strings(true),
+        )
+        .unwrap();
+        assert_eq!(
+            vec!["uri", "uri", "other"],
+            interned.iter().map(|s| &**s).collect::<Vec<_>>()
+        );
+        assert!(Arc::ptr_eq(&interned[0].0, &interned[1].0));
+
+        let copied: Vec<SharedString> = from_slice(&bytes).unwrap();
+        assert_eq!(interned, copied);
+        assert!(!Arc::ptr_eq(&copied[0].0, &copied[1].0));
+    }
+
+    #[test]
+    fn test_read_inline_strings() {
+        use compact_str::CompactString;
+        use smartstring::alias::String as SmartString;
+
+        let short = [163, 117, 114, 105];
+        let compact: CompactString = from_slice(&short).unwrap();
+        assert_eq!("uri", compact);
+        assert!(!compact.is_heap_allocated());
+        let smart: SmartString = from_slice(&short).unwrap();
+        assert_eq!("uri", smart);
+        assert!(smart.is_inline());
+
+        let long_str = "a".repeat(40);
+        let mut long = vec![217, 40];
+        long.extend(long_str.as_bytes());
+        let compact: CompactString = from_slice(&long).unwrap();
+        assert_eq!(long_str, compact);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_read_from_reader() {
+        use super::from_reader;
+        use crate::to_vec;
+        use std::io::{self, Cursor, Read};
+
+        /// Hands out one byte per read, like a slow socket.
+        struct Trickle<'a>(&'a [u8]);
+
+        impl Read for Trickle<'_> {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                let Some((first, rest)) = self.0.split_first() else {
+                    return Ok(0);
+                };
+                buf[0] = *first;
+                self.0 = rest;
+                Ok(1)
+            }
+        }
+
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Manifest {
+            name: String,
+            version: u32,
+            abi: Vec<u8>,
+        }
+
+        let mut map = BTreeMap::new();
+        map.insert("name", "x".repeat(100));
+        let bytes = to_vec(&map).unwrap();
+        let decoded: BTreeMap<String, String> =
+            from_reader(Trickle(&bytes)).unwrap();
+        assert_eq!("x".repeat(100), decoded["name"]);
+
+        let manifest = [
+            131, 164, 110, 97, 109, 101, 163, 102, 111, 111, 167, 118, 101,
+            114, 115, 105, 111, 110, 205, 1, 44, 163, 97, 98, 105, 146, 1, 2,
+        ];
+        let mut stream = Cursor::new([&manifest[..], &[42]].concat());
+        let first: Manifest = from_reader(&mut stream).unwrap();
+        assert_eq!(
+            Manifest {
+                name: "foo".to_string(),
+                version: 300,
+                abi: vec![1, 2],
+            },
+            first
+        );
+        assert_eq!(42, from_reader::<_, u8>(&mut stream).unwrap());
+        assert!(from_reader::<_, u8>(&mut stream).is_err());
+    }
+
+    #[test]
+    fn test_read_borrowed() {
+        use std::borrow::Cow;
+
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Borrowed<'a> {
+            name: &'a str,
+            #[serde(borrow)]
+            label: Cow<'a, str>,
+            #[serde(with = "serde_bytes")]
+            data: &'a [u8],
+        }
+
+        // { name: "foo", label: "bar", data: [1, 2] }
+        let bytes = [
+            131, 164, 110, 97, 109, 101, 163, 102, 111, 111, 165, 108, 97, 98,
+            101, 108, 163, 98, 97, 114, 164, 100, 97, 116, 97, 196, 2, 1, 2,
+        ];
+        let decoded: Borrowed = from_slice(&bytes).unwrap();
+        assert_eq!("foo", decoded.name);
+        assert!(matches!(decoded.label, Cow::Borrowed("bar")));
+        assert_eq!([1, 2], decoded.data);
+        assert_eq!(bytes[7..].as_ptr(), decoded.name.as_ptr());
+
+        let slices = [&bytes[..10], &bytes[10..]];
+        let split: Borrowed = from_slices(&slices).unwrap();
+        assert_eq!(decoded, split);
+
+        // A string straddling two slices can't be borrowed.
+        let slices = [&bytes[..8], &bytes[8..]];
+        assert!(from_slices::<Borrowed, _>(&slices).is_err());
+
+        #[derive(Deserialize)]
+        struct Fallback<'a> {
+            #[serde(borrow)]
+            name: Cow<'a, str>,
+        }
+        let fallback: Fallback = from_slices(&slices).unwrap();
+        assert!(matches!(fallback.name, Cow::Owned(name) if name == "foo"));
+
+        // Strings inside a GenericMap ext are lent too
+        let map = BTreeMap::from([("key", "x".repeat(1000))]);
+        let bytes = crate::to_vec(&map).unwrap();
+        let decoded: BTreeMap<&str, &str> = from_slice(&bytes).unwrap();
+        let value = decoded["key"];
+        assert_eq!(1000, value.len());
+        assert!(bytes.as_ptr_range().contains(&value.as_ptr()));
+    }
+
+    #[test]
+    fn test_read_serde_bytes() {
+        use super::from_reader;
+        use serde_bytes::{ByteBuf, Bytes};
+
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Payload<'a> {
+            #[serde(borrow)]
+            data: &'a Bytes,
+            copy: ByteBuf,
+        }
+
+        // { data: [1, 2], copy: [3] }
+        let bytes = [
+            130, 164, 100, 97, 116, 97, 196, 2, 1, 2, 164, 99, 111, 112, 121,
+            196, 1, 3,
+        ];
+        let payload: Payload = from_slice(&bytes).unwrap();
+        assert_eq!(Bytes::new(&[1, 2]), payload.data);
+        assert_eq!(bytes[8..].as_ptr(), payload.data.as_ptr());
+        assert_eq!(ByteBuf::from([3]), payload.copy);
+
+        let owned: ByteBuf = from_reader(&bytes[6..10]).unwrap();
+        assert_eq!(ByteBuf::from([1, 2]), owned);
+    }
+
+    #[test]
+    fn test_read_unknown_field_observer() {
+        use std::sync::{Arc, Mutex};
+
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Inner {
+            b: u8,
+        }
+
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Outer {
+            a: u8,
+            inner: Inner,
+        }
+
+        let seen = Arc::new(Mutex::new(vec![]));
+        let observer_seen = seen.clone();
+        let config =
+            DeserializerConfig::new().on_unknown_field(move |path, key, raw| {
+                observer_seen.lock().unwrap().push((
+                    path.to_string(),
+                    key.to_string(),
+                    raw.to_vec(),
+                ));
+            });
+
+        // { a: 1, extra: [1, 2], inner: { zz: "x", b: 2 } }
+        let outer: Outer = from_slice_with_config(
+            &[
+                131, 161, 97, 1, 165, 101, 120, 116, 114, 97, 146, 1, 2, 165,
+                105, 110, 110, 101, 114, 130, 162, 122, 122, 161, 120, 161, 98,
+                2,
+            ],
+            config,
+        )
+        .unwrap();
+
+        assert_eq!(
+            Outer {
+                a: 1,
+                inner: Inner { b: 2 }
+            },
+            outer
+        );
+        assert_eq!(
+            vec![
+                (String::new(), "extra".to_string(), vec![146, 1, 2]),
+                ("inner".to_string(), "zz".to_string(), vec![161, 120]),
+            ],
+            *seen.lock().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_read_missing_fields() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Inner {
+            b: Option<String>,
+        }
+
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Foo {
+            a: u8,
+            opt: Option<u8>,
+            #[serde(default)]
+            list: Vec<u8>,
+            inner: Option<Inner>,
+            nested: Inner,
+        }
+
+        // {"a": 1, "nested": {}}
+        let bytes = [130, 161, 97, 1, 166, 110, 101, 115, 116, 101, 100, 128];
+        assert_eq!(
+            Foo {
+                a: 1,
+                opt: None,
+                list: vec![],
+                inner: None,
+                nested: Inner { b: None },
+            },
+            from_slice(&bytes).unwrap()
+        );
+
+        // {"a": 1}
+        let err = from_slice::<Foo>(&[129, 161, 97, 1]).unwrap_err();
+        assert!(err.to_string().contains("missing field `nested`"), "{err}");
+    }
+
+    #[test]
+    fn test_read_non_string_map_keys() {
+        use serde_json::json;
+
+        use crate::{to_vec, value::Value};
+
+        let ints = BTreeMap::from([(1u8, 10u8), (2, 20)]);
+        let bytes = to_vec(&ints).unwrap();
+        assert_eq!(ints, from_slice::<BTreeMap<u8, u8>>(&bytes).unwrap());
+        // Typed maps don't take non-string keys by default
+        assert!(from_slice::<BTreeMap<String, u8>>(&bytes).is_err());
+        assert!(from_slice::<HashMap<String, u8>>(&bytes).is_err());
+        assert!(from_slice::<serde_json::Value>(&bytes).is_err());
+
+        let config = DeserializerConfig::new().scalar_keys_as_strings(true);
+        assert_eq!(
+            json!({"1": 10, "2": 20}),
+            from_slice_with_config::<serde_json::Value>(&bytes, config.clone())
+                .unwrap()
+        );
+        assert_eq!(
+            HashMap::from([("1".to_string(), 10), ("2".to_string(), 20)]),
+            from_slice_with_config::<HashMap<String, u8>>(
+                &bytes,
+                config.clone()
+            )
+            .unwrap()
+        );
+        // Dynamic values keep the keys as they are
+        assert_eq!(
+            Value::Map(vec![
+                (Value::Integer(1), Value::Integer(10)),
+                (Value::Integer(2), Value::Integer(20)),
+            ]),
+            from_slice::<Value>(&bytes).unwrap()
+        );
+
+        let mixed = to_vec(&Value::Map(vec![
+            (Value::Bool(true), Value::Nil),
+            (Value::Integer(-3), Value::Nil),
+            (Value::Float(1.5), Value::Nil),
+        ]))
+        .unwrap();
+        assert_eq!(
+            json!({"true": null, "-3": null, "1.5": null}),
+            from_slice_with_config::<serde_json::Value>(&mixed, config.clone())
+                .unwrap()
+        );
+
+        let bin_keys =
+            to_vec(&Value::Map(vec![(Value::Bytes(vec![1]), Value::Nil)]))
+                .unwrap();
+        assert!(
+            from_slice_with_config::<serde_json::Value>(&bin_keys, config)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_read_integer_field_keys() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Foo {
+            a: u8,
+            b: String,
+            c: Option<bool>,
+        }
+
+        // {0: 1, 1: "x"}, {1: "x", "a": 1, 9: true}
+        let indexed = [130, 0, 1, 1, 161, b'x'];
+        let mixed = [131, 1, 161, b'x', 161, b'a', 1, 9, 195];
+        assert!(from_slice::<Foo>(&indexed).is_err());
+
+        let config = DeserializerConfig::new().integer_field_keys(true);
+        let expected = Foo {
+            a: 1,
+            b: "x".to_string(),
+            c: None,
+        };
+        assert_eq!(
+            expected,
+            from_slice_with_config(&indexed, config.clone()).unwrap()
+        );
+        assert_eq!(
+            expected,
+            from_slice_with_config(&mixed, config.clone()).unwrap()
+        );
+
+        let strict = config.deny_unknown_fields(true);
+        let err = from_slice_with_config::<Foo>(&mixed, strict).unwrap_err();
+        assert!(err.to_string().contains("unknown field `9`"), "{err}");
+    }
+
+    #[test]
+    fn test_read_skips_unknown_fields() {
+        use crate::{to_vec_with_plain_maps, value::Value};
+
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Foo {
+            a: u8,
+        }
+
+        let key = |key: &str| Value::String(key.to_string());
+        let mut entries = vec![
+            (key("a"), Value::Integer(1)),
+            (
+                key("nested"),
+                Value::Map(vec![(
+                    key("list"),
+                    Value::Array(vec![
+                        Value::String("x".repeat(300)),
+                        Value::Bytes(vec![7; 70_000]),
+                    ]),
+                )]),
+            ),
+            (key("ext"), Value::Ext(5, vec![1, 2, 3])),
+        ];
+        let bytes = to_vec_with_plain_maps(&Value::Map(entries.clone()))
+            .unwrap();
+        assert_eq!(Foo { a: 1 }, from_slice(&bytes).unwrap());
+
+        // Known fields after the unknown ones are still decoded
+        entries.rotate_left(1);
+        let bytes = to_vec_with_plain_maps(&Value::Map(entries)).unwrap();
+        assert_eq!(Foo { a: 1 }, from_slice(&bytes).unwrap());
+
+        // {"a": 1, "deep": [[[...[nil]...]]]}, nested far past what
+        // recursion could handle
+        let depth = 1_000_000;
+        let mut bytes = vec![130, 161, b'a', 1, 164, b'd', b'e', b'e', b'p'];
+        bytes.resize(bytes.len() + depth, 145);
+        bytes.push(192);
+        let unlimited = DeserializerConfig::new().max_depth(usize::MAX);
+        assert_eq!(
+            Foo { a: 1 },
+            from_slice_with_config(&bytes, unlimited).unwrap()
+        );
+
+        // Skipped values still count towards the maximum depth
+        let shallow = DeserializerConfig::new().max_depth(depth);
+        assert!(from_slice_with_config::<Foo>(&bytes, shallow).is_err());
+        let deep_enough = DeserializerConfig::new().max_depth(depth + 1);
+        assert_eq!(
+            Foo { a: 1 },
+            from_slice_with_config(&bytes, deep_enough).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_read_field_decoded_observer() {
+        use crate::format::Format;
+        use std::sync::{Arc, Mutex};
+
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Inner {
+            b: String,
+        }
+
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Outer {
+            a: u16,
+            inner: Inner,
+        }
+
+        let seen = Arc::new(Mutex::new(vec![]));
+        let observer_seen = seen.clone();
+        let config = DeserializerConfig::new().on_field_decoded(
+            move |path, format, size| {
+                observer_seen.lock().unwrap().push((
+                    path.to_string(),
+                    format,
+                    size,
+                ));
+            },
+        );
+
+        // { a: 300, inner: { b: "xyz" } }
+        from_slice_with_config::<Outer>(
+            &[
+                130, 161, 97, 205, 1, 44, 165, 105, 110, 110, 101, 114, 129,
+                161, 98, 163, 120, 121, 122,
+            ],
+            config,
+        )
+        .unwrap();
+
+        assert_eq!(
+            vec![
+                ("a".to_string(), Format::Uint16, 3),
+                ("inner.b".to_string(), Format::FixStr(3), 4),
+                ("inner".to_string(), Format::FixMap(1), 7),
+            ],
+            *seen.lock().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_read_from_slices() {
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Bar {
+            bar: u16,
+        }
+
+        let bytes = [
+            146, 129, 163, 98, 97, 114, 2, 129, 163, 98, 97, 114, 205, 1, 44,
+        ];
+        let expected = vec![Bar { bar: 2 }, Bar { bar: 300 }];
+
+        // split inside a header, inside a string and between values
+        let slices: [&[u8]; 5] =
+            [&bytes[..1], &bytes[1..4], &[], &bytes[4..13], &bytes[13..]];
+        let result: Vec<Bar> = from_slices(&slices).unwrap();
+        assert_eq!(expected, result);
+
+        let io_slices = [IoSlice::new(&bytes[..7]), IoSlice::new(&bytes[7..])];
+        let result: Vec<Bar> = from_slices(&io_slices).unwrap();
+        assert_eq!(expected, result);
+
+        let truncated: [&[u8]; 2] = [&bytes[..3], &bytes[3..10]];
+        assert!(from_slices::<Vec<Bar>, _>(&truncated).is_err());
+    }
+
+    #[test]
+    fn test_read_nil_as_default() {
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Foo {
+            int: i32,
+            float: f64,
+            flag: bool,
+            name: String,
+            list: Vec<u8>,
+            map: BTreeMap<String, u8>,
+            opt: Option<u8>,
+        }
+
+        // every property is nil
+        let bytes = [
+            135, 163, 105, 110, 116, 192, 165, 102, 108, 111, 97, 116, 192,
+            164, 102, 108, 97, 103, 192, 164, 110, 97, 109, 101, 192, 164, 108,
+            105, 115, 116, 192, 163, 109, 97, 112, 192, 163, 111, 112, 116,
+            192,
+        ];
+
+        let config = DeserializerConfig::new().nil_as_default(true);
+        let result: Foo = from_slice_with_config(&bytes, config).unwrap();
+        assert_eq!(
+            Foo {
+                int: 0,
+                float: 0.0,
+                flag: false,
+                name: "".to_string(),
+                list: vec![],
+                map: BTreeMap::new(),
+                opt: None,
+            },
+            result
+        );
+
+        assert!(from_slice::<Foo>(&bytes).is_err());
+    }
+
+    #[test]
+    fn test_read_complex_map_keys() {
+        #[derive(Debug, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+        struct Point {
+            x: u8,
+            y: u8,
+        }
+
+        // GenericMap { [1, "a"]: 2 }
+        let tuple_keys: BTreeMap<(u8, String), u8> =
+            from_slice(&[199, 6, 1, 129, 146, 1, 161, 97, 2]).unwrap();
+        assert_eq!(BTreeMap::from([((1, "a".to_string()), 2)]), tuple_keys);
+
+        // GenericMap { { x: 1, y: 2 }: "p" }
+        let struct_keys: BTreeMap<Point, String> = from_slice(&[
+            199, 10, 1, 129, 130, 161, 120, 1, 161, 121, 2, 161, 112,
+        ])
+        .unwrap();
+        assert_eq!(
+            BTreeMap::from([(Point { x: 1, y: 2 }, "p".to_string())]),
+            struct_keys
+        );
+
+        let wrong_arity: crate::DecodeResult<BTreeMap<(u8, u8), u8>> =
+            from_slice(&[199, 6, 1, 129, 147, 1, 2, 3, 4]);
+        assert!(wrong_arity.is_err());
+    }
+
     #[test]
     fn test_bigint() {
         let foo = BigIntWrapper(
@@ -1093,6 +3093,259 @@ mod tests {
         assert_eq!(foo, result);
     }
 
+    #[test]
+    fn test_read_any_ext_maps() {
+        use serde_json::{json, Value};
+
+        let json = json!({"a": {"b": [1, "x", {"c": null}]}, "d": -1.5});
+        let bytes = crate::to_vec(&json).unwrap();
+        assert_eq!(bytes[0], 0xc7);
+        assert_eq!(json, from_slice::<Value>(&bytes).unwrap());
+
+        #[derive(Debug, Deserialize, PartialEq)]
+        #[serde(untagged)]
+        enum Payload {
+            List(Vec<u8>),
+            Map(BTreeMap<String, u8>),
+        }
+
+        let map = BTreeMap::from([("k".to_string(), 1u8)]);
+        let bytes = crate::to_vec(&map).unwrap();
+        assert_eq!(Payload::Map(map), from_slice(&bytes).unwrap());
+    }
+
+    #[test]
+    fn test_read_skips_ignored_values() {
+        use super::from_reader;
+
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Known {
+            a: u8,
+            z: u8,
+        }
+
+        // { a: 1, b: ext 5 [1, 2], c: { d: [bin [7], "xy", 2.5] }, z: 2 }
+        let bytes = [
+            132, 161, 97, 1, 161, 98, 213, 5, 1, 2, 161, 99, 199, 15, 1, 129,
+            161, 100, 147, 196, 1, 7, 162, 120, 121, 202, 64, 32, 0, 0, 161,
+            122, 2,
+        ];
+        let expected = Known { a: 1, z: 2 };
+        assert_eq!(expected, from_slice(&bytes).unwrap());
+        assert_eq!(expected, from_reader(&bytes[..]).unwrap());
+
+        let truncated = &bytes[..bytes.len() - 8];
+        assert!(from_slice::<Known>(truncated).is_err());
+        assert!(from_reader::<_, Known>(truncated).is_err());
+    }
+
+    #[test]
+    fn test_read_tuple_struct() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Color(u8, u8, u8);
+
+        let color: Color = from_slice(&[147, 204, 255, 0, 10]).unwrap();
+        assert_eq!(Color(255, 0, 10), color);
+        assert!(from_slice::<Color>(&[146, 1, 2]).is_err());
+    }
+
+    #[test]
+    fn test_read_struct_variant() {
+        use crate::{to_vec, to_vec_with_config, SerializerConfig};
+        use serde_derive::Serialize;
+
+        #[derive(Debug, Deserialize, PartialEq, Serialize)]
+        enum Event {
+            Deleted,
+            Created { id: u32, name: String },
+        }
+
+        let events = vec![
+            Event::Created {
+                id: 7,
+                name: "a".to_string(),
+            },
+            Event::Deleted,
+        ];
+        let by_index = to_vec(&events).unwrap();
+        assert_eq!(events, from_slice::<Vec<Event>>(&by_index).unwrap());
+        let by_name =
+            to_vec_with_config(&events, SerializerConfig::rmp_compat())
+                .unwrap();
+        assert_eq!(events, from_slice::<Vec<Event>>(&by_name).unwrap());
+
+        // { 1: {}, 0: nil } holds two variants
+        assert!(from_slice::<Event>(&[130, 1, 128, 0, 192]).is_err());
+    }
+
+    #[test]
+    fn test_read_data_carrying_variants() {
+        use crate::{to_vec, to_vec_with_config, SerializerConfig};
+        use serde_derive::Serialize;
+
+        #[derive(Debug, Deserialize, PartialEq, Serialize)]
+        enum Shape {
+            Empty,
+            Circle(f32),
+            Rect(u8, u8),
+            Named { name: String },
+        }
+
+        let shapes = vec![
+            Shape::Empty,
+            Shape::Circle(0.5),
+            Shape::Rect(2, 3),
+            Shape::Named {
+                name: "a".to_string(),
+            },
+        ];
+        let by_index = to_vec(&shapes).unwrap();
+        assert_eq!(
+            [148, 0, 129, 1, 202, 63, 0, 0, 0, 129, 2, 146, 2, 3],
+            by_index[..14]
+        );
+        assert_eq!(shapes, from_slice::<Vec<Shape>>(&by_index).unwrap());
+
+        let config = SerializerConfig::rmp_compat();
+        let by_name = to_vec_with_config(&shapes, config).unwrap();
+        assert_eq!(rmp_serde::to_vec_named(&shapes).unwrap(), by_name);
+        assert_eq!(shapes, from_slice::<Vec<Shape>>(&by_name).unwrap());
+
+        // Unit variants may also be written as a map to nil.
+        assert_eq!(Shape::Empty, from_slice(&[129, 0, 192]).unwrap());
+    }
+
+    #[test]
+    fn test_read_tagged_enums() {
+        use crate::to_vec;
+        use serde_derive::Serialize;
+
+        #[derive(Debug, Deserialize, PartialEq, Serialize)]
+        struct Point {
+            x: i32,
+            y: f64,
+        }
+
+        #[derive(Debug, Deserialize, PartialEq, Serialize)]
+        #[serde(tag = "type")]
+        enum Internal {
+            Ping,
+            Move { to: Point, speed: u8 },
+            At(Point),
+            Log {
+                bytes: Vec<u8>,
+                tags: BTreeMap<String, String>,
+                #[serde(with = "serde_bytes")]
+                raw: Vec<u8>,
+                missing: Option<u64>,
+            },
+        }
+
+        #[derive(Debug, Deserialize, PartialEq, Serialize)]
+        #[serde(tag = "t", content = "c")]
+        enum Adjacent {
+            Ping,
+            Resize(u32, u32),
+            Rename(String),
+            Move { to: Point },
+        }
+
+        let internal = vec![
+            Internal::Ping,
+            Internal::Move {
+                to: Point { x: -3, y: 0.25 },
+                speed: 200,
+            },
+            Internal::At(Point {
+                x: i32::MAX,
+                y: 1e300,
+            }),
+            Internal::Log {
+                bytes: vec![1, 2],
+                tags: BTreeMap::from([("k".to_string(), "v".to_string())]),
+                raw: vec![9],
+                missing: None,
+            },
+            Internal::Log {
+                bytes: vec![],
+                tags: BTreeMap::new(),
+                raw: vec![],
+                missing: Some(1),
+            },
+        ];
+        let bytes = to_vec(&internal).unwrap();
+        assert_eq!(internal, from_slice::<Vec<Internal>>(&bytes).unwrap());
+
+        let adjacent = vec![
+            Adjacent::Ping,
+            Adjacent::Resize(70000, 1),
+            Adjacent::Rename("foo".to_string()),
+            Adjacent::Move {
+                to: Point { x: 1, y: -2.0 },
+            },
+        ];
+        let bytes = to_vec(&adjacent).unwrap();
+        assert_eq!(adjacent, from_slice::<Vec<Adjacent>>(&bytes).unwrap());
+    }
+
+    #[test]
+    fn test_read_untagged_enums() {
+        use crate::to_vec;
+        use serde_derive::Serialize;
+
+        #[derive(Debug, Deserialize, PartialEq, Serialize)]
+        struct Failure {
+            code: u16,
+            message: String,
+        }
+
+        #[derive(Debug, Deserialize, PartialEq, Serialize)]
+        #[serde(untagged)]
+        enum Outcome {
+            Message(String),
+            Count(u64),
+            Failure(Failure),
+            Table(BTreeMap<String, u8>),
+            Items(Vec<Outcome>),
+            Missing(Option<()>),
+        }
+
+        let outcomes = vec![
+            Outcome::Message("done".to_string()),
+            Outcome::Count(u64::MAX),
+            Outcome::Failure(Failure {
+                code: 404,
+                message: "not found".to_string(),
+            }),
+            Outcome::Table(BTreeMap::from([("a".to_string(), 1)])),
+            Outcome::Items(vec![Outcome::Count(1)]),
+            Outcome::Missing(None),
+        ];
+        let bytes = to_vec(&outcomes).unwrap();
+        assert_eq!(outcomes, from_slice::<Vec<Outcome>>(&bytes).unwrap());
+
+        // No variant matches a bool.
+        assert!(from_slice::<Outcome>(&[195]).is_err());
+    }
+
+    #[test]
+    fn test_read_128_bit_integers() {
+        use crate::to_vec;
+
+        let signed = [i128::MIN, i64::MIN as i128, -1, u64::MAX as i128];
+        for v in signed {
+            assert_eq!(v, from_slice::<i128>(&to_vec(&v).unwrap()).unwrap());
+        }
+        let unsigned = [0, u64::MAX as u128, u128::MAX];
+        for v in unsigned {
+            assert_eq!(v, from_slice::<u128>(&to_vec(&v).unwrap()).unwrap());
+        }
+
+        assert_eq!(-5, from_slice::<i128>(&[251]).unwrap());
+        assert!(from_slice::<u128>(&[251]).is_err());
+        assert!(from_slice::<u128>(&[162, 45, 49]).is_err());
+    }
+
     #[test]
     fn test_read_json() {
         use serde_json::Value;
@@ -1126,4 +3379,51 @@ mod tests {
         .unwrap();
         assert_eq!(foo, result);
     }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_read_truncated_input() {
+        use std::io::Cursor;
+
+        use serde_bytes::ByteBuf;
+
+        use super::from_reader;
+        use crate::{value::preserving::PreservingValue, DecodeError};
+
+        fn eof(err: DecodeError) -> (usize, usize, usize) {
+            match err {
+                DecodeError::Eof {
+                    expected,
+                    available,
+                    offset,
+                } => (expected, available, offset),
+                err => panic!("unexpected error {err:?}"),
+            }
+        }
+
+        let err = from_slice::<u8>(&[]).unwrap_err();
+        assert_eq!((1, 0, 0), eof(err));
+        // uint16 header with one byte of its value
+        let err = from_slice::<u16>(&[0xcd, 0x01]).unwrap_err();
+        assert_eq!((2, 1, 1), eof(err));
+        // "abc" cut after the "a"
+        let err = from_slice::<String>(&[0xa3, b'a']).unwrap_err();
+        assert_eq!((3, 1, 1), eof(err));
+        let err = from_reader::<_, ByteBuf>(Cursor::new([0xc4, 5, 1, 2]))
+            .unwrap_err();
+        assert_eq!((5, 2, 2), eof(err));
+        let err = PreservingValue::from_slice(&[0x91, 0xa3, b'a']).unwrap_err();
+        assert_eq!((3, 1, 2), eof(err));
+        // Headers cut short, as a uint16 and an array16
+        let err = PreservingValue::from_slice(&[0xcd, 0x01]).unwrap_err();
+        assert_eq!((2, 1, 1), eof(err));
+        let err = PreservingValue::from_slice(&[0x91, 0xdc, 0x00]).unwrap_err();
+        assert_eq!((2, 1, 2), eof(err));
+        let err = PreservingValue::from_slice(&[]).unwrap_err();
+        assert_eq!((1, 0, 0), eof(err));
+
+        // Corrupt input is not reported as truncated
+        let err = from_slice::<String>(&[0xa1, 0xff]).unwrap_err();
+        assert!(!matches!(err, DecodeError::Eof { .. }));
+    }
 }
@@ -0,0 +1,222 @@
+use core::ops::Deref;
+
+use crate::io;
+
+/// A source of bytes for the [`Deserializer`](crate::Deserializer).
+///
+/// Sources that keep the whole input in memory for `'de` can lend strings
+/// and bytes straight out of it through [`Read::borrow`].
+pub trait Read<'de> {
+    /// Returns the next byte without consuming it, or `None` once the input
+    /// is exhausted.
+    fn peek(&mut self) -> io::Result<Option<u8>>;
+
+    /// Reads up to `buf.len()` bytes, returning how many were read. Returns
+    /// `0` only once the input is exhausted.
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize>;
+
+    /// Number of bytes consumed so far.
+    fn position(&self) -> usize;
+
+    /// Consumes the next `len` bytes and returns them borrowed from the
+    /// input, if they are available contiguously for `'de`. Otherwise
+    /// returns `None` without consuming anything.
+    fn borrow(&mut self, _len: usize) -> Option<&'de [u8]> {
+        None
+    }
+
+    /// Number of bytes left, if the whole input is known up front. Lets
+    /// declared lengths be checked before anything is allocated for them.
+    fn remaining(&self) -> Option<usize> {
+        None
+    }
+}
+
+/// Reads from a single contiguous slice.
+pub struct SliceRead<'a> {
+    slice: &'a [u8],
+    index: usize,
+}
+
+impl<'a> SliceRead<'a> {
+    pub fn new(slice: &'a [u8]) -> Self {
+        Self { slice, index: 0 }
+    }
+
+    /// Continues reading from `index`.
+    pub(crate) fn seek(&mut self, index: usize) {
+        self.index = index;
+    }
+}
+
+impl<'a> Read<'a> for SliceRead<'a> {
+    fn peek(&mut self) -> io::Result<Option<u8>> {
+        Ok(self.slice.get(self.index).copied())
+    }
+
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let remaining = &self.slice[self.index..];
+        let n = buf.len().min(remaining.len());
+        buf[..n].copy_from_slice(&remaining[..n]);
+        self.index += n;
+        Ok(n)
+    }
+
+    fn position(&self) -> usize {
+        self.index
+    }
+
+    fn borrow(&mut self, len: usize) -> Option<&'a [u8]> {
+        let end = self.index.checked_add(len)?;
+        let borrowed = self.slice.get(self.index..end)?;
+        self.index = end;
+        Some(borrowed)
+    }
+
+    fn remaining(&self) -> Option<usize> {
+        Some(self.slice.len() - self.index)
+    }
+}
+
+/// Reads from a sequence of slices as if they were concatenated, e.g. a
+/// payload assembled from several network reads (`&[&[u8]]` or
+/// `&[IoSlice]`), without copying them into one contiguous buffer first.
+pub struct SlicesRead<'a, S> {
+    slices: &'a [S],
+    slice_index: usize,
+    index: usize,
+    position: usize,
+    len: usize,
+}
+
+impl<'a, S> SlicesRead<'a, S>
+where
+    S: Deref<Target = [u8]>,
+{
+    pub fn new(slices: &'a [S]) -> Self {
+        Self {
+            slices,
+            slice_index: 0,
+            index: 0,
+            position: 0,
+            len: slices.iter().map(|slice| slice.len()).sum(),
+        }
+    }
+
+    /// Returns the unread part of the current slice, moving on to the next
+    /// non-empty slice when the current one is exhausted.
+    fn current(&mut self) -> &'a [u8] {
+        while let Some(slice) = self.slices.get(self.slice_index) {
+            if self.index < slice.len() {
+                return &slice[self.index..];
+            }
+            self.slice_index += 1;
+            self.index = 0;
+        }
+        &[]
+    }
+}
+
+impl<'a, S> Read<'a> for SlicesRead<'a, S>
+where
+    S: Deref<Target = [u8]>,
+{
+    fn peek(&mut self) -> io::Result<Option<u8>> {
+        Ok(self.current().first().copied())
+    }
+
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut written = 0;
+        while written < buf.len() {
+            let current = self.current();
+            if current.is_empty() {
+                break;
+            }
+            let n = (buf.len() - written).min(current.len());
+            buf[written..written + n].copy_from_slice(&current[..n]);
+            written += n;
+            self.index += n;
+        }
+        self.position += written;
+        Ok(written)
+    }
+
+    fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Values straddling two slices can't be borrowed.
+    fn borrow(&mut self, len: usize) -> Option<&'a [u8]> {
+        let borrowed = self.current().get(..len)?;
+        self.index += len;
+        self.position += len;
+        Some(borrowed)
+    }
+
+    fn remaining(&self) -> Option<usize> {
+        Some(self.len - self.position)
+    }
+}
+
+/// Reads incrementally from an [`io::Read`], e.g. a file or socket.
+///
+/// Bytes are pulled as values are decoded, so the input is never held in
+/// memory as a whole. Wrap unbuffered sources in an [`io::BufReader`]: the
+/// deserializer makes many small reads.
+pub struct IoRead<R> {
+    reader: R,
+    peeked: Option<u8>,
+    position: usize,
+}
+
+impl<R: io::Read> IoRead<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            peeked: None,
+            position: 0,
+        }
+    }
+}
+
+impl<'de, R: io::Read> Read<'de> for IoRead<R> {
+    fn peek(&mut self) -> io::Result<Option<u8>> {
+        if self.peeked.is_none() {
+            let mut byte = [0];
+            loop {
+                match self.reader.read(&mut byte) {
+                    Ok(0) => return Ok(None),
+                    Ok(_) => break,
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                    Err(e) => return Err(e),
+                }
+            }
+            self.peeked = Some(byte[0]);
+        }
+        Ok(self.peeked)
+    }
+
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let n = match self.peeked.take() {
+            Some(byte) => {
+                buf[0] = byte;
+                1
+            }
+            None => loop {
+                match self.reader.read(buf) {
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                    result => break result?,
+                }
+            },
+        };
+        self.position += n;
+        Ok(n)
+    }
+
+    fn position(&self) -> usize {
+        self.position
+    }
+}
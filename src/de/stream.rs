@@ -0,0 +1,113 @@
+use core::marker::PhantomData;
+
+use serde::de::{Deserialize, IgnoredAny};
+
+use crate::error::DecodeResult;
+
+use super::{
+    read::{Read, SliceRead},
+    Deserializer, DeserializerConfig, ObservedFields,
+};
+
+/// Iterates over values written back-to-back in one buffer, e.g. a batch of
+/// invocations.
+///
+/// A value that fails to decode as `T` is reported and skipped, so the
+/// values after it are still yielded. Iteration ends early only when a value
+/// is malformed and can't be skipped; [`Self::byte_offset`] then points at
+/// its first byte.
+pub struct StreamDeserializer<'de, T> {
+    input: &'de [u8],
+    deserializer: Deserializer<SliceRead<'de>>,
+    failed: bool,
+    output: PhantomData<T>,
+}
+
+impl<'de, T> StreamDeserializer<'de, T>
+where
+    T: Deserialize<'de>,
+{
+    pub fn new(input: &'de [u8]) -> Self {
+        Self::with_config(input, DeserializerConfig::default())
+    }
+
+    pub fn with_config(input: &'de [u8], config: DeserializerConfig) -> Self {
+        Self {
+            input,
+            deserializer: Deserializer::from_slice_with_config(input, config),
+            failed: false,
+            output: PhantomData,
+        }
+    }
+
+    /// Offset of the first byte not yet consumed: the end of the last value
+    /// yielded or skipped.
+    pub fn byte_offset(&self) -> usize {
+        self.deserializer.read.position()
+    }
+
+    /// Moves past the value starting at `start`, which failed to decode.
+    /// Returns whether it was well-formed enough to be skipped.
+    fn skip_failed(&mut self, start: usize) -> bool {
+        let config = self.deserializer.config.clone();
+        let mut skipper =
+            Deserializer::from_slice_with_config(&self.input[start..], config);
+        if IgnoredAny::deserialize(&mut skipper).is_err() {
+            self.deserializer.read.seek(start);
+            return false;
+        }
+        self.deserializer.read.seek(start + skipper.read.position());
+        self.deserializer.observed = ObservedFields::default();
+        true
+    }
+}
+
+impl<'de, T> Iterator for StreamDeserializer<'de, T>
+where
+    T: Deserialize<'de>,
+{
+    type Item = DecodeResult<T>;
+
+    fn next(&mut self) -> Option<DecodeResult<T>> {
+        let start = self.byte_offset();
+        if self.failed || start >= self.input.len() {
+            return None;
+        }
+
+        let result = T::deserialize(&mut self.deserializer);
+        if result.is_err() && !self.skip_failed(start) {
+            self.failed = true;
+        }
+        Some(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::StreamDeserializer;
+
+    #[test]
+    fn test_stream_values() {
+        // 1, "a", [2, 3]
+        let bytes = [1, 161, 97, 146, 2, 3];
+        let mut stream = StreamDeserializer::<crate::Value>::new(&bytes);
+        assert_eq!(0, stream.byte_offset());
+        assert_eq!(crate::Value::Integer(1), stream.next().unwrap().unwrap());
+        assert_eq!(1, stream.byte_offset());
+        assert_eq!(2, stream.count());
+    }
+
+    #[test]
+    fn test_stream_skips_failed_values() {
+        // 1, "a", 2, then a truncated str8
+        let bytes = [1, 161, 97, 2, 217, 5, 98];
+        let mut stream = StreamDeserializer::<u8>::new(&bytes);
+        assert_eq!(1, stream.next().unwrap().unwrap());
+        assert!(stream.next().unwrap().is_err());
+        assert_eq!(3, stream.byte_offset());
+        assert_eq!(2, stream.next().unwrap().unwrap());
+        assert!(stream.next().unwrap().is_err());
+        assert_eq!(4, stream.byte_offset());
+        assert!(stream.next().is_none());
+    }
+}
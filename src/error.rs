@@ -1,8 +1,15 @@
-use std::fmt::Display;
+use alloc::{
+    format,
+    string::{String, ToString},
+    sync::Arc,
+    vec,
+    vec::Vec,
+};
+use core::fmt::Display;
 
 use serde::{ser, de};
 
-use crate::format::Format;
+use crate::{format::Format, io};
 
 pub fn get_error_message(format: Format) -> String {
     match format {
@@ -37,14 +44,63 @@ pub fn get_error_message(format: Format) -> String {
     }
 }
 
-pub type Result<T> = std::result::Result<T, Error>;
+pub type Result<T> = core::result::Result<T, Error>;
+pub type EncodeResult<T> = core::result::Result<T, EncodeError>;
+pub type DecodeResult<T> = core::result::Result<T, DecodeError>;
 
+/// Either an [`EncodeError`] or a [`DecodeError`], returned by APIs that
+/// both encode and decode.
 #[derive(Debug, Clone, thiserror::Error)]
 pub enum Error {
+    #[error(transparent)]
+    Encode(#[from] EncodeError),
+    #[error(transparent)]
+    Decode(#[from] DecodeError),
+}
+
+impl Error {
+    /// The underlying IO error, if the reader or writer failed.
+    pub fn io_error(&self) -> Option<&io::Error> {
+        match self {
+            Error::Encode(e) => e.io_error(),
+            Error::Decode(e) => e.io_error(),
+        }
+    }
+}
+
+/// An error from the [`Serializer`](crate::Serializer).
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum EncodeError {
     #[error("`{0}`")]
     Message(String),
-    #[error("EOF error")]
-    Eof,
+    /// The writer failed. Shared so the error stays `Clone`.
+    #[error("IO error: `{0}`")]
+    Io(#[source] Arc<io::Error>),
+}
+
+impl EncodeError {
+    /// The underlying IO error, if the writer failed.
+    pub fn io_error(&self) -> Option<&io::Error> {
+        match self {
+            EncodeError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// An error from the [`Deserializer`](crate::Deserializer).
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum DecodeError {
+    #[error("`{0}`")]
+    Message(String),
+    /// The input ended inside a value: `expected` bytes were needed at
+    /// `offset` but only `available` were left. More data may complete it.
+    #[error("Unexpected end of input at offset {offset}: needs {expected} bytes, {available} available")]
+    Eof {
+        expected: usize,
+        available: usize,
+        offset: usize,
+    },
     #[error("Syntax Error")]
     Syntax,
     #[error("Expected Boolean: `{0}`")]
@@ -73,22 +129,251 @@ pub enum Error {
     ExpectedEnum(String),
     #[error("Trailing characters in deserialization")]
     TrailingCharacters,
+    #[error("Schema fingerprint mismatch: expected `{expected:#018x}`, found `{found:#018x}`")]
+    FingerprintMismatch { expected: u64, found: u64 },
+    #[error("Invalid wrap manifest: `{0}`")]
+    InvalidManifest(String),
+    /// The reader failed. Shared so the error stays `Clone`.
+    #[error("IO error: `{0}`")]
+    Io(#[source] Arc<io::Error>),
+    /// A string isn't valid UTF-8; `offset` is that of its first invalid
+    /// byte in the input.
+    #[error("Invalid UTF-8 at offset {offset}: `{source}`")]
+    InvalidUtf8 {
+        offset: usize,
+        #[source]
+        source: core::str::Utf8Error,
+    },
+    #[error("JSON error: `{0}`")]
+    Json(#[source] Arc<serde_json::Error>),
+}
+
+impl DecodeError {
+    /// The underlying IO error, if the reader failed.
+    pub fn io_error(&self) -> Option<&io::Error> {
+        match self {
+            DecodeError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
 }
 
-impl From<std::io::Error> for Error {
-  fn from(value: std::io::Error) -> Self {
-      Error::Message(value.to_string())
+/// A short read detected by the deserializer, passed through `io::Read` so
+/// that it can become a [`DecodeError::Eof`] on the way out.
+#[derive(Debug)]
+pub(crate) struct ShortRead {
+    pub(crate) expected: usize,
+    pub(crate) available: usize,
+    pub(crate) offset: usize,
+}
+
+impl Display for ShortRead {
+  fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+      write!(f, "failed to fill whole buffer")
+  }
+}
+
+impl core::error::Error for ShortRead {}
+
+impl From<ShortRead> for DecodeError {
+  fn from(value: ShortRead) -> Self {
+      let ShortRead { expected, available, offset } = value;
+      DecodeError::Eof { expected, available, offset }
+  }
+}
+
+impl From<io::Error> for DecodeError {
+  fn from(value: io::Error) -> Self {
+      if let Some(short) = io::short_read(&value) {
+          let &ShortRead { expected, available, offset } = short;
+          return DecodeError::Eof { expected, available, offset };
+      }
+      DecodeError::Io(Arc::new(value))
+  }
+}
+
+impl From<io::Error> for EncodeError {
+  fn from(value: io::Error) -> Self {
+      EncodeError::Io(Arc::new(value))
   }
 }
 
-impl ser::Error for Error {
+impl From<serde_json::Error> for DecodeError {
+  fn from(value: serde_json::Error) -> Self {
+      DecodeError::Json(Arc::new(value))
+  }
+}
+
+impl ser::Error for EncodeError {
   fn custom<T: Display>(msg: T) -> Self {
-      Error::Message(msg.to_string())
+      EncodeError::Message(msg.to_string())
   }
 }
 
-impl de::Error for Error {
+impl de::Error for DecodeError {
   fn custom<T: Display>(msg: T) -> Self {
-      Error::Message(msg.to_string())
+      DecodeError::Message(msg.to_string())
   }
+
+  fn unknown_field(field: &str, expected: &'static [&'static str]) -> Self {
+      DecodeError::Message(unknown_name_message("field", field, expected))
+  }
+
+  fn unknown_variant(variant: &str, expected: &'static [&'static str]) -> Self {
+      DecodeError::Message(unknown_name_message("variant", variant, expected))
+  }
+}
+
+/// Describes an unknown field or variant name, suggesting the closest
+/// expected name when it looks like a typo.
+pub(crate) fn unknown_name_message(
+    kind: &str,
+    name: &str,
+    expected: &[&str],
+) -> String {
+    if expected.is_empty() {
+        return format!("unknown {kind} `{name}`, there are no {kind}s");
+    }
+
+    let one_of = expected
+        .iter()
+        .map(|expected| format!("`{expected}`"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let mut message =
+        format!("unknown {kind} `{name}`, expected one of {one_of}");
+
+    // Allow roughly one typo per three characters
+    let max_distance = (name.chars().count() / 3).max(1);
+    let closest = expected
+        .iter()
+        .map(|expected| (edit_distance(name, expected), expected))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance);
+    if let Some((_, suggestion)) = closest {
+        message.push_str(&format!("; did you mean `{suggestion}`?"));
+    }
+    message
+}
+
+/// Edit distance between two strings, counted in chars, where swapping two
+/// adjacent chars counts as a single edit (optimal string alignment).
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut rows = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in rows.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    rows[0] = (0..=b.len()).collect();
+
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = (a[i - 1] != b[j - 1]) as usize;
+            let mut distance = (rows[i - 1][j - 1] + cost)
+                .min(rows[i - 1][j] + 1)
+                .min(rows[i][j - 1] + 1);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distance = distance.min(rows[i - 2][j - 2] + 1);
+            }
+            rows[i][j] = distance;
+        }
+    }
+    rows[a.len()][b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_derive::Deserialize;
+
+    use crate::{from_slice, DecodeError};
+
+    #[test]
+    fn test_unknown_field_suggestion() {
+        #[derive(Debug, Deserialize)]
+        #[serde(deny_unknown_fields)]
+        #[allow(dead_code)]
+        struct Manifest {
+            name: String,
+            version: u8,
+        }
+
+        // { "nmae": "a", "version": 1 }
+        let err = from_slice::<Manifest>(&[
+            130, 164, 110, 109, 97, 101, 161, 97, 167, 118, 101, 114, 115, 105,
+            111, 110, 1,
+        ])
+        .unwrap_err();
+        assert_eq!(
+            "unknown field `nmae`, expected one of `name`, `version`; \
+             did you mean `name`?",
+            match err {
+                DecodeError::Message(message) => message,
+                err => panic!("unexpected error {err:?}"),
+            }
+        );
+
+        // { "unrelated": 1 }
+        let err = from_slice::<Manifest>(&[
+            129, 169, 117, 110, 114, 101, 108, 97, 116, 101, 100, 1,
+        ])
+        .unwrap_err();
+        assert!(!err.to_string().contains("did you mean"));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_error_sources() {
+        use std::{error::Error as _, io};
+
+        use crate::{from_reader, token::TokenWriter, EncodeError, Error};
+
+        struct Failing;
+        impl io::Read for Failing {
+            fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+                Err(io::Error::new(io::ErrorKind::ConnectionReset, "gone"))
+            }
+        }
+        let err = from_reader::<_, u8>(Failing).unwrap_err();
+        assert_eq!("gone", err.source().unwrap().to_string());
+        let io = err.io_error().unwrap();
+        assert_eq!(io::ErrorKind::ConnectionReset, io.kind());
+
+        let err = from_slice::<String>(&[0xa1, 0xff]).unwrap_err();
+        assert!(matches!(err, DecodeError::InvalidUtf8 { offset: 1, .. }));
+        assert!(err.source().is_some());
+
+        // Writers fail with an `EncodeError` holding the IO error too
+        impl io::Write for Failing {
+            fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+                Err(io::Error::new(io::ErrorKind::BrokenPipe, "closed"))
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+        let err = TokenWriter::new(Failing).write_nil().unwrap_err();
+        assert!(matches!(err, EncodeError::Io(_)));
+        assert_eq!("closed", err.source().unwrap().to_string());
+
+        // and `Error` passes either side's source through
+        let err = Error::from(err);
+        assert_eq!("closed", err.source().unwrap().to_string());
+        assert_eq!(io::ErrorKind::BrokenPipe, err.io_error().unwrap().kind());
+    }
+
+    #[test]
+    fn test_unknown_variant_suggestion() {
+        #[derive(Debug, Deserialize)]
+        #[allow(dead_code)]
+        enum Color {
+            Red,
+            Green,
+        }
+
+        let err = from_slice::<Color>(&[166, 71, 114, 101, 101, 110, 110])
+            .unwrap_err();
+        assert!(err.to_string().ends_with("did you mean `Green`?`"));
+    }
 }
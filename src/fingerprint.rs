@@ -0,0 +1,224 @@
+//! Schema fingerprints and the envelope that carries them.
+//!
+//! A fingerprinted payload is a two-element array holding the producer's
+//! [`Fingerprint`] of the type followed by the value itself. Decoding checks
+//! the fingerprint before touching the value, so a producer and consumer
+//! built from different versions of a type fail with
+//! [`DecodeError::FingerprintMismatch`] rather than with a confusing field
+//! error.
+
+use alloc::vec::Vec;
+use core::{fmt, marker::PhantomData};
+
+use serde::{
+    de::{self, SeqAccess, Visitor},
+    Deserialize, Serialize,
+};
+
+use crate::{
+    error::{DecodeError, DecodeResult, EncodeResult},
+    to_vec, Deserializer,
+};
+
+/// A stable hash of a type's field names, types and order.
+///
+/// Derive it with `#[derive(Fingerprint)]` (behind the `derive` feature).
+pub trait Fingerprint {
+    const FINGERPRINT: u64;
+}
+
+/// Serializes `value` inside a fingerprint envelope.
+pub fn to_vec_fingerprinted<T>(value: &T) -> EncodeResult<Vec<u8>>
+where
+    T: Serialize + Fingerprint,
+{
+    to_vec(&(T::FINGERPRINT, value))
+}
+
+/// Deserializes a value written by [`to_vec_fingerprinted`], failing with
+/// [`DecodeError::FingerprintMismatch`] if it was written for a different
+/// schema.
+pub fn from_slice_fingerprinted<'a, T>(buffer: &'a [u8]) -> DecodeResult<T>
+where
+    T: Deserialize<'a> + Fingerprint,
+{
+    let mut deserializer = Deserializer::from_slice(buffer);
+    let envelope = de::Deserializer::deserialize_tuple(
+        &mut deserializer,
+        2,
+        EnvelopeVisitor(PhantomData),
+    )?;
+
+    match envelope {
+        Envelope::Value(value) => Ok(value),
+        Envelope::Mismatch(found) => Err(DecodeError::FingerprintMismatch {
+            expected: T::FINGERPRINT,
+            found,
+        }),
+    }
+}
+
+enum Envelope<T> {
+    Value(T),
+    Mismatch(u64),
+}
+
+struct EnvelopeVisitor<T>(PhantomData<T>);
+
+impl<'de, T> Visitor<'de> for EnvelopeVisitor<T>
+where
+    T: Deserialize<'de> + Fingerprint,
+{
+    type Value = Envelope<T>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a fingerprint followed by a value")
+    }
+
+    fn visit_seq<A>(
+        self,
+        mut seq: A,
+    ) -> core::result::Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let fingerprint: u64 = seq
+            .next_element()?
+            .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+        if fingerprint != T::FINGERPRINT {
+            return Ok(Envelope::Mismatch(fingerprint));
+        }
+
+        let value = seq
+            .next_element()?
+            .ok_or_else(|| de::Error::invalid_length(1, &self))?;
+        Ok(Envelope::Value(value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_derive::{Deserialize, Serialize};
+
+    use polywrap_msgpack_serde_derive::Fingerprint;
+
+    use super::{from_slice_fingerprinted, to_vec_fingerprinted, Fingerprint};
+    use crate::DecodeError;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize, Fingerprint)]
+    struct Foo {
+        a: u8,
+        b: String,
+    }
+
+    mod v2 {
+        use super::*;
+
+        #[derive(Debug, Serialize, Deserialize, Fingerprint)]
+        pub struct Foo {
+            pub b: String,
+            pub a: u8,
+        }
+    }
+
+    #[derive(Fingerprint)]
+    #[allow(dead_code)]
+    enum Bar {
+        A,
+        B(u8),
+        C { c: bool },
+    }
+
+    #[test]
+    fn test_fingerprint_is_stable() {
+        // FNV-1a of "struct Foo{a:u8,b:String}"
+        assert_eq!(0x044ff447e18ce53f, Foo::FINGERPRINT);
+        assert_ne!(Foo::FINGERPRINT, v2::Foo::FINGERPRINT);
+        assert_ne!(0, Bar::FINGERPRINT);
+    }
+
+    #[test]
+    fn test_fingerprint_follows_wire_shape() {
+        mod paths {
+            use super::*;
+
+            #[derive(Fingerprint)]
+            #[allow(dead_code)]
+            pub struct Foo {
+                a: ::core::primitive::u8,
+                b: std::string::String,
+                #[serde(skip)]
+                c: Vec<u8>,
+            }
+        }
+
+        mod renamed {
+            use super::*;
+
+            #[derive(Fingerprint)]
+            #[serde(rename_all = "UPPERCASE")]
+            #[allow(dead_code)]
+            pub struct Foo {
+                a: u8,
+                b: String,
+            }
+        }
+
+        mod flattened {
+            use super::*;
+
+            #[derive(Fingerprint)]
+            #[allow(dead_code)]
+            pub struct Foo {
+                a: u8,
+                b: std::collections::BTreeMap<String, u8>,
+            }
+
+            #[derive(Fingerprint)]
+            #[serde(rename = "Foo")]
+            #[allow(dead_code)]
+            pub struct Flat {
+                a: u8,
+                #[serde(flatten)]
+                b: std::collections::BTreeMap<String, u8>,
+            }
+        }
+
+        mod tagged {
+            use super::*;
+
+            #[derive(Fingerprint)]
+            #[serde(tag = "type", rename_all = "snake_case")]
+            #[allow(dead_code)]
+            pub enum Bar {
+                A,
+                B(u8),
+                C { c: bool },
+            }
+        }
+
+        // Paths and skipped fields don't change the wire shape
+        assert_eq!(Foo::FINGERPRINT, paths::Foo::FINGERPRINT);
+        assert_ne!(Foo::FINGERPRINT, renamed::Foo::FINGERPRINT);
+        assert_ne!(flattened::Foo::FINGERPRINT, flattened::Flat::FINGERPRINT);
+        assert_ne!(Bar::FINGERPRINT, tagged::Bar::FINGERPRINT);
+    }
+
+    #[test]
+    fn test_fingerprint_envelope() {
+        let foo = Foo {
+            a: 1,
+            b: "x".to_string(),
+        };
+        let bytes = to_vec_fingerprinted(&foo).unwrap();
+        assert_eq!(foo, from_slice_fingerprinted(&bytes).unwrap());
+
+        let drifted = from_slice_fingerprinted::<v2::Foo>(&bytes).unwrap_err();
+        assert!(matches!(
+            drifted,
+            DecodeError::FingerprintMismatch { expected, found }
+                if expected == v2::Foo::FINGERPRINT
+                    && found == Foo::FINGERPRINT
+        ));
+    }
+}
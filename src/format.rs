@@ -1,6 +1,9 @@
-use byteorder::{self, ReadBytesExt, WriteBytesExt};
+use alloc::format;
 
-use crate::error::Error;
+use crate::{
+    error::DecodeError,
+    io::{ReadBytesExt, WriteBytesExt},
+};
 
 const FIX_ARRAY_SIZE: u8 = 0x0f;
 const FIX_MAP_SIZE: u8 = 0x0f;
@@ -8,17 +11,22 @@ const FIX_STR_SIZE: u8 = 0x1f;
 
 #[derive(Debug, Clone)]
 pub enum ExtensionType {
-    // must be in range 0-127
+    // application types must be in range 0-127
     GenericMap,
+    /// The predefined timestamp type, -1.
+    Timestamp,
 }
 
 impl TryFrom<u8> for ExtensionType {
-    type Error = crate::error::Error;
+    type Error = DecodeError;
 
-    fn try_from(value: u8) -> Result<Self, Error> {
+    fn try_from(value: u8) -> Result<Self, DecodeError> {
         match value {
             1 => Ok(Self::GenericMap),
-            v => Err(Error::Message(format!("Unrecognized Ext type '{v}'"))),
+            0xff => Ok(Self::Timestamp),
+            v => Err(DecodeError::Message(format!(
+                "Unrecognized Ext type '{v}'"
+            ))),
         }
     }
 }
@@ -27,6 +35,7 @@ impl From<ExtensionType> for u8 {
     fn from(value: ExtensionType) -> Self {
         match value {
             ExtensionType::GenericMap => 1,
+            ExtensionType::Timestamp => 0xff,
         }
     }
 }
@@ -95,18 +104,18 @@ impl Format {
         (u & 0xe0) == Format::to_u8(Format::FixStr(u))
     }
 
-    pub fn set_format<W: std::io::Write>(
+    pub fn set_format<W: crate::io::Write>(
         writer: &mut W,
         format: Format,
-    ) -> Result<(), std::io::Error> {
+    ) -> Result<(), crate::io::Error> {
         WriteBytesExt::write_u8(writer, format.to_u8())?;
 
         Ok(())
     }
 
-    pub fn get_format<R: std::io::Read>(
+    pub fn get_format<R: crate::io::Read>(
         reader: &mut R,
-    ) -> Result<Format, std::io::Error> {
+    ) -> Result<Format, crate::io::Error> {
         let bytesval = ReadBytesExt::read_u8(reader)?;
         Ok(Format::from_u8(bytesval))
     }
@@ -198,8 +207,8 @@ impl Format {
     }
 }
 
-impl std::fmt::Display for Format {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+impl core::fmt::Display for Format {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
         write!(f, "{:?}", self)
     }
 }
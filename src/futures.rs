@@ -0,0 +1,143 @@
+//! Encoding to and decoding from the `futures` [`AsyncWrite`] and
+//! [`AsyncRead`] traits, for executors other than tokio.
+//!
+//! These work like their counterparts in the `tokio` module, decoding
+//! with the same [`IncrementalDecoder`].
+//!
+//! [`IncrementalDecoder`]: crate::IncrementalDecoder
+
+use futures_io::{AsyncRead, AsyncWrite};
+use futures_util::io::{AsyncReadExt, AsyncWriteExt};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{
+    de::AsyncReadState,
+    error::{DecodeResult, EncodeResult},
+    to_vec, DeserializerConfig,
+};
+
+/// Writes `value` to `writer`. The writer isn't flushed.
+pub async fn to_writer_async<W, T>(
+    writer: &mut W,
+    value: &T,
+) -> EncodeResult<()>
+where
+    W: AsyncWrite + Unpin + ?Sized,
+    T: Serialize,
+{
+    let bytes = to_vec(value)?;
+    writer.write_all(&bytes).await?;
+    Ok(())
+}
+
+/// Reads one value from `reader`.
+///
+/// Only the bytes of that value are read, so that the next one can be read
+/// from the same reader; as they're read in small pieces, unbuffered
+/// readers are best wrapped in a `BufReader`.
+pub async fn from_reader_async<R, T>(reader: &mut R) -> DecodeResult<T>
+where
+    R: AsyncRead + Unpin + ?Sized,
+    T: DeserializeOwned,
+{
+    from_reader_async_with_config(reader, DeserializerConfig::default()).await
+}
+
+pub async fn from_reader_async_with_config<R, T>(
+    reader: &mut R,
+    config: DeserializerConfig,
+) -> DecodeResult<T>
+where
+    R: AsyncRead + Unpin + ?Sized,
+    T: DeserializeOwned,
+{
+    let mut state = AsyncReadState::new(config);
+    loop {
+        if let Some(value) = state.decode()? {
+            return Ok(value);
+        }
+        let read = reader.read(state.spare()).await?;
+        state.filled(read)?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io,
+        pin::Pin,
+        task::{Context, Poll},
+    };
+
+    use futures_executor::block_on;
+    use futures_io::AsyncRead;
+    use serde_derive::{Deserialize, Serialize};
+
+    use super::{from_reader_async, to_writer_async};
+    use crate::DecodeError;
+
+    /// Hands out a byte at a time, and only every other time it's polled.
+    struct Trickle<'a> {
+        bytes: &'a [u8],
+        ready: bool,
+    }
+
+    impl AsyncRead for Trickle<'_> {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &mut [u8],
+        ) -> Poll<io::Result<usize>> {
+            self.ready = !self.ready;
+            if !self.ready {
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+            let n = buf.len().min(self.bytes.len()).min(1);
+            buf[..n].copy_from_slice(&self.bytes[..n]);
+            self.bytes = &self.bytes[n..];
+            Poll::Ready(Ok(n))
+        }
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Invocation {
+        method: String,
+        args: Vec<Vec<u8>>,
+    }
+
+    #[test]
+    fn test_read_values_as_they_arrive() {
+        let first = Invocation {
+            method: "run".to_string(),
+            args: vec![vec![1; 300], vec![]],
+        };
+        let second = Invocation {
+            method: "stop".to_string(),
+            args: vec![],
+        };
+
+        let mut bytes = Vec::new();
+        block_on(async {
+            to_writer_async(&mut bytes, &first).await.unwrap();
+            to_writer_async(&mut bytes, &second).await.unwrap();
+        });
+
+        let mut reader = Trickle {
+            bytes: &bytes[..bytes.len() - 1],
+            ready: false,
+        };
+        block_on(async {
+            let value: Invocation =
+                from_reader_async(&mut reader).await.unwrap();
+            assert_eq!(first, value);
+            let err = from_reader_async::<_, Invocation>(&mut reader)
+                .await
+                .unwrap_err();
+            assert!(
+                matches!(err, DecodeError::Eof { expected: 1, .. }),
+                "{err}"
+            );
+        });
+    }
+}
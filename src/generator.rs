@@ -0,0 +1,220 @@
+//! Seeded generator of randomized, WRAP-manifest-shaped documents.
+//!
+//! Documents mix nested GenericMaps, enum indexes and names, BigInt and
+//! BigNumber strings, JSON blobs, bytes and scalars. The generator uses its
+//! own PRNG so a given seed yields the same documents on every platform and
+//! release, letting clients in other languages stress their codecs with
+//! identical inputs.
+
+use crate::{error::EncodeResult, to_vec, Value};
+
+const NAMES: &[&str] = &[
+    "name",
+    "type",
+    "version",
+    "abi",
+    "uri",
+    "methods",
+    "arguments",
+    "return",
+    "required",
+    "kind",
+    "properties",
+    "imports",
+    "env",
+    "module",
+];
+
+/// Generates random documents from a seed.
+#[derive(Debug, Clone)]
+pub struct DocumentGenerator {
+    state: u64,
+    max_depth: usize,
+}
+
+impl DocumentGenerator {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            state: seed,
+            max_depth: 4,
+        }
+    }
+
+    /// Limits how deeply maps and arrays nest. Defaults to 4.
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Generates the next document: a map of manifest-like properties.
+    pub fn value(&mut self) -> Value {
+        let mut entries = vec![
+            (
+                Value::String("version".to_string()),
+                Value::String(format!("0.{}", self.below(10))),
+            ),
+            (Value::String("name".to_string()), self.identifier()),
+            (
+                Value::String("type".to_string()),
+                Value::Integer(self.below(4) as i128),
+            ),
+        ];
+        for _ in 0..self.below(6) {
+            entries.push((self.identifier(), self.any(self.max_depth)));
+        }
+        Value::Map(entries)
+    }
+
+    /// Generates and encodes the next document.
+    pub fn document(&mut self) -> EncodeResult<Vec<u8>> {
+        to_vec(&self.value())
+    }
+
+    fn any(&mut self, depth: usize) -> Value {
+        let kinds = if depth == 0 { 9 } else { 11 };
+        match self.below(kinds) {
+            0 => Value::Nil,
+            1 => Value::Bool(self.below(2) == 1),
+            2 => Value::Integer(self.integer()),
+            3 => Value::Float(self.float()),
+            4 => self.identifier(),
+            5 => self.big_int(),
+            6 => self.big_number(),
+            7 => self.json(),
+            8 => {
+                let len = 1 + self.below(40) as usize;
+                Value::Bytes((0..len).map(|_| self.next() as u8).collect())
+            }
+            9 => {
+                let len = self.below(5);
+                Value::Array((0..len).map(|_| self.any(depth - 1)).collect())
+            }
+            _ => {
+                let len = self.below(5);
+                Value::Map(
+                    (0..len)
+                        .map(|_| (self.identifier(), self.any(depth - 1)))
+                        .collect(),
+                )
+            }
+        }
+    }
+
+    /// An integer of a random msgpack width.
+    fn integer(&mut self) -> i128 {
+        let v = self.next();
+        match self.below(6) {
+            0 => (v % 128) as i128,
+            1 => -((v % 32) as i128),
+            2 => (v as u16) as i128,
+            3 => (v as i32) as i128,
+            4 => (v as i64) as i128,
+            _ => v as i128,
+        }
+    }
+
+    /// A finite float, half of them exactly representable as `f32`.
+    fn float(&mut self) -> f64 {
+        let v =
+            (self.next() as i64) as f64 / (1u64 << (self.below(60) + 1)) as f64;
+        if self.below(2) == 0 {
+            v as f32 as f64
+        } else {
+            v
+        }
+    }
+
+    fn identifier(&mut self) -> Value {
+        let name = NAMES[self.below(NAMES.len() as u64) as usize];
+        match self.below(3) {
+            0 => Value::String(name.to_string()),
+            _ => Value::String(format!("{name}{}", self.below(1000))),
+        }
+    }
+
+    fn digits(&mut self, len: u64) -> String {
+        let mut digits = (1 + self.below(9)).to_string();
+        for _ in 1..len {
+            digits.push(char::from(b'0' + self.below(10) as u8));
+        }
+        digits
+    }
+
+    fn big_int(&mut self) -> Value {
+        let sign = if self.below(2) == 0 { "" } else { "-" };
+        let len = 1 + self.below(60);
+        Value::String(format!("{sign}{}", self.digits(len)))
+    }
+
+    fn big_number(&mut self) -> Value {
+        let (int_len, frac_len) = (1 + self.below(30), 1 + self.below(30));
+        Value::String(format!(
+            "{}.{}",
+            self.digits(int_len),
+            self.digits(frac_len)
+        ))
+    }
+
+    fn json(&mut self) -> Value {
+        let key = NAMES[self.below(NAMES.len() as u64) as usize];
+        let json = match self.below(3) {
+            0 => format!(r#"{{"{key}":{},"ok":true}}"#, self.below(1000)),
+            1 => format!(r#"["{key}",null,{}]"#, self.digits(5)),
+            _ => format!(r#""{key}""#),
+        };
+        Value::String(json)
+    }
+
+    fn below(&mut self, bound: u64) -> u64 {
+        self.next() % bound
+    }
+
+    /// SplitMix64.
+    fn next(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DocumentGenerator;
+    use crate::{from_slice, from_slices, to_vec, PreservingValue, Value};
+
+    #[test]
+    fn test_generator_is_deterministic() {
+        let mut a = DocumentGenerator::new(7);
+        let mut b = DocumentGenerator::new(7);
+        for _ in 0..10 {
+            assert_eq!(a.document().unwrap(), b.document().unwrap());
+        }
+        assert_ne!(
+            DocumentGenerator::new(7).value(),
+            DocumentGenerator::new(8).value()
+        );
+    }
+
+    #[test]
+    fn test_generated_documents_round_trip() {
+        let mut generator = DocumentGenerator::new(0x5eed);
+        for _ in 0..200 {
+            let value = generator.value();
+            let bytes = to_vec(&value).unwrap();
+
+            let decoded: Value = from_slice(&bytes).unwrap();
+            assert_eq!(value, decoded);
+            assert_eq!(bytes, to_vec(&decoded).unwrap());
+
+            let (head, tail) = bytes.split_at(bytes.len() / 2);
+            let split: Value = from_slices(&[head, tail]).unwrap();
+            assert_eq!(value, split);
+
+            let preserved = PreservingValue::from_slice(&bytes).unwrap();
+            assert_eq!(bytes, preserved.to_vec().unwrap());
+            assert_eq!(value, preserved.to_value());
+        }
+    }
+}
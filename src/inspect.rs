@@ -0,0 +1,397 @@
+//! Annotated, human-readable rendering of encoded buffers.
+//!
+//! Every value gets a line with its offset, marker byte, format and decoded
+//! header or payload, indented by nesting depth:
+//!
+//! ```text
+//! 0x00 c7 Ext8 len=11 type=1 (GenericMap)
+//! 0x03   81 FixMap len=1
+//! 0x04     a4 FixStr len=4 "name"
+//! 0x09     a3 FixStr len=3 "foo"
+//! ```
+
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::fmt::Write;
+
+use crate::{
+    format::{ExtensionType, Format},
+    wrappers::timestamp::Timestamp,
+};
+
+/// How many payload bytes of bins and exts are shown before eliding.
+const PREVIEW_LEN: usize = 16;
+/// Nesting beyond this is reported instead of followed.
+const MAX_DEPTH: usize = 256;
+
+/// Renders every value in `bytes`, one line per value.
+///
+/// Malformed input doesn't fail: rendering stops at the first truncated
+/// value with a line explaining what was missing.
+pub fn inspect(bytes: &[u8]) -> String {
+    Inspector::new(bytes, true, &[]).render()
+}
+
+/// Rendering stopped early; the reason has already been written.
+struct Stop;
+
+pub(crate) struct Inspector<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    out: String,
+    offset_width: Option<usize>,
+    redact: &'a [&'a str],
+}
+
+impl<'a> Inspector<'a> {
+    /// `redact` lists map keys whose values are rendered as `[redacted]`,
+    /// at any depth.
+    pub(crate) fn new(
+        bytes: &'a [u8],
+        offsets: bool,
+        redact: &'a [&'a str],
+    ) -> Self {
+        let digits = format!("{:x}", bytes.len()).len().max(2);
+        Self {
+            bytes,
+            pos: 0,
+            out: String::new(),
+            offset_width: offsets.then_some(digits + 2),
+            redact,
+        }
+    }
+
+    pub(crate) fn render(mut self) -> String {
+        while self.pos < self.bytes.len() {
+            if self.value(0).is_err() {
+                break;
+            }
+        }
+        self.out
+    }
+
+    /// Renders one value, returning its contents if it was a string.
+    fn value(&mut self, depth: usize) -> Result<Option<&'a str>, Stop> {
+        let start = self.pos;
+        if depth > MAX_DEPTH {
+            self.line(start, depth, "!! nesting too deep".to_string());
+            return Err(Stop);
+        }
+        let marker = self.take(start, depth, 1)?[0];
+        let format = Format::from_u8(marker);
+        let mut string = None;
+        let mut children = Children::None;
+
+        let description = match format {
+            Format::PositiveFixInt(v) => format!("PositiveFixInt {v}"),
+            Format::NegativeFixInt(v) => format!("NegativeFixInt {v}"),
+            Format::Nil => "Nil".to_string(),
+            Format::Reserved => "Reserved".to_string(),
+            Format::False => "False".to_string(),
+            Format::True => "True".to_string(),
+            Format::Uint8 => format!("Uint8 {}", self.uint(start, depth, 1)?),
+            Format::Uint16 => {
+                format!("Uint16 {}", self.uint(start, depth, 2)?)
+            }
+            Format::Uint32 => {
+                format!("Uint32 {}", self.uint(start, depth, 4)?)
+            }
+            Format::Uint64 => {
+                format!("Uint64 {}", self.uint(start, depth, 8)?)
+            }
+            Format::Int8 => format!("Int8 {}", self.int(start, depth, 1)?),
+            Format::Int16 => format!("Int16 {}", self.int(start, depth, 2)?),
+            Format::Int32 => format!("Int32 {}", self.int(start, depth, 4)?),
+            Format::Int64 => format!("Int64 {}", self.int(start, depth, 8)?),
+            Format::Float32 => {
+                let bits = self.uint(start, depth, 4)? as u32;
+                format!("Float32 {:?}", f32::from_bits(bits))
+            }
+            Format::Float64 => {
+                let bits = self.uint(start, depth, 8)?;
+                format!("Float64 {:?}", f64::from_bits(bits))
+            }
+            Format::FixStr(_)
+            | Format::Str8
+            | Format::Str16
+            | Format::Str32 => {
+                let len = match format {
+                    Format::FixStr(len) => len as u64,
+                    Format::Str8 => self.uint(start, depth, 1)?,
+                    Format::Str16 => self.uint(start, depth, 2)?,
+                    _ => self.uint(start, depth, 4)?,
+                };
+                let bytes = self.take(start, depth, len)?;
+                let name = name(format);
+                match core::str::from_utf8(bytes) {
+                    Ok(s) => {
+                        string = Some(s);
+                        format!("{name} len={len} {s:?}")
+                    }
+                    Err(_) => format!(
+                        "{name} len={len} <invalid utf-8> {}",
+                        preview(bytes)
+                    ),
+                }
+            }
+            Format::Bin8 | Format::Bin16 | Format::Bin32 => {
+                let len = match format {
+                    Format::Bin8 => self.uint(start, depth, 1)?,
+                    Format::Bin16 => self.uint(start, depth, 2)?,
+                    _ => self.uint(start, depth, 4)?,
+                };
+                let bytes = self.take(start, depth, len)?;
+                format!("{} len={len} {}", name(format), preview(bytes))
+            }
+            Format::FixArray(_) | Format::Array16 | Format::Array32 => {
+                let len = match format {
+                    Format::FixArray(len) => len as u64,
+                    Format::Array16 => self.uint(start, depth, 2)?,
+                    _ => self.uint(start, depth, 4)?,
+                };
+                children = Children::Array(len);
+                format!("{} len={len}", name(format))
+            }
+            Format::FixMap(_) | Format::Map16 | Format::Map32 => {
+                let len = match format {
+                    Format::FixMap(len) => len as u64,
+                    Format::Map16 => self.uint(start, depth, 2)?,
+                    _ => self.uint(start, depth, 4)?,
+                };
+                children = Children::Map(len);
+                format!("{} len={len}", name(format))
+            }
+            Format::FixExt1
+            | Format::FixExt2
+            | Format::FixExt4
+            | Format::FixExt8
+            | Format::FixExt16
+            | Format::Ext8
+            | Format::Ext16
+            | Format::Ext32 => {
+                let len = match format {
+                    Format::FixExt1 => 1,
+                    Format::FixExt2 => 2,
+                    Format::FixExt4 => 4,
+                    Format::FixExt8 => 8,
+                    Format::FixExt16 => 16,
+                    Format::Ext8 => self.uint(start, depth, 1)?,
+                    Format::Ext16 => self.uint(start, depth, 2)?,
+                    _ => self.uint(start, depth, 4)?,
+                };
+                let ext_type = self.take(start, depth, 1)?[0];
+                match ExtensionType::try_from(ext_type) {
+                    Ok(ExtensionType::GenericMap) => {
+                        children = Children::Ext(len);
+                        format!(
+                            "{} len={len} type={ext_type} (GenericMap)",
+                            name(format)
+                        )
+                    }
+                    Ok(ExtensionType::Timestamp) => {
+                        let data = self.take(start, depth, len)?;
+                        let timestamp = match Timestamp::from_ext_data(data) {
+                            Ok(t) => format!(
+                                "{}.{:09}s",
+                                t.seconds(),
+                                t.nanoseconds()
+                            ),
+                            Err(_) => preview(data),
+                        };
+                        format!(
+                            "{} len={len} type=-1 (Timestamp) {timestamp}",
+                            name(format)
+                        )
+                    }
+                    Err(_) => {
+                        let data = self.take(start, depth, len)?;
+                        format!(
+                            "{} len={len} type={} {}",
+                            name(format),
+                            ext_type as i8,
+                            preview(data)
+                        )
+                    }
+                }
+            }
+        };
+        self.line(start, depth, format!("{marker:02x} {description}"));
+
+        match children {
+            Children::None => {}
+            Children::Array(len) => {
+                for _ in 0..len {
+                    self.value(depth + 1)?;
+                }
+            }
+            Children::Map(len) => {
+                for _ in 0..len {
+                    let key = self.value(depth + 1)?;
+                    if key.is_some_and(|key| self.redact.contains(&key)) {
+                        self.redacted(depth + 1)?;
+                    } else {
+                        self.value(depth + 1)?;
+                    }
+                }
+            }
+            Children::Ext(len) => {
+                let end = self.pos.saturating_add(len as usize);
+                while self.pos < end {
+                    self.value(depth + 1)?;
+                }
+            }
+        }
+        Ok(string)
+    }
+
+    /// Skips a value, rendering a placeholder in its place.
+    fn redacted(&mut self, depth: usize) -> Result<(), Stop> {
+        let start = self.pos;
+        let rendered = self.out.len();
+        // Keep whatever explains a failure, only the value is hidden.
+        self.value(depth)?;
+        self.out.truncate(rendered);
+        self.line(start, depth, "[redacted]".to_string());
+        Ok(())
+    }
+
+    fn line(&mut self, offset: usize, depth: usize, text: String) {
+        if let Some(width) = self.offset_width {
+            let _ = write!(self.out, "{offset:#0width$x} ");
+        }
+        let _ = writeln!(self.out, "{:indent$}{text}", "", indent = depth * 2);
+    }
+
+    /// Takes the next `len` bytes, or reports the value at `start` as
+    /// truncated.
+    fn take(
+        &mut self,
+        start: usize,
+        depth: usize,
+        len: u64,
+    ) -> Result<&'a [u8], Stop> {
+        let available = self.bytes.len() - self.pos;
+        if len > available as u64 {
+            self.line(
+                start,
+                depth,
+                format!(
+                    "!! truncated at offset {:#x}: needs {len} bytes, \
+                     {available} available",
+                    self.pos
+                ),
+            );
+            return Err(Stop);
+        }
+        let taken = &self.bytes[self.pos..self.pos + len as usize];
+        self.pos += len as usize;
+        Ok(taken)
+    }
+
+    fn uint(
+        &mut self,
+        start: usize,
+        depth: usize,
+        len: u64,
+    ) -> Result<u64, Stop> {
+        let bytes = self.take(start, depth, len)?;
+        Ok(bytes.iter().fold(0, |acc, b| acc << 8 | *b as u64))
+    }
+
+    fn int(
+        &mut self,
+        start: usize,
+        depth: usize,
+        len: u64,
+    ) -> Result<i64, Stop> {
+        let unsigned = self.uint(start, depth, len)?;
+        let shift = 64 - len * 8;
+        Ok(((unsigned << shift) as i64) >> shift)
+    }
+}
+
+enum Children {
+    None,
+    Array(u64),
+    Map(u64),
+    /// A GenericMap ext whose payload is itself msgpack.
+    Ext(u64),
+}
+
+/// The format's name without any inline value, e.g. `FixStr` for
+/// `FixStr(4)`.
+fn name(format: Format) -> String {
+    let debug = format!("{format:?}");
+    match debug.find('(') {
+        Some(paren) => debug[..paren].to_string(),
+        None => debug,
+    }
+}
+
+fn preview(bytes: &[u8]) -> String {
+    let mut hex = bytes
+        .iter()
+        .take(PREVIEW_LEN)
+        .map(|b| format!("{b:02x}"))
+        .collect::<Vec<_>>()
+        .join(" ");
+    if bytes.len() > PREVIEW_LEN {
+        hex.push_str(" ..");
+    }
+    format!("[{hex}]")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::inspect;
+    use crate::{to_vec, Map};
+
+    #[test]
+    fn test_inspect_nested_document() {
+        let mut map = Map::new();
+        map.insert("name", vec![1u8, 200]);
+        let bytes = to_vec(&map).unwrap();
+
+        assert_eq!(
+            inspect(&bytes),
+            "\
+0x00 c7 Ext8 len=10 type=1 (GenericMap)
+0x03   81 FixMap len=1
+0x04     a4 FixStr len=4 \"name\"
+0x09     92 FixArray len=2
+0x0a       01 PositiveFixInt 1
+0x0b       cc Uint8 200
+"
+        );
+    }
+
+    #[test]
+    fn test_inspect_payload_previews() {
+        let mut bytes = vec![0x93, 0xc4, 20];
+        bytes.extend(0..20);
+        bytes.extend([0xd4, 0x05, 0xaa, 0xd6, 0xff, 0, 0, 0, 60]);
+        assert_eq!(
+            inspect(&bytes),
+            "\
+0x00 93 FixArray len=3
+0x01   c4 Bin8 len=20 [00 01 02 03 04 05 06 07 08 09 0a 0b 0c 0d 0e 0f ..]
+0x17   d4 FixExt1 len=1 type=5 [aa]
+0x1a   d6 FixExt4 len=4 type=-1 (Timestamp) 60.000000000s
+"
+        );
+    }
+
+    #[test]
+    fn test_inspect_truncated() {
+        assert_eq!(
+            inspect(&[0x92, 0xcd, 0x01]),
+            "\
+0x00 92 FixArray len=2
+0x01   !! truncated at offset 0x2: needs 2 bytes, 1 available
+"
+        );
+    }
+}
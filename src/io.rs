@@ -0,0 +1,322 @@
+//! The IO traits the deserializer reads from and the serializer writes to.
+//!
+//! With the `std` feature these are `std::io`'s, along with `byteorder`'s
+//! extensions for them. Without it, minimal stand-ins with the same names
+//! cover reading from `&[u8]` and writing to `Vec<u8>` or a [`Cursor`], and
+//! can be implemented for other sources and sinks.
+
+#[cfg(feature = "std")]
+pub use byteorder::{ReadBytesExt, WriteBytesExt};
+#[cfg(feature = "std")]
+pub use std::io::{Cursor, Error, ErrorKind, Read, Result, Write};
+
+#[cfg(not(feature = "std"))]
+pub use self::core_io::*;
+
+use crate::error::ShortRead;
+
+/// The [`ShortRead`] an IO error was made from, if any.
+#[cfg(feature = "std")]
+pub(crate) fn short_read(error: &Error) -> Option<&ShortRead> {
+    error.get_ref().and_then(|e| e.downcast_ref::<ShortRead>())
+}
+
+/// The [`ShortRead`] an IO error was made from, if any.
+#[cfg(not(feature = "std"))]
+pub(crate) fn short_read(error: &Error) -> Option<&ShortRead> {
+    error.short_read.as_ref()
+}
+
+impl From<ShortRead> for Error {
+    #[cfg(feature = "std")]
+    fn from(value: ShortRead) -> Self {
+        Error::new(ErrorKind::UnexpectedEof, value)
+    }
+
+    #[cfg(not(feature = "std"))]
+    fn from(value: ShortRead) -> Self {
+        Error {
+            kind: ErrorKind::UnexpectedEof,
+            message: "failed to fill whole buffer",
+            short_read: Some(value),
+        }
+    }
+}
+
+#[cfg(not(feature = "std"))]
+mod core_io {
+    use alloc::vec::Vec;
+    use core::fmt;
+
+    use byteorder::ByteOrder;
+
+    use crate::error::ShortRead;
+
+    pub type Result<T> = core::result::Result<T, Error>;
+
+    /// The kinds of [`Error`] raised by this crate's readers and writers.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    #[non_exhaustive]
+    pub enum ErrorKind {
+        UnexpectedEof,
+        WriteZero,
+        Interrupted,
+        Other,
+    }
+
+    /// A failed read or write.
+    #[derive(Debug)]
+    pub struct Error {
+        pub(super) kind: ErrorKind,
+        pub(super) message: &'static str,
+        pub(super) short_read: Option<ShortRead>,
+    }
+
+    impl Error {
+        pub fn new(kind: ErrorKind, message: &'static str) -> Self {
+            Self {
+                kind,
+                message,
+                short_read: None,
+            }
+        }
+
+        pub fn kind(&self) -> ErrorKind {
+            self.kind
+        }
+    }
+
+    impl fmt::Display for Error {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str(self.message)
+        }
+    }
+
+    impl core::error::Error for Error {}
+
+    /// A source of bytes.
+    pub trait Read {
+        /// Reads up to `buf.len()` bytes, returning how many were read.
+        /// Returns `0` only once the input is exhausted.
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize>;
+
+        /// Fills `buf` entirely, failing with
+        /// [`ErrorKind::UnexpectedEof`] if the input ends first.
+        fn read_exact(&mut self, mut buf: &mut [u8]) -> Result<()> {
+            while !buf.is_empty() {
+                match self.read(buf) {
+                    Ok(0) => break,
+                    Ok(n) => buf = &mut buf[n..],
+                    Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                    Err(e) => return Err(e),
+                }
+            }
+            if buf.is_empty() {
+                Ok(())
+            } else {
+                Err(Error::new(
+                    ErrorKind::UnexpectedEof,
+                    "failed to fill whole buffer",
+                ))
+            }
+        }
+    }
+
+    impl<R: Read + ?Sized> Read for &mut R {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+            (**self).read(buf)
+        }
+
+        fn read_exact(&mut self, buf: &mut [u8]) -> Result<()> {
+            (**self).read_exact(buf)
+        }
+    }
+
+    impl Read for &[u8] {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+            let n = buf.len().min(self.len());
+            let (read, rest) = self.split_at(n);
+            buf[..n].copy_from_slice(read);
+            *self = rest;
+            Ok(n)
+        }
+    }
+
+    /// A sink for bytes.
+    pub trait Write {
+        /// Writes some of `buf`, returning how many bytes were written.
+        fn write(&mut self, buf: &[u8]) -> Result<usize>;
+
+        fn flush(&mut self) -> Result<()>;
+
+        /// Writes all of `buf`, failing with [`ErrorKind::WriteZero`] if
+        /// the sink stops accepting bytes.
+        fn write_all(&mut self, mut buf: &[u8]) -> Result<()> {
+            while !buf.is_empty() {
+                match self.write(buf) {
+                    Ok(0) => {
+                        return Err(Error::new(
+                            ErrorKind::WriteZero,
+                            "failed to write whole buffer",
+                        ))
+                    }
+                    Ok(n) => buf = &buf[n..],
+                    Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                    Err(e) => return Err(e),
+                }
+            }
+            Ok(())
+        }
+    }
+
+    impl<W: Write + ?Sized> Write for &mut W {
+        fn write(&mut self, buf: &[u8]) -> Result<usize> {
+            (**self).write(buf)
+        }
+
+        fn flush(&mut self) -> Result<()> {
+            (**self).flush()
+        }
+    }
+
+    impl Write for Vec<u8> {
+        fn write(&mut self, buf: &[u8]) -> Result<usize> {
+            self.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    /// A byte buffer with a write position, overwriting bytes before its
+    /// end and growing past it.
+    #[derive(Debug, Default, Clone)]
+    pub struct Cursor<T> {
+        inner: T,
+        position: u64,
+    }
+
+    impl<T> Cursor<T> {
+        pub fn new(inner: T) -> Self {
+            Self { inner, position: 0 }
+        }
+
+        pub fn get_ref(&self) -> &T {
+            &self.inner
+        }
+
+        pub fn get_mut(&mut self) -> &mut T {
+            &mut self.inner
+        }
+
+        pub fn into_inner(self) -> T {
+            self.inner
+        }
+
+        pub fn position(&self) -> u64 {
+            self.position
+        }
+
+        pub fn set_position(&mut self, position: u64) {
+            self.position = position;
+        }
+    }
+
+    impl Write for Cursor<Vec<u8>> {
+        fn write(&mut self, buf: &[u8]) -> Result<usize> {
+            let start = self.position as usize;
+            let end = start + buf.len();
+            if self.inner.len() < start {
+                self.inner.resize(start, 0);
+            }
+            let overlap = self.inner.len().min(end) - start;
+            self.inner[start..start + overlap]
+                .copy_from_slice(&buf[..overlap]);
+            self.inner.extend_from_slice(&buf[overlap..]);
+            self.position = end as u64;
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    macro_rules! read_bytes {
+        ($($method:ident -> $ty:ty, $read:ident;)*) => {
+            $(
+                fn $method<B: ByteOrder>(&mut self) -> Result<$ty> {
+                    let mut buf = [0; core::mem::size_of::<$ty>()];
+                    self.read_exact(&mut buf)?;
+                    Ok(B::$read(&buf))
+                }
+            )*
+        };
+    }
+
+    macro_rules! write_bytes {
+        ($($method:ident($ty:ty), $write:ident;)*) => {
+            $(
+                fn $method<B: ByteOrder>(&mut self, n: $ty) -> Result<()> {
+                    let mut buf = [0; core::mem::size_of::<$ty>()];
+                    B::$write(&mut buf, n);
+                    self.write_all(&buf)
+                }
+            )*
+        };
+    }
+
+    /// Reads numbers in a given byte order, like `byteorder`'s trait of the
+    /// same name.
+    pub trait ReadBytesExt: Read {
+        fn read_u8(&mut self) -> Result<u8> {
+            let mut buf = [0];
+            self.read_exact(&mut buf)?;
+            Ok(buf[0])
+        }
+
+        fn read_i8(&mut self) -> Result<i8> {
+            Ok(self.read_u8()? as i8)
+        }
+
+        read_bytes! {
+            read_u16 -> u16, read_u16;
+            read_i16 -> i16, read_i16;
+            read_u32 -> u32, read_u32;
+            read_i32 -> i32, read_i32;
+            read_u64 -> u64, read_u64;
+            read_i64 -> i64, read_i64;
+            read_f32 -> f32, read_f32;
+            read_f64 -> f64, read_f64;
+        }
+    }
+
+    impl<R: Read + ?Sized> ReadBytesExt for R {}
+
+    /// Writes numbers in a given byte order, like `byteorder`'s trait of the
+    /// same name.
+    pub trait WriteBytesExt: Write {
+        fn write_u8(&mut self, n: u8) -> Result<()> {
+            self.write_all(&[n])
+        }
+
+        fn write_i8(&mut self, n: i8) -> Result<()> {
+            self.write_all(&[n as u8])
+        }
+
+        write_bytes! {
+            write_u16(u16), write_u16;
+            write_i16(i16), write_i16;
+            write_u32(u32), write_u32;
+            write_i32(i32), write_i32;
+            write_u64(u64), write_u64;
+            write_i64(i64), write_i64;
+            write_f32(f32), write_f32;
+            write_f64(f64), write_f64;
+        }
+    }
+
+    impl<W: Write + ?Sized> WriteBytesExt for W {}
+}
@@ -1,19 +1,79 @@
-#[allow(irrefutable_let_patterns)]
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
 
+// Lets derived impls name `::polywrap_msgpack_serde` inside this crate too.
+extern crate self as polywrap_msgpack_serde;
+extern crate alloc;
+
+#[cfg(feature = "codec")]
+pub mod codec;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod conformance;
 mod de;
 pub mod error;
 pub use error::*;
-mod format;
+mod fingerprint;
+pub mod format;
+#[cfg(feature = "futures")]
+pub mod futures;
+pub mod io;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod generator;
+pub mod inspect;
+pub mod manifest;
+pub mod rpc;
 mod ser;
+#[cfg(feature = "insta")]
+pub mod snapshot;
+pub mod token;
+#[cfg(feature = "transcode")]
+pub mod transcode;
+#[cfg(feature = "tokio")]
+pub mod tokio;
+pub mod validate;
+pub mod value;
 pub mod wrappers;
 
 pub use bigdecimal::BigDecimal as BigNumber;
 pub use serde_json as JSON;
-pub use std::collections::BTreeMap as Map;
+pub use alloc::collections::BTreeMap as Map;
 pub use serde_bytes;
 pub use num_bigint::{BigInt, ParseBigIntError};
+pub use wrappers::flattened::Flattened;
+pub use wrappers::generic_map::GenericMap;
+pub use wrappers::int128_string::{I128String, U128String};
 pub use wrappers::polywrap_bigint::BigIntWrapper;
+pub use wrappers::polywrap_bignumber::BigNumberWrapper;
 pub use wrappers::polywrap_json::JSONString;
+#[cfg(feature = "uuid")]
+pub use wrappers::polywrap_uuid::UuidWrapper;
+pub use wrappers::shared_string::SharedString;
+#[cfg(feature = "compact_str")]
+pub use compact_str::CompactString;
+#[cfg(feature = "smartstring")]
+pub use smartstring::alias::String as SmartString;
 
-pub use crate::de::{from_slice, Deserializer};
-pub use ser::{to_vec, Serializer};
+pub use crate::de::{
+    from_reader, from_slice, from_slice_with_config, from_slices, read,
+    Deserializer, DeserializerConfig, FloatToInt, IncrementalDecoder,
+    StreamDeserializer, Utf8Policy,
+};
+#[cfg(feature = "bytes")]
+pub use crate::de::from_bytes;
+pub use ser::{
+    encode_batch, encode_batch_to, to_vec, to_vec_reuse, to_vec_with_config,
+    to_vec_with_plain_maps, EncodedFields, FloatCanonicalization, MapEncoding,
+    Serializer, SerializerConfig,
+};
+#[cfg(feature = "tokio")]
+pub use crate::tokio::{from_reader_async, to_writer_async};
+pub use fingerprint::{
+    from_slice_fingerprinted, to_vec_fingerprinted, Fingerprint,
+};
+#[cfg(feature = "derive")]
+pub use polywrap_msgpack_serde_derive::{EncodedFields, Fingerprint};
+pub use value::schema::{decode_with_schema, FieldInfo, TypeInfo};
+pub use value::preserving::PreservingValue;
+pub use validate::{validate, ValidationError};
+#[cfg(feature = "base64")]
+pub use value::{json_string_to_msgpack, msgpack_to_json_string};
+pub use value::Value;
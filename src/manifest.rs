@@ -0,0 +1,478 @@
+//! Typed WRAP manifest (`wrap.info`), version 0.1.
+//!
+//! [`serialize_wrap_manifest`] and [`deserialize_wrap_manifest`] check the
+//! manifest against the spec on the way in and out, so an invalid
+//! `wrap.info` is reported where it's produced rather than by the client
+//! that later fails to load it.
+//!
+//! Property, argument and return types share the recursive
+//! [`PropertyDefinition`], whose nested array, map, scalar and reference
+//! definitions are all optional.
+
+use alloc::{boxed::Box, format, string::String, vec::Vec};
+use core::{fmt, str::FromStr};
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{
+    error::{DecodeResult, Result},
+    from_slice, to_vec, DecodeError,
+};
+
+/// The only manifest and ABI version this module reads and writes.
+pub const WRAP_MANIFEST_VERSION: &str = "0.1";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WrapManifest {
+    pub version: String,
+    #[serde(rename = "type")]
+    pub type_: WrapManifestType,
+    pub name: String,
+    pub abi: Abi,
+}
+
+/// What the wrap is, written as its lowercase name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WrapManifestType {
+    Wasm,
+    Interface,
+    Plugin,
+}
+
+impl WrapManifestType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Wasm => "wasm",
+            Self::Interface => "interface",
+            Self::Plugin => "plugin",
+        }
+    }
+}
+
+impl fmt::Display for WrapManifestType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for WrapManifestType {
+    type Err = String;
+
+    fn from_str(s: &str) -> core::result::Result<Self, Self::Err> {
+        match s {
+            "wasm" => Ok(Self::Wasm),
+            "interface" => Ok(Self::Interface),
+            "plugin" => Ok(Self::Plugin),
+            _ => Err(format!(
+                "unknown wrap type `{s}`, expected `wasm`, `interface` or \
+                 `plugin`"
+            )),
+        }
+    }
+}
+
+impl Serialize for WrapManifestType {
+    fn serialize<S>(
+        &self,
+        serializer: S,
+    ) -> core::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for WrapManifestType {
+    fn deserialize<D>(deserializer: D) -> core::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = <alloc::borrow::Cow<str>>::deserialize(deserializer)?;
+        s.parse().map_err(de::Error::custom)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Abi {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub object_types: Option<Vec<ObjectDefinition>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub module_type: Option<ModuleDefinition>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub enum_types: Option<Vec<EnumDefinition>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub interface_types: Option<Vec<InterfaceDefinition>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub imported_object_types: Option<Vec<ImportedObjectDefinition>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub imported_module_types: Option<Vec<ImportedModuleDefinition>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub imported_enum_types: Option<Vec<ImportedEnumDefinition>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub imported_env_types: Option<Vec<ImportedObjectDefinition>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub env_type: Option<ObjectDefinition>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ObjectDefinition {
+    #[serde(rename = "type")]
+    pub type_: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub required: Option<bool>,
+    pub kind: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub properties: Option<Vec<PropertyDefinition>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub interfaces: Option<Vec<TypeReference>>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportedObjectDefinition {
+    #[serde(rename = "type")]
+    pub type_: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub required: Option<bool>,
+    pub kind: u32,
+    pub uri: String,
+    pub namespace: String,
+    pub native_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub properties: Option<Vec<PropertyDefinition>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub interfaces: Option<Vec<TypeReference>>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ModuleDefinition {
+    #[serde(rename = "type")]
+    pub type_: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub required: Option<bool>,
+    pub kind: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub methods: Option<Vec<MethodDefinition>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub imports: Option<Vec<ImportedModuleReference>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub interfaces: Option<Vec<TypeReference>>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportedModuleDefinition {
+    #[serde(rename = "type")]
+    pub type_: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub required: Option<bool>,
+    pub kind: u32,
+    pub uri: String,
+    pub namespace: String,
+    pub native_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub is_interface: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub methods: Option<Vec<MethodDefinition>>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ImportedModuleReference {
+    #[serde(rename = "type")]
+    pub type_: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MethodDefinition {
+    #[serde(rename = "type")]
+    pub type_: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub required: Option<bool>,
+    pub kind: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub arguments: Option<Vec<PropertyDefinition>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub env: Option<MethodEnv>,
+    #[serde(rename = "return", skip_serializing_if = "Option::is_none")]
+    pub return_: Option<PropertyDefinition>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MethodEnv {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub required: Option<bool>,
+}
+
+/// A property, argument, return value, array item or map key or value.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PropertyDefinition {
+    #[serde(rename = "type")]
+    pub type_: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub required: Option<bool>,
+    pub kind: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub array: Option<Box<PropertyDefinition>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub map: Option<Box<PropertyDefinition>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scalar: Option<Box<PropertyDefinition>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub object: Option<Box<PropertyDefinition>>,
+    #[serde(rename = "enum", skip_serializing_if = "Option::is_none")]
+    pub enum_: Option<Box<PropertyDefinition>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unresolved_object_or_enum: Option<Box<PropertyDefinition>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub item: Option<Box<PropertyDefinition>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key: Option<Box<PropertyDefinition>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<Box<PropertyDefinition>>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct EnumDefinition {
+    #[serde(rename = "type")]
+    pub type_: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub required: Option<bool>,
+    pub kind: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub constants: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportedEnumDefinition {
+    #[serde(rename = "type")]
+    pub type_: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub required: Option<bool>,
+    pub kind: u32,
+    pub uri: String,
+    pub namespace: String,
+    pub native_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub constants: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InterfaceDefinition {
+    #[serde(rename = "type")]
+    pub type_: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub required: Option<bool>,
+    pub kind: u32,
+    pub uri: String,
+    pub namespace: String,
+    pub native_type: String,
+    pub capabilities: Capabilities,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Capabilities {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub get_implementations: Option<CapabilityEnabled>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CapabilityEnabled {
+    pub enabled: bool,
+}
+
+/// A reference to an interface or other type by name.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TypeReference {
+    #[serde(rename = "type")]
+    pub type_: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub required: Option<bool>,
+    pub kind: u32,
+}
+
+impl WrapManifest {
+    /// Checks the constraints of the wrap.info schema that the types alone
+    /// don't enforce, failing with [`DecodeError::InvalidManifest`].
+    pub fn validate(&self) -> DecodeResult<()> {
+        if self.version != WRAP_MANIFEST_VERSION {
+            return Err(invalid(format!(
+                "unsupported version `{}`, expected `{WRAP_MANIFEST_VERSION}`",
+                self.version
+            )));
+        }
+        if self.name.is_empty()
+            || !self
+                .name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            return Err(invalid(format!(
+                "name `{}` must be non-empty and only contain letters, \
+                 digits, `-` and `_`",
+                self.name
+            )));
+        }
+        if let Some(version) = &self.abi.version {
+            if version != WRAP_MANIFEST_VERSION {
+                return Err(invalid(format!(
+                    "unsupported abi version `{version}`, expected \
+                     `{WRAP_MANIFEST_VERSION}`"
+                )));
+            }
+        }
+
+        let methods = self
+            .abi
+            .module_type
+            .iter()
+            .flat_map(|module| module.methods.iter().flatten());
+        for method in methods {
+            if method.name.as_deref().map_or(true, str::is_empty) {
+                return Err(invalid(format!(
+                    "method of type `{}` has no name",
+                    method.type_
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn invalid(message: String) -> DecodeError {
+    DecodeError::InvalidManifest(message)
+}
+
+/// Validates and encodes a manifest as `wrap.info` bytes.
+pub fn serialize_wrap_manifest(manifest: &WrapManifest) -> Result<Vec<u8>> {
+    manifest.validate()?;
+    Ok(to_vec(manifest)?)
+}
+
+/// Decodes and validates `wrap.info` bytes.
+pub fn deserialize_wrap_manifest(bytes: &[u8]) -> DecodeResult<WrapManifest> {
+    let manifest: WrapManifest = from_slice(bytes)?;
+    manifest.validate()?;
+    Ok(manifest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Error, Value};
+
+    fn manifest() -> WrapManifest {
+        let string = |name: &str| PropertyDefinition {
+            type_: "String".to_string(),
+            name: Some(name.to_string()),
+            required: Some(true),
+            kind: 34,
+            scalar: Some(Box::new(PropertyDefinition {
+                type_: "String".to_string(),
+                name: Some(name.to_string()),
+                required: Some(true),
+                kind: 4,
+                ..Default::default()
+            })),
+            ..Default::default()
+        };
+        WrapManifest {
+            version: "0.1".to_string(),
+            type_: WrapManifestType::Wasm,
+            name: "hello-world".to_string(),
+            abi: Abi {
+                version: Some("0.1".to_string()),
+                module_type: Some(ModuleDefinition {
+                    type_: "Module".to_string(),
+                    kind: 128,
+                    methods: Some(vec![MethodDefinition {
+                        type_: "Method".to_string(),
+                        name: Some("greet".to_string()),
+                        required: Some(true),
+                        kind: 64,
+                        arguments: Some(vec![string("who")]),
+                        return_: Some(string("greet")),
+                        ..Default::default()
+                    }]),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+        }
+    }
+
+    #[test]
+    fn test_wrap_manifest_round_trip() {
+        let manifest = manifest();
+        let bytes = serialize_wrap_manifest(&manifest).unwrap();
+        assert_eq!(manifest, deserialize_wrap_manifest(&bytes).unwrap());
+
+        // The type is its name, and absent fields are left out.
+        let value: Value = from_slice(&bytes).unwrap();
+        let Value::Map(entries) = value else {
+            panic!("expected a map, found {value:?}");
+        };
+        assert!(entries.contains(&(
+            Value::String("type".to_string()),
+            Value::String("wasm".to_string())
+        )));
+        assert!(!bytes.windows(6).any(|w| w == b"envTyp"));
+    }
+
+    #[test]
+    fn test_wrap_manifest_validation() {
+        let mut bad_version = manifest();
+        bad_version.version = "0.2".to_string();
+        let err = serialize_wrap_manifest(&bad_version).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::Decode(DecodeError::InvalidManifest(_))
+        ));
+
+        let mut bad_name = manifest();
+        bad_name.name = "hello world".to_string();
+        assert!(serialize_wrap_manifest(&bad_name).is_err());
+
+        let mut unnamed = manifest();
+        let module = unnamed.abi.module_type.as_mut().unwrap();
+        module.methods.as_mut().unwrap()[0].name = None;
+        assert!(serialize_wrap_manifest(&unnamed).is_err());
+
+        // Invalid manifests are caught on decode too
+        let bytes = to_vec(&bad_version).unwrap();
+        assert!(deserialize_wrap_manifest(&bytes).is_err());
+        let bytes = to_vec(&manifest()).unwrap();
+        let mut bad_type = bytes.clone();
+        let at = bad_type.windows(4).position(|w| w == b"wasm").unwrap();
+        bad_type[at..at + 4].copy_from_slice(b"wast");
+        let err = deserialize_wrap_manifest(&bad_type).unwrap_err();
+        assert!(err.to_string().contains("unknown wrap type `wast`"));
+    }
+}
@@ -0,0 +1,463 @@
+//! Messages of the [msgpack-rpc] protocol, for hosts exchanging invocations
+//! over a transport.
+//!
+//! Each message is an array led by its type: `[0, msgid, method, params]`
+//! for a [`Request`], `[1, msgid, error, result]` for its [`Response`] and
+//! `[2, method, params]` for a [`Notification`], which gets no response.
+//! They're written with [`to_vec`](crate::to_vec) and read with
+//! [`from_slice`](crate::from_slice) like any other value; [`Message`] reads
+//! whichever of them comes next.
+//!
+//! A [`Correlator`] hands out msgids and matches responses to the requests
+//! they answer.
+//!
+//! [msgpack-rpc]: https://github.com/msgpack-rpc/msgpack-rpc/blob/master/spec.md
+
+use alloc::{collections::BTreeMap, string::String, vec::Vec};
+use core::{fmt, marker::PhantomData};
+
+use serde::{
+    de::{self, IgnoredAny, SeqAccess, Unexpected, Visitor},
+    ser::SerializeTuple,
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+
+use crate::Value;
+
+const REQUEST: u8 = 0;
+const RESPONSE: u8 = 1;
+const NOTIFICATION: u8 = 2;
+
+/// A call of `method` that expects a [`Response`] with the same `id`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Request<P = Vec<Value>> {
+    pub id: u32,
+    pub method: String,
+    /// The arguments, which the spec requires to be an array.
+    pub params: P,
+}
+
+impl<P> Request<P> {
+    /// The response to this request, carrying its `id`.
+    pub fn respond<R, E>(
+        &self,
+        result: core::result::Result<R, E>,
+    ) -> Response<R, E> {
+        Response {
+            id: self.id,
+            result,
+        }
+    }
+}
+
+/// The outcome of the [`Request`] with the same `id`: `Ok` with its result,
+/// or `Err` when the error element isn't nil.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Response<R = Value, E = Value> {
+    pub id: u32,
+    pub result: core::result::Result<R, E>,
+}
+
+/// A call of `method` that gets no response.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Notification<P = Vec<Value>> {
+    pub method: String,
+    pub params: P,
+}
+
+/// Any of the three messages, told apart by their type element.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Message<P = Vec<Value>, R = Value, E = Value> {
+    Request(Request<P>),
+    Response(Response<R, E>),
+    Notification(Notification<P>),
+}
+
+impl<P: Serialize> Serialize for Request<P> {
+    fn serialize<S>(
+        &self,
+        serializer: S,
+    ) -> core::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut tuple = serializer.serialize_tuple(4)?;
+        tuple.serialize_element(&REQUEST)?;
+        tuple.serialize_element(&self.id)?;
+        tuple.serialize_element(&self.method)?;
+        tuple.serialize_element(&self.params)?;
+        tuple.end()
+    }
+}
+
+impl<R: Serialize, E: Serialize> Serialize for Response<R, E> {
+    fn serialize<S>(
+        &self,
+        serializer: S,
+    ) -> core::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut tuple = serializer.serialize_tuple(4)?;
+        tuple.serialize_element(&RESPONSE)?;
+        tuple.serialize_element(&self.id)?;
+        match &self.result {
+            Ok(result) => {
+                tuple.serialize_element(&())?;
+                tuple.serialize_element(result)?;
+            }
+            Err(error) => {
+                tuple.serialize_element(error)?;
+                tuple.serialize_element(&())?;
+            }
+        }
+        tuple.end()
+    }
+}
+
+impl<P: Serialize> Serialize for Notification<P> {
+    fn serialize<S>(
+        &self,
+        serializer: S,
+    ) -> core::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut tuple = serializer.serialize_tuple(3)?;
+        tuple.serialize_element(&NOTIFICATION)?;
+        tuple.serialize_element(&self.method)?;
+        tuple.serialize_element(&self.params)?;
+        tuple.end()
+    }
+}
+
+impl<P, R, E> Serialize for Message<P, R, E>
+where
+    P: Serialize,
+    R: Serialize,
+    E: Serialize,
+{
+    fn serialize<S>(
+        &self,
+        serializer: S,
+    ) -> core::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            Message::Request(request) => request.serialize(serializer),
+            Message::Response(response) => response.serialize(serializer),
+            Message::Notification(notification) => {
+                notification.serialize(serializer)
+            }
+        }
+    }
+}
+
+/// Reads the element at `index` of a message, which must be there.
+fn element<'de, A, T>(
+    seq: &mut A,
+    index: usize,
+    expected: &dyn de::Expected,
+) -> core::result::Result<T, A::Error>
+where
+    A: SeqAccess<'de>,
+    T: Deserialize<'de>,
+{
+    seq.next_element()?
+        .ok_or_else(|| de::Error::invalid_length(index, expected))
+}
+
+struct MessageVisitor<P, R, E>(PhantomData<(P, R, E)>);
+
+impl<'de, P, R, E> Visitor<'de> for MessageVisitor<P, R, E>
+where
+    P: Deserialize<'de>,
+    R: Deserialize<'de>,
+    E: Deserialize<'de>,
+{
+    type Value = Message<P, R, E>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a msgpack-rpc message")
+    }
+
+    fn visit_seq<A>(
+        self,
+        mut seq: A,
+    ) -> core::result::Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let message = match element(&mut seq, 0, &self)? {
+            REQUEST => Message::Request(Request {
+                id: element(&mut seq, 1, &self)?,
+                method: element(&mut seq, 2, &self)?,
+                params: element(&mut seq, 3, &self)?,
+            }),
+            RESPONSE => {
+                let id = element(&mut seq, 1, &self)?;
+                // The result is nil when there's an error, so it's only
+                // decoded as `R` when there isn't
+                let result = match element(&mut seq, 2, &self)? {
+                    Some(error) => {
+                        element::<_, IgnoredAny>(&mut seq, 3, &self)?;
+                        Err(error)
+                    }
+                    None => Ok(element(&mut seq, 3, &self)?),
+                };
+                Message::Response(Response { id, result })
+            }
+            NOTIFICATION => Message::Notification(Notification {
+                method: element(&mut seq, 1, &self)?,
+                params: element(&mut seq, 2, &self)?,
+            }),
+            kind => {
+                return Err(de::Error::invalid_value(
+                    Unexpected::Unsigned(kind as u64),
+                    &"a message type of 0, 1 or 2",
+                ))
+            }
+        };
+        Ok(message)
+    }
+}
+
+impl<'de, P, R, E> Deserialize<'de> for Message<P, R, E>
+where
+    P: Deserialize<'de>,
+    R: Deserialize<'de>,
+    E: Deserialize<'de>,
+{
+    fn deserialize<D>(deserializer: D) -> core::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_seq(MessageVisitor(PhantomData))
+    }
+}
+
+/// Fails on a message other than the one expected.
+fn unexpected_message<Error: de::Error>(found: &str, expected: &str) -> Error {
+    Error::custom(format_args!("expected a {expected}, found a {found}"))
+}
+
+impl<'de, P: Deserialize<'de>> Deserialize<'de> for Request<P> {
+    fn deserialize<D>(deserializer: D) -> core::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        match Message::<P, IgnoredAny, IgnoredAny>::deserialize(deserializer)? {
+            Message::Request(request) => Ok(request),
+            Message::Response(_) => {
+                Err(unexpected_message("response", "request"))
+            }
+            Message::Notification(_) => {
+                Err(unexpected_message("notification", "request"))
+            }
+        }
+    }
+}
+
+impl<'de, R, E> Deserialize<'de> for Response<R, E>
+where
+    R: Deserialize<'de>,
+    E: Deserialize<'de>,
+{
+    fn deserialize<D>(deserializer: D) -> core::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        match Message::<IgnoredAny, R, E>::deserialize(deserializer)? {
+            Message::Response(response) => Ok(response),
+            Message::Request(_) => {
+                Err(unexpected_message("request", "response"))
+            }
+            Message::Notification(_) => {
+                Err(unexpected_message("notification", "response"))
+            }
+        }
+    }
+}
+
+impl<'de, P: Deserialize<'de>> Deserialize<'de> for Notification<P> {
+    fn deserialize<D>(deserializer: D) -> core::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        match Message::<P, IgnoredAny, IgnoredAny>::deserialize(deserializer)? {
+            Message::Notification(notification) => Ok(notification),
+            Message::Request(_) => {
+                Err(unexpected_message("request", "notification"))
+            }
+            Message::Response(_) => {
+                Err(unexpected_message("response", "notification"))
+            }
+        }
+    }
+}
+
+/// Hands out msgids for outgoing requests and matches each response to the
+/// request it answers, along with whatever `T` the caller kept for it, such
+/// as the sender half of a channel.
+#[derive(Debug, Clone)]
+pub struct Correlator<T = ()> {
+    next_id: u32,
+    pending: BTreeMap<u32, T>,
+}
+
+impl<T> Default for Correlator<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Correlator<T> {
+    pub fn new() -> Self {
+        Self {
+            next_id: 0,
+            pending: BTreeMap::new(),
+        }
+    }
+
+    /// A request with a msgid no pending request has, keeping `context`
+    /// until its response is resolved. Msgids wrap around after
+    /// `u32::MAX`.
+    pub fn request<P>(
+        &mut self,
+        method: impl Into<String>,
+        params: P,
+        context: T,
+    ) -> Request<P> {
+        while self.pending.contains_key(&self.next_id) {
+            self.next_id = self.next_id.wrapping_add(1);
+        }
+        let id = self.next_id;
+        self.next_id = id.wrapping_add(1);
+        self.pending.insert(id, context);
+        Request {
+            id,
+            method: method.into(),
+            params,
+        }
+    }
+
+    /// Takes the context of the request `response` answers, or `None` if
+    /// no request with its msgid is pending.
+    pub fn resolve<R, E>(&mut self, response: &Response<R, E>) -> Option<T> {
+        self.pending.remove(&response.id)
+    }
+
+    /// Stops waiting for the response to the request with msgid `id`, e.g.
+    /// once it has timed out, and returns its context.
+    pub fn cancel(&mut self, id: u32) -> Option<T> {
+        self.pending.remove(&id)
+    }
+
+    /// Number of requests still waiting for their response.
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_bytes::ByteBuf;
+
+    use super::{Correlator, Message, Notification, Request, Response};
+    use crate::{from_slice, to_vec, Value};
+
+    #[test]
+    fn test_rpc_messages_follow_the_spec() {
+        let request = Request {
+            id: 7,
+            method: "add".to_string(),
+            params: (1, 2),
+        };
+        let bytes = to_vec(&request).unwrap();
+        assert_eq!(vec![0x94, 0, 7, 0xa3, b'a', b'd', b'd', 0x92, 1, 2], bytes);
+        assert_eq!(request, from_slice(&bytes).unwrap());
+
+        let ok = request.respond::<u8, String>(Ok(3));
+        let bytes = to_vec(&ok).unwrap();
+        assert_eq!(vec![0x94, 1, 7, 0xc0, 3], bytes);
+        assert_eq!(ok, from_slice(&bytes).unwrap());
+
+        let err = request.respond::<u8, String>(Err("overflow".to_string()));
+        let bytes = to_vec(&err).unwrap();
+        assert_eq!(&[0x94, 1, 7, 0xa8], &bytes[..4]);
+        assert_eq!(0xc0, bytes[bytes.len() - 1]);
+        assert_eq!(err, from_slice(&bytes).unwrap());
+
+        let notification = Notification {
+            method: "log".to_string(),
+            params: vec![ByteBuf::from(vec![1])],
+        };
+        let bytes = to_vec(&notification).unwrap();
+        assert_eq!(
+            vec![0x93, 2, 0xa3, b'l', b'o', b'g', 0x91, 0xc4, 1, 1],
+            bytes
+        );
+        assert_eq!(notification, from_slice(&bytes).unwrap());
+    }
+
+    #[test]
+    fn test_read_any_rpc_message() {
+        let messages: Vec<Message> = vec![
+            Message::Request(Request {
+                id: 1,
+                method: "get".to_string(),
+                params: vec![Value::String("uri".to_string())],
+            }),
+            Message::Response(Response {
+                id: 1,
+                result: Ok(Value::Nil),
+            }),
+            Message::Response(Response {
+                id: 2,
+                result: Err(Value::Integer(-1)),
+            }),
+            Message::Notification(Notification {
+                method: "ping".to_string(),
+                params: vec![],
+            }),
+        ];
+        for message in messages {
+            let bytes = to_vec(&message).unwrap();
+            assert_eq!(message, from_slice(&bytes).unwrap());
+        }
+
+        // A response where a request is expected, an unknown type and a
+        // request missing its params
+        let response = to_vec(&Response::<u8, u8> {
+            id: 0,
+            result: Ok(1),
+        });
+        let err = from_slice::<Request>(&response.unwrap()).unwrap_err();
+        assert!(err.to_string().contains("found a response"), "{err}");
+        assert!(from_slice::<Message>(&[0x93, 3, 0, 0]).is_err());
+        assert!(from_slice::<Message>(&[0x93, 0, 1, 0xa0]).is_err());
+    }
+
+    #[test]
+    fn test_correlate_responses_with_requests() {
+        let mut correlator = Correlator::new();
+        let first = correlator.request("a", (), "first");
+        let second = correlator.request("b", (), "second");
+        assert_ne!(first.id, second.id);
+        assert_eq!(2, correlator.pending());
+
+        let response = second.respond::<(), ()>(Ok(()));
+        assert_eq!(Some("second"), correlator.resolve(&response));
+        assert_eq!(None, correlator.resolve(&response));
+        assert_eq!(Some("first"), correlator.cancel(first.id));
+        assert_eq!(0, correlator.pending());
+
+        // Msgids still pending are skipped once they wrap around
+        let mut correlator = Correlator::new();
+        correlator.request("a", (), ());
+        correlator.next_id = u32::MAX;
+        assert_eq!(u32::MAX, correlator.request("b", (), ()).id);
+        assert_eq!(1, correlator.request("c", (), ()).id);
+    }
+}
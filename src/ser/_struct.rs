@@ -1,50 +1,196 @@
-use std::io::Write;
+use alloc::{string::ToString, vec, vec::Vec};
+use core::{ops::Range, str::FromStr};
 
-use serde::{ser, Serialize};
+use bigdecimal::BigDecimal;
+use serde::{ser, Serialize, Serializer as _};
 
-use crate::{error::Error, Serializer};
+use crate::{error::EncodeError, io::Write, Serializer};
 
-use super::{map::MapSerializer};
+use super::{array::ArraySerializer, fields::FieldCursor, map::MapSerializer};
+
+/// Struct name under which serde_json's `arbitrary_precision` feature
+/// serializes a `Number`, as its decimal text in a single field.
+pub(crate) const JSON_NUMBER_TOKEN: &str = "$serde_json::private::Number";
 
 pub struct StructSerializer<'a> {
+    /// Whether this is a serde_json `Number`, written as a scalar.
+    json_number: bool,
+    /// The field count the header was written for.
+    len: u32,
     entries: u32,
-    struct_serializer: Serializer,
-    parent_encoder: &'a mut Serializer,
+    /// Where the header is in the buffer, to rewrite it if the field count
+    /// turns out different from the one announced.
+    header: Range<usize>,
+    /// Precomputed field names, if the struct was registered.
+    fields: Option<FieldCursor>,
+    /// Encoded fields, kept aside until `end` when fields must be sorted.
+    sorted_fields: Vec<(&'static str, Vec<u8>)>,
+    serializer: &'a mut Serializer,
 }
 
 impl<'a> StructSerializer<'a> {
-    pub fn new(serializer: &'a mut Serializer) -> Self {
-        Self {
+    /// Writes the header for the `len` fields announced by `serialize_struct`
+    /// right away, so that the fields are written in place after it.
+    pub fn new(
+        serializer: &'a mut Serializer,
+        name: &'static str,
+        len: usize,
+    ) -> Result<Self, EncodeError> {
+        let json_number = name == JSON_NUMBER_TOKEN;
+        let len = u32::try_from(len).unwrap_or(u32::MAX);
+        let start = serializer.buffer.get_ref().len();
+        if !json_number {
+            let as_array = serializer.config.struct_as_array;
+            write_header(serializer, as_array, len)?;
+        }
+        Ok(Self {
+            json_number,
+            len,
             entries: 0,
-            struct_serializer: Serializer::default(),
-            parent_encoder: serializer,
+            header: start..serializer.buffer.get_ref().len(),
+            fields: serializer.fields.get(name).copied().map(FieldCursor::new),
+            sorted_fields: vec![],
+            serializer,
+        })
+    }
+
+    fn write_key(&mut self, key: &'static str) -> Result<(), EncodeError> {
+        if self.serializer.config.struct_as_array {
+            return Ok(());
+        }
+        if let Some(encoded) =
+            self.fields.as_mut().and_then(|fields| fields.encoded(key))
+        {
+            return Ok(self.serializer.write_all(encoded)?);
+        }
+
+        let serializer = &mut *self.serializer;
+        if let Some(encoded) = serializer.key_cache.get(key) {
+            return Ok(serializer.buffer.write_all(encoded)?);
+        }
+        let start = serializer.buffer.get_ref().len();
+        key.serialize(&mut *serializer)?;
+        let encoded = &serializer.buffer.get_ref()[start..];
+        serializer.key_cache.insert(key, encoded);
+        Ok(())
+    }
+}
+
+/// Writes the field of a serde_json `Number`, its decimal text, as an
+/// integer or float when that keeps every digit, and as the text itself (the
+/// BigInt/BigNumber encoding) otherwise.
+pub(super) fn write_json_number<T>(
+    serializer: &mut Serializer,
+    value: &T,
+) -> Result<(), EncodeError>
+where
+    T: ?Sized + Serialize,
+{
+    let number = match serde_json::to_value(value) {
+        Ok(serde_json::Value::String(number)) => number,
+        _ => return Err(ser::Error::custom("invalid serde_json number")),
+    };
+    if let Ok(v) = number.parse::<i64>() {
+        return serializer.write_int(v);
+    }
+    if let Ok(v) = number.parse::<u64>() {
+        return serializer.write_uint(v);
+    }
+    if let (Ok(v), Ok(exact)) =
+        (number.parse::<f64>(), BigDecimal::from_str(&number))
+    {
+        let is_integer = !number.contains(['.', 'e', 'E']);
+        if !is_integer
+            && v.is_finite()
+            && BigDecimal::from_str(&v.to_string()).ok() == Some(exact)
+        {
+            return serializer.serialize_f64(v);
         }
     }
+    serializer.serialize_str(&number)
+}
+
+fn write_header<W: Write>(
+    writer: &mut W,
+    as_array: bool,
+    len: u32,
+) -> Result<(), EncodeError> {
+    if as_array {
+        ArraySerializer::write_array_length(writer, &len)
+    } else {
+        MapSerializer::write_map_length(writer, &len)
+    }
 }
 
 impl ser::SerializeStruct for StructSerializer<'_> {
     type Ok = ();
-    type Error = Error;
+    type Error = EncodeError;
 
-    fn serialize_field<T: ?Sized>(
+    fn serialize_field<T>(
         &mut self,
         key: &'static str,
         value: &T,
-    ) -> std::result::Result<(), Self::Error>
+    ) -> core::result::Result<(), Self::Error>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
-        key.serialize(&mut self.struct_serializer)?;
-        value.serialize(&mut self.struct_serializer)?;
+        if self.json_number {
+            return write_json_number(self.serializer, value);
+        }
+        let config = &self.serializer.config;
+        if config.sort_struct_fields && !config.struct_as_array {
+            let mut field_serializer = self.serializer.child();
+            value.serialize(&mut field_serializer)?;
+            self.serializer.reclaim(&mut field_serializer);
+            self.sorted_fields
+                .push((key, field_serializer.into_inner()));
+        } else {
+            self.write_key(key)?;
+            value.serialize(&mut *self.serializer)?;
+        }
         self.entries += 1;
 
         Ok(())
     }
 
-    fn end(self) -> std::result::Result<Self::Ok, Self::Error> {
-        MapSerializer::write_map_length(self.parent_encoder, &self.entries)?;
-        self.parent_encoder
-            .write_all(&self.struct_serializer.get_buffer())?;
+    fn end(mut self) -> core::result::Result<Self::Ok, Self::Error> {
+        if self.json_number {
+            return Ok(());
+        }
+        let mut sorted_fields = core::mem::take(&mut self.sorted_fields);
+        sorted_fields.sort_by_key(|(key, _)| *key);
+        for (key, value) in sorted_fields {
+            self.write_key(key)?;
+            self.serializer.write_all(&value)?;
+        }
+
+        // Only hand-written impls (or skipped fields) get the count wrong
+        if self.entries != self.len {
+            let mut header = Vec::with_capacity(5);
+            let as_array = self.serializer.config.struct_as_array;
+            write_header(&mut header, as_array, self.entries)?;
+            self.serializer.replace(self.header.clone(), &header);
+        }
         Ok(())
     }
 }
+
+impl ser::SerializeStructVariant for StructSerializer<'_> {
+    type Ok = ();
+    type Error = EncodeError;
+
+    fn serialize_field<T>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> core::result::Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        ser::SerializeStruct::serialize_field(self, key, value)
+    }
+
+    fn end(self) -> core::result::Result<Self::Ok, Self::Error> {
+        ser::SerializeStruct::end(self)
+    }
+}
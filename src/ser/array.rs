@@ -1,32 +1,84 @@
-use std::io::Write;
+use alloc::vec::Vec;
+use core::ops::Range;
 
-use byteorder::{BigEndian, WriteBytesExt};
-use serde::{ser, Serialize};
+use byteorder::BigEndian;
+use serde::{ser, Serialize, Serializer as _};
 
 use crate::{
-    error::{Error, Result},
-    format::Format, Serializer,
+    error::{EncodeError, EncodeResult},
+    format::Format,
+    io::{Write, WriteBytesExt},
+    Serializer,
 };
 
+use super::{byte_probe::ByteProbe, write_bin_len};
+
 pub struct ArraySerializer<'a> {
+    /// The length the header was written for.
+    len: u32,
     array_len: u32,
-    array_serializer: Serializer,
-    parent_encoder: &'a mut Serializer,
+    /// Where the header is in the buffer, to rewrite it if the length
+    /// turns out different.
+    header: Range<usize>,
+    /// Whether every element so far was a `u8`, written as a raw byte to be
+    /// turned into a bin in `end`.
+    raw_bytes: bool,
+    serializer: &'a mut Serializer,
 }
 
 impl<'a> ArraySerializer<'a> {
-    pub fn new(serializer: &'a mut Serializer) -> Self {
-        Self {
+    /// Writes the header upfront so that elements are serialized in place.
+    /// An unknown `len` is written as `0` and patched in `end`, moving the
+    /// elements once.
+    pub fn new(
+        serializer: &'a mut Serializer,
+        len: Option<usize>,
+    ) -> EncodeResult<Self> {
+        let len = len.map_or(0, |len| u32::try_from(len).unwrap_or(u32::MAX));
+        let start = serializer.buffer.get_ref().len();
+        ArraySerializer::write_array_length(serializer, &len)?;
+        // Every element takes at least a byte
+        serializer.buffer.get_mut().reserve(len as usize);
+        Ok(Self {
+            len,
             array_len: 0,
-            array_serializer: Serializer::default(),
-            parent_encoder: serializer,
+            header: start..serializer.buffer.get_ref().len(),
+            raw_bytes: false,
+            serializer,
+        })
+    }
+
+    /// Like [`Self::new`], writing the elements as a bin if they all turn out
+    /// to be bytes and `byte_seqs_as_bin` is enabled.
+    pub fn new_seq(
+        serializer: &'a mut Serializer,
+        len: Option<usize>,
+    ) -> EncodeResult<Self> {
+        let raw_bytes = serializer.config.byte_seqs_as_bin;
+        let mut array = ArraySerializer::new(serializer, len)?;
+        array.raw_bytes = raw_bytes;
+        Ok(array)
+    }
+
+    /// Rewrites the raw bytes written so far as integers, once an element
+    /// that isn't a byte shows up.
+    fn encode_raw_bytes(&mut self) -> EncodeResult<()> {
+        self.raw_bytes = false;
+        let elements = self.header.end..self.serializer.buffer.get_ref().len();
+        let raw = self.serializer.buffer.get_ref()[elements.clone()].to_vec();
+        let mut encoded = self.serializer.child();
+        for byte in raw {
+            (&mut encoded).serialize_u8(byte)?;
         }
+        self.serializer.reclaim(&mut encoded);
+        self.serializer.replace(elements, &encoded.into_inner());
+        Ok(())
     }
 
     pub fn write_array_length<W: Write>(
         writer: &mut W,
         length: &u32,
-    ) -> std::result::Result<(), Error> {
+    ) -> core::result::Result<(), EncodeError> {
         let length = *length;
         if length < 16 {
             Format::set_format(writer, Format::FixArray(length as u8))?;
@@ -43,51 +95,93 @@ impl<'a> ArraySerializer<'a> {
 
 impl ser::SerializeSeq for ArraySerializer<'_> {
     type Ok = ();
-    type Error = Error;
+    type Error = EncodeError;
 
-    fn serialize_element<T>(&mut self, value: &T) -> Result<Self::Ok>
+    fn serialize_element<T>(&mut self, value: &T) -> EncodeResult<Self::Ok>
     where
         T: ?Sized + Serialize,
     {
-        value.serialize(&mut self.array_serializer)?;
+        if self.raw_bytes {
+            match value.serialize(ByteProbe) {
+                Ok(byte) => {
+                    self.serializer.write_all(&[byte])?;
+                    self.array_len += 1;
+                    return Ok(());
+                }
+                Err(_) => self.encode_raw_bytes()?,
+            }
+        }
+        value.serialize(&mut *self.serializer)?;
         self.array_len += 1;
         Ok(())
     }
 
-    fn end(self) -> Result<Self::Ok> {
-        ArraySerializer::write_array_length(
-            self.parent_encoder,
-            &self.array_len,
-        )?;
-        self.parent_encoder
-            .write_all(&self.array_serializer.get_buffer())?;
-        Ok(())
+    fn end(self) -> EncodeResult<Self::Ok> {
+        // Empty sequences stay arrays, as nothing says they hold bytes
+        if self.raw_bytes && self.array_len > 0 {
+            let mut header = Vec::with_capacity(5);
+            write_bin_len(&mut header, self.array_len)?;
+            self.serializer.replace(self.header, &header);
+            return Ok(());
+        }
+        ser::SerializeTuple::end(self)
     }
 }
 
 impl ser::SerializeTuple for ArraySerializer<'_> {
     type Ok = ();
-    type Error = Error;
+    type Error = EncodeError;
 
-    fn serialize_element<T: ?Sized>(
+    fn serialize_element<T>(
         &mut self,
         value: &T,
-    ) -> std::result::Result<(), Self::Error>
+    ) -> core::result::Result<(), Self::Error>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
-        value.serialize(&mut self.array_serializer)?;
+        value.serialize(&mut *self.serializer)?;
         self.array_len += 1;
         Ok(())
     }
 
-    fn end(self) -> std::result::Result<Self::Ok, Self::Error> {
-        ArraySerializer::write_array_length(
-            self.parent_encoder,
-            &self.array_len,
-        )?;
-        self.parent_encoder
-            .write_all(&self.array_serializer.get_buffer())?;
+    fn end(self) -> core::result::Result<Self::Ok, Self::Error> {
+        if self.array_len != self.len {
+            let mut header = Vec::with_capacity(5);
+            ArraySerializer::write_array_length(&mut header, &self.array_len)?;
+            self.serializer.replace(self.header, &header);
+        }
         Ok(())
     }
 }
+
+impl ser::SerializeTupleStruct for ArraySerializer<'_> {
+    type Ok = ();
+    type Error = EncodeError;
+
+    fn serialize_field<T>(&mut self, value: &T) -> EncodeResult<Self::Ok>
+    where
+        T: ?Sized + Serialize,
+    {
+        ser::SerializeTuple::serialize_element(self, value)
+    }
+
+    fn end(self) -> EncodeResult<Self::Ok> {
+        ser::SerializeTuple::end(self)
+    }
+}
+
+impl ser::SerializeTupleVariant for ArraySerializer<'_> {
+    type Ok = ();
+    type Error = EncodeError;
+
+    fn serialize_field<T>(&mut self, value: &T) -> EncodeResult<Self::Ok>
+    where
+        T: ?Sized + Serialize,
+    {
+        ser::SerializeTuple::serialize_element(self, value)
+    }
+
+    fn end(self) -> EncodeResult<Self::Ok> {
+        ser::SerializeTuple::end(self)
+    }
+}
@@ -0,0 +1,103 @@
+use alloc::string::String;
+
+use serde::{
+    ser::{self, Impossible},
+    Serialize,
+};
+
+use crate::error::{EncodeError, EncodeResult};
+
+/// Serializes a value only if it's a `u8`, handing back the byte, so that
+/// sequences of bytes can be told apart from other sequences.
+pub(crate) struct ByteProbe;
+
+fn not_a_byte() -> EncodeError {
+    EncodeError::Message(String::new())
+}
+
+macro_rules! reject {
+    ($($method:ident($($arg:ty),*) -> $ok:ty;)*) => {
+        $(
+            fn $method(self, $(_: $arg),*) -> EncodeResult<$ok> {
+                Err(not_a_byte())
+            }
+        )*
+    };
+}
+
+impl ser::Serializer for ByteProbe {
+    type Ok = u8;
+    type Error = EncodeError;
+    type SerializeSeq = Impossible<u8, EncodeError>;
+    type SerializeTuple = Impossible<u8, EncodeError>;
+    type SerializeTupleStruct = Impossible<u8, EncodeError>;
+    type SerializeTupleVariant = Impossible<u8, EncodeError>;
+    type SerializeMap = Impossible<u8, EncodeError>;
+    type SerializeStruct = Impossible<u8, EncodeError>;
+    type SerializeStructVariant = Impossible<u8, EncodeError>;
+
+    fn serialize_u8(self, v: u8) -> EncodeResult<u8> {
+        Ok(v)
+    }
+
+    fn serialize_newtype_struct<T>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> EncodeResult<u8>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(self)
+    }
+
+    reject! {
+        serialize_bool(bool) -> u8;
+        serialize_i8(i8) -> u8;
+        serialize_i16(i16) -> u8;
+        serialize_i32(i32) -> u8;
+        serialize_i64(i64) -> u8;
+        serialize_u16(u16) -> u8;
+        serialize_u32(u32) -> u8;
+        serialize_u64(u64) -> u8;
+        serialize_f32(f32) -> u8;
+        serialize_f64(f64) -> u8;
+        serialize_char(char) -> u8;
+        serialize_str(&str) -> u8;
+        serialize_bytes(&[u8]) -> u8;
+        serialize_none() -> u8;
+        serialize_unit() -> u8;
+        serialize_unit_struct(&'static str) -> u8;
+        serialize_unit_variant(&'static str, u32, &'static str) -> u8;
+        serialize_seq(Option<usize>) -> Self::SerializeSeq;
+        serialize_tuple(usize) -> Self::SerializeTuple;
+        serialize_tuple_struct(&'static str, usize)
+            -> Self::SerializeTupleStruct;
+        serialize_tuple_variant(&'static str, u32, &'static str, usize)
+            -> Self::SerializeTupleVariant;
+        serialize_map(Option<usize>) -> Self::SerializeMap;
+        serialize_struct(&'static str, usize) -> Self::SerializeStruct;
+        serialize_struct_variant(&'static str, u32, &'static str, usize)
+            -> Self::SerializeStructVariant;
+    }
+
+    fn serialize_some<T>(self, _value: &T) -> EncodeResult<u8>
+    where
+        T: ?Sized + Serialize,
+    {
+        Err(not_a_byte())
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> EncodeResult<u8>
+    where
+        T: ?Sized + Serialize,
+    {
+        Err(not_a_byte())
+    }
+}
@@ -0,0 +1,173 @@
+/// How the [`Serializer`] treats floats with more than one encoding of the
+/// same value: `-0.0`, and NaNs with payload bits or the signaling bit set.
+///
+/// [`Serializer`]: crate::Serializer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FloatCanonicalization {
+    /// Write the bits as given.
+    #[default]
+    Preserve,
+    /// Write `-0.0` as `0.0` and every NaN as the canonical quiet NaN.
+    Normalize,
+    /// Fail on `-0.0` and on any NaN other than the canonical quiet NaN.
+    Reject,
+}
+
+/// How the [`Serializer`] encodes maps. Structs are always written as plain
+/// maps, as are those with flattened fields when wrapped in [`Flattened`],
+/// and `GenericMap`s always use the ext.
+///
+/// [`Serializer`]: crate::Serializer
+/// [`Flattened`]: crate::Flattened
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MapEncoding {
+    /// Wrap every map in the GenericMap ext, as Polywrap expects.
+    #[default]
+    Ext,
+    /// Write every map as a plain msgpack map.
+    Plain,
+    /// Write maps whose keys are all strings as plain maps, which any
+    /// msgpack library can read, and only wrap the others in the ext.
+    ExtForNonStringKeys,
+}
+
+/// Options controlling the wire format produced by the [`Serializer`].
+///
+/// The default configuration produces the Polywrap encoding.
+///
+/// [`Serializer`]: crate::Serializer
+#[derive(Debug, Clone)]
+pub struct SerializerConfig {
+    pub(crate) map_encoding: MapEncoding,
+    /// Write `f64`s that are exactly representable as `f32` as Float32.
+    pub(crate) shrink_floats: bool,
+    /// Write integers in the format of their Rust type rather than the
+    /// smallest one that holds the value.
+    pub(crate) declared_int_width: bool,
+    /// Write empty byte slices as nil rather than an empty bin.
+    pub(crate) empty_bytes_as_nil: bool,
+    /// Write unit variants as their name rather than their index.
+    pub(crate) variant_names: bool,
+    /// Write unit structs as an empty array rather than nil.
+    pub(crate) unit_struct_as_array: bool,
+    /// Write structs as an array of their field values rather than a map.
+    pub(crate) struct_as_array: bool,
+    /// Write struct fields sorted by name rather than in declaration order.
+    pub(crate) sort_struct_fields: bool,
+    /// Write sequences of `u8` as bin rather than an array of integers.
+    pub(crate) byte_seqs_as_bin: bool,
+    pub(crate) float_canonicalization: FloatCanonicalization,
+}
+
+impl Default for SerializerConfig {
+    fn default() -> Self {
+        Self {
+            map_encoding: MapEncoding::Ext,
+            shrink_floats: false,
+            declared_int_width: false,
+            empty_bytes_as_nil: false,
+            variant_names: false,
+            unit_struct_as_array: false,
+            struct_as_array: false,
+            sort_struct_fields: false,
+            byte_seqs_as_bin: false,
+            float_canonicalization: FloatCanonicalization::Preserve,
+        }
+    }
+}
+
+impl SerializerConfig {
+    /// Produces the same bytes as `rmp_serde::to_vec_named`: plain maps,
+    /// floats at their declared width, empty bins, unit variants by name and
+    /// unit structs as empty arrays.
+    pub fn rmp_compat() -> Self {
+        Self {
+            map_encoding: MapEncoding::Plain,
+            shrink_floats: false,
+            declared_int_width: false,
+            empty_bytes_as_nil: false,
+            variant_names: true,
+            unit_struct_as_array: true,
+            struct_as_array: false,
+            sort_struct_fields: false,
+            byte_seqs_as_bin: false,
+            float_canonicalization: FloatCanonicalization::Preserve,
+        }
+    }
+
+    /// Write structs as arrays of their field values in declaration order,
+    /// a more compact encoding that doesn't survive fields being reordered.
+    /// Fields skipped during serialization shift the positions of the ones
+    /// after them.
+    pub fn struct_as_array(mut self, enabled: bool) -> Self {
+        self.struct_as_array = enabled;
+        self
+    }
+
+    /// Write enum variants as their name rather than their index.
+    pub fn variant_names(mut self, enabled: bool) -> Self {
+        self.variant_names = enabled;
+        self
+    }
+
+    /// Write `f64`s that are exactly representable as `f32` as Float32.
+    /// Off by default, so floats keep their declared width and other
+    /// Polywrap encoders produce the same bytes.
+    pub fn shrink_floats(mut self, enabled: bool) -> Self {
+        self.shrink_floats = enabled;
+        self
+    }
+
+    /// Write integers in the format matching their Rust type, e.g. every
+    /// `u64` as Uint64 and every `i8` as Int8, for consumers that expect a
+    /// field to always have the same format. By default integers use the
+    /// smallest format that holds their value.
+    pub fn declared_int_width(mut self, enabled: bool) -> Self {
+        self.declared_int_width = enabled;
+        self
+    }
+
+    /// Write empty byte slices as nil, as older versions of this crate did,
+    /// rather than an empty Bin8.
+    pub fn empty_bytes_as_nil(mut self, enabled: bool) -> Self {
+        self.empty_bytes_as_nil = enabled;
+        self
+    }
+
+    /// Sets how maps are encoded. Decoding accepts every encoding either
+    /// way.
+    pub fn map_encoding(mut self, encoding: MapEncoding) -> Self {
+        self.map_encoding = encoding;
+        self
+    }
+
+    /// Emit struct fields sorted by name, so the output doesn't change when
+    /// fields are reordered in the Rust definition. Ignored for structs
+    /// written as arrays by [`struct_as_array`], whose positions follow
+    /// declaration order.
+    ///
+    /// [`struct_as_array`]: SerializerConfig::struct_as_array
+    pub fn sort_struct_fields(mut self, enabled: bool) -> Self {
+        self.sort_struct_fields = enabled;
+        self
+    }
+
+    /// Write sequences of `u8`, such as `Vec<u8>` fields not annotated with
+    /// `serde_bytes`, as a bin rather than an array of integers: one byte
+    /// per element instead of up to two. The deserializer reads a bin into
+    /// any sequence of integers, but other decoders may expect the array.
+    pub fn byte_seqs_as_bin(mut self, enabled: bool) -> Self {
+        self.byte_seqs_as_bin = enabled;
+        self
+    }
+
+    /// Sets how `-0.0` and non-canonical NaNs are written, e.g. to keep
+    /// encodings that get hashed independent of the producer's FPU.
+    pub fn float_canonicalization(
+        mut self,
+        policy: FloatCanonicalization,
+    ) -> Self {
+        self.float_canonicalization = policy;
+        self
+    }
+}
@@ -0,0 +1,88 @@
+use alloc::{boxed::Box, sync::Arc};
+
+#[cfg(not(feature = "std"))]
+use alloc::collections::BTreeMap as KeyMap;
+#[cfg(feature = "std")]
+use std::collections::HashMap as KeyMap;
+
+/// Precomputed msgpack encodings of a struct's field names.
+///
+/// Derive it with `#[derive(EncodedFields)]` (behind the `derive` feature)
+/// and register the struct on a [`Serializer`] with
+/// [`Serializer::register_fields`]; its field names are then copied into the
+/// output as-is instead of being encoded on every write.
+///
+/// [`Serializer`]: crate::Serializer
+/// [`Serializer::register_fields`]: crate::Serializer::register_fields
+pub trait EncodedFields {
+    /// The name serde passes to `serialize_struct`.
+    const NAME: &'static str;
+    /// Each serialized field name alongside its encoded str bytes, in
+    /// declaration order.
+    const ENCODED_FIELDS: &'static [(&'static str, &'static [u8])];
+}
+
+pub(crate) type FieldTable = &'static [(&'static str, &'static [u8])];
+
+/// Registered field tables by struct name, shared with child serializers.
+pub(crate) type FieldTables = Arc<KeyMap<&'static str, FieldTable>>;
+
+/// Upper bound on cached field names, so structs with generated or
+/// unbounded key sets can't grow the cache indefinitely.
+const KEY_CACHE_CAPACITY: usize = 256;
+
+/// Encoded field names seen so far by a serializer that has no precomputed
+/// table for the struct.
+///
+/// Entries are keyed by the address and length of the `&'static str` serde
+/// passes in, so a lookup never hashes the name itself.
+#[derive(Default)]
+pub(crate) struct KeyCache {
+    entries: KeyMap<(usize, usize), Box<[u8]>>,
+}
+
+impl KeyCache {
+    pub(crate) fn get(&self, key: &'static str) -> Option<&[u8]> {
+        self.entries
+            .get(&(key.as_ptr() as usize, key.len()))
+            .map(|encoded| &encoded[..])
+    }
+
+    #[cfg(test)]
+    pub(crate) fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub(crate) fn insert(&mut self, key: &'static str, encoded: &[u8]) {
+        if self.entries.len() < KEY_CACHE_CAPACITY {
+            self.entries
+                .insert((key.as_ptr() as usize, key.len()), encoded.into());
+        }
+    }
+}
+
+/// Walks a struct's field table alongside the fields serde hands over.
+pub(crate) struct FieldCursor {
+    table: FieldTable,
+    next: usize,
+}
+
+impl FieldCursor {
+    pub(crate) fn new(table: FieldTable) -> Self {
+        Self { table, next: 0 }
+    }
+
+    /// Returns the encoded bytes of `key`. Fields normally arrive in table
+    /// order, so the search starts after the last match and only wraps
+    /// around for skipped or reordered fields.
+    pub(crate) fn encoded(&mut self, key: &str) -> Option<&'static [u8]> {
+        let len = self.table.len();
+        (0..len)
+            .map(|offset| (self.next + offset) % len)
+            .find(|&index| self.table[index].0 == key)
+            .map(|index| {
+                self.next = index + 1;
+                self.table[index].1
+            })
+    }
+}
@@ -1,32 +1,80 @@
-use std::io::Write;
+use alloc::vec::Vec;
+use core::ops::Range;
 
-use byteorder::{BigEndian, WriteBytesExt};
+use byteorder::BigEndian;
 use serde::{ser, Serialize};
 
 use crate::{
-    error::{Error},
-    format::{ExtensionType, Format}, Serializer,
+    error::{EncodeError},
+    format::{ExtensionType, Format},
+    io::{Write, WriteBytesExt},
+    MapEncoding, Serializer,
 };
 
+use super::_struct::{write_json_number, JSON_NUMBER_TOKEN};
+
+/// An Ext32 header with its type byte, the largest an ext header gets.
+const MAX_EXT_HEADER_LEN: usize = 6;
+
 pub struct MapSerializer<'a> {
-    map_serializer: Serializer,
+    /// Whether to wrap the map in the GenericMap ext.
+    ext: bool,
+    /// Whether to decide on the ext once the keys are known: only maps with
+    /// a non-string key are wrapped.
+    ext_for_non_string_keys: bool,
+    /// Where the ext header goes: reserved at its largest size when the ext
+    /// is known upfront, empty otherwise.
+    ext_header: Range<usize>,
+    /// Where the map header is, written for `len` entries.
+    header: Range<usize>,
+    len: u32,
     map_entries: u32,
-    parent_encoder: &'a mut Serializer,
+    /// Whether this is a serde_json `Number` handed over as a map, written
+    /// as a scalar.
+    json_number: bool,
+    serializer: &'a mut Serializer,
 }
 
 impl<'a> MapSerializer<'a> {
-    pub fn new(serializer: &'a mut Serializer) -> Self {
-        Self {
-            parent_encoder: serializer,
-            map_serializer: Serializer::default(),
-            map_entries: 0,
+    /// Writes the headers upfront so that entries are serialized in place,
+    /// and patches them in `end` once the lengths are known.
+    ///
+    /// Maps of unknown length get the ext like any other, unless they're
+    /// structs with flattened fields wrapped in `Flattened`, which are
+    /// written as plain maps like any other struct.
+    pub fn new(
+        serializer: &'a mut Serializer,
+        len: Option<usize>,
+    ) -> Result<Self, EncodeError> {
+        let encoding = serializer.config.map_encoding;
+        let flattened =
+            core::mem::take(&mut serializer.flattened) && len.is_none();
+        let ext = (encoding == MapEncoding::Ext && !flattened)
+            || core::mem::take(&mut serializer.force_ext_map);
+        let start = serializer.buffer.get_ref().len();
+        if ext {
+            serializer.write_all(&[0; MAX_EXT_HEADER_LEN])?;
         }
+        let len = len.map_or(0, |len| u32::try_from(len).unwrap_or(u32::MAX));
+        let header_start = serializer.buffer.get_ref().len();
+        MapSerializer::write_map_length(serializer, &len)?;
+        Ok(Self {
+            ext,
+            ext_for_non_string_keys: encoding
+                == MapEncoding::ExtForNonStringKeys,
+            ext_header: start..header_start,
+            header: header_start..serializer.buffer.get_ref().len(),
+            len,
+            map_entries: 0,
+            json_number: false,
+            serializer,
+        })
     }
 
     pub fn write_map_length<W: Write>(
         writer: &mut W,
         length: &u32,
-    ) -> std::result::Result<(), Error> {
+    ) -> core::result::Result<(), EncodeError> {
         let length = *length;
         if length < 16 {
             Format::set_format(writer, Format::FixMap(length as u8))?;
@@ -42,18 +90,30 @@ impl<'a> MapSerializer<'a> {
 
     pub fn write_ext_map_type<W: Write>(
         writer: &mut W,
-    ) -> Result<(), Error> {
+    ) -> Result<(), EncodeError> {
         Ok(WriteBytesExt::write_u8(
             writer,
             ExtensionType::GenericMap.into(),
         )?)
     }
 
-    pub fn write_ext_map_len<W: Write>(
+    /// Writes the header of an ext holding `length` bytes, using a FixExt
+    /// format for the lengths that have one.
+    pub fn write_ext_len<W: Write>(
         writer: &mut W,
         length: usize,
-    ) -> std::result::Result<(), Error> {
-        if length <= u8::MAX as usize {
+    ) -> core::result::Result<(), EncodeError> {
+        let fix_ext = match length {
+            1 => Some(Format::FixExt1),
+            2 => Some(Format::FixExt2),
+            4 => Some(Format::FixExt4),
+            8 => Some(Format::FixExt8),
+            16 => Some(Format::FixExt16),
+            _ => None,
+        };
+        if let Some(format) = fix_ext {
+            Format::set_format(writer, format)?;
+        } else if length <= u8::MAX as usize {
             Format::set_format(writer, Format::Ext8)?;
             WriteBytesExt::write_u8(writer, length.try_into().unwrap())?;
         } else if length <= u16::MAX as usize {
@@ -76,48 +136,80 @@ impl<'a> MapSerializer<'a> {
 
 impl ser::SerializeMap for MapSerializer<'_> {
     type Ok = ();
-    type Error = Error;
+    type Error = EncodeError;
 
-    fn serialize_key<T: ?Sized>(
+    fn serialize_key<T>(
         &mut self,
         key: &T,
-    ) -> std::result::Result<(), Self::Error>
+    ) -> core::result::Result<(), Self::Error>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
-        key.serialize(&mut self.map_serializer)?;
+        let start = self.serializer.buffer.get_ref().len();
+        key.serialize(&mut *self.serializer)?;
+        if self.map_entries == 0
+            && is_json_number_key(&self.serializer.buffer.get_ref()[start..])
+        {
+            let end = self.serializer.buffer.get_ref().len();
+            self.serializer.replace(self.ext_header.start..end, &[]);
+            self.json_number = true;
+            return Ok(());
+        }
         self.map_entries += 1;
 
+        if self.ext_for_non_string_keys && !self.ext {
+            let marker = self.serializer.buffer.get_ref()[start];
+            self.ext = !matches!(
+                Format::from_u8(marker),
+                Format::FixStr(_) | Format::Str8 | Format::Str16 | Format::Str32
+            );
+        }
+
         Ok(())
     }
 
-    fn serialize_value<T: ?Sized>(
+    fn serialize_value<T>(
         &mut self,
         value: &T,
-    ) -> std::result::Result<(), Self::Error>
+    ) -> core::result::Result<(), Self::Error>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
-        value.serialize(&mut self.map_serializer)
+        if self.json_number {
+            return write_json_number(self.serializer, value);
+        }
+        value.serialize(&mut *self.serializer)
     }
 
-    fn end(self) -> std::result::Result<Self::Ok, Self::Error> {
-        let mut aux_map_encoder = Serializer::default();
-        MapSerializer::write_map_length(
-            &mut aux_map_encoder,
-            &self.map_entries,
-        )?;
-
-        aux_map_encoder.write_all(&self.map_serializer.get_buffer())?;
-
-        let map_buffer = aux_map_encoder.get_buffer();
+    fn end(self) -> core::result::Result<Self::Ok, Self::Error> {
+        if self.json_number {
+            return Ok(());
+        }
+        if self.map_entries != self.len {
+            let mut header = Vec::with_capacity(5);
+            MapSerializer::write_map_length(&mut header, &self.map_entries)?;
+            self.serializer.replace(self.header.clone(), &header);
+        }
+        if !self.ext {
+            return Ok(());
+        }
 
-        MapSerializer::write_ext_map_len(
-            self.parent_encoder,
-            map_buffer.len(),
-        )?;
-        MapSerializer::write_ext_map_type(self.parent_encoder)?;
-        self.parent_encoder.write_all(&map_buffer)?;
+        // Fit the ext header to the map's length
+        let map_start = self.ext_header.end;
+        let map_len = self.serializer.buffer.get_ref().len() - map_start;
+        let mut header = Vec::with_capacity(MAX_EXT_HEADER_LEN);
+        MapSerializer::write_ext_len(&mut header, map_len)?;
+        MapSerializer::write_ext_map_type(&mut header)?;
+        self.serializer.replace(self.ext_header, &header);
         Ok(())
     }
 }
+
+/// Whether `key` is the name serde_json's `arbitrary_precision` feature gives
+/// a `Number`'s only entry when it's deserialized, e.g. while transcoding,
+/// rather than serialized as a struct.
+fn is_json_number_key(key: &[u8]) -> bool {
+    let marker = Format::FixStr(JSON_NUMBER_TOKEN.len() as u8).to_u8();
+    cfg!(feature = "arbitrary_precision")
+        && key.split_first() == Some((&marker, JSON_NUMBER_TOKEN.as_bytes()))
+}
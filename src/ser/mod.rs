@@ -1,163 +1,489 @@
-mod array;
-mod map;
+pub(crate) mod array;
+mod byte_probe;
+mod config;
+mod fields;
+pub(crate) mod map;
 mod _struct;
 
-use std::io::{Cursor, Write};
+use alloc::{format, string::ToString, sync::Arc, vec, vec::Vec};
+use core::ops::Range;
 
 use crate::{
-    error::{Error, Result},
+    error::{EncodeError, EncodeResult},
     format::Format,
+    io::{self, Cursor, Write, WriteBytesExt},
+    value::EXT_TOKEN,
+    wrappers::{flattened::FLATTENED_TOKEN, generic_map::GENERIC_MAP_TOKEN},
 };
-use byteorder::{BigEndian, WriteBytesExt};
+use byteorder::BigEndian;
 use serde::ser::{self, Serialize};
 
 use _struct::StructSerializer;
 use array::ArraySerializer;
 use map::MapSerializer;
 
+pub use config::{FloatCanonicalization, MapEncoding, SerializerConfig};
+pub use fields::EncodedFields;
+
+use fields::{FieldTables, KeyCache};
+
 pub struct Serializer {
     buffer: Cursor<Vec<u8>>,
+    config: SerializerConfig,
+    fields: FieldTables,
+    key_cache: KeyCache,
+    /// Set while serializing a `GenericMap`, which always uses the ext.
+    force_ext_map: bool,
+    /// Set while serializing a `Flattened` struct, until its map starts.
+    flattened: bool,
 }
 
 impl Serializer {
+    pub fn with_config(config: SerializerConfig) -> Self {
+        Self {
+            buffer: Cursor::new(vec![]),
+            config,
+            fields: FieldTables::default(),
+            key_cache: KeyCache::default(),
+            force_ext_map: false,
+            flattened: false,
+        }
+    }
+
+    /// Writes the field names of `T` from its precomputed table rather than
+    /// encoding them on every struct.
+    pub fn register_fields<T: EncodedFields>(&mut self) {
+        Arc::make_mut(&mut self.fields).insert(T::NAME, T::ENCODED_FIELDS);
+    }
+
+    /// Returns a copy of the bytes written so far. Prefer [`Self::as_bytes`]
+    /// or [`Self::into_inner`], which don't copy.
     pub fn get_buffer(&self) -> Vec<u8> {
-        self.buffer.clone().into_inner()
+        self.buffer.get_ref().clone()
+    }
+
+    /// The bytes written so far.
+    pub fn as_bytes(&self) -> &[u8] {
+        self.buffer.get_ref()
+    }
+
+    /// Consumes the serializer, returning the bytes written.
+    pub fn into_inner(self) -> Vec<u8> {
+        self.buffer.into_inner()
+    }
+
+    /// Creates an empty serializer sharing this serializer's configuration,
+    /// used to encode nested values into their own buffer. The key cache is
+    /// lent to the child until it is handed back with [`Self::reclaim`].
+    fn child(&mut self) -> Serializer {
+        Serializer {
+            buffer: Cursor::new(vec![]),
+            config: self.config.clone(),
+            fields: self.fields.clone(),
+            key_cache: core::mem::take(&mut self.key_cache),
+            force_ext_map: false,
+            flattened: false,
+        }
+    }
+
+    /// Discards the bytes written so far, keeping the buffer's allocation
+    /// and the key cache for the next value.
+    pub fn reset(&mut self) {
+        self.buffer.get_mut().clear();
+        self.buffer.set_position(0);
+        self.force_ext_map = false;
+        self.flattened = false;
+    }
+
+    /// Replaces the buffer's contents with `value`, keeping its allocation.
+    fn encode_scratch<T>(&mut self, value: &T) -> EncodeResult<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.reset();
+        value.serialize(self)
+    }
+
+    /// Writes a `(type, data)` tuple as a raw ext.
+    fn write_ext<T>(&mut self, value: &T) -> EncodeResult<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        let mut tuple = self.child();
+        value.serialize(&mut tuple)?;
+        self.reclaim(&mut tuple);
+        let (ext_type, data): (i8, serde_bytes::ByteBuf) =
+            crate::from_slice(tuple.as_bytes())
+                .map_err(|e| EncodeError::Message(e.to_string()))?;
+
+        MapSerializer::write_ext_len(self, data.len())?;
+        self.write_i8(ext_type)?;
+        Ok(self.write_all(&data)?)
+    }
+
+    /// Replaces the bytes at `range`, e.g. a header written before the
+    /// final length was known, moving what follows if the size changes.
+    fn replace(&mut self, range: Range<usize>, bytes: &[u8]) {
+        let buffer = self.buffer.get_mut();
+        buffer.splice(range, bytes.iter().copied());
+        let end = buffer.len() as u64;
+        self.buffer.set_position(end);
+    }
+
+    /// Takes back the key cache lent to `child`.
+    fn reclaim(&mut self, child: &mut Serializer) {
+        self.key_cache = core::mem::take(&mut child.key_cache);
+    }
+
+    fn canonical_f32(&self, v: f32) -> EncodeResult<f32> {
+        const QUIET_NAN: u32 = 0x7fc0_0000;
+        let canonical = if v.is_nan() {
+            f32::from_bits(QUIET_NAN)
+        } else if v == 0.0 {
+            0.0
+        } else {
+            return Ok(v);
+        };
+        let is_canonical = v.to_bits() == canonical.to_bits();
+        self.apply_float_policy(is_canonical, v, canonical)
     }
 
-    fn write_positive_fixed_int(
-        &mut self,
-        value: u8,
-    ) -> std::result::Result<(), Error> {
-        assert!(value < 128);
-        Ok(Format::set_format(self, Format::PositiveFixInt(value))?)
+    fn canonical_f64(&self, v: f64) -> EncodeResult<f64> {
+        const QUIET_NAN: u64 = 0x7ff8_0000_0000_0000;
+        let canonical = if v.is_nan() {
+            f64::from_bits(QUIET_NAN)
+        } else if v == 0.0 {
+            0.0
+        } else {
+            return Ok(v);
+        };
+        let is_canonical = v.to_bits() == canonical.to_bits();
+        self.apply_float_policy(is_canonical, v, canonical)
+    }
+
+    fn apply_float_policy<F: core::fmt::Debug>(
+        &self,
+        is_canonical: bool,
+        v: F,
+        canonical: F,
+    ) -> EncodeResult<F> {
+        match self.config.float_canonicalization {
+            _ if is_canonical => Ok(v),
+            FloatCanonicalization::Preserve => Ok(v),
+            FloatCanonicalization::Normalize => Ok(canonical),
+            FloatCanonicalization::Reject => Err(ser::Error::custom(format!(
+                "non-canonical float {v:?}"
+            ))),
+        }
+    }
+
+    /// Writes an integer in the smallest format that holds it.
+    pub(crate) fn write_int(&mut self, v: i64) -> EncodeResult<()> {
+        write_int(self, v)
     }
 
-    fn write_negative_fixed_int(
-        &mut self,
-        value: i8,
-    ) -> std::result::Result<(), Error> {
-        assert!((-32..=0).contains(&value));
-        Ok(Format::set_format(self, Format::NegativeFixInt(value))?)
+    /// Writes an unsigned integer in the smallest format that holds it.
+    pub(crate) fn write_uint(&mut self, v: u64) -> EncodeResult<()> {
+        write_uint(self, v)
     }
 }
 
+/// Writes an integer in the smallest format that holds it.
+pub(crate) fn write_int<W: Write>(writer: &mut W, v: i64) -> EncodeResult<()> {
+    if v >= 0 {
+        write_uint(writer, v as u64)?;
+    } else if (-(1 << 5)..0).contains(&v) {
+        Format::set_format(writer, Format::NegativeFixInt(v as i8))?;
+    } else if v <= i8::MAX as i64 && v >= i8::MIN as i64 {
+        Format::set_format(writer, Format::Int8)?;
+        WriteBytesExt::write_i8(writer, v as i8)?;
+    } else if v <= i16::MAX as i64 && v >= i16::MIN as i64 {
+        Format::set_format(writer, Format::Int16)?;
+        WriteBytesExt::write_i16::<BigEndian>(writer, v as i16)?;
+    } else if v <= i32::MAX as i64 && v >= i32::MIN as i64 {
+        Format::set_format(writer, Format::Int32)?;
+        WriteBytesExt::write_i32::<BigEndian>(writer, v as i32)?;
+    } else {
+        Format::set_format(writer, Format::Int64)?;
+        WriteBytesExt::write_i64::<BigEndian>(writer, v)?;
+    }
+    Ok(())
+}
+
+/// Writes an unsigned integer in the smallest format that holds it.
+pub(crate) fn write_uint<W: Write>(writer: &mut W, v: u64) -> EncodeResult<()> {
+    if v < 1 << 7 {
+        Format::set_format(writer, Format::PositiveFixInt(v as u8))?
+    } else if v <= u8::MAX as u64 {
+        Format::set_format(writer, Format::Uint8)?;
+        WriteBytesExt::write_u8(writer, v as u8)?
+    } else if v <= u16::MAX as u64 {
+        Format::set_format(writer, Format::Uint16)?;
+        WriteBytesExt::write_u16::<BigEndian>(writer, v as u16)?
+    } else if v <= u32::MAX as u64 {
+        Format::set_format(writer, Format::Uint32)?;
+        WriteBytesExt::write_u32::<BigEndian>(writer, v as u32)?
+    } else {
+        Format::set_format(writer, Format::Uint64)?;
+        WriteBytesExt::write_u64::<BigEndian>(writer, v)?
+    }
+
+    Ok(())
+}
+
+/// Writes the header of a string of `length` bytes.
+pub(crate) fn write_str_len<W: Write>(
+    writer: &mut W,
+    length: u32,
+) -> EncodeResult<()> {
+    if length < 32 {
+        Format::set_format(writer, Format::FixStr(length as u8))?;
+    } else if length <= u8::MAX as u32 {
+        Format::set_format(writer, Format::Str8)?;
+        WriteBytesExt::write_u8(writer, length as u8)?;
+    } else if length <= u16::MAX as u32 {
+        Format::set_format(writer, Format::Str16)?;
+        WriteBytesExt::write_u16::<BigEndian>(writer, length as u16)?;
+    } else {
+        Format::set_format(writer, Format::Str32)?;
+        WriteBytesExt::write_u32::<BigEndian>(writer, length)?;
+    }
+    Ok(())
+}
+
+/// Writes the header of a bin of `length` bytes.
+pub(crate) fn write_bin_len<W: Write>(
+    writer: &mut W,
+    length: u32,
+) -> EncodeResult<()> {
+    if length <= u8::MAX as u32 {
+        Format::set_format(writer, Format::Bin8)?;
+        WriteBytesExt::write_u8(writer, length as u8)?;
+    } else if length <= u16::MAX as u32 {
+        Format::set_format(writer, Format::Bin16)?;
+        WriteBytesExt::write_u16::<BigEndian>(writer, length as u16)?;
+    } else {
+        Format::set_format(writer, Format::Bin32)?;
+        WriteBytesExt::write_u32::<BigEndian>(writer, length)?;
+    }
+    Ok(())
+}
+
 impl Default for Serializer {
     fn default() -> Self {
-        Self {
-            buffer: Cursor::new(vec![]),
-        }
+        Self::with_config(SerializerConfig::default())
     }
 }
 
-pub fn to_vec<T>(value: &T) -> Result<Vec<u8>>
+pub fn to_vec<T>(value: &T) -> EncodeResult<Vec<u8>>
+where
+    T: Serialize,
+{
+    to_vec_with_config(value, SerializerConfig::default())
+}
+
+pub fn to_vec_with_config<T>(
+    value: &T,
+    config: SerializerConfig,
+) -> EncodeResult<Vec<u8>>
 where
     T: Serialize,
 {
-    let mut serializer = Serializer::default();
+    let mut serializer = Serializer::with_config(config);
     value.serialize(&mut serializer)?;
-    Ok(serializer.get_buffer())
+    Ok(serializer.into_inner())
+}
+
+/// Serializes `value` with every map written as a plain msgpack map rather
+/// than the GenericMap ext, for consumers that aren't Polywrap decoders.
+/// `GenericMap`s still use the ext.
+pub fn to_vec_with_plain_maps<T>(value: &T) -> EncodeResult<Vec<u8>>
+where
+    T: Serialize,
+{
+    to_vec_with_config(
+        value,
+        SerializerConfig::default().map_encoding(MapEncoding::Plain),
+    )
+}
+
+/// Serializes `value` into `serializer` after [resetting](Serializer::reset)
+/// it, returning the bytes from its buffer. Keeping one serializer across
+/// calls, e.g. per invocation in a hot loop, reuses its allocation.
+pub fn to_vec_reuse<'a, T>(
+    serializer: &'a mut Serializer,
+    value: &T,
+) -> EncodeResult<&'a [u8]>
+where
+    T: ?Sized + Serialize,
+{
+    serializer.encode_scratch(value)?;
+    Ok(serializer.as_bytes())
+}
+
+/// Serializes each item into its own buffer, reusing one scratch serializer
+/// (and its buffer and key cache) across items.
+pub fn encode_batch<T>(items: &[T]) -> EncodeResult<Vec<Vec<u8>>>
+where
+    T: Serialize,
+{
+    let mut scratch = Serializer::default();
+    items
+        .iter()
+        .map(|item| {
+            scratch.encode_scratch(item)?;
+            Ok(scratch.as_bytes().to_vec())
+        })
+        .collect()
+}
+
+/// Like [`encode_batch`], writing each item to the writer at the same index.
+pub fn encode_batch_to<T, W>(items: &[T], writers: &mut [W]) -> EncodeResult<()>
+where
+    T: Serialize,
+    W: Write,
+{
+    if items.len() != writers.len() {
+        return Err(EncodeError::Message(format!(
+            "{} items but {} writers",
+            items.len(),
+            writers.len()
+        )));
+    }
+
+    let mut scratch = Serializer::default();
+    for (item, writer) in items.iter().zip(writers) {
+        scratch.encode_scratch(item)?;
+        writer.write_all(scratch.as_bytes())?;
+    }
+    Ok(())
 }
 
 impl<'a> ser::Serializer for &'a mut Serializer {
     type Ok = ();
-    type Error = Error;
+    type Error = EncodeError;
 
     type SerializeSeq = ArraySerializer<'a>;
     // TODO: should tuples be serialized as sequences?. Ex: (u8, bool) = [3, true]?
     type SerializeTuple = ArraySerializer<'a>;
-    // TODO: should tuples be serialized as sequences?. Ex: Color(u8, bool) = [3, true]?
-    type SerializeTupleStruct = Self;
-    // TODO: should tuples be serialized as sequences?. Ex: Color(u8, bool) = [3, true]?
-    type SerializeTupleVariant = Self;
+    type SerializeTupleStruct = ArraySerializer<'a>;
+    type SerializeTupleVariant = ArraySerializer<'a>;
     type SerializeMap = MapSerializer<'a>;
     type SerializeStruct = StructSerializer<'a>;
-    // TODO: how should we serialize struct variants?
-    type SerializeStructVariant = Self;
+    type SerializeStructVariant = StructSerializer<'a>;
 
-    fn serialize_bool(self, v: bool) -> Result<()> {
+    fn serialize_bool(self, v: bool) -> EncodeResult<()> {
         let format = if v { Format::True } else { Format::False };
         Format::set_format(self, format)?;
         Ok(())
     }
 
-    fn serialize_i8(self, v: i8) -> Result<()> {
-        self.serialize_i64(v as i64)
+    fn serialize_i8(self, v: i8) -> EncodeResult<()> {
+        if !self.config.declared_int_width {
+            return self.write_int(v as i64);
+        }
+        Format::set_format(self, Format::Int8)?;
+        Ok(WriteBytesExt::write_i8(self, v)?)
+    }
+
+    fn serialize_i16(self, v: i16) -> EncodeResult<()> {
+        if !self.config.declared_int_width {
+            return self.write_int(v as i64);
+        }
+        Format::set_format(self, Format::Int16)?;
+        Ok(WriteBytesExt::write_i16::<BigEndian>(self, v)?)
     }
 
-    fn serialize_i16(self, v: i16) -> Result<()> {
-        self.serialize_i64(v as i64)
+    fn serialize_i32(self, v: i32) -> EncodeResult<()> {
+        if !self.config.declared_int_width {
+            return self.write_int(v as i64);
+        }
+        Format::set_format(self, Format::Int32)?;
+        Ok(WriteBytesExt::write_i32::<BigEndian>(self, v)?)
     }
 
-    fn serialize_i32(self, v: i32) -> Result<()> {
-        self.serialize_i64(v as i64)
+    fn serialize_i64(self, v: i64) -> EncodeResult<()> {
+        if !self.config.declared_int_width {
+            return self.write_int(v);
+        }
+        Format::set_format(self, Format::Int64)?;
+        Ok(WriteBytesExt::write_i64::<BigEndian>(self, v)?)
     }
 
-    fn serialize_i64(self, v: i64) -> Result<()> {
-        if v >= 0 {
-            self.serialize_u64(v as u64)?;
-        } else if (-(1 << 5)..0).contains(&v) {
-            self.write_negative_fixed_int(v as i8)?;
-        } else if v <= i8::MAX as i64 && v >= i8::MIN as i64 {
-            Format::set_format(self, Format::Int8)?;
-            WriteBytesExt::write_i8(self, v as i8)?;
-        } else if v <= i16::MAX as i64 && v >= i16::MIN as i64 {
-            Format::set_format(self, Format::Int16)?;
-            WriteBytesExt::write_i16::<BigEndian>(self, v as i16)?;
-        } else if v <= i32::MAX as i64 && v >= i32::MIN as i64 {
-            Format::set_format(self, Format::Int32)?;
-            WriteBytesExt::write_i32::<BigEndian>(self, v as i32)?;
-        } else {
-            Format::set_format(self, Format::Int64)?;
-            WriteBytesExt::write_i64::<BigEndian>(self, v)?;
+    /// Values outside the 64-bit range are written as decimal strings, the
+    /// BigInt encoding.
+    fn serialize_i128(self, v: i128) -> EncodeResult<()> {
+        if let Ok(v) = i64::try_from(v) {
+            return self.write_int(v);
         }
-        Ok(())
+        if let Ok(v) = u64::try_from(v) {
+            return self.write_uint(v);
+        }
+        self.serialize_str(&v.to_string())
     }
 
-    fn serialize_u8(self, v: u8) -> Result<()> {
-        self.serialize_u64(v as u64)
+    fn serialize_u8(self, v: u8) -> EncodeResult<()> {
+        if !self.config.declared_int_width {
+            return self.write_uint(v as u64);
+        }
+        Format::set_format(self, Format::Uint8)?;
+        Ok(WriteBytesExt::write_u8(self, v)?)
     }
 
-    fn serialize_u16(self, v: u16) -> Result<()> {
-        self.serialize_u64(v as u64)
+    fn serialize_u16(self, v: u16) -> EncodeResult<()> {
+        if !self.config.declared_int_width {
+            return self.write_uint(v as u64);
+        }
+        Format::set_format(self, Format::Uint16)?;
+        Ok(WriteBytesExt::write_u16::<BigEndian>(self, v)?)
     }
 
-    fn serialize_u32(self, v: u32) -> Result<()> {
-        self.serialize_u64(v as u64)
+    fn serialize_u32(self, v: u32) -> EncodeResult<()> {
+        if !self.config.declared_int_width {
+            return self.write_uint(v as u64);
+        }
+        Format::set_format(self, Format::Uint32)?;
+        Ok(WriteBytesExt::write_u32::<BigEndian>(self, v)?)
     }
 
-    fn serialize_u64(self, v: u64) -> Result<()> {
-        if v < 1 << 7 {
-            self.write_positive_fixed_int(v as u8)?
-        } else if v <= u8::MAX as u64 {
-            Format::set_format(self, Format::Uint8)?;
-            WriteBytesExt::write_u8(self, v as u8)?
-        } else if v <= u16::MAX as u64 {
-            Format::set_format(self, Format::Uint16)?;
-            WriteBytesExt::write_u16::<BigEndian>(self, v as u16)?
-        } else if v <= u32::MAX as u64 {
-            Format::set_format(self, Format::Uint32)?;
-            WriteBytesExt::write_u32::<BigEndian>(self, v as u32)?
-        } else {
-            Format::set_format(self, Format::Uint64)?;
-            WriteBytesExt::write_u64::<BigEndian>(self, v)?
+    fn serialize_u64(self, v: u64) -> EncodeResult<()> {
+        if !self.config.declared_int_width {
+            return self.write_uint(v);
         }
+        Format::set_format(self, Format::Uint64)?;
+        Ok(WriteBytesExt::write_u64::<BigEndian>(self, v)?)
+    }
 
-        Ok(())
+    /// Values outside the 64-bit range are written as decimal strings, the
+    /// BigInt encoding.
+    fn serialize_u128(self, v: u128) -> EncodeResult<()> {
+        match u64::try_from(v) {
+            Ok(v) => self.write_uint(v),
+            Err(_) => self.serialize_str(&v.to_string()),
+        }
     }
 
-    fn serialize_f32(self, v: f32) -> Result<()> {
+    fn serialize_f32(self, v: f32) -> EncodeResult<()> {
+        let v = self.canonical_f32(v)?;
+        if !self.config.shrink_floats {
+            Format::set_format(self, Format::Float32)?;
+            WriteBytesExt::write_f32::<BigEndian>(self, v)?;
+            return Ok(());
+        }
+
         self.serialize_f64(v as f64)?;
         Ok(())
     }
 
-    fn serialize_f64(self, v: f64) -> Result<()> {
+    fn serialize_f64(self, v: f64) -> EncodeResult<()> {
         fn is_exact_f32(num: f64) -> bool {
             let f32_num = num as f32;
             let f64_num = f32_num as f64;
             f64_num == num
         }
 
-        if is_exact_f32(v) {
+        let v = self.canonical_f64(v)?;
+        if self.config.shrink_floats && is_exact_f32(v) {
             Format::set_format(self, Format::Float32)?;
             WriteBytesExt::write_f32::<BigEndian>(self, (v) as f32)?;
         } else {
@@ -167,65 +493,46 @@ impl<'a> ser::Serializer for &'a mut Serializer {
         Ok(())
     }
 
-    fn serialize_char(self, v: char) -> Result<()> {
-        self.serialize_str(&v.to_string())?;
-        Ok(())
+    /// Always a FixStr, as a char takes at most four bytes.
+    fn serialize_char(self, v: char) -> EncodeResult<()> {
+        self.serialize_str(v.encode_utf8(&mut [0; 4]))
     }
 
-    fn serialize_str(self, v: &str) -> Result<()> {
-        let length = v.len() as u32;
-        if length < 32 {
-            Format::set_format(self, Format::FixStr(length as u8))?;
-        } else if length <= u8::MAX as u32 {
-            Format::set_format(self, Format::Str8)?;
-            WriteBytesExt::write_u8(self, length as u8)?;
-        } else if length <= u16::MAX as u32 {
-            Format::set_format(self, Format::Str16)?;
-            WriteBytesExt::write_u16::<BigEndian>(self, length as u16)?;
-        } else {
-            Format::set_format(self, Format::Str32)?;
-            WriteBytesExt::write_u32::<BigEndian>(self, length)?;
-        }
-
+    fn serialize_str(self, v: &str) -> EncodeResult<()> {
+        write_str_len(self, v.len() as u32)?;
         self.write_all(v.as_bytes())?;
         Ok(())
     }
 
-    fn serialize_bytes(self, v: &[u8]) -> Result<()> {
-        if v.is_empty() {
+    fn serialize_bytes(self, v: &[u8]) -> EncodeResult<()> {
+        if v.is_empty() && self.config.empty_bytes_as_nil {
             return self.serialize_unit();
         }
-        let length = v.len() as u32;
-        if length <= u8::MAX as u32 {
-            Format::set_format(self, Format::Bin8)?;
-            WriteBytesExt::write_u8(self, length as u8)?;
-        } else if length <= u16::MAX as u32 {
-            Format::set_format(self, Format::Bin16)?;
-            WriteBytesExt::write_u16::<BigEndian>(self, length as u16)?;
-        } else {
-            Format::set_format(self, Format::Bin32)?;
-            WriteBytesExt::write_u32::<BigEndian>(self, length)?;
-        }
+        write_bin_len(self, v.len() as u32)?;
         Ok(self.write_all(v)?)
     }
 
-    fn serialize_none(self) -> Result<()> {
+    fn serialize_none(self) -> EncodeResult<()> {
         self.serialize_unit()
     }
 
-    fn serialize_some<T>(self, value: &T) -> Result<()>
+    fn serialize_some<T>(self, value: &T) -> EncodeResult<()>
     where
         T: ?Sized + Serialize,
     {
         value.serialize(self)
     }
 
-    fn serialize_unit(self) -> Result<()> {
+    fn serialize_unit(self) -> EncodeResult<()> {
         Format::set_format(self, Format::Nil)?;
         Ok(())
     }
 
-    fn serialize_unit_struct(self, _name: &'static str) -> Result<()> {
+    fn serialize_unit_struct(self, _name: &'static str) -> EncodeResult<()> {
+        if self.config.unit_struct_as_array {
+            return ArraySerializer::write_array_length(self, &0);
+        }
+
         self.serialize_unit()
     }
 
@@ -233,150 +540,133 @@ impl<'a> ser::Serializer for &'a mut Serializer {
         self,
         _name: &'static str,
         _variant_index: u32,
-        _: &'static str,
-    ) -> Result<()> {
-        self.serialize_u32(_variant_index)?;
-        Ok(())
+        variant: &'static str,
+    ) -> EncodeResult<()> {
+        if self.config.variant_names {
+            return self.serialize_str(variant);
+        }
+
+        self.write_uint(_variant_index as u64)
     }
 
     fn serialize_newtype_struct<T>(
         self,
-        _name: &'static str,
+        name: &'static str,
         value: &T,
-    ) -> Result<()>
+    ) -> EncodeResult<()>
     where
         T: ?Sized + Serialize,
     {
+        if name == GENERIC_MAP_TOKEN {
+            self.force_ext_map = true;
+            let result = value.serialize(&mut *self);
+            self.force_ext_map = false;
+            return result;
+        }
+        if name == FLATTENED_TOKEN {
+            self.flattened = true;
+            let result = value.serialize(&mut *self);
+            self.flattened = false;
+            return result;
+        }
+        if name == EXT_TOKEN {
+            return self.write_ext(value);
+        }
         value.serialize(self)
     }
 
+    /// Writes `{ variant: value }`, identifying the variant the same way as
+    /// unit variants.
     fn serialize_newtype_variant<T>(
         self,
-        _name: &'static str,
-        _variant_index: u32,
-        _: &'static str,
-        _: &T,
-    ) -> Result<()>
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> EncodeResult<()>
     where
         T: ?Sized + Serialize,
     {
-        todo!()
+        MapSerializer::write_map_length(self, &1)?;
+        self.serialize_unit_variant(name, variant_index, variant)?;
+        value.serialize(self)
     }
 
-    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq> {
-        // TODO: optimize for the case where len is defined
-        let array_ser = ArraySerializer::new(self);
-        Ok(array_ser)
+    fn serialize_seq(
+        self,
+        len: Option<usize>,
+    ) -> EncodeResult<Self::SerializeSeq> {
+        ArraySerializer::new_seq(self, len)
     }
 
-    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple> {
-        self.serialize_seq(Some(len))
+    fn serialize_tuple(self, len: usize) -> EncodeResult<Self::SerializeTuple> {
+        ArraySerializer::new(self, Some(len))
     }
 
     fn serialize_tuple_struct(
         self,
         _name: &'static str,
-        _: usize,
-    ) -> Result<Self::SerializeTupleStruct> {
-        todo!()
+        len: usize,
+    ) -> EncodeResult<Self::SerializeTupleStruct> {
+        self.serialize_tuple(len)
     }
 
+    /// Writes `{ variant: [field, ..] }`.
     fn serialize_tuple_variant(
         self,
-        _name: &'static str,
-        _variant_index: u32,
-        _: &'static str,
-        _len: usize,
-    ) -> Result<Self::SerializeTupleVariant> {
-        todo!()
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> EncodeResult<Self::SerializeTupleVariant> {
+        MapSerializer::write_map_length(self, &1)?;
+        self.serialize_unit_variant(name, variant_index, variant)?;
+        self.serialize_tuple(len)
     }
 
-    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
-        let map_ser = MapSerializer::new(self);
-        Ok(map_ser)
+    fn serialize_map(
+        self,
+        len: Option<usize>,
+    ) -> EncodeResult<Self::SerializeMap> {
+        MapSerializer::new(self, len)
     }
 
     fn serialize_struct(
         self,
-        _name: &'static str,
-        _: usize,
-    ) -> Result<Self::SerializeStruct> {
-        let struct_ser = StructSerializer::new(self);
-        Ok(struct_ser)
+        name: &'static str,
+        len: usize,
+    ) -> EncodeResult<Self::SerializeStruct> {
+        // Without flattened fields, nothing is left for `Flattened` to do
+        self.flattened = false;
+        StructSerializer::new(self, name, len)
     }
 
+    /// Writes `{ variant: { field: value, .. } }`, identifying the variant
+    /// the same way as unit variants.
     fn serialize_struct_variant(
         self,
-        _name: &'static str,
-        _variant_index: u32,
-        _: &'static str,
-        _len: usize,
-    ) -> Result<Self::SerializeStructVariant> {
-        todo!()
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> EncodeResult<Self::SerializeStructVariant> {
+        MapSerializer::write_map_length(self, &1)?;
+        self.serialize_unit_variant(name, variant_index, variant)?;
+        // No precomputed field table applies to a variant's fields.
+        StructSerializer::new(self, "", len)
     }
 }
 
 impl Write for Serializer {
-    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
         self.buffer.write(buf)
     }
 
-    fn flush(&mut self) -> std::io::Result<()> {
+    fn flush(&mut self) -> io::Result<()> {
         self.buffer.flush()
     }
 }
 
-impl<'a> ser::SerializeTupleStruct for &'a mut Serializer {
-    type Ok = ();
-    type Error = Error;
-
-    fn serialize_field<T>(&mut self, _: &T) -> Result<()>
-    where
-        T: ?Sized + Serialize,
-    {
-        todo!()
-    }
-
-    fn end(self) -> Result<()> {
-        todo!()
-    }
-}
-
-impl<'a> ser::SerializeTupleVariant for &'a mut Serializer {
-    type Ok = ();
-    type Error = Error;
-
-    fn serialize_field<T>(&mut self, _: &T) -> Result<()>
-    where
-        T: ?Sized + Serialize,
-    {
-        todo!()
-    }
-
-    fn end(self) -> Result<()> {
-        todo!()
-    }
-}
-
-impl<'a> ser::SerializeStructVariant for &'a mut Serializer {
-    type Ok = ();
-    type Error = Error;
-
-    fn serialize_field<T: ?Sized>(
-        &mut self,
-        _: &'static str,
-        _: &T,
-    ) -> std::result::Result<(), Self::Error>
-    where
-        T: Serialize,
-    {
-        todo!()
-    }
-
-    fn end(self) -> std::result::Result<Self::Ok, Self::Error> {
-        todo!()
-    }
-}
 
 ////////////////////////////////////////////////////////////////////////////////
 
@@ -422,6 +712,16 @@ mod tests {
         assert_eq!([195], result.as_slice());
     }
 
+    #[test]
+    fn test_write_char() {
+        assert_eq!([161, 97], to_vec(&'a').unwrap().as_slice());
+        assert_eq!([162, 0xc3, 0xa9], to_vec(&'é').unwrap().as_slice());
+        assert_eq!(
+            [164, 0xf0, 0x9f, 0xa6, 0x80],
+            to_vec(&'🦀').unwrap().as_slice()
+        );
+    }
+
     #[test]
     fn test_write_u8() {
         let cases = [
@@ -575,7 +875,7 @@ mod tests {
 
     #[test]
     fn test_write_f32() {
-        let cases = [Case::new("32-bit float", 0.5, &[202, 63, 0, 0, 0])];
+        let cases = [Case::new("32-bit float", 0.5f32, &[202, 63, 0, 0, 0])];
 
         for case in cases {
             let result = to_vec(&case.input).unwrap();
@@ -585,11 +885,18 @@ mod tests {
 
     #[test]
     fn test_write_f64() {
-        let cases = [Case::new(
-            "64-bit float",
-            3.141592653589793,
-            &[203, 64, 9, 33, 251, 84, 68, 45, 24],
-        )];
+        let cases = [
+            Case::new(
+                "64-bit float",
+                core::f64::consts::PI,
+                &[203, 64, 9, 33, 251, 84, 68, 45, 24],
+            ),
+            Case::new(
+                "64-bit float exactly representable as 32-bit",
+                0.5,
+                &[203, 63, 224, 0, 0, 0, 0, 0, 0],
+            ),
+        ];
 
         for case in cases {
             let result = to_vec(&case.input).unwrap();
@@ -646,11 +953,10 @@ mod tests {
 
     #[test]
     fn test_write_bytes() {
-        let cases = [Case::new(
-            "Bytes",
-            serde_bytes::ByteBuf::from([1]),
-            &[196, 1, 1],
-        )];
+        let cases = [
+            Case::new("Bytes", serde_bytes::ByteBuf::from([1]), &[196, 1, 1]),
+            Case::new("Empty bytes", serde_bytes::ByteBuf::new(), &[196, 0]),
+        ];
 
         for case in cases {
             let result = to_vec(&case.input).unwrap();
@@ -658,6 +964,31 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_write_empty_bytes_as_nil() {
+        use crate::{from_slice, to_vec_with_config, SerializerConfig};
+        use serde_bytes::ByteBuf;
+        use serde_derive::Deserialize;
+
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Foo {
+            #[serde(with = "serde_bytes")]
+            data: Vec<u8>,
+        }
+
+        let foo = Foo { data: vec![] };
+        let config = SerializerConfig::default().empty_bytes_as_nil(true);
+        let legacy = to_vec_with_config(&foo, config).unwrap();
+        assert_eq!([129, 164, 100, 97, 116, 97, 192], legacy.as_slice());
+
+        // Both historical encodings decode to empty bytes.
+        let current = to_vec(&foo).unwrap();
+        assert_eq!([129, 164, 100, 97, 116, 97, 196, 0], current.as_slice());
+        assert_eq!(foo, from_slice(&legacy).unwrap());
+        assert_eq!(foo, from_slice(&current).unwrap());
+        assert!(from_slice::<ByteBuf>(&[192]).unwrap().is_empty());
+    }
+
     #[test]
     fn test_write_array() {
         let cases = [
@@ -687,6 +1018,105 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_write_array_unknown_length() {
+        use serde::ser::{Serialize, Serializer};
+
+        /// Numbers serialized from an iterator of unknown length.
+        struct Filtered(u32);
+
+        impl Serialize for Filtered {
+            fn serialize<S>(&self, s: S) -> Result<S::Ok, S::Error>
+            where
+                S: Serializer,
+            {
+                s.collect_seq((0..self.0).filter(|i| i % 2 == 0))
+            }
+        }
+
+        for (len, header) in
+            [(0, vec![0x90]), (15, vec![0x9f]), (16, vec![0xdc, 0, 16])]
+        {
+            let result = to_vec(&Filtered(len * 2)).unwrap();
+            assert_eq!(header, result[..header.len()]);
+            assert_eq!(
+                (0..len * 2).step_by(2).collect::<Vec<_>>(),
+                crate::from_slice::<Vec<u32>>(&result).unwrap()
+            );
+        }
+
+        let nested = (Filtered(40), vec![Filtered(2)], 7u8);
+        let result = to_vec(&nested).unwrap();
+        let decoded: (Vec<u32>, Vec<Vec<u32>>, u8) =
+            crate::from_slice(&result).unwrap();
+        assert_eq!(
+            ((0..40).step_by(2).collect(), vec![vec![0]], 7),
+            decoded
+        );
+    }
+
+    #[test]
+    fn test_write_byte_seqs_as_bin() {
+        use crate::{to_vec_with_config, MapEncoding, SerializerConfig};
+        use serde_derive::Deserialize;
+
+        #[derive(Debug, Deserialize, PartialEq, Serialize)]
+        struct Foo {
+            bytes: Vec<u8>,
+            ints: Vec<u32>,
+            empty: Vec<u8>,
+            pair: (u8, u8),
+        }
+
+        let foo = Foo {
+            bytes: vec![1, 200, 3],
+            ints: vec![1, 300],
+            empty: vec![],
+            pair: (1, 2),
+        };
+        let config = SerializerConfig::default()
+            .map_encoding(MapEncoding::Plain)
+            .byte_seqs_as_bin(true);
+        let result = to_vec_with_config(&foo, config).unwrap();
+        let mut expected = vec![132, 165];
+        expected.extend_from_slice(b"bytes");
+        expected.extend_from_slice(&[0xc4, 3, 1, 200, 3, 164]);
+        expected.extend_from_slice(b"ints");
+        expected.extend_from_slice(&[146, 1, 0xcd, 1, 44, 165]);
+        expected.extend_from_slice(b"empty");
+        expected.extend_from_slice(&[144, 164]);
+        expected.extend_from_slice(b"pair");
+        expected.extend_from_slice(&[146, 1, 2]);
+        assert_eq!(expected, result);
+        assert_eq!(foo, crate::from_slice(&result).unwrap());
+
+        // Bytes written before the first element that isn't one are turned
+        // back into integers
+        struct Mixed;
+
+        impl serde::Serialize for Mixed {
+            fn serialize<S>(&self, s: S) -> Result<S::Ok, S::Error>
+            where
+                S: serde::Serializer,
+            {
+                use serde::ser::SerializeSeq;
+
+                let mut seq = s.serialize_seq(Some(4))?;
+                for byte in [1u8, 2, 200] {
+                    seq.serialize_element(&byte)?;
+                }
+                seq.serialize_element(&300u16)?;
+                seq.end()
+            }
+        }
+
+        let config = SerializerConfig::default().byte_seqs_as_bin(true);
+        assert_eq!(
+            vec![148, 1, 2, 0xcc, 200, 0xcd, 1, 44],
+            to_vec_with_config(&Mixed, config).unwrap()
+        );
+    }
+
     #[test]
     fn test_write_struct() {
         #[derive(Serialize)]
@@ -725,6 +1155,599 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_write_complex_map_keys() {
+        #[derive(Serialize, PartialEq, Eq, PartialOrd, Ord)]
+        struct Point {
+            x: u8,
+            y: u8,
+        }
+
+        let tuple_keys = BTreeMap::from([((1u8, "a"), 2u8)]);
+        assert_eq!(
+            vec![199, 6, 1, 129, 146, 1, 161, 97, 2],
+            to_vec(&tuple_keys).unwrap()
+        );
+
+        let struct_keys = BTreeMap::from([(Point { x: 1, y: 2 }, "p")]);
+        assert_eq!(
+            vec![199, 10, 1, 129, 130, 161, 120, 1, 161, 121, 2, 161, 112],
+            to_vec(&struct_keys).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_write_struct_precomputed_fields() {
+        use crate::{EncodedFields as _, Serializer};
+        use polywrap_msgpack_serde_derive::EncodedFields;
+        use serde::Serialize as _;
+
+        #[derive(Serialize, EncodedFields)]
+        #[serde(rename_all = "camelCase")]
+        struct Foo {
+            first_name: String,
+            #[serde(rename = "years")]
+            age: u8,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            nickname: Option<String>,
+        }
+
+        let foo = Foo {
+            first_name: "a".to_string(),
+            age: 3,
+            nickname: None,
+        };
+
+        let mut serializer = Serializer::default();
+        serializer.register_fields::<Foo>();
+        foo.serialize(&mut serializer).unwrap();
+
+        assert_eq!(
+            Foo::ENCODED_FIELDS,
+            &[
+                ("firstName", &b"\xa9firstName"[..]),
+                ("years", b"\xa5years"),
+                ("nickname", b"\xa8nickname"),
+            ]
+        );
+        assert_eq!(to_vec(&foo).unwrap(), serializer.get_buffer());
+    }
+
+    #[test]
+    fn test_write_struct_miscounted_fields() {
+        use serde::ser::{Serialize, SerializeStruct, Serializer};
+
+        use crate::{to_vec_with_config, SerializerConfig};
+
+        /// Announces `announced` fields but writes `written`, then a
+        /// correctly counted struct after it.
+        struct Miscounted {
+            announced: usize,
+            written: u8,
+        }
+
+        impl Serialize for Miscounted {
+            fn serialize<S>(&self, s: S) -> Result<S::Ok, S::Error>
+            where
+                S: Serializer,
+            {
+                const KEYS: [&str; 20] = [
+                    "a", "b", "c", "d", "e", "f", "g", "h", "i", "j", "k", "l",
+                    "m", "n", "o", "p", "q", "r", "s", "t",
+                ];
+                let mut state =
+                    s.serialize_struct("Miscounted", self.announced)?;
+                for i in 0..self.written {
+                    state.serialize_field(KEYS[i as usize], &i)?;
+                }
+                state.end()
+            }
+        }
+
+        #[derive(Serialize)]
+        struct Wrapper {
+            x: Miscounted,
+            y: u8,
+        }
+
+        let expected = |written: u8| {
+            let mut map = BTreeMap::new();
+            for i in 0..written {
+                map.insert(((b'a' + i) as char).to_string(), i);
+            }
+            let mut outer = vec![130, 161, b'x'];
+            outer.extend(crate::to_vec_with_plain_maps(&map).unwrap());
+            outer.extend([161, b'y', 7]);
+            outer
+        };
+        for (announced, written) in [(0, 17), (3, 1), (20, 2), (1, 1)] {
+            let wrapper = Wrapper {
+                x: Miscounted { announced, written },
+                y: 7,
+            };
+            assert_eq!(expected(written), to_vec(&wrapper).unwrap());
+        }
+
+        let as_array = SerializerConfig::default().struct_as_array(true);
+        let wrapper = Wrapper {
+            x: Miscounted {
+                announced: 16,
+                written: 2,
+            },
+            y: 7,
+        };
+        assert_eq!(
+            vec![146, 146, 0, 1, 7],
+            to_vec_with_config(&wrapper, as_array).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_write_tuple_struct() {
+        #[derive(Serialize)]
+        struct Color(u8, u8, u8);
+
+        let cases = [Case::new(
+            "Color",
+            Color(255, 0, 10),
+            &[147, 204, 255, 0, 10],
+        )];
+
+        for case in cases {
+            let result = to_vec(&case.input).unwrap();
+            assert_eq!(case.want, result.as_slice());
+        }
+    }
+
+    #[test]
+    fn test_write_struct_variant() {
+        use crate::{to_vec_with_config, SerializerConfig};
+
+        #[derive(Serialize)]
+        enum Event {
+            _Deleted,
+            Created { id: u32, name: String },
+        }
+
+        let event = Event::Created {
+            id: 7,
+            name: "a".to_string(),
+        };
+        assert_eq!(
+            [129, 1, 130, 162, 105, 100, 7, 164, 110, 97, 109, 101, 161, 97],
+            to_vec(&event).unwrap().as_slice()
+        );
+        assert_eq!(
+            rmp_serde::to_vec_named(&event).unwrap(),
+            to_vec_with_config(&event, SerializerConfig::rmp_compat()).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_write_128_bit_integers() {
+        assert_eq!([255], to_vec(&-1i128).unwrap().as_slice());
+        assert_eq!(
+            [207, 255, 255, 255, 255, 255, 255, 255, 255],
+            to_vec(&(u64::MAX as u128)).unwrap().as_slice()
+        );
+
+        let big = u64::MAX as i128 + 1;
+        let mut expected = vec![180];
+        expected.extend(b"18446744073709551616");
+        assert_eq!(expected, to_vec(&big).unwrap());
+        assert_eq!(expected, to_vec(&(big as u128)).unwrap());
+    }
+
+    #[test]
+    fn test_write_flattened_struct() {
+        use crate::{from_slice, Flattened};
+        use serde_derive::Deserialize;
+
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Meta {
+            version: u8,
+        }
+
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Flat {
+            name: String,
+            #[serde(flatten)]
+            meta: Meta,
+            #[serde(flatten)]
+            extra: BTreeMap<String, BTreeMap<String, u8>>,
+        }
+
+        #[derive(Serialize)]
+        struct Merged {
+            name: String,
+            version: u8,
+            labels: BTreeMap<String, u8>,
+        }
+
+        let labels = BTreeMap::from([("a".to_string(), 1)]);
+        let flat = Flat {
+            name: "x".to_string(),
+            meta: Meta { version: 2 },
+            extra: BTreeMap::from([("labels".to_string(), labels.clone())]),
+        };
+        let merged = Merged {
+            name: "x".to_string(),
+            version: 2,
+            labels,
+        };
+
+        let bytes = to_vec(&Flattened(&flat)).unwrap();
+        assert_eq!(to_vec(&merged).unwrap(), bytes);
+        assert_eq!(flat, from_slice::<Flat>(&bytes).unwrap());
+
+        // Unwrapped, it's a map of unknown length like any other, which
+        // decodes all the same
+        let bytes = to_vec(&flat).unwrap();
+        assert_eq!([0xc7, 30, 1, 0x83], bytes[..4]);
+        assert_eq!(flat, from_slice::<Flat>(&bytes).unwrap());
+
+        // As a field, and around a struct without flattened fields
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Outer {
+            inner: Flattened<Flat>,
+        }
+        let outer = Outer {
+            inner: Flattened(flat),
+        };
+        let bytes = to_vec(&outer).unwrap();
+        assert_eq!(to_vec(&merged).unwrap(), bytes[7..]);
+        assert_eq!(outer, from_slice::<Outer>(&bytes).unwrap());
+        assert_eq!(
+            to_vec(&merged).unwrap(),
+            to_vec(&Flattened(&merged)).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_write_map_of_unknown_length() {
+        use serde::Serializer as _;
+
+        // {"a": 1} out of a filtered map
+        let map = BTreeMap::from([("a", 1), ("b", 2)]);
+        let mut serializer = crate::Serializer::default();
+        serializer
+            .collect_map(map.iter().filter(|(k, _)| **k == "a"))
+            .unwrap();
+        assert_eq!(
+            vec![0xd6, 1, 0x81, 0xa1, b'a', 1],
+            serializer.into_inner()
+        );
+    }
+
+    #[test]
+    fn test_write_config_builder() {
+        use crate::{from_slice, to_vec_with_config, SerializerConfig};
+        use serde_derive::Deserialize;
+
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        enum Kind {
+            Small,
+            Large,
+        }
+
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Item {
+            id: u8,
+            kind: Kind,
+            weight: f64,
+        }
+
+        let item = Item {
+            id: 1,
+            kind: Kind::Large,
+            weight: 0.5,
+        };
+        let config = SerializerConfig::default()
+            .struct_as_array(true)
+            .variant_names(true)
+            .shrink_floats(true);
+        let bytes = to_vec_with_config(&item, config).unwrap();
+        assert_eq!(
+            [147, 1, 165, 76, 97, 114, 103, 101, 202, 63, 0, 0, 0],
+            bytes.as_slice()
+        );
+        assert_eq!(item, from_slice::<Item>(&bytes).unwrap());
+
+        let config = SerializerConfig::default().shrink_floats(false);
+        let bytes = to_vec_with_config(&item, config).unwrap();
+        assert_eq!(203, bytes[bytes.len() - 9]);
+        assert_eq!(item, from_slice::<Item>(&bytes).unwrap());
+    }
+
+    #[test]
+    fn test_write_declared_int_width() {
+        use crate::{from_slice, to_vec_with_config, SerializerConfig};
+
+        #[derive(Debug, PartialEq, Serialize, serde_derive::Deserialize)]
+        enum Kind {
+            A,
+            B,
+        }
+
+        let value = (1u8, -1i16, 2u32, 3i64, u64::MAX, Kind::B);
+        let config = SerializerConfig::default().declared_int_width(true);
+        let bytes = to_vec_with_config(&value, config).unwrap();
+        assert_eq!(
+            [
+                150, 204, 1, 209, 255, 255, 206, 0, 0, 0, 2, 211, 0, 0, 0, 0,
+                0, 0, 0, 3, 207, 255, 255, 255, 255, 255, 255, 255, 255, 1
+            ],
+            bytes.as_slice()
+        );
+        assert_eq!(value, from_slice(&bytes).unwrap());
+
+        assert_eq!(
+            [150, 1, 255, 2, 3, 207, 255, 255, 255, 255, 255, 255, 255, 255, 1],
+            to_vec(&value).unwrap().as_slice()
+        );
+    }
+
+    #[test]
+    fn test_write_into_inner() {
+        use crate::Serializer;
+        use serde::Serialize as _;
+
+        let mut serializer = Serializer::default();
+        (1u8, "a").serialize(&mut serializer).unwrap();
+        assert_eq!([146, 1, 161, 97], serializer.as_bytes());
+
+        let bytes = serializer.into_inner();
+        assert_eq!([146, 1, 161, 97], bytes.as_slice());
+    }
+
+    #[test]
+    fn test_write_struct_cached_keys() {
+        use crate::Serializer;
+        use serde::Serialize as _;
+
+        #[derive(Serialize)]
+        struct Inner {
+            a: u8,
+        }
+
+        #[derive(Serialize)]
+        struct Outer {
+            inner: Inner,
+            b: u8,
+        }
+
+        let values: Vec<Outer> = (0..3)
+            .map(|b| Outer {
+                inner: Inner { a: 1 },
+                b,
+            })
+            .collect();
+
+        let mut serializer = Serializer::default();
+        values.serialize(&mut serializer).unwrap();
+
+        let element =
+            [130, 165, 105, 110, 110, 101, 114, 129, 161, 97, 1, 161, 98];
+        let mut expected = vec![147];
+        for b in 0..3 {
+            expected.extend(element);
+            expected.push(b);
+        }
+        assert_eq!(expected, serializer.get_buffer());
+        // "inner", "a" and "b", handed back up to the root serializer
+        assert_eq!(3, serializer.key_cache.len());
+    }
+
+    #[test]
+    fn test_write_float_canonicalization() {
+        use crate::{
+            to_vec_with_config, FloatCanonicalization, SerializerConfig,
+        };
+
+        let payload_nan = f64::from_bits(0x7ff8_0000_0000_0001);
+        let signaling_nan = f32::from_bits(0x7f80_0001);
+        let config = |policy| {
+            SerializerConfig::default().float_canonicalization(policy)
+        };
+
+        let preserve = config(FloatCanonicalization::Preserve);
+        assert_eq!(
+            vec![203, 128, 0, 0, 0, 0, 0, 0, 0],
+            to_vec_with_config(&-0.0f64, preserve.clone()).unwrap()
+        );
+        assert_eq!(
+            vec![203, 127, 248, 0, 0, 0, 0, 0, 1],
+            to_vec_with_config(&payload_nan, preserve).unwrap()
+        );
+
+        let normalize = config(FloatCanonicalization::Normalize);
+        assert_eq!(
+            vec![203, 0, 0, 0, 0, 0, 0, 0, 0],
+            to_vec_with_config(&-0.0f64, normalize.clone()).unwrap()
+        );
+        assert_eq!(
+            vec![203, 127, 248, 0, 0, 0, 0, 0, 0],
+            to_vec_with_config(&payload_nan, normalize.clone()).unwrap()
+        );
+        assert_eq!(
+            vec![202, 127, 192, 0, 0],
+            to_vec_with_config(&signaling_nan, normalize).unwrap()
+        );
+
+        let reject = config(FloatCanonicalization::Reject);
+        assert!(to_vec_with_config(&-0.0f32, reject.clone()).is_err());
+        assert!(to_vec_with_config(&payload_nan, reject.clone()).is_err());
+        assert!(to_vec_with_config(&signaling_nan, reject.clone()).is_err());
+        assert_eq!(
+            vec![203, 127, 248, 0, 0, 0, 0, 0, 0],
+            to_vec_with_config(&f64::NAN, reject.clone()).unwrap()
+        );
+        assert_eq!(
+            vec![203, 63, 240, 0, 0, 0, 0, 0, 0],
+            to_vec_with_config(&1.0f64, reject).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_write_reusing_serializer() {
+        use super::{to_vec_reuse, Serializer};
+        use crate::{to_vec_with_config, MapEncoding, SerializerConfig};
+
+        let config =
+            SerializerConfig::default().map_encoding(MapEncoding::Plain);
+        let mut serializer = Serializer::with_config(config.clone());
+        let long = "x".repeat(1000);
+        assert_eq!(
+            to_vec_with_config(&long, config.clone()).unwrap(),
+            to_vec_reuse(&mut serializer, &long).unwrap()
+        );
+        let capacity = serializer.buffer.get_ref().capacity();
+
+        let map = BTreeMap::from([("a", 1)]);
+        assert_eq!(
+            to_vec_with_config(&map, config).unwrap(),
+            to_vec_reuse(&mut serializer, &map).unwrap()
+        );
+        assert_eq!(capacity, serializer.buffer.get_ref().capacity());
+
+        serializer.reset();
+        assert!(serializer.as_bytes().is_empty());
+    }
+
+    #[test]
+    fn test_encode_batch() {
+        use super::{encode_batch, encode_batch_to};
+
+        #[derive(Serialize)]
+        struct Foo {
+            a: u8,
+        }
+
+        let items = [Foo { a: 1 }, Foo { a: 200 }];
+        let expected =
+            vec![vec![129, 161, 97, 1], vec![129, 161, 97, 204, 200]];
+        assert_eq!(expected, encode_batch(&items).unwrap());
+
+        let mut writers = [vec![], vec![]];
+        encode_batch_to(&items, &mut writers).unwrap();
+        assert_eq!(expected, writers);
+
+        assert!(encode_batch_to(&items, &mut [vec![]]).is_err());
+    }
+
+    #[test]
+    fn test_write_json_number_precision() {
+        use crate::{from_slice, wrappers::polywrap_json};
+        use serde_derive::Deserialize;
+        use serde_json::Value;
+
+        #[derive(Serialize, Deserialize)]
+        struct Foo {
+            #[serde(with = "polywrap_json")]
+            json: Value,
+        }
+
+        let json = "[18446744073709551617,0.1000000000000000000000000001]";
+        let foo = Foo {
+            json: serde_json::from_str(json).unwrap(),
+        };
+        let decoded: Foo = from_slice(&to_vec(&foo).unwrap()).unwrap();
+        let big_int: Value =
+            serde_json::from_str("18446744073709551617").unwrap();
+
+        if cfg!(feature = "arbitrary_precision") {
+            // Every digit survives, both inside JSON strings and when
+            // transcoding numbers directly
+            assert_eq!(json, decoded.json.to_string());
+            let mut want = vec![180];
+            want.extend(b"18446744073709551617");
+            assert_eq!(want, to_vec(&big_int).unwrap());
+
+            let decimal: Value = serde_json::from_str("0.5").unwrap();
+            assert_eq!(
+                vec![203, 63, 224, 0, 0, 0, 0, 0, 0],
+                to_vec(&decimal).unwrap()
+            );
+            let int: Value = serde_json::from_str("-42").unwrap();
+            assert_eq!(vec![208, 214], to_vec(&int).unwrap());
+        } else {
+            // serde_json rounds to the nearest f64 while parsing
+            assert_eq!(
+                "[1.8446744073709552e+19,0.1]",
+                decoded.json.to_string()
+            );
+            // 2^64
+            assert_eq!(
+                vec![203, 67, 240, 0, 0, 0, 0, 0, 0],
+                to_vec(&big_int).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_write_struct_sorted_fields() {
+        use crate::{to_vec_with_config, SerializerConfig};
+
+        #[derive(Serialize)]
+        struct Foo {
+            zeta: u8,
+            alpha: Bar,
+        }
+
+        #[derive(Serialize)]
+        struct Bar {
+            b: u8,
+            a: u8,
+        }
+
+        #[derive(Serialize)]
+        struct Reordered {
+            alpha: Bar,
+            zeta: u8,
+        }
+
+        let config = SerializerConfig::default().sort_struct_fields(true);
+        let foo = to_vec_with_config(
+            &Foo {
+                zeta: 1,
+                alpha: Bar { b: 2, a: 3 },
+            },
+            config.clone(),
+        )
+        .unwrap();
+        let reordered = to_vec_with_config(
+            &Reordered {
+                alpha: Bar { b: 2, a: 3 },
+                zeta: 1,
+            },
+            config.clone(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            vec![
+                130, 165, 97, 108, 112, 104, 97, 130, 161, 97, 3, 161, 98, 2,
+                164, 122, 101, 116, 97, 1
+            ],
+            foo
+        );
+        assert_eq!(foo, reordered);
+
+        // Arrays keep declaration order, which their positions stand for
+        let as_array = config.struct_as_array(true);
+        assert_eq!(
+            vec![146, 1, 146, 2, 3],
+            to_vec_with_config(
+                &Foo {
+                    zeta: 1,
+                    alpha: Bar { b: 2, a: 3 },
+                },
+                as_array
+            )
+            .unwrap()
+        );
+    }
+
     #[test]
     fn test_write_ext_generic_map() {
         let mut map1: BTreeMap<i32, Vec<i32>> = BTreeMap::new();
@@ -785,6 +1808,70 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_write_ext_generic_map_header_sizes() {
+        use crate::to_vec_with_plain_maps;
+
+        // FixExt1, Ext8, Ext16 and Ext32 headers, each with the type byte
+        for (entries, format, header_len) in [
+            (0u32, 0xd4, 2),
+            (50, 0xc7, 3),
+            (100, 0xc8, 4),
+            (20_000, 0xc9, 6),
+        ] {
+            let map: BTreeMap<u32, u32> =
+                (0..entries).map(|i| (i, 1000 + i)).collect();
+            let plain = to_vec_with_plain_maps(&map).unwrap();
+            let result = to_vec(&map).unwrap();
+            let (header, payload) = result.split_at(header_len);
+            assert_eq!(format, header[0]);
+            assert_eq!(1, header[header_len - 1]);
+            assert_eq!(plain, payload);
+            if header_len > 2 {
+                let len = header[1..header_len - 1]
+                    .iter()
+                    .fold(0, |len, byte| len << 8 | *byte as usize);
+                assert_eq!(plain.len(), len);
+            }
+        }
+    }
+
+    #[test]
+    fn test_write_map_miscounted_entries() {
+        use serde::ser::{Serialize, SerializeMap, Serializer};
+
+        use crate::to_vec_with_plain_maps;
+
+        /// Announces `announced` entries but writes `written`.
+        struct Miscounted {
+            announced: usize,
+            written: u8,
+        }
+
+        impl Serialize for Miscounted {
+            fn serialize<S>(&self, s: S) -> Result<S::Ok, S::Error>
+            where
+                S: Serializer,
+            {
+                let mut state = s.serialize_map(Some(self.announced))?;
+                for i in 0..self.written {
+                    state.serialize_entry(&i, &i)?;
+                }
+                state.end()
+            }
+        }
+
+        for (announced, written) in [(0, 17), (20, 2)] {
+            let miscounted = Miscounted { announced, written };
+            let map: BTreeMap<u8, u8> = (0..written).map(|i| (i, i)).collect();
+            assert_eq!(
+                to_vec_with_plain_maps(&map).unwrap(),
+                to_vec_with_plain_maps(&miscounted).unwrap()
+            );
+            assert_eq!(to_vec(&map).unwrap(), to_vec(&miscounted).unwrap());
+        }
+    }
+
     #[test]
     fn test_write_enum() {
         #[derive(Serialize)]
@@ -804,6 +1891,150 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_write_enum_map_keys() {
+        #[derive(Serialize, PartialEq, Eq, PartialOrd, Ord)]
+        enum Foo {
+            First,
+            Second,
+        }
+
+        let mut map = BTreeMap::new();
+        map.insert(Foo::First, 3u8);
+        map.insert(Foo::Second, 5u8);
+
+        let cases = [Case::new(
+            "enum keys",
+            map,
+            &[199, 5, 1, 130, 0, 3, 1, 5],
+        )];
+
+        for case in cases {
+            let result = to_vec(&case.input).unwrap();
+            assert_eq!(case.want, result.as_slice());
+        }
+    }
+
+    #[test]
+    fn test_rmp_compat_matches_rmp_serde() {
+        use crate::{to_vec_with_config, SerializerConfig};
+        use serde_bytes::ByteBuf;
+
+        #[derive(Serialize)]
+        enum Kind {
+            _Small,
+            Large,
+        }
+
+        #[derive(Serialize)]
+        struct Marker;
+
+        #[derive(Serialize)]
+        struct Inner {
+            values: Vec<i64>,
+            ratio: f32,
+        }
+
+        #[derive(Serialize)]
+        struct Foo {
+            id: u64,
+            offset: i32,
+            half: f64,
+            pi: f64,
+            name: String,
+            payload: ByteBuf,
+            empty: ByteBuf,
+            kind: Kind,
+            marker: Marker,
+            missing: Option<u8>,
+            inner: Inner,
+            labels: BTreeMap<String, Vec<u8>>,
+            by_id: BTreeMap<u16, Inner>,
+        }
+
+        let mut labels = BTreeMap::new();
+        labels.insert("a".to_string(), vec![1, 2]);
+        labels.insert("long label ".repeat(4), vec![]);
+        let mut by_id = BTreeMap::new();
+        by_id.insert(
+            300,
+            Inner {
+                values: vec![-1, -33, -129, i64::MIN, 128, u32::MAX as i64],
+                ratio: 0.25,
+            },
+        );
+
+        let foo = Foo {
+            id: u64::MAX,
+            offset: -40000,
+            half: 0.5,
+            pi: core::f64::consts::PI,
+            name: "x".repeat(40),
+            payload: ByteBuf::from(vec![7; 300]),
+            empty: ByteBuf::new(),
+            kind: Kind::Large,
+            marker: Marker,
+            missing: None,
+            inner: Inner {
+                values: vec![],
+                ratio: 1.5,
+            },
+            labels,
+            by_id,
+        };
+
+        let expected = rmp_serde::to_vec_named(&foo).unwrap();
+        let result =
+            to_vec_with_config(&foo, SerializerConfig::rmp_compat()).unwrap();
+        assert_eq!(expected, result);
+    }
+
+    #[test]
+    fn test_write_map_encoding_by_key_type() {
+        use crate::{from_slice, to_vec_with_config, MapEncoding};
+        use crate::{GenericMap, SerializerConfig};
+
+        let config = SerializerConfig::default()
+            .map_encoding(MapEncoding::ExtForNonStringKeys);
+
+        let mut by_name = BTreeMap::new();
+        by_name.insert("a".to_string(), 1u8);
+        let bytes = to_vec_with_config(&by_name, config.clone()).unwrap();
+        assert_eq!(bytes, [0x81, 0xa1, b'a', 1]);
+        assert_eq!(by_name, from_slice::<BTreeMap<_, _>>(&bytes).unwrap());
+
+        let mut by_id = BTreeMap::new();
+        by_id.insert(2u8, "b".to_string());
+        let bytes = to_vec_with_config(&by_id, config.clone()).unwrap();
+        assert_eq!(bytes, [0xd6, 1, 0x81, 2, 0xa1, b'b']);
+        assert_eq!(by_id, from_slice::<BTreeMap<_, _>>(&bytes).unwrap());
+
+        // GenericMaps keep the ext whatever their keys.
+        let generic = GenericMap::from(by_name);
+        let bytes = to_vec_with_config(&generic, config).unwrap();
+        assert_eq!(bytes, [0xd6, 1, 0x81, 0xa1, b'a', 1]);
+    }
+
+    #[test]
+    fn test_write_plain_maps() {
+        use crate::{from_slice, to_vec_with_plain_maps};
+        use std::collections::HashMap;
+
+        let mut small = HashMap::new();
+        small.insert("a".to_string(), vec![1u8]);
+        let bytes = to_vec_with_plain_maps(&small).unwrap();
+        assert_eq!(bytes, [0x81, 0xa1, b'a', 0x91, 1]);
+
+        let large: BTreeMap<u8, u8> = (0..16).map(|i| (i, i)).collect();
+        let bytes = to_vec_with_plain_maps(&large).unwrap();
+        assert_eq!(bytes[..3], [0xde, 0, 16]);
+        assert_eq!(
+            large,
+            rmp_serde::from_slice::<BTreeMap<u8, u8>>(&bytes).unwrap()
+        );
+        assert_eq!(large, from_slice::<BTreeMap<u8, u8>>(&bytes).unwrap());
+    }
+
     #[test]
     fn test_bignumber() {
         let cases = [Case::new(
@@ -0,0 +1,69 @@
+//! `insta` snapshots of encoded buffers.
+//!
+//! Buffers are snapshotted through the annotated [`inspect`] rendering
+//! rather than as raw bytes, so a review shows which field changed instead
+//! of a wall of shifted decimal bytes. Offsets are left out so a change in
+//! one value doesn't ripple through every following line.
+//!
+//! [`inspect`]: crate::inspect::inspect
+
+#[doc(hidden)]
+pub use insta;
+
+use crate::inspect::Inspector;
+
+/// Renders `bytes` for a snapshot, replacing the values of the map keys
+/// listed in `redact` (at any depth) with `[redacted]`.
+pub fn render(bytes: &[u8], redact: &[&str]) -> String {
+    Inspector::new(bytes, false, redact).render()
+}
+
+/// Asserts an encoded buffer against an `insta` snapshot of its annotated
+/// rendering.
+///
+/// Values under the map keys listed in `redact = [...]` are replaced with
+/// `[redacted]`, for fields such as timestamps or ids that change between
+/// runs. Anything after the buffer and redactions is forwarded to
+/// `insta::assert_snapshot!`, e.g. an inline `@"..."` snapshot.
+///
+/// ```ignore
+/// let bytes = polywrap_msgpack_serde::to_vec(&manifest)?;
+/// assert_msgpack_snapshot!(bytes, redact = ["timestamp"]);
+/// ```
+#[macro_export]
+macro_rules! assert_msgpack_snapshot {
+    ($bytes:expr, redact = [$($key:expr),* $(,)?] $(, $($rest:tt)*)?) => {
+        $crate::snapshot::insta::assert_snapshot!(
+            $crate::snapshot::render(&$bytes, &[$($key),*])
+            $(, $($rest)*)?
+        )
+    };
+    ($bytes:expr $(, $($rest:tt)*)?) => {
+        $crate::snapshot::insta::assert_snapshot!(
+            $crate::snapshot::render(&$bytes, &[])
+            $(, $($rest)*)?
+        )
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{to_vec, Map};
+
+    #[test]
+    fn test_snapshot_redacts_keys() {
+        let mut map = Map::new();
+        map.insert("id", 12345u32);
+        map.insert("version", 1);
+        let bytes = to_vec(&map).unwrap();
+
+        assert_msgpack_snapshot!(bytes, redact = ["id"], @r#"
+        d8 FixExt16 len=16 type=1 (GenericMap)
+          82 FixMap len=2
+            a2 FixStr len=2 "id"
+            [redacted]
+            a7 FixStr len=7 "version"
+            01 PositiveFixInt 1
+        "#);
+    }
+}
@@ -0,0 +1,396 @@
+//! Low-level access to the msgpack token stream, below the serde layer.
+//!
+//! A [`Tokenizer`] yields one [`Token`] per msgpack item in wire order.
+//! Containers only yield their header, so the elements (or key and value
+//! pairs) that follow belong to it; nothing is buffered or checked for
+//! balance, which makes it suitable for validators, transcoders and
+//! linters that need to see the exact formats.
+//!
+//! A [`TokenWriter`] is the other direction, for hand-written encoders on
+//! hot paths that don't go through serde.
+
+use alloc::format;
+
+use byteorder::BigEndian;
+
+use crate::{
+    error::{DecodeError, DecodeResult, EncodeError, EncodeResult},
+    format::Format,
+    io::{Write, WriteBytesExt},
+    ser::{
+        array::ArraySerializer, map::MapSerializer, write_bin_len, write_int,
+        write_str_len, write_uint,
+    },
+};
+
+/// One msgpack item as it appears on the wire.
+///
+/// Integers written in an unsigned format or as a positive fixint are
+/// [`Token::UInt`], the others [`Token::Int`]. Strings are their raw bytes,
+/// which aren't checked to be UTF-8.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Token<'a> {
+    Nil,
+    Bool(bool),
+    UInt(u64),
+    Int(i64),
+    F32(f32),
+    F64(f64),
+    Str(&'a [u8]),
+    Bin(&'a [u8]),
+    /// Followed by `len` elements.
+    ArrayHeader(u32),
+    /// Followed by `len` keys, each followed by its value.
+    MapHeader(u32),
+    /// A whole ext. The payload of a GenericMap (type 1) is itself a map,
+    /// which a nested [`Tokenizer`] over `data` reads.
+    Ext {
+        ext_type: i8,
+        data: &'a [u8],
+    },
+}
+
+/// Pulls [`Token`]s out of a buffer.
+///
+/// Iterating stops after the first error, which reports the offset of the
+/// offending item.
+#[derive(Debug, Clone)]
+pub struct Tokenizer<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Tokenizer<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, pos: 0 }
+    }
+
+    /// Tokenizes `bytes[pos..end]` while reporting offsets into `bytes`.
+    pub(crate) fn range(bytes: &'a [u8], pos: usize, end: usize) -> Self {
+        Self {
+            bytes: &bytes[..end],
+            pos,
+        }
+    }
+
+    /// Offset of the next token.
+    pub fn offset(&self) -> usize {
+        self.pos
+    }
+
+    /// The bytes that haven't been tokenized yet.
+    pub fn remaining(&self) -> &'a [u8] {
+        &self.bytes[self.pos..]
+    }
+
+    /// Reads the next token, or `None` at the end of the buffer. On error
+    /// the tokenizer doesn't advance.
+    pub fn next_token(&mut self) -> DecodeResult<Option<Token<'a>>> {
+        if self.pos == self.bytes.len() {
+            return Ok(None);
+        }
+        let start = self.pos;
+        let token = self.read_token();
+        if token.is_err() {
+            self.pos = start;
+        }
+        token.map(Some)
+    }
+
+    fn read_token(&mut self) -> DecodeResult<Token<'a>> {
+        let start = self.pos;
+        let format = Format::from_u8(self.take(1)?[0]);
+        Ok(match format {
+            Format::PositiveFixInt(v) => Token::UInt(v as u64),
+            Format::NegativeFixInt(v) => Token::Int(v as i64),
+            Format::Nil => Token::Nil,
+            Format::Reserved => {
+                return Err(DecodeError::Message(format!(
+                    "Found reserved format byte 0xc1 at offset {start}"
+                )))
+            }
+            Format::False => Token::Bool(false),
+            Format::True => Token::Bool(true),
+            Format::Uint8 => Token::UInt(self.uint(1)?),
+            Format::Uint16 => Token::UInt(self.uint(2)?),
+            Format::Uint32 => Token::UInt(self.uint(4)?),
+            Format::Uint64 => Token::UInt(self.uint(8)?),
+            Format::Int8 => Token::Int(self.uint(1)? as i8 as i64),
+            Format::Int16 => Token::Int(self.uint(2)? as i16 as i64),
+            Format::Int32 => Token::Int(self.uint(4)? as i32 as i64),
+            Format::Int64 => Token::Int(self.uint(8)? as i64),
+            Format::Float32 => Token::F32(f32::from_bits(self.uint(4)? as u32)),
+            Format::Float64 => Token::F64(f64::from_bits(self.uint(8)?)),
+            Format::FixStr(len) => Token::Str(self.take(len as usize)?),
+            Format::Str8 => Token::Str(self.sized(1)?),
+            Format::Str16 => Token::Str(self.sized(2)?),
+            Format::Str32 => Token::Str(self.sized(4)?),
+            Format::Bin8 => Token::Bin(self.sized(1)?),
+            Format::Bin16 => Token::Bin(self.sized(2)?),
+            Format::Bin32 => Token::Bin(self.sized(4)?),
+            Format::FixArray(len) => Token::ArrayHeader(len as u32),
+            Format::Array16 => Token::ArrayHeader(self.uint(2)? as u32),
+            Format::Array32 => Token::ArrayHeader(self.uint(4)? as u32),
+            Format::FixMap(len) => Token::MapHeader(len as u32),
+            Format::Map16 => Token::MapHeader(self.uint(2)? as u32),
+            Format::Map32 => Token::MapHeader(self.uint(4)? as u32),
+            Format::FixExt1 => self.ext(1)?,
+            Format::FixExt2 => self.ext(2)?,
+            Format::FixExt4 => self.ext(4)?,
+            Format::FixExt8 => self.ext(8)?,
+            Format::FixExt16 => self.ext(16)?,
+            Format::Ext8 => {
+                let len = self.uint(1)?;
+                self.ext(len as usize)?
+            }
+            Format::Ext16 => {
+                let len = self.uint(2)?;
+                self.ext(len as usize)?
+            }
+            Format::Ext32 => {
+                let len = self.uint(4)?;
+                self.ext(len as usize)?
+            }
+        })
+    }
+
+    fn take(&mut self, len: usize) -> DecodeResult<&'a [u8]> {
+        let available = self.bytes.len() - self.pos;
+        if len > available {
+            return Err(DecodeError::Message(format!(
+                "Truncated input at offset {}: needs {len} bytes, \
+                 {available} available",
+                self.pos
+            )));
+        }
+        let bytes = &self.bytes[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn uint(&mut self, len: usize) -> DecodeResult<u64> {
+        Ok(self
+            .take(len)?
+            .iter()
+            .fold(0, |n, byte| n << 8 | *byte as u64))
+    }
+
+    /// A payload preceded by its `len_size`-byte length.
+    fn sized(&mut self, len_size: usize) -> DecodeResult<&'a [u8]> {
+        let len = self.uint(len_size)?;
+        self.take(len as usize)
+    }
+
+    fn ext(&mut self, len: usize) -> DecodeResult<Token<'a>> {
+        let ext_type = self.take(1)?[0] as i8;
+        let data = self.take(len)?;
+        Ok(Token::Ext { ext_type, data })
+    }
+}
+
+impl<'a> Iterator for Tokenizer<'a> {
+    type Item = DecodeResult<Token<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.next_token() {
+            Ok(token) => token.map(Ok),
+            Err(e) => {
+                self.pos = self.bytes.len();
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+/// Pushes msgpack items to a writer, each in the smallest format that
+/// holds it.
+///
+/// Like the [`Tokenizer`], containers are only a header: writing the
+/// elements (or key and value pairs) that follow is up to the caller.
+#[derive(Debug)]
+pub struct TokenWriter<W> {
+    writer: W,
+}
+
+impl<W: Write> TokenWriter<W> {
+    pub fn new(writer: W) -> Self {
+        Self { writer }
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    pub fn write_nil(&mut self) -> EncodeResult<()> {
+        Ok(Format::set_format(&mut self.writer, Format::Nil)?)
+    }
+
+    pub fn write_bool(&mut self, v: bool) -> EncodeResult<()> {
+        let format = if v { Format::True } else { Format::False };
+        Ok(Format::set_format(&mut self.writer, format)?)
+    }
+
+    pub fn write_uint(&mut self, v: u64) -> EncodeResult<()> {
+        write_uint(&mut self.writer, v)
+    }
+
+    pub fn write_int(&mut self, v: i64) -> EncodeResult<()> {
+        write_int(&mut self.writer, v)
+    }
+
+    pub fn write_f32(&mut self, v: f32) -> EncodeResult<()> {
+        Format::set_format(&mut self.writer, Format::Float32)?;
+        Ok(self.writer.write_f32::<BigEndian>(v)?)
+    }
+
+    pub fn write_f64(&mut self, v: f64) -> EncodeResult<()> {
+        Format::set_format(&mut self.writer, Format::Float64)?;
+        Ok(self.writer.write_f64::<BigEndian>(v)?)
+    }
+
+    pub fn write_str(&mut self, v: &str) -> EncodeResult<()> {
+        self.write_str_bytes(v.as_bytes())
+    }
+
+    /// Writes a string from raw bytes, which aren't checked to be UTF-8.
+    pub fn write_str_bytes(&mut self, v: &[u8]) -> EncodeResult<()> {
+        write_str_len(&mut self.writer, len(v.len())?)?;
+        Ok(self.writer.write_all(v)?)
+    }
+
+    pub fn write_bin(&mut self, v: &[u8]) -> EncodeResult<()> {
+        write_bin_len(&mut self.writer, len(v.len())?)?;
+        Ok(self.writer.write_all(v)?)
+    }
+
+    /// Must be followed by `len` elements.
+    pub fn write_array_header(&mut self, len: u32) -> EncodeResult<()> {
+        ArraySerializer::write_array_length(&mut self.writer, &len)
+    }
+
+    /// Must be followed by `len` keys, each followed by its value.
+    pub fn write_map_header(&mut self, len: u32) -> EncodeResult<()> {
+        MapSerializer::write_map_length(&mut self.writer, &len)
+    }
+
+    pub fn write_ext(&mut self, ext_type: i8, data: &[u8]) -> EncodeResult<()> {
+        MapSerializer::write_ext_len(
+            &mut self.writer,
+            len(data.len())? as usize,
+        )?;
+        self.writer.write_i8(ext_type)?;
+        Ok(self.writer.write_all(data)?)
+    }
+
+    /// Writes a token read by a [`Tokenizer`], in the smallest format that
+    /// holds it rather than its original one.
+    pub fn write_token(&mut self, token: Token) -> EncodeResult<()> {
+        match token {
+            Token::Nil => self.write_nil(),
+            Token::Bool(v) => self.write_bool(v),
+            Token::UInt(v) => self.write_uint(v),
+            Token::Int(v) => self.write_int(v),
+            Token::F32(v) => self.write_f32(v),
+            Token::F64(v) => self.write_f64(v),
+            Token::Str(v) => self.write_str_bytes(v),
+            Token::Bin(v) => self.write_bin(v),
+            Token::ArrayHeader(len) => self.write_array_header(len),
+            Token::MapHeader(len) => self.write_map_header(len),
+            Token::Ext { ext_type, data } => self.write_ext(ext_type, data),
+        }
+    }
+}
+
+/// Payload lengths are at most `u32::MAX` bytes.
+fn len(len: usize) -> EncodeResult<u32> {
+    u32::try_from(len).map_err(|_| {
+        EncodeError::Message(format!("{len} bytes exceeds the msgpack maximum"))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Token, TokenWriter, Tokenizer};
+    use crate::{to_vec, to_vec_with_plain_maps, Map, Value};
+
+    #[test]
+    fn test_tokenize_document() {
+        let mut map = Map::new();
+        map.insert("a", (-3i8, 300u16, 0.5f64, serde_bytes::Bytes::new(&[7])));
+        let bytes = to_vec(&map).unwrap();
+
+        let tokens = Tokenizer::new(&bytes).collect::<Result<Vec<_>, _>>();
+        let tokens = tokens.unwrap();
+        let [Token::Ext { ext_type: 1, data }] = tokens[..] else {
+            panic!("expected a GenericMap ext, found {tokens:?}");
+        };
+        assert_eq!(
+            vec![
+                Token::MapHeader(1),
+                Token::Str(b"a"),
+                Token::ArrayHeader(4),
+                Token::Int(-3),
+                Token::UInt(300),
+                Token::F64(0.5),
+                Token::Bin(&[7]),
+            ],
+            Tokenizer::new(data).collect::<Result<Vec<_>, _>>().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_tokenize_errors() {
+        let mut tokenizer = Tokenizer::new(&[0x92, 0xcd, 0x01]);
+        assert_eq!(
+            Some(Token::ArrayHeader(2)),
+            tokenizer.next_token().unwrap()
+        );
+        let err = tokenizer.next_token().unwrap_err();
+        assert!(err.to_string().contains("offset 2: needs 2 bytes"));
+        assert_eq!(1, tokenizer.offset());
+        assert_eq!([0xcd, 0x01], tokenizer.remaining());
+
+        let mut tokenizer = Tokenizer::new(&[0xc0, 0xc1, 0xc0]);
+        assert!(matches!(tokenizer.next(), Some(Ok(Token::Nil))));
+        assert!(matches!(tokenizer.next(), Some(Err(_))));
+        assert!(tokenizer.next().is_none());
+    }
+
+    #[test]
+    fn test_token_writer() {
+        let mut writer = TokenWriter::new(vec![]);
+        writer.write_map_header(2).unwrap();
+        writer.write_str("a").unwrap();
+        writer.write_array_header(3).unwrap();
+        writer.write_int(-3).unwrap();
+        writer.write_uint(300).unwrap();
+        writer.write_nil().unwrap();
+        writer.write_str("b").unwrap();
+        writer.write_ext(5, &[1, 2]).unwrap();
+        let bytes = writer.into_inner();
+
+        let mut map = Map::new();
+        map.insert(
+            "a",
+            Value::Array(vec![
+                Value::Integer(-3),
+                Value::Integer(300),
+                Value::Nil,
+            ]),
+        );
+        map.insert("b", Value::Ext(5, vec![1, 2]));
+        let expected = to_vec_with_plain_maps(&map).unwrap();
+        assert_eq!(expected, bytes);
+
+        // Tokens pass straight through
+        let mut copy = TokenWriter::new(vec![]);
+        for token in Tokenizer::new(&bytes) {
+            copy.write_token(token.unwrap()).unwrap();
+        }
+        assert_eq!(bytes, *copy.get_ref());
+    }
+}
@@ -0,0 +1,132 @@
+//! Encoding to and decoding from tokio's [`AsyncWrite`] and [`AsyncRead`].
+//!
+//! Values are decoded with an [`IncrementalDecoder`], which suspends
+//! whenever the input runs out, so a message needn't have fully arrived
+//! before decoding starts, and its length needn't be sent ahead of it.
+//!
+//! [`IncrementalDecoder`]: crate::IncrementalDecoder
+
+use ::tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{
+    de::AsyncReadState,
+    error::{DecodeResult, EncodeResult},
+    to_vec, DeserializerConfig,
+};
+
+/// Writes `value` to `writer`. The writer isn't flushed.
+pub async fn to_writer_async<W, T>(
+    writer: &mut W,
+    value: &T,
+) -> EncodeResult<()>
+where
+    W: AsyncWrite + Unpin + ?Sized,
+    T: Serialize,
+{
+    let bytes = to_vec(value)?;
+    writer.write_all(&bytes).await?;
+    Ok(())
+}
+
+/// Reads one value from `reader`.
+///
+/// Only the bytes of that value are read, so that the next one can be read
+/// from the same reader; as they're read in small pieces, unbuffered
+/// readers such as sockets are best wrapped in a `BufReader`.
+pub async fn from_reader_async<R, T>(reader: &mut R) -> DecodeResult<T>
+where
+    R: AsyncRead + Unpin + ?Sized,
+    T: DeserializeOwned,
+{
+    from_reader_async_with_config(reader, DeserializerConfig::default()).await
+}
+
+pub async fn from_reader_async_with_config<R, T>(
+    reader: &mut R,
+    config: DeserializerConfig,
+) -> DecodeResult<T>
+where
+    R: AsyncRead + Unpin + ?Sized,
+    T: DeserializeOwned,
+{
+    let mut state = AsyncReadState::new(config);
+    loop {
+        if let Some(value) = state.decode()? {
+            return Ok(value);
+        }
+        let read = reader.read(state.spare()).await?;
+        state.filled(read)?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ::tokio::io::{duplex, AsyncWriteExt};
+    use serde_derive::{Deserialize, Serialize};
+
+    use super::{from_reader_async, from_reader_async_with_config};
+    use crate::{to_vec, DecodeError, DeserializerConfig};
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Invocation {
+        method: String,
+        args: Vec<Vec<u8>>,
+    }
+
+    #[::tokio::test]
+    async fn test_read_values_as_they_arrive() {
+        let first = Invocation {
+            method: "run".to_string(),
+            args: vec![vec![1; 300], vec![]],
+        };
+        let second = Invocation {
+            method: "stop".to_string(),
+            args: vec![],
+        };
+
+        // A pipe holding a few bytes at a time, so that the reader suspends
+        // inside headers and payloads alike
+        let (mut client, mut server) = duplex(3);
+        let write = async {
+            super::to_writer_async(&mut client, &first).await.unwrap();
+            super::to_writer_async(&mut client, &second).await.unwrap();
+            client.shutdown().await.unwrap();
+        };
+        let read = async {
+            let values: (Invocation, Invocation) = (
+                from_reader_async(&mut server).await.unwrap(),
+                from_reader_async(&mut server).await.unwrap(),
+            );
+            let end = from_reader_async::<_, u8>(&mut server).await;
+            (values, end)
+        };
+        let ((), (values, end)) = ::tokio::join!(write, read);
+        assert_eq!((first, second), values);
+        assert!(matches!(end, Err(DecodeError::Eof { offset: 0, .. })));
+    }
+
+    #[::tokio::test]
+    async fn test_read_truncated_and_malformed_values() {
+        let bytes = to_vec(&vec!["a"; 20]).unwrap();
+        let err = from_reader_async::<_, Vec<String>>(&mut &bytes[..30])
+            .await
+            .unwrap_err();
+        assert!(matches!(err, DecodeError::Eof { offset: 30, .. }), "{err}");
+
+        // A reserved byte inside an array
+        let err = from_reader_async::<_, Vec<u8>>(&mut &[0x92, 1, 0xc1][..])
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("0xc1"), "{err}");
+
+        let nested = [0x91, 0x91, 0x91, 0x90];
+        let config = DeserializerConfig::new().max_depth(2);
+        assert!(from_reader_async_with_config::<_, crate::Value>(
+            &mut &nested[..],
+            config,
+        )
+        .await
+        .is_err());
+    }
+}
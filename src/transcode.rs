@@ -0,0 +1,133 @@
+//! Streaming conversion between msgpack and other serde formats.
+//!
+//! [`transcode`] pipes any deserializer straight into any serializer, so a
+//! value is written out as it's read instead of first being collected into
+//! a [`Value`](crate::Value). [`msgpack_to_json`] and [`json_to_msgpack`]
+//! apply it to this crate's [`Deserializer`] and [`Serializer`] and
+//! `serde_json`'s.
+//!
+//! Unlike [`msgpack_to_json_string`], nothing is tagged: bytes become JSON
+//! arrays of numbers, as they do with `serde_json::to_writer`, and exts
+//! `[type, [data...]]` arrays, so neither converts back. Map keys are
+//! written as JSON strings, which fails for arrays, maps and the like.
+//!
+//! [`msgpack_to_json_string`]: crate::msgpack_to_json_string
+
+use crate::{
+    error::{DecodeError, DecodeResult, Result},
+    io, Deserializer, Serializer,
+};
+
+/// Passes the value `deserializer` reads to `serializer` as it's read,
+/// element by element, and returns what the serializer did.
+///
+/// Errors from either side are reported as `S::Error`.
+pub fn transcode<'de, D, S>(
+    deserializer: D,
+    serializer: S,
+) -> core::result::Result<S::Ok, S::Error>
+where
+    D: serde::Deserializer<'de>,
+    S: serde::Serializer,
+{
+    serde_transcode::transcode(deserializer, serializer)
+}
+
+/// Writes the msgpack value at the start of `bytes` to `writer` as compact
+/// JSON.
+pub fn msgpack_to_json<W: io::Write>(
+    bytes: &[u8],
+    writer: W,
+) -> DecodeResult<()> {
+    let mut deserializer = Deserializer::from_slice(bytes);
+    let mut serializer = serde_json::Serializer::new(writer);
+    transcode(&mut deserializer, &mut serializer)?;
+    Ok(())
+}
+
+/// Encodes the JSON document `json` as msgpack, with objects wrapped in the
+/// GenericMap ext.
+pub fn json_to_msgpack(json: &str) -> Result<Vec<u8>> {
+    let mut deserializer = serde_json::Deserializer::from_str(json);
+    let mut serializer = Serializer::default();
+    transcode(&mut deserializer, &mut serializer)?;
+    deserializer.end().map_err(DecodeError::from)?;
+    Ok(serializer.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_bytes::ByteBuf;
+
+    use super::{json_to_msgpack, msgpack_to_json, transcode};
+    use crate::{
+        to_vec, to_vec_with_config, wrappers::timestamp::Timestamp, Map,
+        MapEncoding, SerializerConfig, Value,
+    };
+
+    fn to_json(bytes: &[u8]) -> String {
+        let mut json = Vec::new();
+        msgpack_to_json(bytes, &mut json).unwrap();
+        String::from_utf8(json).unwrap()
+    }
+
+    fn json_string_to_msgpack(
+        json: &str,
+        map_encoding: MapEncoding,
+    ) -> Vec<u8> {
+        let json: serde_json::Value = serde_json::from_str(json).unwrap();
+        let config = SerializerConfig::default().map_encoding(map_encoding);
+        to_vec_with_config(&Value::try_from(json).unwrap(), config).unwrap()
+    }
+
+    #[test]
+    fn test_transcode_msgpack_to_json() {
+        let json =
+            r#"{"a":[1,-2,1.5,true,null,"s",{}],"b":18446744073709551615}"#;
+        let ext = json_to_msgpack(json).unwrap();
+        assert_eq!(json_string_to_msgpack(json, MapEncoding::Ext), ext);
+        assert_eq!(json, to_json(&ext));
+
+        // Plain maps are written as objects too
+        let plain = json_string_to_msgpack(json, MapEncoding::Plain);
+        assert_ne!(plain, ext);
+        assert_eq!(json, to_json(&plain));
+
+        // Bytes become arrays, exts `[type, [data...]]` and integer keys
+        // strings
+        let mut map = Map::new();
+        map.insert(1, ByteBuf::from(vec![7, 8]));
+        assert_eq!(r#"{"1":[7,8]}"#, to_json(&to_vec(&map).unwrap()));
+
+        let timestamp = Timestamp::new(60, 0).unwrap();
+        assert_eq!("[-1,[0,0,0,60]]", to_json(&to_vec(&timestamp).unwrap()));
+
+        // Array keys have no JSON form
+        let map = Value::Map(vec![(Value::Array(vec![]), Value::Nil)]);
+        let mut json = Vec::new();
+        assert!(msgpack_to_json(&to_vec(&map).unwrap(), &mut json).is_err());
+    }
+
+    #[test]
+    fn test_transcode_json_to_msgpack() {
+        assert!(json_to_msgpack("[1] 2").is_err());
+        assert!(json_to_msgpack("[1").is_err());
+
+        // Numbers come out as when serializing a `serde_json::Value`, which
+        // keeps every digit with `arbitrary_precision`
+        let precise = "[18446744073709551617,0.1000000000000000000000000001]";
+        let value: serde_json::Value = serde_json::from_str(precise).unwrap();
+        assert_eq!(to_vec(&value).unwrap(), json_to_msgpack(precise).unwrap());
+
+        // Any serializer can be the target, e.g. one writing pretty JSON
+        let bytes = json_to_msgpack(r#"{"k":[1]}"#).unwrap();
+        let mut deserializer = crate::Deserializer::from_slice(&bytes);
+        let mut pretty = Vec::new();
+        let mut serializer = serde_json::Serializer::pretty(&mut pretty);
+        transcode(&mut deserializer, &mut serializer).unwrap();
+        assert_eq!(
+            "{\n  \"k\": [\n    1\n  ]\n}",
+            String::from_utf8(pretty).unwrap()
+        );
+    }
+}
@@ -0,0 +1,137 @@
+//! Structural validation of encoded buffers.
+
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec,
+};
+use core::fmt;
+
+use crate::token::{Token, Tokenizer};
+
+/// Where and why a buffer isn't valid msgpack.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationError {
+    /// Offset of the first item that couldn't be read, or of the end of the
+    /// buffer when a container was left unfinished.
+    pub offset: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid msgpack at offset {}: {}",
+            self.offset, self.message
+        )
+    }
+}
+
+impl core::error::Error for ValidationError {}
+
+/// Checks that `bytes` holds exactly one well-formed msgpack value: no
+/// truncated lengths or payloads, no reserved `0xc1` byte, containers
+/// holding as many items as their headers announce and nothing after the
+/// value. GenericMap ext payloads must hold exactly one map, and are
+/// checked the same way.
+///
+/// Strings aren't checked to be UTF-8. The walk doesn't recurse, so deeply
+/// nested input can't overflow the stack.
+pub fn validate(bytes: &[u8]) -> Result<(), ValidationError> {
+    // Each region is the whole buffer or a GenericMap payload, with the
+    // stack of item counts its open containers are still waiting for.
+    let mut regions = vec![(Tokenizer::new(bytes), vec![1u64])];
+
+    while let Some((tokens, pending)) = regions.last_mut() {
+        let Some(remaining) = pending.last_mut() else {
+            if !tokens.remaining().is_empty() {
+                return Err(error(
+                    tokens.offset(),
+                    format!("{} trailing bytes", tokens.remaining().len()),
+                ));
+            }
+            regions.pop();
+            continue;
+        };
+        if *remaining == 0 {
+            pending.pop();
+            continue;
+        }
+        *remaining -= 1;
+
+        let offset = tokens.offset();
+        let token = match tokens.next_token() {
+            Ok(Some(token)) => token,
+            Ok(None) => {
+                let missing: u64 = pending.iter().sum::<u64>() + 1;
+                return Err(error(
+                    offset,
+                    format!("input ended with {missing} items missing"),
+                ));
+            }
+            Err(e) => return Err(error(offset, e.to_string())),
+        };
+        match token {
+            Token::ArrayHeader(len) => pending.push(len as u64),
+            Token::MapHeader(len) => pending.push(len as u64 * 2),
+            Token::Ext { ext_type: 1, data } => {
+                let end = tokens.offset();
+                let start = end - data.len();
+                let mut payload = Tokenizer::range(bytes, start, end);
+                if !matches!(
+                    payload.next_token(),
+                    Ok(Some(Token::MapHeader(_)))
+                ) {
+                    return Err(error(
+                        start,
+                        "GenericMap ext doesn't hold a map".to_string(),
+                    ));
+                }
+                regions.push((Tokenizer::range(bytes, start, end), vec![1]));
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn error(offset: usize, message: String) -> ValidationError {
+    ValidationError { offset, message }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::validate;
+    use crate::{to_vec, Map};
+
+    #[test]
+    fn test_validate() {
+        let mut map = Map::new();
+        map.insert("a", vec![Map::from([(1u8, "x")])]);
+        let bytes = to_vec(&map).unwrap();
+        assert_eq!(Ok(()), validate(&bytes));
+
+        let cases: [(&[u8], usize, &str); 6] = [
+            (&[0x92, 0x01], 2, "1 items missing"),
+            (&[0x81, 0xa1, b'a', 0x93], 4, "3 items missing"),
+            (&[0x92, 0xc1, 0x01], 1, "reserved"),
+            (&[0xda, 0x00, 0x05, b'a'], 0, "needs 5 bytes"),
+            (&[0x01, 0x02], 1, "1 trailing bytes"),
+            (&[0xd4, 0x01, 0x01], 2, "doesn't hold a map"),
+        ];
+        for (bytes, offset, message) in cases {
+            let err = validate(bytes).unwrap_err();
+            assert_eq!(offset, err.offset, "{err}");
+            assert!(err.message.contains(message), "{err}");
+        }
+
+        // A GenericMap payload holding more than its map
+        let err = validate(&[0xd5, 0x01, 0x80, 0xc0]).unwrap_err();
+        assert_eq!((3, "1 trailing bytes"), (err.offset, &*err.message));
+
+        // Deep nesting is walked without recursion
+        let deep = [[0x91].repeat(100_000), vec![0xc0]].concat();
+        assert_eq!(Ok(()), validate(&deep));
+    }
+}
@@ -0,0 +1,356 @@
+//! Conversions between [`Value`], `serde_json::Value` and JSON text.
+
+#[cfg(feature = "base64")]
+use alloc::string::String;
+use alloc::{format, string::ToString, vec, vec::Vec};
+
+#[cfg(feature = "base64")]
+use base64::{engine::general_purpose::STANDARD, Engine};
+#[cfg(feature = "base64")]
+use serde_json::json;
+use serde_json::{Map as JsonMap, Number, Value as Json};
+
+use super::Value;
+use crate::error::DecodeError;
+#[cfg(feature = "base64")]
+use crate::{
+    error::Error,
+    from_slice, to_vec_with_config,
+    wrappers::timestamp::{Timestamp, TIMESTAMP_EXT_TYPE},
+    MapEncoding, SerializerConfig,
+};
+
+/// Renders a msgpack value as JSON, the way `serde_json::to_value` would
+/// where it can: bytes become arrays of numbers, exts `[type, [data...]]`
+/// arrays and non-finite floats `null`.
+///
+/// Unlike `serde_json::to_value` this never fails: map keys that aren't
+/// strings are replaced by their JSON text, e.g. `1` becomes `"1"` and
+/// `[1, 2]` becomes `"[1,2]"`.
+impl From<Value> for Json {
+    fn from(value: Value) -> Self {
+        render(value, false)
+    }
+}
+
+/// Decodes msgpack bytes and renders them as pretty-printed JSON for
+/// debugging, e.g. a payload another client fails to read.
+///
+/// Values JSON can't hold are written as tagged objects: bytes as
+/// `{"$bytes": "<base64>"}` and exts as `{"$ext": type, "data":
+/// "<base64>"}`, plus the decoded time for timestamp exts. GenericMaps
+/// render as objects and non-string map keys as their JSON text.
+#[cfg(feature = "base64")]
+pub fn msgpack_to_json_string(bytes: &[u8]) -> Result<String, DecodeError> {
+    let value: Value = from_slice(bytes)?;
+    Ok(serde_json::to_string_pretty(&render(value, true))?)
+}
+
+#[cfg_attr(not(feature = "base64"), allow(clippy::only_used_in_recursion))]
+fn render(value: Value, debug: bool) -> Json {
+    match value {
+        Value::Nil => Json::Null,
+        Value::Bool(v) => Json::Bool(v),
+        Value::Integer(v) => integer(v),
+        Value::Float(v) => Number::from_f64(v).map_or(Json::Null, Json::Number),
+        Value::String(v) => Json::String(v),
+        #[cfg(feature = "base64")]
+        Value::Bytes(v) if debug => json!({ "$bytes": base64(&v) }),
+        Value::Bytes(v) => bytes(v),
+        Value::Array(elements) => Json::Array(
+            elements.into_iter().map(|v| render(v, debug)).collect(),
+        ),
+        Value::Map(entries) => {
+            let mut map = JsonMap::with_capacity(entries.len());
+            for (key, value) in entries {
+                let key = match render(key, debug) {
+                    Json::String(key) => key,
+                    key => key.to_string(),
+                };
+                map.insert(key, render(value, debug));
+            }
+            Json::Object(map)
+        }
+        #[cfg(feature = "base64")]
+        Value::Ext(ext_type, data) if debug => {
+            let mut ext = json!({ "$ext": ext_type, "data": base64(&data) });
+            if ext_type == TIMESTAMP_EXT_TYPE {
+                if let Ok(t) = Timestamp::from_ext_data(&data) {
+                    ext["timestamp"] = Json::String(format!(
+                        "{}.{:09}s",
+                        t.seconds(),
+                        t.nanoseconds()
+                    ));
+                }
+            }
+            ext
+        }
+        Value::Ext(ext_type, data) => {
+            Json::Array(vec![integer(ext_type as i128), bytes(data)])
+        }
+    }
+}
+
+/// Builds a msgpack value from JSON, e.g. a test fixture.
+///
+/// Fails only for numbers that fit neither an `i128` nor a finite `f64`,
+/// which JSON with `arbitrary_precision` can hold.
+impl TryFrom<Json> for Value {
+    type Error = DecodeError;
+
+    fn try_from(json: Json) -> Result<Self, DecodeError> {
+        build(json, false)
+    }
+}
+
+/// Parses a JSON document and encodes it as msgpack, e.g. to author
+/// fixtures as JSON. Objects are written as maps with `map_encoding`.
+///
+/// The tagged `{"$bytes": ...}` and `{"$ext": ..., "data": ...}` objects
+/// written by [`msgpack_to_json_string`] become bins and exts again, so its
+/// output converts back to the original value.
+#[cfg(feature = "base64")]
+pub fn json_string_to_msgpack(
+    json: &str,
+    map_encoding: MapEncoding,
+) -> Result<Vec<u8>, Error> {
+    let json: Json = serde_json::from_str(json).map_err(DecodeError::from)?;
+    let value = build(json, true)?;
+    Ok(to_vec_with_config(
+        &value,
+        SerializerConfig::default().map_encoding(map_encoding),
+    )?)
+}
+
+#[cfg_attr(not(feature = "base64"), allow(clippy::only_used_in_recursion))]
+fn build(json: Json, tagged: bool) -> Result<Value, DecodeError> {
+    Ok(match json {
+        Json::Null => Value::Nil,
+        Json::Bool(v) => Value::Bool(v),
+        Json::Number(number) => number_value(&number)?,
+        Json::String(v) => Value::String(v),
+        Json::Array(elements) => Value::Array(
+            elements
+                .into_iter()
+                .map(|v| build(v, tagged))
+                .collect::<Result<_, _>>()?,
+        ),
+        Json::Object(map) => {
+            #[cfg(feature = "base64")]
+            if tagged {
+                if let Some(value) = tagged_value(&map)? {
+                    return Ok(value);
+                }
+            }
+            Value::Map(
+                map.into_iter()
+                    .map(|(key, value)| {
+                        Ok((Value::String(key), build(value, tagged)?))
+                    })
+                    .collect::<Result<_, DecodeError>>()?,
+            )
+        }
+    })
+}
+
+/// Reads the objects [`msgpack_to_json_string`] writes for bytes and exts,
+/// which hold no other keys than those it writes. Any other object is a
+/// map, even one with a `$bytes` or `$ext` key.
+#[cfg(feature = "base64")]
+fn tagged_value(
+    map: &JsonMap<String, Json>,
+) -> Result<Option<Value>, DecodeError> {
+    let data = |json: Option<&Json>| match json {
+        Some(Json::String(data)) => from_base64(data),
+        _ => Err(DecodeError::Message(
+            "tagged value has no base64 data string".to_string(),
+        )),
+    };
+    if map.len() == 1 && map.contains_key("$bytes") {
+        return Ok(Some(Value::Bytes(data(map.get("$bytes"))?)));
+    }
+    // Timestamps also carry their decoded time, which isn't read back
+    let ext_keys = ["$ext", "data", "timestamp"];
+    if map.contains_key("$ext")
+        && map.contains_key("data")
+        && map.keys().all(|k| ext_keys.contains(&k.as_str()))
+    {
+        let ext_type = &map["$ext"];
+        let ext_type = ext_type
+            .as_i64()
+            .and_then(|v| i8::try_from(v).ok())
+            .ok_or_else(|| {
+                DecodeError::Message(format!("invalid ext type {ext_type}"))
+            })?;
+        return Ok(Some(Value::Ext(ext_type, data(map.get("data"))?)));
+    }
+    Ok(None)
+}
+
+fn integer(v: i128) -> Json {
+    if let Ok(v) = i64::try_from(v) {
+        Json::from(v)
+    } else if let Ok(v) = u64::try_from(v) {
+        Json::from(v)
+    } else {
+        // Exact with `arbitrary_precision`, the nearest float otherwise.
+        v.to_string().parse().map_or(Json::Null, Json::Number)
+    }
+}
+
+fn bytes(v: Vec<u8>) -> Json {
+    Json::Array(v.into_iter().map(Json::from).collect())
+}
+
+/// Standard, padded base64, as written by `wrappers::base64_bytes`.
+#[cfg(feature = "base64")]
+fn base64(data: &[u8]) -> String {
+    STANDARD.encode(data)
+}
+
+#[cfg(feature = "base64")]
+fn from_base64(text: &str) -> Result<Vec<u8>, DecodeError> {
+    STANDARD.decode(text).map_err(|e| {
+        DecodeError::Message(format!("invalid base64 `{text}`: {e}"))
+    })
+}
+
+fn number_value(number: &Number) -> Result<Value, DecodeError> {
+    if let Some(v) = number.as_i64() {
+        return Ok(Value::Integer(v as i128));
+    }
+    if let Some(v) = number.as_u64() {
+        return Ok(Value::Integer(v as i128));
+    }
+    let text = number.to_string();
+    if let Ok(v) = text.parse::<i128>() {
+        return Ok(Value::Integer(v));
+    }
+    match number.as_f64() {
+        Some(v) if v.is_finite() => Ok(Value::Float(v)),
+        _ => Err(DecodeError::Message(format!(
+            "JSON number {text} can't be represented in msgpack"
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value as Json};
+
+    use crate::Value;
+
+    #[test]
+    fn test_value_to_json() {
+        let value = Value::Map(vec![
+            (Value::String("a".to_string()), Value::Integer(-1)),
+            (Value::Integer(2), Value::Bytes(vec![1, 2])),
+            (
+                Value::Array(vec![Value::Bool(true)]),
+                Value::Ext(-1, vec![7]),
+            ),
+            (Value::String("nan".to_string()), Value::Float(f64::NAN)),
+        ]);
+        assert_eq!(
+            json!({
+                "a": -1,
+                "2": [1, 2],
+                "[true]": [-1, [7]],
+                "nan": null,
+            }),
+            Json::from(value)
+        );
+    }
+
+    #[cfg(feature = "base64")]
+    #[test]
+    fn test_msgpack_to_json_string() {
+        use crate::{to_vec, wrappers::timestamp::Timestamp, Map};
+
+        let mut map = Map::new();
+        map.insert(1u8, Value::Bytes(b"hello".to_vec()));
+        map.insert(2, Value::Ext(5, vec![0xff]));
+        let bytes = to_vec(&(map, Timestamp::new(1, 5).unwrap())).unwrap();
+
+        let rendered = super::msgpack_to_json_string(&bytes).unwrap();
+        let expected = json!([
+            {
+                "1": {"$bytes": "aGVsbG8="},
+                "2": {"$ext": 5, "data": "/w=="},
+            },
+            {"$ext": -1, "data": "AAAAFAAAAAE=", "timestamp": "1.000000005s"},
+        ]);
+        assert_eq!(expected, serde_json::from_str::<Json>(&rendered).unwrap());
+        assert!(rendered.contains('\n'));
+        assert!(super::msgpack_to_json_string(&[0x92, 0x01]).is_err());
+
+        assert_eq!("", super::base64(b""));
+        assert_eq!("Zg==", super::base64(b"f"));
+        assert_eq!("Zm8=", super::base64(b"fo"));
+        assert_eq!("Zm9v", super::base64(b"foo"));
+    }
+
+    #[cfg(feature = "base64")]
+    #[test]
+    fn test_json_string_to_msgpack() {
+        use super::{json_string_to_msgpack, msgpack_to_json_string};
+        use crate::MapEncoding;
+
+        let json = r#"{"a": [1, null], "b": {"$bytes": "AQI="}}"#;
+        let plain = json_string_to_msgpack(json, MapEncoding::Plain).unwrap();
+        assert_eq!(
+            vec![
+                0x82, 0xa1, b'a', 0x92, 0x01, 0xc0, 0xa1, b'b', 0xc4, 0x02,
+                0x01, 0x02
+            ],
+            plain
+        );
+        let ext = json_string_to_msgpack(json, MapEncoding::Ext).unwrap();
+        assert_eq!([0xc7, 12, 0x01], ext[..3]);
+        assert_eq!(plain[..], ext[3..]);
+
+        // The debug rendering converts back to the same bytes
+        let bytes = [0x92, 0xd4, 0x05, 0xff, 0xc4, 0x01, 0x07];
+        let rendered = msgpack_to_json_string(&bytes).unwrap();
+        assert_eq!(
+            bytes.to_vec(),
+            json_string_to_msgpack(&rendered, MapEncoding::Plain).unwrap()
+        );
+
+        for invalid in [r#"{"$bytes": "A"}"#, r#"{"$ext": 300, "data": ""}"#] {
+            assert!(
+                json_string_to_msgpack(invalid, MapEncoding::Plain).is_err()
+            );
+        }
+        assert!(json_string_to_msgpack("{", MapEncoding::Plain).is_err());
+
+        // Objects with other keys besides the tags are plain maps
+        let json = r#"{"$ext": 1, "other": 2}"#;
+        let map = json_string_to_msgpack(json, MapEncoding::Plain).unwrap();
+        assert_eq!([0x82, 0xa4, b'$', b'e', b'x', b't', 0x01], map[..7]);
+        let json = r#"{"$ext": 1, "data": "", "other": 2}"#;
+        let map = json_string_to_msgpack(json, MapEncoding::Plain).unwrap();
+        assert_eq!(0x83, map[0]);
+        let json = r#"{"$ext": 5, "data": "/w==", "timestamp": "-"}"#;
+        let ext = json_string_to_msgpack(json, MapEncoding::Plain).unwrap();
+        assert_eq!(vec![0xd4, 0x05, 0xff], ext);
+        assert_eq!(b"fo".to_vec(), super::from_base64("Zm8=").unwrap());
+    }
+
+    #[test]
+    fn test_json_to_value() {
+        let json = json!({"name": "foo", "list": [1, -2, 0.5, null]});
+        let value = Value::try_from(json.clone()).unwrap();
+        assert_eq!(Some("foo"), value.get("name").and_then(Value::as_str));
+        assert_eq!(
+            Some(&Value::Array(vec![
+                Value::Integer(1),
+                Value::Integer(-2),
+                Value::Float(0.5),
+                Value::Nil,
+            ])),
+            value.get("list")
+        );
+        assert_eq!(json, Json::from(value));
+    }
+}
@@ -0,0 +1,421 @@
+mod json;
+pub mod preserving;
+pub mod schema;
+
+#[cfg(feature = "base64")]
+pub use json::{json_string_to_msgpack, msgpack_to_json_string};
+
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{fmt, str::FromStr};
+
+use bigdecimal::BigDecimal;
+use num_bigint::BigInt;
+use serde::{
+    de::{self, Deserialize, MapAccess, SeqAccess, Visitor},
+    ser::{self, Serialize, SerializeMap, SerializeSeq},
+};
+
+use crate::{error::DecodeError, BigIntWrapper, JSONString};
+
+/// Newtype name through which raw exts ([`Value::Ext`], timestamps and
+/// `ExtValue`s) pass as a `(type, data)` tuple, in both directions.
+pub(crate) const EXT_TOKEN: &str = "$polywrap_msgpack::Ext";
+
+/// Caps a collection's declared length before it's used to preallocate
+/// `T`s, to about 1 MiB worth, like serde does for its own collections:
+/// the declared length is untrusted input.
+pub(crate) fn cautious_capacity<T>(size_hint: Option<usize>) -> usize {
+    const MAX_PREALLOC_BYTES: usize = 1024 * 1024;
+    let max = MAX_PREALLOC_BYTES / core::mem::size_of::<T>().max(1);
+    size_hint.unwrap_or(0).min(max)
+}
+
+/// A dynamically typed msgpack value.
+///
+/// Maps keep their entries in wire order and allow any value as a key, since
+/// the GenericMap ext is not restricted to string keys.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Nil,
+    Bool(bool),
+    Integer(i128),
+    Float(f64),
+    String(String),
+    Bytes(Vec<u8>),
+    Array(Vec<Value>),
+    Map(Vec<(Value, Value)>),
+    /// An ext of a type the crate doesn't interpret: its type and raw data.
+    /// GenericMap exts decode to [`Value::Map`] instead.
+    Ext(i8, Vec<u8>),
+}
+
+impl Value {
+    pub fn is_nil(&self) -> bool {
+        matches!(self, Value::Nil)
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Looks up the value stored under a string key, if this is a map.
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Map(entries) => entries
+                .iter()
+                .find(|(k, _)| k.as_str() == Some(key))
+                .map(|(_, v)| v),
+            _ => None,
+        }
+    }
+}
+
+// Wrapper types are encoded as strings, so that is how they appear in a
+// dynamically built document.
+
+impl From<BigIntWrapper> for Value {
+    fn from(value: BigIntWrapper) -> Self {
+        Value::String(value.0.to_string())
+    }
+}
+
+impl From<JSONString> for Value {
+    fn from(value: JSONString) -> Self {
+        Value::String(value.to_json().to_string())
+    }
+}
+
+impl From<BigDecimal> for Value {
+    fn from(value: BigDecimal) -> Self {
+        Value::String(value.to_string())
+    }
+}
+
+impl TryFrom<Value> for BigIntWrapper {
+    type Error = DecodeError;
+
+    fn try_from(value: Value) -> Result<Self, DecodeError> {
+        match value {
+            Value::Integer(v) => Ok(BigIntWrapper(BigInt::from(v))),
+            Value::String(v) => {
+                BigInt::from_str(&v).map(BigIntWrapper).map_err(|e| {
+                    DecodeError::Message(format!("Error parsing BigInt: {e}"))
+                })
+            }
+            other => Err(DecodeError::ExpectedString(format!(
+                "Expected a BigInt string, found {other:?}"
+            ))),
+        }
+    }
+}
+
+impl TryFrom<Value> for JSONString {
+    type Error = DecodeError;
+
+    fn try_from(value: Value) -> Result<Self, DecodeError> {
+        match value {
+            Value::String(v) => {
+                serde_json::from_str(&v).map(JSONString::new).map_err(|e| {
+                    DecodeError::Message(format!("Error parsing JSON: {e}"))
+                })
+            }
+            other => Err(DecodeError::ExpectedString(format!(
+                "Expected a JSON string, found {other:?}"
+            ))),
+        }
+    }
+}
+
+impl TryFrom<Value> for BigDecimal {
+    type Error = DecodeError;
+
+    fn try_from(value: Value) -> Result<Self, DecodeError> {
+        match value {
+            Value::Integer(v) => Ok(BigDecimal::from(BigInt::from(v))),
+            Value::String(v) => BigDecimal::from_str(&v).map_err(|e| {
+                DecodeError::Message(format!("Error parsing BigNumber: {e}"))
+            }),
+            other => Err(DecodeError::ExpectedString(format!(
+                "Expected a BigNumber string, found {other:?}"
+            ))),
+        }
+    }
+}
+
+pub(crate) struct ValueVisitor;
+
+impl<'de> Visitor<'de> for ValueVisitor {
+    type Value = Value;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("any msgpack value")
+    }
+
+    fn visit_bool<E>(self, v: bool) -> Result<Value, E> {
+        Ok(Value::Bool(v))
+    }
+
+    fn visit_i64<E>(self, v: i64) -> Result<Value, E> {
+        Ok(Value::Integer(v as i128))
+    }
+
+    fn visit_u64<E>(self, v: u64) -> Result<Value, E> {
+        Ok(Value::Integer(v as i128))
+    }
+
+    fn visit_f32<E>(self, v: f32) -> Result<Value, E> {
+        Ok(Value::Float(v as f64))
+    }
+
+    fn visit_f64<E>(self, v: f64) -> Result<Value, E> {
+        Ok(Value::Float(v))
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Value, E> {
+        Ok(Value::String(v.to_string()))
+    }
+
+    fn visit_string<E>(self, v: String) -> Result<Value, E> {
+        Ok(Value::String(v))
+    }
+
+    fn visit_bytes<E>(self, v: &[u8]) -> Result<Value, E> {
+        Ok(Value::Bytes(v.to_vec()))
+    }
+
+    fn visit_byte_buf<E>(self, v: Vec<u8>) -> Result<Value, E> {
+        Ok(Value::Bytes(v))
+    }
+
+    fn visit_none<E>(self) -> Result<Value, E> {
+        Ok(Value::Nil)
+    }
+
+    fn visit_some<D>(self, deserializer: D) -> Result<Value, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        Value::deserialize(deserializer)
+    }
+
+    fn visit_unit<E>(self) -> Result<Value, E> {
+        Ok(Value::Nil)
+    }
+
+    fn visit_newtype_struct<D>(self, deserializer: D) -> Result<Value, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        let (ext_type, data): (i8, serde_bytes::ByteBuf) =
+            Deserialize::deserialize(deserializer)?;
+        Ok(Value::Ext(ext_type, data.into_vec()))
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let mut elements =
+            Vec::with_capacity(cautious_capacity::<Value>(seq.size_hint()));
+        while let Some(element) = seq.next_element()? {
+            elements.push(element);
+        }
+        Ok(Value::Array(elements))
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let mut entries = Vec::with_capacity(
+            cautious_capacity::<(Value, Value)>(map.size_hint()),
+        );
+        while let Some(entry) = map.next_entry()? {
+            entries.push(entry);
+        }
+        Ok(Value::Map(entries))
+    }
+}
+
+impl<'de> Deserialize<'de> for Value {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        deserializer.deserialize_any(ValueVisitor)
+    }
+}
+
+impl Serialize for Value {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        match self {
+            Value::Nil => serializer.serialize_unit(),
+            Value::Bool(v) => serializer.serialize_bool(*v),
+            Value::Integer(v) => {
+                if let Ok(v) = i64::try_from(*v) {
+                    serializer.serialize_i64(v)
+                } else if let Ok(v) = u64::try_from(*v) {
+                    serializer.serialize_u64(v)
+                } else {
+                    Err(ser::Error::custom(format!(
+                        "integer {v} is out of the msgpack range"
+                    )))
+                }
+            }
+            Value::Float(v) => serializer.serialize_f64(*v),
+            Value::String(v) => serializer.serialize_str(v),
+            Value::Bytes(v) => serializer.serialize_bytes(v),
+            Value::Array(elements) => {
+                let mut seq = serializer.serialize_seq(Some(elements.len()))?;
+                for element in elements {
+                    seq.serialize_element(element)?;
+                }
+                seq.end()
+            }
+            Value::Map(entries) => {
+                let mut map = serializer.serialize_map(Some(entries.len()))?;
+                for (key, value) in entries {
+                    map.serialize_entry(key, value)?;
+                }
+                map.end()
+            }
+            Value::Ext(ext_type, data) => serializer.serialize_newtype_struct(
+                EXT_TOKEN,
+                &(ext_type, serde_bytes::Bytes::new(data)),
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use serde_derive::{Deserialize, Serialize};
+
+    use super::Value;
+    use crate::{from_slice, to_vec, BigIntWrapper, BigNumber, JSONString};
+
+    #[test]
+    fn test_capture_unknown_fields() {
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct NewClient {
+            name: String,
+            age: u8,
+            tags: Vec<String>,
+        }
+
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct OldClient {
+            name: String,
+            #[serde(flatten)]
+            extra: BTreeMap<String, Value>,
+        }
+
+        let new = NewClient {
+            name: "a".to_string(),
+            age: 3,
+            tags: vec!["t".to_string()],
+        };
+        let old: OldClient = from_slice(&to_vec(&new).unwrap()).unwrap();
+        assert_eq!(
+            BTreeMap::from([
+                ("age".to_string(), Value::Integer(3)),
+                (
+                    "tags".to_string(),
+                    Value::Array(vec![Value::String("t".to_string())])
+                ),
+            ]),
+            old.extra
+        );
+
+        let reemitted: NewClient =
+            from_slice(&to_vec(&old).unwrap()).unwrap();
+        assert_eq!(new, reemitted);
+    }
+
+    #[test]
+    fn test_wrapper_conversions() {
+        use core::str::FromStr;
+
+        use num_bigint::BigInt;
+
+        let big_int = BigIntWrapper(
+            BigInt::from_str("123456789012345678901234567890").unwrap(),
+        );
+        let value = Value::from(big_int.clone());
+        assert_eq!(
+            Value::String("123456789012345678901234567890".to_string()),
+            value
+        );
+        assert_eq!(big_int, BigIntWrapper::try_from(value).unwrap());
+        assert_eq!(
+            BigIntWrapper(BigInt::from(-5)),
+            BigIntWrapper::try_from(Value::Integer(-5)).unwrap()
+        );
+
+        let json = JSONString::new(serde_json::json!({ "a": [1, true] }));
+        let value = Value::from(json.clone());
+        assert_eq!(Value::String(r#"{"a":[1,true]}"#.to_string()), value);
+        assert_eq!(json, JSONString::try_from(value).unwrap());
+
+        let big_number = BigNumber::from_str("3.14159265358979323846").unwrap();
+        let value = Value::from(big_number.clone());
+        assert_eq!(Value::String("3.14159265358979323846".to_string()), value);
+        assert_eq!(big_number, BigNumber::try_from(value).unwrap());
+
+        assert!(BigIntWrapper::try_from(Value::Bool(true)).is_err());
+        assert!(JSONString::try_from(Value::String("{".to_string())).is_err());
+        assert!(BigNumber::try_from(Value::String("x".to_string())).is_err());
+
+        // Wrappers and their Value form encode identically
+        assert_eq!(
+            to_vec(&big_int).unwrap(),
+            to_vec(&Value::from(big_int)).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_value_round_trip() {
+        let value = Value::Map(vec![
+            (Value::Integer(1), Value::Ext(5, vec![1, 2, 3])),
+            (
+                Value::String("inner".to_string()),
+                Value::Array(vec![
+                    Value::Nil,
+                    Value::Bool(true),
+                    Value::Integer(-300),
+                    Value::Float(0.5),
+                    Value::Bytes(vec![9]),
+                    Value::Ext(-1, vec![0; 4]),
+                ]),
+            ),
+        ]);
+
+        let bytes = to_vec(&value).unwrap();
+        assert_eq!([0xc7, 3, 5, 1, 2, 3], bytes[5..11]);
+        assert_eq!(value, from_slice::<Value>(&bytes).unwrap());
+
+        // Exts of unknown types decode whether the data is borrowed or not.
+        let split: Value =
+            crate::from_slices(&[&bytes[..8], &bytes[8..]]).unwrap();
+        assert_eq!(value, split);
+    }
+
+    #[test]
+    fn test_untrusted_lengths_dont_preallocate() {
+        // An array32 declaring 268 million elements, without any of them
+        let bytes = [0xdd, 0x0f, 0xff, 0xff, 0xff];
+        assert!(crate::from_reader::<_, Value>(&bytes[..]).is_err());
+    }
+}
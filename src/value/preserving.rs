@@ -0,0 +1,660 @@
+use alloc::{boxed::Box, format, string::String, vec, vec::Vec};
+
+use byteorder::BigEndian;
+
+use crate::{
+    error::{
+        get_error_message, DecodeError, DecodeResult, EncodeError, EncodeResult,
+    },
+    format::{ExtensionType, Format},
+    io::{Write, WriteBytesExt},
+};
+
+use super::Value;
+
+/// How deep arrays, maps and GenericMap exts may nest, the same as the
+/// deserializer's default `max_depth`.
+const MAX_DEPTH: usize = 128;
+
+/// A msgpack value that remembers the exact wire format of every node.
+///
+/// Decoding into a `PreservingValue` and encoding it again reproduces the
+/// original bytes, which plain [`Value`]s can't guarantee since they
+/// normalize integer widths, string headers and float precision. Formats
+/// recorded on a node are reused on encode as long as the (possibly
+/// modified) contents still fit them; otherwise the smallest format is used.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PreservingValue {
+    Nil,
+    Bool(bool),
+    Integer(Format, i128),
+    Float32(f32),
+    Float64(f64),
+    /// Raw string bytes, kept as-is even if they aren't valid UTF-8.
+    String(Format, Vec<u8>),
+    Bytes(Format, Vec<u8>),
+    Array(Format, Vec<PreservingValue>),
+    Map(Format, Vec<(PreservingValue, PreservingValue)>),
+    /// A GenericMap ext; the format is that of the ext header and the
+    /// wrapped value is the inner map.
+    GenericMap(Format, Box<PreservingValue>),
+    Ext(Format, i8, Vec<u8>),
+}
+
+impl PreservingValue {
+    pub fn from_slice(buffer: &[u8]) -> DecodeResult<Self> {
+        Self::parse(buffer, 0)
+    }
+
+    pub fn to_vec(&self) -> EncodeResult<Vec<u8>> {
+        let mut buffer = vec![];
+        self.write(&mut buffer)?;
+        Ok(buffer)
+    }
+
+    /// Drops the format information, keeping only the decoded contents.
+    pub fn to_value(&self) -> Value {
+        match self {
+            PreservingValue::Nil => Value::Nil,
+            PreservingValue::Bool(v) => Value::Bool(*v),
+            PreservingValue::Integer(_, v) => Value::Integer(*v),
+            PreservingValue::Float32(v) => Value::Float(*v as f64),
+            PreservingValue::Float64(v) => Value::Float(*v),
+            PreservingValue::String(_, bytes) => {
+                Value::String(String::from_utf8_lossy(bytes).into_owned())
+            }
+            PreservingValue::Bytes(_, bytes) => Value::Bytes(bytes.clone()),
+            PreservingValue::Array(_, elements) => {
+                Value::Array(elements.iter().map(Self::to_value).collect())
+            }
+            PreservingValue::Map(_, entries) => Value::Map(
+                entries
+                    .iter()
+                    .map(|(k, v)| (k.to_value(), v.to_value()))
+                    .collect(),
+            ),
+            PreservingValue::GenericMap(_, map) => map.to_value(),
+            PreservingValue::Ext(_, ext_type, data) => {
+                Value::Ext(*ext_type, data.clone())
+            }
+        }
+    }
+
+    /// Decodes `buffer`, which must hold exactly one value nested `depth`
+    /// levels deep.
+    fn parse(buffer: &[u8], depth: usize) -> DecodeResult<Self> {
+        let mut reader = buffer;
+        let value = Self::read(&mut reader, depth).map_err(|e| match e {
+            // Short reads are detected before consuming anything, so the
+            // remaining input starts where the missing bytes should be.
+            DecodeError::Eof {
+                expected,
+                available,
+                ..
+            } => DecodeError::Eof {
+                expected,
+                available,
+                offset: buffer.len() - reader.len(),
+            },
+            e => e,
+        })?;
+        if !reader.is_empty() {
+            return Err(DecodeError::TrailingCharacters);
+        }
+        Ok(value)
+    }
+
+    fn read(reader: &mut &[u8], depth: usize) -> DecodeResult<Self> {
+        let format = Format::get_format(&mut take(reader, 1)?)?;
+        let value = match format {
+            Format::Nil => PreservingValue::Nil,
+            Format::False => PreservingValue::Bool(false),
+            Format::True => PreservingValue::Bool(true),
+            Format::PositiveFixInt(v) => {
+                PreservingValue::Integer(format, v as i128)
+            }
+            Format::NegativeFixInt(v) => {
+                PreservingValue::Integer(format, v as i128)
+            }
+            Format::Uint8 => {
+                PreservingValue::Integer(format, read_u8(reader)? as i128)
+            }
+            Format::Uint16 => PreservingValue::Integer(
+                format,
+                u16::from_be_bytes(read_array(reader)?) as i128,
+            ),
+            Format::Uint32 => PreservingValue::Integer(
+                format,
+                u32::from_be_bytes(read_array(reader)?) as i128,
+            ),
+            Format::Uint64 => PreservingValue::Integer(
+                format,
+                u64::from_be_bytes(read_array(reader)?) as i128,
+            ),
+            Format::Int8 => {
+                PreservingValue::Integer(format, read_u8(reader)? as i8 as i128)
+            }
+            Format::Int16 => PreservingValue::Integer(
+                format,
+                i16::from_be_bytes(read_array(reader)?) as i128,
+            ),
+            Format::Int32 => PreservingValue::Integer(
+                format,
+                i32::from_be_bytes(read_array(reader)?) as i128,
+            ),
+            Format::Int64 => PreservingValue::Integer(
+                format,
+                i64::from_be_bytes(read_array(reader)?) as i128,
+            ),
+            Format::Float32 => PreservingValue::Float32(f32::from_be_bytes(
+                read_array(reader)?,
+            )),
+            Format::Float64 => PreservingValue::Float64(f64::from_be_bytes(
+                read_array(reader)?,
+            )),
+            Format::FixStr(len) => {
+                PreservingValue::String(format, read_exact(reader, len as u32)?)
+            }
+            Format::Str8 => {
+                let len = read_u8(reader)? as u32;
+                PreservingValue::String(format, read_exact(reader, len)?)
+            }
+            Format::Str16 => {
+                let len = u16::from_be_bytes(read_array(reader)?) as u32;
+                PreservingValue::String(format, read_exact(reader, len)?)
+            }
+            Format::Str32 => {
+                let len = u32::from_be_bytes(read_array(reader)?);
+                PreservingValue::String(format, read_exact(reader, len)?)
+            }
+            Format::Bin8 => {
+                let len = read_u8(reader)? as u32;
+                PreservingValue::Bytes(format, read_exact(reader, len)?)
+            }
+            Format::Bin16 => {
+                let len = u16::from_be_bytes(read_array(reader)?) as u32;
+                PreservingValue::Bytes(format, read_exact(reader, len)?)
+            }
+            Format::Bin32 => {
+                let len = u32::from_be_bytes(read_array(reader)?);
+                PreservingValue::Bytes(format, read_exact(reader, len)?)
+            }
+            Format::FixArray(_) | Format::Array16 | Format::Array32 => {
+                let len = match format {
+                    Format::FixArray(len) => len as u32,
+                    Format::Array16 => {
+                        u16::from_be_bytes(read_array(reader)?) as u32
+                    }
+                    _ => u32::from_be_bytes(read_array(reader)?),
+                };
+                let mut elements = vec![];
+                for _ in 0..len {
+                    elements.push(Self::read_nested(reader, depth)?);
+                }
+                PreservingValue::Array(format, elements)
+            }
+            Format::FixMap(_) | Format::Map16 | Format::Map32 => {
+                let len = match format {
+                    Format::FixMap(len) => len as u32,
+                    Format::Map16 => {
+                        u16::from_be_bytes(read_array(reader)?) as u32
+                    }
+                    _ => u32::from_be_bytes(read_array(reader)?),
+                };
+                let mut entries = vec![];
+                for _ in 0..len {
+                    let key = Self::read_nested(reader, depth)?;
+                    let value = Self::read_nested(reader, depth)?;
+                    entries.push((key, value));
+                }
+                PreservingValue::Map(format, entries)
+            }
+            Format::FixExt1
+            | Format::FixExt2
+            | Format::FixExt4
+            | Format::FixExt8
+            | Format::FixExt16
+            | Format::Ext8
+            | Format::Ext16
+            | Format::Ext32 => {
+                let len = match format {
+                    Format::FixExt1 => 1,
+                    Format::FixExt2 => 2,
+                    Format::FixExt4 => 4,
+                    Format::FixExt8 => 8,
+                    Format::FixExt16 => 16,
+                    Format::Ext8 => read_u8(reader)? as u32,
+                    Format::Ext16 => {
+                        u16::from_be_bytes(read_array(reader)?) as u32
+                    }
+                    _ => u32::from_be_bytes(read_array(reader)?),
+                };
+                let ext_type = read_u8(reader)? as i8;
+                let data = read_exact(reader, len)?;
+
+                // A payload that isn't a map on its own, or that would nest
+                // too deep, is kept as a plain ext like any other ext type
+                if ext_type == u8::from(ExtensionType::GenericMap) as i8
+                    && depth < MAX_DEPTH
+                {
+                    let map = Self::parse(&data, depth + 1).ok();
+                    if let Some(map @ PreservingValue::Map(..)) = map {
+                        return Ok(PreservingValue::GenericMap(
+                            format,
+                            Box::new(map),
+                        ));
+                    }
+                }
+                PreservingValue::Ext(format, ext_type, data)
+            }
+            Format::Reserved => {
+                return Err(DecodeError::Message(format!(
+                    "Invalid msgpack format. {}",
+                    get_error_message(format)
+                )));
+            }
+        };
+        Ok(value)
+    }
+
+    /// Reads an element of a collection at `depth`, refusing to recurse
+    /// past [`MAX_DEPTH`].
+    fn read_nested(reader: &mut &[u8], depth: usize) -> DecodeResult<Self> {
+        if depth >= MAX_DEPTH {
+            return Err(DecodeError::Message(format!(
+                "Nesting exceeds the maximum depth of {MAX_DEPTH}"
+            )));
+        }
+        Self::read(reader, depth + 1)
+    }
+
+    fn write<W: Write>(&self, writer: &mut W) -> EncodeResult<()> {
+        match self {
+            PreservingValue::Nil => Format::set_format(writer, Format::Nil)?,
+            PreservingValue::Bool(v) => {
+                let format = if *v { Format::True } else { Format::False };
+                Format::set_format(writer, format)?
+            }
+            PreservingValue::Integer(format, v) => {
+                write_integer(writer, *format, *v)?
+            }
+            PreservingValue::Float32(v) => {
+                Format::set_format(writer, Format::Float32)?;
+                writer.write_f32::<BigEndian>(*v)?
+            }
+            PreservingValue::Float64(v) => {
+                Format::set_format(writer, Format::Float64)?;
+                writer.write_f64::<BigEndian>(*v)?
+            }
+            PreservingValue::String(format, bytes) => {
+                let candidates = [
+                    (Format::FixStr(0), 31),
+                    (Format::Str8, u8::MAX as u32),
+                    (Format::Str16, u16::MAX as u32),
+                    (Format::Str32, u32::MAX),
+                ];
+                write_header(writer, *format, &candidates, bytes.len())?;
+                writer.write_all(bytes)?
+            }
+            PreservingValue::Bytes(format, bytes) => {
+                let candidates = [
+                    (Format::Bin8, u8::MAX as u32),
+                    (Format::Bin16, u16::MAX as u32),
+                    (Format::Bin32, u32::MAX),
+                ];
+                write_header(writer, *format, &candidates, bytes.len())?;
+                writer.write_all(bytes)?
+            }
+            PreservingValue::Array(format, elements) => {
+                let candidates = [
+                    (Format::FixArray(0), 15),
+                    (Format::Array16, u16::MAX as u32),
+                    (Format::Array32, u32::MAX),
+                ];
+                write_header(writer, *format, &candidates, elements.len())?;
+                for element in elements {
+                    element.write(writer)?;
+                }
+            }
+            PreservingValue::Map(format, entries) => {
+                let candidates = [
+                    (Format::FixMap(0), 15),
+                    (Format::Map16, u16::MAX as u32),
+                    (Format::Map32, u32::MAX),
+                ];
+                write_header(writer, *format, &candidates, entries.len())?;
+                for (key, value) in entries {
+                    key.write(writer)?;
+                    value.write(writer)?;
+                }
+            }
+            PreservingValue::GenericMap(format, map) => {
+                let data = map.to_vec()?;
+                write_ext(
+                    writer,
+                    *format,
+                    ExtensionType::GenericMap.into(),
+                    &data,
+                )?
+            }
+            PreservingValue::Ext(format, ext_type, data) => {
+                write_ext(writer, *format, *ext_type as u8, data)?
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Takes `len` bytes off `reader`, leaving it untouched if it's too short.
+/// The offset is filled in by [`PreservingValue::parse`].
+fn take<'a>(reader: &mut &'a [u8], len: usize) -> DecodeResult<&'a [u8]> {
+    if reader.len() < len {
+        return Err(DecodeError::Eof {
+            expected: len,
+            available: reader.len(),
+            offset: 0,
+        });
+    }
+    let (taken, rest) = reader.split_at(len);
+    *reader = rest;
+    Ok(taken)
+}
+
+fn read_exact(reader: &mut &[u8], len: u32) -> DecodeResult<Vec<u8>> {
+    Ok(take(reader, len as usize)?.to_vec())
+}
+
+fn read_array<const N: usize>(reader: &mut &[u8]) -> DecodeResult<[u8; N]> {
+    Ok(take(reader, N)?.try_into().unwrap())
+}
+
+fn read_u8(reader: &mut &[u8]) -> DecodeResult<u8> {
+    Ok(take(reader, 1)?[0])
+}
+
+fn fits(format: Format, v: i128) -> bool {
+    match format {
+        Format::PositiveFixInt(_) => (0..128).contains(&v),
+        Format::NegativeFixInt(_) => (-32..0).contains(&v),
+        Format::Uint8 => u8::try_from(v).is_ok(),
+        Format::Uint16 => u16::try_from(v).is_ok(),
+        Format::Uint32 => u32::try_from(v).is_ok(),
+        Format::Uint64 => u64::try_from(v).is_ok(),
+        Format::Int8 => i8::try_from(v).is_ok(),
+        Format::Int16 => i16::try_from(v).is_ok(),
+        Format::Int32 => i32::try_from(v).is_ok(),
+        Format::Int64 => i64::try_from(v).is_ok(),
+        _ => false,
+    }
+}
+
+fn write_integer<W: Write>(
+    writer: &mut W,
+    format: Format,
+    v: i128,
+) -> EncodeResult<()> {
+    let format = if fits(format, v) {
+        format
+    } else {
+        [
+            Format::PositiveFixInt(0),
+            Format::NegativeFixInt(0),
+            Format::Uint8,
+            Format::Int8,
+            Format::Uint16,
+            Format::Int16,
+            Format::Uint32,
+            Format::Int32,
+            Format::Uint64,
+            Format::Int64,
+        ]
+        .into_iter()
+        .find(|f| fits(*f, v))
+        .ok_or_else(|| {
+            EncodeError::Message(format!(
+                "integer overflow: value = {v}; bits = 64"
+            ))
+        })?
+    };
+
+    match format {
+        Format::PositiveFixInt(_) => {
+            Format::set_format(writer, Format::PositiveFixInt(v as u8))?
+        }
+        Format::NegativeFixInt(_) => {
+            Format::set_format(writer, Format::NegativeFixInt(v as i8))?
+        }
+        Format::Uint8 => {
+            Format::set_format(writer, format)?;
+            writer.write_u8(v as u8)?
+        }
+        Format::Uint16 => {
+            Format::set_format(writer, format)?;
+            writer.write_u16::<BigEndian>(v as u16)?
+        }
+        Format::Uint32 => {
+            Format::set_format(writer, format)?;
+            writer.write_u32::<BigEndian>(v as u32)?
+        }
+        Format::Uint64 => {
+            Format::set_format(writer, format)?;
+            writer.write_u64::<BigEndian>(v as u64)?
+        }
+        Format::Int8 => {
+            Format::set_format(writer, format)?;
+            writer.write_i8(v as i8)?
+        }
+        Format::Int16 => {
+            Format::set_format(writer, format)?;
+            writer.write_i16::<BigEndian>(v as i16)?
+        }
+        Format::Int32 => {
+            Format::set_format(writer, format)?;
+            writer.write_i32::<BigEndian>(v as i32)?
+        }
+        _ => {
+            Format::set_format(writer, Format::Int64)?;
+            writer.write_i64::<BigEndian>(v as i64)?
+        }
+    }
+    Ok(())
+}
+
+/// Writes a length header, reusing the recorded `format` when `len` still
+/// fits in it and falling back to the smallest candidate otherwise.
+///
+/// `candidates` lists the formats of a family from smallest to largest,
+/// along with the maximum length each of them can carry.
+fn write_header<W: Write>(
+    writer: &mut W,
+    format: Format,
+    candidates: &[(Format, u32)],
+    len: usize,
+) -> EncodeResult<()> {
+    let len = u32::try_from(len)
+        .map_err(|_| EncodeError::Message(format!("length overflow: {len}")))?;
+
+    let recorded = candidates.iter().find(|(f, max)| {
+        core::mem::discriminant(f) == core::mem::discriminant(&format)
+            && len <= *max
+    });
+    let (format, max) = match recorded {
+        Some(candidate) => *candidate,
+        None => *candidates.iter().find(|(_, max)| len <= *max).unwrap(),
+    };
+
+    match format {
+        Format::FixStr(_) => {
+            Format::set_format(writer, Format::FixStr(len as u8))?
+        }
+        Format::FixArray(_) => {
+            Format::set_format(writer, Format::FixArray(len as u8))?
+        }
+        Format::FixMap(_) => {
+            Format::set_format(writer, Format::FixMap(len as u8))?
+        }
+        _ => {
+            Format::set_format(writer, format)?;
+            if max == u8::MAX as u32 {
+                writer.write_u8(len as u8)?
+            } else if max == u16::MAX as u32 {
+                writer.write_u16::<BigEndian>(len as u16)?
+            } else {
+                writer.write_u32::<BigEndian>(len)?
+            }
+        }
+    }
+    Ok(())
+}
+
+fn write_ext<W: Write>(
+    writer: &mut W,
+    format: Format,
+    ext_type: u8,
+    data: &[u8],
+) -> EncodeResult<()> {
+    let len = data.len();
+    let fixed = match len {
+        1 => Some(Format::FixExt1),
+        2 => Some(Format::FixExt2),
+        4 => Some(Format::FixExt4),
+        8 => Some(Format::FixExt8),
+        16 => Some(Format::FixExt16),
+        _ => None,
+    };
+    let format = match format {
+        Format::FixExt1
+        | Format::FixExt2
+        | Format::FixExt4
+        | Format::FixExt8
+        | Format::FixExt16
+            if fixed == Some(format) =>
+        {
+            format
+        }
+        Format::Ext8 if len <= u8::MAX as usize => format,
+        Format::Ext16 if len <= u16::MAX as usize => format,
+        Format::Ext32 => format,
+        _ if len <= u8::MAX as usize => Format::Ext8,
+        _ if len <= u16::MAX as usize => Format::Ext16,
+        _ => Format::Ext32,
+    };
+
+    Format::set_format(writer, format)?;
+    match format {
+        Format::Ext8 => writer.write_u8(len as u8)?,
+        Format::Ext16 => writer.write_u16::<BigEndian>(len as u16)?,
+        Format::Ext32 => writer.write_u32::<BigEndian>(len as u32)?,
+        _ => {}
+    }
+    writer.write_u8(ext_type)?;
+    writer.write_all(data)?;
+    Ok(())
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::PreservingValue;
+    use crate::{format::Format, Value};
+
+    #[test]
+    fn test_preserving_round_trip() {
+        let cases: [&[u8]; 6] = [
+            // positive fixint 1 written as uint8 and int16
+            &[147, 1, 204, 1, 209, 0, 1],
+            // "hi" written as str8 instead of fixstr
+            &[217, 2, 104, 105],
+            // fixmap written as map16, with a float64 that fits a float32
+            &[222, 0, 1, 161, 97, 203, 63, 224, 0, 0, 0, 0, 0, 0],
+            // ext generic map written with an Ext16 header
+            &[200, 0, 4, 1, 129, 1, 204, 3],
+            // unknown ext type
+            &[214, 42, 1, 2, 3, 4],
+            // invalid utf-8 inside a string
+            &[162, 0xff, 0xfe],
+        ];
+
+        for case in cases {
+            let value = PreservingValue::from_slice(case).unwrap();
+            assert_eq!(case, value.to_vec().unwrap().as_slice());
+        }
+    }
+
+    #[test]
+    fn test_preserving_records_formats() {
+        let value =
+            PreservingValue::from_slice(&[200, 0, 4, 1, 129, 1, 204, 3])
+                .unwrap();
+        assert_eq!(
+            PreservingValue::GenericMap(
+                Format::Ext16,
+                Box::new(PreservingValue::Map(
+                    Format::FixMap(1),
+                    vec![(
+                        PreservingValue::Integer(Format::PositiveFixInt(1), 1),
+                        PreservingValue::Integer(Format::Uint8, 3),
+                    )]
+                ))
+            ),
+            value
+        );
+        assert_eq!(
+            Value::Map(vec![(Value::Integer(1), Value::Integer(3))]),
+            value.to_value()
+        );
+    }
+
+    #[test]
+    fn test_preserving_falls_back_when_format_no_longer_fits() {
+        let value = PreservingValue::Array(
+            Format::FixArray(0),
+            vec![PreservingValue::Integer(Format::Uint8, 300)],
+        );
+        assert_eq!(vec![145, 205, 1, 44], value.to_vec().unwrap());
+    }
+
+    #[test]
+    fn test_preserving_rejects_trailing_bytes() {
+        assert!(PreservingValue::from_slice(&[192, 192]).is_err());
+    }
+
+    #[test]
+    fn test_preserving_keeps_malformed_generic_maps_as_exts() {
+        // GenericMap exts holding two nils, and a reserved byte
+        let cases: [&[u8]; 2] = [&[199, 2, 1, 192, 192], &[199, 1, 1, 0xc1]];
+        for case in cases {
+            let value = PreservingValue::from_slice(case).unwrap();
+            assert_eq!(
+                PreservingValue::Ext(Format::Ext8, 1, case[3..].to_vec()),
+                value
+            );
+            assert_eq!(case, value.to_vec().unwrap().as_slice());
+        }
+    }
+
+    #[test]
+    fn test_preserving_limits_depth() {
+        // [[[...[nil]...]]]
+        let mut arrays = vec![0x91; 128];
+        arrays.push(0xc0);
+        assert!(PreservingValue::from_slice(&arrays).is_ok());
+        arrays.insert(0, 0x91);
+        assert!(PreservingValue::from_slice(&arrays).is_err());
+        assert!(PreservingValue::from_slice(&[0x91; 200_000]).is_err());
+
+        // GenericMap exts wrapping each other, the innermost one an empty
+        // map, aren't unwrapped past the limit either
+        let depth = 200_000;
+        let mut exts = vec![];
+        for level in (0..depth).rev() {
+            exts.push(0xc9);
+            exts.extend_from_slice(&(level as u32 * 6 + 1).to_be_bytes());
+            exts.push(1);
+        }
+        exts.push(0x80);
+        let value = PreservingValue::from_slice(&exts).unwrap();
+        assert!(matches!(value, PreservingValue::Ext(Format::Ext32, 1, _)));
+    }
+}
@@ -0,0 +1,414 @@
+use alloc::{
+    boxed::Box,
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{fmt, str::FromStr};
+
+use serde::de::{self, DeserializeSeed, MapAccess, SeqAccess, Visitor};
+use serde::Deserialize;
+
+use crate::{error, BigInt, BigNumber, Deserializer};
+
+use super::{cautious_capacity, Value, ValueVisitor};
+
+/// Runtime description of a WRAP type, used to decode payloads whose shape
+/// is only known at runtime (e.g. from a wrapper's ABI).
+#[derive(Debug, Clone, PartialEq)]
+pub enum TypeInfo {
+    /// Accepts any well-formed value without validation.
+    Any,
+    Boolean,
+    Int8,
+    Int16,
+    Int32,
+    Int64,
+    UInt8,
+    UInt16,
+    UInt32,
+    UInt64,
+    Float32,
+    Float64,
+    String,
+    Bytes,
+    /// A BigInt encoded as a decimal string.
+    BigInt,
+    /// A BigNumber encoded as a decimal string.
+    BigNumber,
+    /// A JSON document encoded as a string.
+    JSON,
+    Optional(Box<TypeInfo>),
+    Array(Box<TypeInfo>),
+    Map {
+        key: Box<TypeInfo>,
+        value: Box<TypeInfo>,
+    },
+    Object {
+        name: String,
+        fields: Vec<FieldInfo>,
+    },
+    /// An enum encoded as its variant index. Variant names are accepted on
+    /// the wire and coerced to their index.
+    Enum {
+        name: String,
+        variants: Vec<String>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldInfo {
+    pub name: String,
+    pub type_info: TypeInfo,
+}
+
+impl FieldInfo {
+    pub fn new(name: &str, type_info: TypeInfo) -> Self {
+        Self {
+            name: name.to_string(),
+            type_info,
+        }
+    }
+}
+
+impl TypeInfo {
+    pub fn is_optional(&self) -> bool {
+        matches!(self, TypeInfo::Optional(_) | TypeInfo::Any)
+    }
+}
+
+/// Decodes `bytes` into a [`Value`], validating and coercing every node
+/// according to `schema`.
+pub fn decode_with_schema(
+    bytes: &[u8],
+    schema: &TypeInfo,
+) -> error::DecodeResult<Value> {
+    let mut deserializer = Deserializer::from_slice(bytes);
+    SchemaSeed(schema).deserialize(&mut deserializer)
+}
+
+pub(crate) struct SchemaSeed<'a>(pub &'a TypeInfo);
+
+impl<'de, 'a> DeserializeSeed<'de> for SchemaSeed<'a> {
+    type Value = Value;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Value, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        match self.0 {
+            TypeInfo::Any => Value::deserialize(deserializer),
+            TypeInfo::Boolean => deserializer.deserialize_bool(ValueVisitor),
+            TypeInfo::Int8 => deserializer.deserialize_i8(ValueVisitor),
+            TypeInfo::Int16 => deserializer.deserialize_i16(ValueVisitor),
+            TypeInfo::Int32 => deserializer.deserialize_i32(ValueVisitor),
+            TypeInfo::Int64 => deserializer.deserialize_i64(ValueVisitor),
+            TypeInfo::UInt8 => deserializer.deserialize_u8(ValueVisitor),
+            TypeInfo::UInt16 => deserializer.deserialize_u16(ValueVisitor),
+            TypeInfo::UInt32 => deserializer.deserialize_u32(ValueVisitor),
+            TypeInfo::UInt64 => deserializer.deserialize_u64(ValueVisitor),
+            TypeInfo::Float32 => deserializer.deserialize_f32(ValueVisitor),
+            TypeInfo::Float64 => deserializer.deserialize_f64(ValueVisitor),
+            TypeInfo::String => deserializer.deserialize_string(ValueVisitor),
+            TypeInfo::Bytes => deserializer.deserialize_byte_buf(ValueVisitor),
+            TypeInfo::BigInt => {
+                let s = String::deserialize(deserializer)?;
+                BigInt::from_str(&s).map_err(|e| {
+                    de::Error::custom(format!("Error parsing BigInt: {e}"))
+                })?;
+                Ok(Value::String(s))
+            }
+            TypeInfo::BigNumber => {
+                let s = String::deserialize(deserializer)?;
+                BigNumber::from_str(&s).map_err(|e| {
+                    de::Error::custom(format!("Error parsing BigNumber: {e}"))
+                })?;
+                Ok(Value::String(s))
+            }
+            TypeInfo::JSON => {
+                let s = String::deserialize(deserializer)?;
+                serde_json::from_str::<serde_json::Value>(&s).map_err(|e| {
+                    de::Error::custom(format!("Error parsing JSON: {e}"))
+                })?;
+                Ok(Value::String(s))
+            }
+            TypeInfo::Optional(inner) => {
+                deserializer.deserialize_option(OptionalVisitor(inner))
+            }
+            TypeInfo::Array(item) => {
+                deserializer.deserialize_seq(ArrayVisitor(item))
+            }
+            TypeInfo::Map { key, value } => {
+                deserializer.deserialize_map(MapVisitor { key, value })
+            }
+            TypeInfo::Object { name, fields } => {
+                deserializer.deserialize_map(ObjectVisitor { name, fields })
+            }
+            TypeInfo::Enum { name, variants } => {
+                deserializer.deserialize_any(EnumVisitor { name, variants })
+            }
+        }
+    }
+}
+
+struct OptionalVisitor<'a>(&'a TypeInfo);
+
+impl<'de, 'a> Visitor<'de> for OptionalVisitor<'a> {
+    type Value = Value;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "an optional {:?}", self.0)
+    }
+
+    fn visit_none<E>(self) -> Result<Value, E> {
+        Ok(Value::Nil)
+    }
+
+    fn visit_unit<E>(self) -> Result<Value, E> {
+        Ok(Value::Nil)
+    }
+
+    fn visit_some<D>(self, deserializer: D) -> Result<Value, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        SchemaSeed(self.0).deserialize(deserializer)
+    }
+}
+
+struct ArrayVisitor<'a>(&'a TypeInfo);
+
+impl<'de, 'a> Visitor<'de> for ArrayVisitor<'a> {
+    type Value = Value;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "an array of {:?}", self.0)
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let mut elements =
+            Vec::with_capacity(cautious_capacity::<Value>(seq.size_hint()));
+        while let Some(element) = seq.next_element_seed(SchemaSeed(self.0))? {
+            elements.push(element);
+        }
+        Ok(Value::Array(elements))
+    }
+}
+
+struct MapVisitor<'a> {
+    key: &'a TypeInfo,
+    value: &'a TypeInfo,
+}
+
+impl<'de, 'a> Visitor<'de> for MapVisitor<'a> {
+    type Value = Value;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "a map of {:?} to {:?}", self.key, self.value)
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let mut entries = Vec::with_capacity(
+            cautious_capacity::<(Value, Value)>(map.size_hint()),
+        );
+        while let Some(key) = map.next_key_seed(SchemaSeed(self.key))? {
+            let value = map.next_value_seed(SchemaSeed(self.value))?;
+            entries.push((key, value));
+        }
+        Ok(Value::Map(entries))
+    }
+}
+
+struct ObjectVisitor<'a> {
+    name: &'a str,
+    fields: &'a [FieldInfo],
+}
+
+impl<'de, 'a> Visitor<'de> for ObjectVisitor<'a> {
+    type Value = Value;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "an object of type '{}'", self.name)
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let mut entries = Vec::with_capacity(self.fields.len());
+        while let Some(key) = map.next_key::<String>()? {
+            // Properties missing from the schema are kept untouched so that
+            // payloads from newer wrappers still decode.
+            let value = match self.fields.iter().find(|f| f.name == key) {
+                Some(field) => {
+                    map.next_value_seed(SchemaSeed(&field.type_info))?
+                }
+                None => map.next_value::<Value>()?,
+            };
+            entries.push((Value::String(key), value));
+        }
+
+        for field in self.fields {
+            let present = entries
+                .iter()
+                .any(|(k, _)| k.as_str() == Some(field.name.as_str()));
+            if !present && !field.type_info.is_optional() {
+                return Err(de::Error::custom(format!(
+                    "Missing required property '{}' of object '{}'",
+                    field.name, self.name
+                )));
+            }
+        }
+
+        Ok(Value::Map(entries))
+    }
+}
+
+struct EnumVisitor<'a> {
+    name: &'a str,
+    variants: &'a [String],
+}
+
+impl<'de, 'a> Visitor<'de> for EnumVisitor<'a> {
+    type Value = Value;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "a variant of enum '{}'", self.name)
+    }
+
+    fn visit_u64<E>(self, v: u64) -> Result<Value, E>
+    where
+        E: de::Error,
+    {
+        if (v as usize) < self.variants.len() {
+            Ok(Value::Integer(v as i128))
+        } else {
+            Err(E::custom(format!(
+                "Invalid value for enum '{}': {v}",
+                self.name
+            )))
+        }
+    }
+
+    fn visit_i64<E>(self, v: i64) -> Result<Value, E>
+    where
+        E: de::Error,
+    {
+        if v < 0 {
+            return Err(E::custom(format!(
+                "Invalid value for enum '{}': {v}",
+                self.name
+            )));
+        }
+        self.visit_u64(v as u64)
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Value, E>
+    where
+        E: de::Error,
+    {
+        match self.variants.iter().position(|variant| variant == v) {
+            Some(index) => Ok(Value::Integer(index as i128)),
+            None => Err(E::custom(format!(
+                "Invalid key for enum '{}': {v}",
+                self.name
+            ))),
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use serde_derive::Serialize;
+
+    use super::{decode_with_schema, FieldInfo, TypeInfo};
+    use crate::{to_vec, Value};
+
+    fn object_schema() -> TypeInfo {
+        TypeInfo::Object {
+            name: "Foo".to_string(),
+            fields: vec![
+                FieldInfo::new("id", TypeInfo::UInt32),
+                FieldInfo::new(
+                    "tags",
+                    TypeInfo::Array(Box::new(TypeInfo::String)),
+                ),
+                FieldInfo::new(
+                    "color",
+                    TypeInfo::Enum {
+                        name: "Color".to_string(),
+                        variants: vec!["RED".to_string(), "BLUE".to_string()],
+                    },
+                ),
+                FieldInfo::new(
+                    "amount",
+                    TypeInfo::Optional(Box::new(TypeInfo::BigInt)),
+                ),
+            ],
+        }
+    }
+
+    #[test]
+    fn test_decode_object_with_schema() {
+        #[derive(Serialize)]
+        struct Foo {
+            id: u32,
+            tags: Vec<String>,
+            color: String,
+            amount: Option<String>,
+        }
+
+        let bytes = to_vec(&Foo {
+            id: 300,
+            tags: vec!["a".to_string()],
+            color: "BLUE".to_string(),
+            amount: None,
+        })
+        .unwrap();
+
+        let value = decode_with_schema(&bytes, &object_schema()).unwrap();
+        assert_eq!(value.get("id"), Some(&Value::Integer(300)));
+        assert_eq!(
+            value.get("tags"),
+            Some(&Value::Array(vec![Value::String("a".to_string())]))
+        );
+        assert_eq!(value.get("color"), Some(&Value::Integer(1)));
+        assert_eq!(value.get("amount"), Some(&Value::Nil));
+    }
+
+    #[test]
+    fn test_decode_with_schema_missing_required_property() {
+        #[derive(Serialize)]
+        struct Foo {
+            tags: Vec<String>,
+        }
+
+        let bytes = to_vec(&Foo { tags: vec![] }).unwrap();
+        let err = decode_with_schema(&bytes, &object_schema()).unwrap_err();
+        assert!(err.to_string().contains("Missing required property 'id'"));
+    }
+
+    #[test]
+    fn test_decode_with_schema_rejects_invalid_values() {
+        let bytes = to_vec(&300).unwrap();
+        assert!(decode_with_schema(&bytes, &TypeInfo::UInt8).is_err());
+
+        let bytes = to_vec(&"not a number").unwrap();
+        assert!(decode_with_schema(&bytes, &TypeInfo::BigInt).is_err());
+
+        let bytes = to_vec(&5).unwrap();
+        let schema = TypeInfo::Enum {
+            name: "Color".to_string(),
+            variants: vec!["RED".to_string()],
+        };
+        assert!(decode_with_schema(&bytes, &schema).is_err());
+    }
+}
@@ -0,0 +1,72 @@
+//! `#[serde(with = "base64_bytes")]` helpers encoding a byte field as a
+//! standard, padded base64 string rather than a bin, for values that must
+//! survive a round trip through JSON.
+
+use alloc::{format, vec::Vec};
+use core::fmt;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::{de, Deserializer, Serializer};
+
+pub fn serialize<T, S>(bytes: &T, s: S) -> Result<S::Ok, S::Error>
+where
+    T: ?Sized + AsRef<[u8]>,
+    S: Serializer,
+{
+    s.serialize_str(&STANDARD.encode(bytes.as_ref()))
+}
+
+pub fn deserialize<'de, D>(deserializer: D) -> Result<Vec<u8>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserializer.deserialize_str(Base64Visitor)
+}
+
+struct Base64Visitor;
+
+impl<'de> de::Visitor<'de> for Base64Visitor {
+    type Value = Vec<u8>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a base64 string")
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Vec<u8>, E>
+    where
+        E: de::Error,
+    {
+        STANDARD
+            .decode(v)
+            .map_err(|e| E::custom(format!("Error parsing base64: {e}")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_derive::{Deserialize, Serialize};
+
+    use crate::{from_slice, to_vec};
+
+    #[test]
+    fn test_base64_bytes_field() {
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Blob {
+            #[serde(with = "super")]
+            data: Vec<u8>,
+        }
+
+        let blob = Blob {
+            data: b"hello".to_vec(),
+        };
+        let bytes = to_vec(&blob).unwrap();
+        assert!(bytes.ends_with(b"\xa8aGVsbG8="));
+        assert_eq!(blob, from_slice(&bytes).unwrap());
+
+        let empty = to_vec(&Blob { data: vec![] }).unwrap();
+        assert_eq!(Blob { data: vec![] }, from_slice(&empty).unwrap());
+
+        let err = from_slice::<Blob>(b"\x81\xa4data\xa3a*=").unwrap_err();
+        assert!(err.to_string().contains("Error parsing base64"));
+    }
+}
@@ -0,0 +1,102 @@
+use alloc::vec::Vec;
+use core::fmt;
+
+use serde::{de::Visitor, Deserialize, Deserializer, Serialize, Serializer};
+use serde_bytes::ByteBuf;
+
+use crate::value::{Value, EXT_TOKEN};
+
+/// An ext of any type kept as its raw type code and data, so extensions
+/// from other runtimes can be forwarded through a struct unchanged.
+///
+/// GenericMap exts are kept undecoded as well.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ExtValue {
+    pub type_code: i8,
+    pub data: Vec<u8>,
+}
+
+impl ExtValue {
+    pub fn new(type_code: i8, data: Vec<u8>) -> Self {
+        Self { type_code, data }
+    }
+}
+
+impl Serialize for ExtValue {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_newtype_struct(
+            EXT_TOKEN,
+            &(self.type_code, serde_bytes::Bytes::new(&self.data)),
+        )
+    }
+}
+
+struct ExtValueVisitor;
+
+impl<'de> Visitor<'de> for ExtValueVisitor {
+    type Value = ExtValue;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("an ext")
+    }
+
+    fn visit_newtype_struct<D>(
+        self,
+        deserializer: D,
+    ) -> Result<ExtValue, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let (type_code, data) = <(i8, ByteBuf)>::deserialize(deserializer)?;
+        Ok(ExtValue::new(type_code, data.into_vec()))
+    }
+}
+
+impl<'de> Deserialize<'de> for ExtValue {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_newtype_struct(EXT_TOKEN, ExtValueVisitor)
+    }
+}
+
+impl From<ExtValue> for Value {
+    fn from(ext: ExtValue) -> Self {
+        Value::Ext(ext.type_code, ext.data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_derive::{Deserialize, Serialize};
+
+    use super::ExtValue;
+    use crate::{from_slice, to_vec};
+
+    #[test]
+    fn test_forward_unknown_exts() {
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Envelope {
+            id: u8,
+            payload: ExtValue,
+        }
+
+        // {"id": 1, "payload": FixExt2 of type 42}
+        let bytes = [
+            130, 162, 105, 100, 1, 167, 112, 97, 121, 108, 111, 97, 100, 0xd5,
+            42, 7, 8,
+        ];
+        let envelope: Envelope = from_slice(&bytes).unwrap();
+        assert_eq!(ExtValue::new(42, vec![7, 8]), envelope.payload);
+        assert_eq!(bytes.to_vec(), to_vec(&envelope).unwrap());
+
+        // GenericMaps stay undecoded
+        let map: ExtValue = from_slice(&[0xd4, 1, 0x80]).unwrap();
+        assert_eq!(ExtValue::new(1, vec![0x80]), map);
+        assert!(from_slice::<ExtValue>(&[0xc0]).is_err());
+    }
+}
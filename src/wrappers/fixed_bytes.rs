@@ -0,0 +1,107 @@
+//! `#[serde(with = "fixed_bytes")]` helpers encoding a `[u8; N]` field, such
+//! as a hash, as a single bin rather than an array of `N` integers.
+//! Decoding checks the length, and also accepts the array form written
+//! without the helper.
+
+use core::{fmt, marker::PhantomData};
+
+use serde::{de, Deserializer, Serializer};
+
+pub fn serialize<const N: usize, S>(
+    bytes: &[u8; N],
+    s: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    s.serialize_bytes(bytes)
+}
+
+pub fn deserialize<'de, const N: usize, D>(
+    deserializer: D,
+) -> Result<[u8; N], D::Error>
+where
+    D: Deserializer<'de>,
+{
+    // Any, so the array form reaches `visit_seq`
+    deserializer.deserialize_any(FixedBytesVisitor::<N>(PhantomData))
+}
+
+struct FixedBytesVisitor<const N: usize>(PhantomData<[u8; N]>);
+
+impl<'de, const N: usize> de::Visitor<'de> for FixedBytesVisitor<N> {
+    type Value = [u8; N];
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "{N} bytes")
+    }
+
+    fn visit_bytes<E>(self, v: &[u8]) -> Result<[u8; N], E>
+    where
+        E: de::Error,
+    {
+        v.try_into().map_err(|_| E::invalid_length(v.len(), &self))
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<[u8; N], A::Error>
+    where
+        A: de::SeqAccess<'de>,
+    {
+        let mut bytes = [0; N];
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = seq
+                .next_element()?
+                .ok_or_else(|| de::Error::invalid_length(i, &self))?;
+        }
+        if seq.next_element::<de::IgnoredAny>()?.is_some() {
+            return Err(de::Error::invalid_length(
+                N + 1 + seq.size_hint().unwrap_or(0),
+                &self,
+            ));
+        }
+        Ok(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_derive::{Deserialize, Serialize};
+
+    use crate::{from_slice, to_vec};
+
+    #[test]
+    fn test_fixed_bytes_field() {
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Block {
+            #[serde(with = "super")]
+            hash: [u8; 32],
+        }
+
+        let block = Block { hash: [7; 32] };
+        let bytes = to_vec(&block).unwrap();
+        let mut expected = b"\x81\xa4hash\xc4\x20".to_vec();
+        expected.extend([7; 32]);
+        assert_eq!(expected, bytes);
+        assert_eq!(block, from_slice(&bytes).unwrap());
+
+        // The element-by-element array form
+        #[derive(Serialize)]
+        struct LegacyBlock {
+            hash: [u8; 32],
+        }
+        let legacy = to_vec(&LegacyBlock { hash: [7; 32] }).unwrap();
+        assert_eq!(block, from_slice(&legacy).unwrap());
+
+        let short = b"\x81\xa4hash\xc4\x02\x07\x07";
+        let err = from_slice::<Block>(short).unwrap_err();
+        assert!(err.to_string().contains("invalid length 2"));
+
+        #[derive(Debug, Deserialize)]
+        struct Pair {
+            #[serde(with = "super")]
+            _hash: [u8; 2],
+        }
+        let long = b"\x81\xa5_hash\x93\x07\x07\x07";
+        assert!(from_slice::<Pair>(long).is_err());
+    }
+}
@@ -0,0 +1,36 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Newtype name through which [`Flattened`] tells the serializer that the
+/// map of unknown length it starts next is a struct with flattened fields.
+pub(crate) const FLATTENED_TOKEN: &str = "$polywrap_msgpack::Flattened";
+
+/// A struct with `#[serde(flatten)]` fields, written as a plain map like
+/// any other struct.
+///
+/// serde writes such structs as a map of unknown length, which the
+/// serializer can't tell apart from other maps of unknown length (e.g. a
+/// filtered iterator passed to `collect_map`), so unless wrapped they're
+/// written with the GenericMap ext under [`MapEncoding::Ext`]. Either
+/// encoding decodes into the struct.
+///
+/// [`MapEncoding::Ext`]: crate::MapEncoding::Ext
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Flattened<T>(pub T);
+
+impl<T: Serialize> Serialize for Flattened<T> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_newtype_struct(FLATTENED_TOKEN, &self.0)
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Flattened<T> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        T::deserialize(deserializer).map(Flattened)
+    }
+}
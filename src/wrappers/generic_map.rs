@@ -0,0 +1,208 @@
+use alloc::collections::{btree_map, BTreeMap};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Newtype name through which [`GenericMap`] asks the serializer for the
+/// GenericMap ext even when plain maps are configured.
+pub(crate) const GENERIC_MAP_TOKEN: &str = "$polywrap_msgpack::GenericMap";
+
+/// A map that is always encoded as the Polywrap GenericMap ext, regardless
+/// of the serializer's `map_encoding` setting.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct GenericMap<K, V>(BTreeMap<K, V>);
+
+impl<K, V> GenericMap<K, V> {
+    pub fn new() -> Self {
+        GenericMap(BTreeMap::new())
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn iter(&self) -> btree_map::Iter<'_, K, V> {
+        self.0.iter()
+    }
+
+    pub fn iter_mut(&mut self) -> btree_map::IterMut<'_, K, V> {
+        self.0.iter_mut()
+    }
+
+    pub fn keys(&self) -> btree_map::Keys<'_, K, V> {
+        self.0.keys()
+    }
+
+    pub fn values(&self) -> btree_map::Values<'_, K, V> {
+        self.0.values()
+    }
+
+    pub fn clear(&mut self) {
+        self.0.clear()
+    }
+}
+
+impl<K: Ord, V> GenericMap<K, V> {
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: core::borrow::Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.0.get(key)
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: core::borrow::Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.0.get_mut(key)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: core::borrow::Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.0.contains_key(key)
+    }
+
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        self.0.insert(key, value)
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: core::borrow::Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.0.remove(key)
+    }
+
+    pub fn entry(&mut self, key: K) -> btree_map::Entry<'_, K, V> {
+        self.0.entry(key)
+    }
+}
+
+impl<K, V> Default for GenericMap<K, V> {
+    fn default() -> Self {
+        GenericMap(BTreeMap::new())
+    }
+}
+
+impl<K: Ord, V> FromIterator<(K, V)> for GenericMap<K, V> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        GenericMap(BTreeMap::from_iter(iter))
+    }
+}
+
+impl<K: Ord, V> Extend<(K, V)> for GenericMap<K, V> {
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        self.0.extend(iter)
+    }
+}
+
+impl<K, V> IntoIterator for GenericMap<K, V> {
+    type Item = (K, V);
+    type IntoIter = btree_map::IntoIter<K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+impl<'a, K, V> IntoIterator for &'a GenericMap<K, V> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = btree_map::Iter<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
+impl<K, V> From<BTreeMap<K, V>> for GenericMap<K, V> {
+    fn from(value: BTreeMap<K, V>) -> Self {
+        GenericMap(value)
+    }
+}
+
+impl<K, V> From<GenericMap<K, V>> for BTreeMap<K, V> {
+    fn from(value: GenericMap<K, V>) -> Self {
+        value.0
+    }
+}
+
+impl<K: Serialize, V: Serialize> Serialize for GenericMap<K, V> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_newtype_struct(GENERIC_MAP_TOKEN, &self.0)
+    }
+}
+
+impl<'de, K, V> Deserialize<'de> for GenericMap<K, V>
+where
+    K: Ord + Deserialize<'de>,
+    V: Deserialize<'de>,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        BTreeMap::deserialize(deserializer).map(GenericMap)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::GenericMap;
+    use crate::{from_slice, to_vec, to_vec_with_config, SerializerConfig};
+
+    #[test]
+    fn test_generic_map_collection_api() {
+        let mut map: GenericMap<String, u32> =
+            [("a".to_string(), 1)].into_iter().collect();
+        map.extend([("b".to_string(), 2)]);
+        *map.entry("a".to_string()).or_insert(0) += 10;
+        map.entry("c".to_string()).or_insert(3);
+
+        assert_eq!(Some(&11), map.get("a"));
+        assert_eq!(Some(2), map.insert("b".to_string(), 20));
+        assert!(map.contains_key("c"));
+        assert_eq!(
+            vec![("a", 11), ("b", 20), ("c", 3)],
+            map.iter().map(|(k, v)| (k.as_str(), *v)).collect::<Vec<_>>()
+        );
+
+        let btree: BTreeMap<String, u32> = map.clone().into();
+        assert_eq!(map, GenericMap::from(btree));
+    }
+
+    #[test]
+    fn test_generic_map_always_uses_ext() {
+        let map: GenericMap<String, u8> =
+            [("a".to_string(), 1)].into_iter().collect();
+        let bytes = [214, 1, 129, 161, 97, 1];
+
+        assert_eq!(bytes.to_vec(), to_vec(&map).unwrap());
+        assert_eq!(
+            bytes.to_vec(),
+            to_vec_with_config(&map, SerializerConfig::rmp_compat()).unwrap()
+        );
+        // Nested maps follow the configuration again
+        let nested: GenericMap<u8, BTreeMap<u8, u8>> =
+            [(1, BTreeMap::from([(2, 3)]))].into_iter().collect();
+        assert_eq!(
+            vec![199, 5, 1, 129, 1, 129, 2, 3],
+            to_vec_with_config(&nested, SerializerConfig::rmp_compat())
+                .unwrap()
+        );
+        assert_eq!(map, from_slice(&bytes).unwrap());
+    }
+}
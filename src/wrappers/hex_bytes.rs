@@ -0,0 +1,106 @@
+//! `#[serde(with = "hex_bytes")]` helpers encoding a byte field as a
+//! `0x`-prefixed lowercase hex string rather than a bin, as Ethereum-facing
+//! wraps expect. Decoding accepts either case, with or without the prefix.
+
+use alloc::{format, string::String, vec::Vec};
+use core::fmt;
+
+use serde::{de, Deserializer, Serializer};
+
+const DIGITS: &[u8; 16] = b"0123456789abcdef";
+
+pub fn serialize<T, S>(bytes: &T, s: S) -> Result<S::Ok, S::Error>
+where
+    T: ?Sized + AsRef<[u8]>,
+    S: Serializer,
+{
+    s.serialize_str(&encode(bytes.as_ref()))
+}
+
+pub fn deserialize<'de, D>(deserializer: D) -> Result<Vec<u8>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserializer.deserialize_str(HexVisitor)
+}
+
+pub(crate) fn encode(bytes: &[u8]) -> String {
+    let mut hex = String::with_capacity(2 + bytes.len() * 2);
+    hex.push_str("0x");
+    for byte in bytes {
+        hex.push(DIGITS[(byte >> 4) as usize] as char);
+        hex.push(DIGITS[(byte & 0xf) as usize] as char);
+    }
+    hex
+}
+
+pub(crate) fn decode(hex: &str) -> Result<Vec<u8>, String> {
+    let digits = hex
+        .strip_prefix("0x")
+        .or_else(|| hex.strip_prefix("0X"))
+        .unwrap_or(hex)
+        .as_bytes();
+    if digits.len() % 2 != 0 {
+        return Err(format!(
+            "Invalid hex string of {} digits, expected an even number",
+            digits.len()
+        ));
+    }
+
+    let nibble = |i: usize| match digits[i] {
+        c @ b'0'..=b'9' => Ok(c - b'0'),
+        c @ b'a'..=b'f' => Ok(c - b'a' + 10),
+        c @ b'A'..=b'F' => Ok(c - b'A' + 10),
+        c => Err(format!("Invalid hex digit {:?} at position {i}", c as char)),
+    };
+    (0..digits.len())
+        .step_by(2)
+        .map(|i| Ok(nibble(i)? << 4 | nibble(i + 1)?))
+        .collect()
+}
+
+struct HexVisitor;
+
+impl<'de> de::Visitor<'de> for HexVisitor {
+    type Value = Vec<u8>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a hex string")
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Vec<u8>, E>
+    where
+        E: de::Error,
+    {
+        decode(v).map_err(E::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_derive::{Deserialize, Serialize};
+
+    use crate::{from_slice, to_vec};
+
+    #[test]
+    fn test_hex_bytes_field() {
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Tx {
+            #[serde(with = "super")]
+            data: Vec<u8>,
+        }
+
+        let tx = Tx {
+            data: vec![0xde, 0xad, 0x00, 0x0f],
+        };
+        let bytes = to_vec(&tx).unwrap();
+        // FixStr of 10: "0xdead000f"
+        assert!(bytes.ends_with(b"\xaa0xdead000f"));
+        assert_eq!(tx, from_slice(&bytes).unwrap());
+
+        assert_eq!(Ok(vec![0xab, 0x01]), super::decode("AB01"));
+        assert_eq!(Ok(vec![]), super::decode("0x"));
+        assert!(super::decode("0xabc").unwrap_err().contains("even"));
+        assert!(super::decode("0xzz").unwrap_err().contains("'z'"));
+    }
+}
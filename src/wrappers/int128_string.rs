@@ -0,0 +1,164 @@
+use alloc::format;
+use core::{fmt, str::FromStr};
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+macro_rules! int128_string {
+    ($(#[$doc:meta])* $name:ident, $int:ty, $expecting:literal) => {
+        $(#[$doc])*
+        #[derive(
+            Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash,
+        )]
+        pub struct $name(pub $int);
+
+        impl From<$int> for $name {
+            fn from(value: $int) -> Self {
+                Self(value)
+            }
+        }
+
+        impl From<$name> for $int {
+            fn from(value: $name) -> Self {
+                value.0
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                fmt::Display::fmt(&self.0, f)
+            }
+        }
+
+        impl FromStr for $name {
+            type Err = core::num::ParseIntError;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                s.parse().map(Self)
+            }
+        }
+
+        impl Serialize for $name {
+            fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+            where
+                S: Serializer,
+            {
+                serializer.collect_str(&self.0)
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $name {
+            fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+            where
+                D: Deserializer<'de>,
+            {
+                struct StrVisitor;
+
+                impl<'de> de::Visitor<'de> for StrVisitor {
+                    type Value = $name;
+
+                    fn expecting(
+                        &self,
+                        formatter: &mut fmt::Formatter,
+                    ) -> fmt::Result {
+                        formatter.write_str($expecting)
+                    }
+
+                    fn visit_str<E>(self, v: &str) -> Result<$name, E>
+                    where
+                        E: de::Error,
+                    {
+                        v.parse().map_err(|e| {
+                            E::custom(format!(
+                                "Error parsing {}: {e}",
+                                stringify!($int)
+                            ))
+                        })
+                    }
+                }
+
+                deserializer.deserialize_str(StrVisitor)
+            }
+        }
+    };
+}
+
+int128_string!(
+    /// A `u128` encoded as its decimal string, for runtimes without 128-bit
+    /// integers.
+    U128String,
+    u128,
+    "a u128 string"
+);
+
+int128_string!(
+    /// An `i128` encoded as its decimal string, for runtimes without 128-bit
+    /// integers.
+    I128String,
+    i128,
+    "an i128 string"
+);
+
+impl TryFrom<I128String> for U128String {
+    type Error = core::num::TryFromIntError;
+
+    fn try_from(value: I128String) -> Result<Self, Self::Error> {
+        u128::try_from(value.0).map(Self)
+    }
+}
+
+impl TryFrom<U128String> for I128String {
+    type Error = core::num::TryFromIntError;
+
+    fn try_from(value: U128String) -> Result<Self, Self::Error> {
+        i128::try_from(value.0).map(Self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_derive::{Deserialize, Serialize};
+
+    use super::{I128String, U128String};
+    use crate::{from_slice, to_vec};
+
+    #[test]
+    fn test_int128_strings() {
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Supply {
+            total: U128String,
+            delta: I128String,
+        }
+
+        #[derive(Serialize)]
+        struct Strings {
+            total: String,
+            delta: String,
+        }
+
+        let supply = Supply {
+            total: U128String(u128::MAX),
+            delta: I128String(i128::MIN),
+        };
+        let bytes = to_vec(&supply).unwrap();
+        let expected = to_vec(&Strings {
+            total: u128::MAX.to_string(),
+            delta: i128::MIN.to_string(),
+        })
+        .unwrap();
+        assert_eq!(expected, bytes);
+        assert_eq!(supply, from_slice(&bytes).unwrap());
+
+        // One past u128::MAX
+        let overflow =
+            to_vec(&"340282366920938463463374607431768211456").unwrap();
+        let err = from_slice::<U128String>(&overflow).unwrap_err();
+        assert!(err.to_string().contains("Error parsing u128"));
+        assert!(from_slice::<U128String>(&to_vec(&"-1").unwrap()).is_err());
+        assert!(from_slice::<U128String>(&to_vec(&1u8).unwrap()).is_err());
+
+        assert_eq!(Ok(I128String(5)), I128String::try_from(U128String(5)));
+        assert!(I128String::try_from(U128String(u128::MAX)).is_err());
+        assert!(U128String::try_from(I128String(-1)).is_err());
+        assert_eq!(7u128, u128::from(U128String(7)));
+    }
+}
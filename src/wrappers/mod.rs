@@ -1,2 +1,94 @@
+#[cfg(feature = "base64")]
+pub mod base64_bytes;
+pub mod ext_value;
+pub mod fixed_bytes;
+pub mod flattened;
+pub mod generic_map;
+pub mod hex_bytes;
+pub mod int128_string;
 pub mod polywrap_bigint;
-pub mod polywrap_json;
\ No newline at end of file
+pub mod polywrap_bignumber;
+#[cfg(feature = "chrono")]
+pub mod polywrap_datetime;
+pub mod polywrap_json;
+#[cfg(feature = "uuid")]
+pub mod polywrap_uuid;
+#[cfg(feature = "bytes")]
+pub mod shared_bytes;
+pub mod shared_string;
+pub mod timestamp;
+
+use alloc::vec::Vec;
+use core::{fmt, marker::PhantomData};
+
+use serde::{
+    de::{MapAccess, Visitor},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+
+use crate::value::cautious_capacity;
+
+/// Serializes a map with each value converted by `f`, for the
+/// `map_values` with-helpers.
+pub(crate) fn serialize_map_values<'a, S, K, V, T, M>(
+    map: &'a M,
+    s: S,
+    f: impl Fn(&V) -> T,
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+    &'a M: IntoIterator<Item = (&'a K, &'a V)>,
+    K: Serialize + 'a,
+    V: 'a,
+    T: Serialize,
+{
+    s.collect_map(map.into_iter().map(|(k, v)| (k, f(v))))
+}
+
+/// Deserializes a map whose values decode as `W`, converting each by `f`,
+/// for the `map_values` with-helpers.
+pub(crate) fn deserialize_map_values<'de, D, K, W, V, M>(
+    deserializer: D,
+    f: fn(W) -> V,
+) -> Result<M, D::Error>
+where
+    D: Deserializer<'de>,
+    K: Deserialize<'de>,
+    W: Deserialize<'de>,
+    M: FromIterator<(K, V)>,
+{
+    deserializer.deserialize_map(MapValuesVisitor {
+        f,
+        marker: PhantomData,
+    })
+}
+
+struct MapValuesVisitor<K, W, V, M> {
+    f: fn(W) -> V,
+    marker: PhantomData<(K, M)>,
+}
+
+impl<'de, K, W, V, M> Visitor<'de> for MapValuesVisitor<K, W, V, M>
+where
+    K: Deserialize<'de>,
+    W: Deserialize<'de>,
+    M: FromIterator<(K, V)>,
+{
+    type Value = M;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a map")
+    }
+
+    fn visit_map<A>(self, mut access: A) -> Result<M, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let mut entries =
+            Vec::with_capacity(cautious_capacity::<(K, V)>(access.size_hint()));
+        while let Some((k, w)) = access.next_entry::<K, W>()? {
+            entries.push((k, (self.f)(w)));
+        }
+        Ok(entries.into_iter().collect())
+    }
+}
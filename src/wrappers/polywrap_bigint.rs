@@ -1,14 +1,85 @@
-use std::{
+use alloc::{format, string::ToString};
+use core::{
     fmt::{self},
+    ops::{Deref, DerefMut},
     str::FromStr,
 };
 
-use num_bigint::BigInt;
+use num_bigint::{BigInt, ParseBigIntError};
 use serde::{de::Visitor, Deserialize, Serialize, Serializer, Deserializer};
 
-#[derive(Debug, PartialEq, Clone)]
+/// A `BigInt` encoded as its decimal string. Derefs to the `BigInt`, so its
+/// methods can be called directly.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Default)]
 pub struct BigIntWrapper(pub BigInt);
 
+impl BigIntWrapper {
+    pub fn into_inner(self) -> BigInt {
+        self.0
+    }
+}
+
+impl Deref for BigIntWrapper {
+    type Target = BigInt;
+
+    fn deref(&self) -> &BigInt {
+        &self.0
+    }
+}
+
+impl DerefMut for BigIntWrapper {
+    fn deref_mut(&mut self) -> &mut BigInt {
+        &mut self.0
+    }
+}
+
+impl fmt::Display for BigIntWrapper {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+impl FromStr for BigIntWrapper {
+    type Err = ParseBigIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        BigInt::from_str(s).map(Self)
+    }
+}
+
+/// Parsing can fail, so strings convert with `TryFrom` rather than `From`.
+impl TryFrom<&str> for BigIntWrapper {
+    type Error = ParseBigIntError;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<BigInt> for BigIntWrapper {
+    fn from(value: BigInt) -> Self {
+        Self(value)
+    }
+}
+
+impl From<BigIntWrapper> for BigInt {
+    fn from(value: BigIntWrapper) -> Self {
+        value.0
+    }
+}
+
+impl From<i64> for BigIntWrapper {
+    fn from(value: i64) -> Self {
+        Self(value.into())
+    }
+}
+
+impl From<u64> for BigIntWrapper {
+    fn from(value: u64) -> Self {
+        Self(value.into())
+    }
+}
+
 pub fn serialize<S>(x: &BigInt, s: S) -> Result<S::Ok, S::Error>
 where
     S: Serializer,
@@ -61,3 +132,184 @@ impl<'a> Deserialize<'a> for BigIntWrapper {
         deserializer.deserialize_str(BigIntStrVisitor)
     }
 }
+
+/// `#[serde(with = "polywrap_bigint::option")]` helpers for
+/// `Option<BigInt>` fields, written as nil when `None`.
+pub mod option {
+    use alloc::string::ToString;
+
+    use num_bigint::BigInt;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    use super::BigIntWrapper;
+
+    pub fn serialize<S>(x: &Option<BigInt>, s: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match x {
+            Some(x) => s.serialize_some(&x.to_string()),
+            None => s.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D>(
+        deserializer: D,
+    ) -> Result<Option<BigInt>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Ok(Option::<BigIntWrapper>::deserialize(deserializer)?.map(|x| x.0))
+    }
+}
+
+/// `#[serde(with = "polywrap_bigint::vec")]` helpers for `Vec<BigInt>`
+/// fields, written as an array of strings.
+pub mod vec {
+    use alloc::{string::ToString, vec::Vec};
+
+    use num_bigint::BigInt;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    use super::BigIntWrapper;
+
+    pub fn serialize<S>(x: &[BigInt], s: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        s.collect_seq(x.iter().map(|x| x.to_string()))
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Vec<BigInt>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let wrappers = Vec::<BigIntWrapper>::deserialize(deserializer)?;
+        Ok(wrappers.into_iter().map(|x| x.0).collect())
+    }
+}
+
+/// `#[serde(with = "polywrap_bigint::map_values")]` helpers for maps of
+/// `BigInt`s, such as `Map<String, BigInt>`, written with string values.
+pub mod map_values {
+    use alloc::string::ToString;
+
+    use num_bigint::BigInt;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::BigIntWrapper;
+    use crate::wrappers::{deserialize_map_values, serialize_map_values};
+
+    pub fn serialize<'a, S, K, M>(x: &'a M, s: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+        &'a M: IntoIterator<Item = (&'a K, &'a BigInt)>,
+        K: Serialize + 'a,
+    {
+        serialize_map_values(x, s, BigInt::to_string)
+    }
+
+    pub fn deserialize<'de, D, K, M>(deserializer: D) -> Result<M, D::Error>
+    where
+        D: Deserializer<'de>,
+        K: Deserialize<'de>,
+        M: FromIterator<(K, BigInt)>,
+    {
+        deserialize_map_values(deserializer, |x: BigIntWrapper| x.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use num_bigint::BigInt;
+    use serde_derive::{Deserialize, Serialize};
+
+    use crate::{from_slice, to_vec, wrappers::polywrap_bigint};
+
+    #[test]
+    fn test_optional_bigint_fields() {
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Balance {
+            #[serde(with = "polywrap_bigint::option")]
+            locked: Option<BigInt>,
+            #[serde(with = "polywrap_bigint::option", default)]
+            pending: Option<BigInt>,
+        }
+
+        let balance = Balance {
+            locked: Some(BigInt::from(-170141183460469231731687303715i128)),
+            pending: None,
+        };
+        let bytes = to_vec(&balance).unwrap();
+        assert_eq!(balance, from_slice(&bytes).unwrap());
+        assert!(bytes.ends_with(&[0xc0]));
+
+        // {"locked": "12"}, with "pending" missing
+        let bytes = [129, 166, 108, 111, 99, 107, 101, 100, 162, 49, 50];
+        let balance: Balance = from_slice(&bytes).unwrap();
+        assert_eq!(Some(BigInt::from(12)), balance.locked);
+        assert_eq!(None, balance.pending);
+    }
+
+    #[test]
+    fn test_bigint_wrapper_conversions() {
+        use super::BigIntWrapper;
+
+        let mut wrapper = BigIntWrapper::from(-5i64);
+        assert_eq!(BigInt::from(-5), *wrapper);
+        *wrapper += 10;
+        assert_eq!("5", wrapper.to_string());
+        assert_eq!(3, wrapper.bits());
+
+        let parsed: BigIntWrapper = "123456789012345678901234567890"
+            .parse()
+            .unwrap();
+        assert!(parsed > BigIntWrapper::from(u64::MAX));
+        let text = parsed.to_string();
+        assert_eq!(parsed, BigIntWrapper::try_from(text.as_str()).unwrap());
+        assert!(BigIntWrapper::try_from("12a").is_err());
+
+        let set = std::collections::HashSet::from([wrapper.clone(), parsed]);
+        assert!(set.contains(&BigIntWrapper::from(5u64)));
+        assert_eq!(BigInt::from(5), wrapper.into_inner());
+    }
+
+    #[test]
+    fn test_bigint_collection_fields() {
+        use std::collections::HashMap;
+
+        use crate::Map;
+
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Ledger {
+            #[serde(with = "polywrap_bigint::vec")]
+            history: Vec<BigInt>,
+            #[serde(with = "polywrap_bigint::map_values")]
+            balances: Map<String, BigInt>,
+            #[serde(with = "polywrap_bigint::map_values")]
+            by_id: HashMap<u8, BigInt>,
+        }
+
+        #[derive(Serialize)]
+        struct Strings {
+            history: Vec<&'static str>,
+            balances: Map<&'static str, &'static str>,
+            by_id: HashMap<u8, String>,
+        }
+
+        let ledger = Ledger {
+            history: vec![BigInt::from(1), BigInt::from(-2)],
+            balances: Map::from([("a".to_string(), BigInt::from(3))]),
+            by_id: HashMap::from([(7, BigInt::from(u128::MAX))]),
+        };
+        let bytes = to_vec(&ledger).unwrap();
+        let expected = to_vec(&Strings {
+            history: vec!["1", "-2"],
+            balances: Map::from([("a", "3")]),
+            by_id: HashMap::from([(7, u128::MAX.to_string())]),
+        })
+        .unwrap();
+        assert_eq!(expected, bytes);
+        assert_eq!(ledger, from_slice(&bytes).unwrap());
+    }
+}
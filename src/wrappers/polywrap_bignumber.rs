@@ -0,0 +1,144 @@
+use alloc::{format, string::ToString};
+use core::{fmt, str::FromStr};
+
+use bigdecimal::BigDecimal;
+use serde::{de::Visitor, Deserialize, Deserializer, Serialize, Serializer};
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct BigNumberWrapper(pub BigDecimal);
+
+pub fn serialize<S>(x: &BigDecimal, s: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    s.serialize_str(&x.to_string())
+}
+
+pub fn deserialize<'de, D>(deserializer: D) -> Result<BigDecimal, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(deserializer.deserialize_str(BigNumberStrVisitor)?.0)
+}
+
+impl Serialize for BigNumberWrapper {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(&self.0.to_string())
+    }
+}
+
+struct BigNumberStrVisitor;
+
+impl<'de> Visitor<'de> for BigNumberStrVisitor {
+    type Value = BigNumberWrapper;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a BigNumber string")
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+    where
+        E: serde::de::Error,
+    {
+        let big_number = BigDecimal::from_str(v).map_err(|e| {
+            serde::de::Error::custom(format!("Error parsing BigNumber: {e}"))
+        })?;
+
+        Ok(BigNumberWrapper(big_number))
+    }
+}
+
+impl<'a> Deserialize<'a> for BigNumberWrapper {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'a>,
+    {
+        deserializer.deserialize_str(BigNumberStrVisitor)
+    }
+}
+
+/// `#[serde(with = "polywrap_bignumber::option")]` helpers for
+/// `Option<BigDecimal>` fields, written as nil when `None`.
+pub mod option {
+    use alloc::string::ToString;
+
+    use bigdecimal::BigDecimal;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    use super::BigNumberWrapper;
+
+    pub fn serialize<S>(x: &Option<BigDecimal>, s: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match x {
+            Some(x) => s.serialize_some(&x.to_string()),
+            None => s.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D>(
+        deserializer: D,
+    ) -> Result<Option<BigDecimal>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Ok(Option::<BigNumberWrapper>::deserialize(deserializer)?.map(|x| x.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::str::FromStr;
+
+    use bigdecimal::BigDecimal;
+    use serde_derive::{Deserialize, Serialize};
+
+    use super::BigNumberWrapper;
+    use crate::{from_slice, to_vec, wrappers::polywrap_bignumber};
+
+    #[test]
+    fn test_bignumber_fields() {
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Price {
+            #[serde(with = "polywrap_bignumber")]
+            amount: BigDecimal,
+            fee: BigNumberWrapper,
+        }
+
+        let price = Price {
+            amount: BigDecimal::from_str("3124124512.598273468017578125")
+                .unwrap(),
+            fee: BigNumberWrapper(BigDecimal::from_str("-0.5").unwrap()),
+        };
+        let bytes = to_vec(&price).unwrap();
+        let mut expected = vec![0x82, 0xa6];
+        expected.extend(b"amount\xbd3124124512.598273468017578125");
+        expected.extend(b"\xa3fee\xa4-0.5");
+        assert_eq!(expected, bytes);
+        assert_eq!(price, from_slice(&bytes).unwrap());
+
+        assert!(from_slice::<BigNumberWrapper>(&[0xa1, b'x']).is_err());
+    }
+
+    #[test]
+    fn test_optional_bignumber_fields() {
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Quote {
+            #[serde(with = "polywrap_bignumber::option")]
+            bid: Option<BigDecimal>,
+            #[serde(with = "polywrap_bignumber::option")]
+            ask: Option<BigDecimal>,
+        }
+
+        let quote = Quote {
+            bid: Some(BigDecimal::from_str("1.25").unwrap()),
+            ask: None,
+        };
+        let bytes = to_vec(&quote).unwrap();
+        assert_eq!(quote, from_slice(&bytes).unwrap());
+    }
+}
@@ -0,0 +1,95 @@
+//! `#[serde(with = "polywrap_datetime")]` helpers for `DateTime<Utc>`
+//! fields, written as ISO-8601 strings as the WRAP schema's `datetime`
+//! convention expects.
+//!
+//! Fields shared with other msgpack libraries rather than wrappers can use
+//! [`timestamp`] instead, which writes the msgpack timestamp ext.
+
+use alloc::format;
+use core::fmt;
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::{de::Visitor, Deserializer, Serializer};
+
+pub fn serialize<S>(x: &DateTime<Utc>, s: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    s.serialize_str(&x.to_rfc3339_opts(SecondsFormat::AutoSi, true))
+}
+
+/// Accepts any offset, converting the date-time to UTC.
+pub fn deserialize<'de, D>(deserializer: D) -> Result<DateTime<Utc>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserializer.deserialize_str(DateTimeStrVisitor)
+}
+
+struct DateTimeStrVisitor;
+
+impl<'de> Visitor<'de> for DateTimeStrVisitor {
+    type Value = DateTime<Utc>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("an ISO-8601 date-time string")
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+    where
+        E: serde::de::Error,
+    {
+        DateTime::parse_from_rfc3339(v)
+            .map(|datetime| datetime.with_timezone(&Utc))
+            .map_err(|e| {
+                serde::de::Error::custom(format!(
+                    "Error parsing date-time '{v}': {e}"
+                ))
+            })
+    }
+}
+
+/// `#[serde(with = "polywrap_datetime::timestamp")]` helpers writing the
+/// msgpack timestamp ext instead of a string.
+pub mod timestamp {
+    pub use crate::wrappers::timestamp::chrono_utc::{deserialize, serialize};
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, Utc};
+    use serde_derive::{Deserialize, Serialize};
+
+    use crate::{from_slice, to_vec, wrappers::polywrap_datetime};
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Event {
+        #[serde(with = "polywrap_datetime")]
+        created: DateTime<Utc>,
+        #[serde(with = "polywrap_datetime::timestamp")]
+        updated: DateTime<Utc>,
+    }
+
+    #[test]
+    fn test_datetime_fields() {
+        let event = Event {
+            created: DateTime::from_timestamp(1_700_000_000, 5_000_000)
+                .unwrap(),
+            updated: DateTime::from_timestamp(1_700_000_000, 0).unwrap(),
+        };
+        let bytes = to_vec(&event).unwrap();
+        let mut expected = vec![0x82, 0xa7];
+        expected.extend(b"created\xb82023-11-14T22:13:20.005Z\xa7updated");
+        expected.extend([0xd6, 0xff, 0x65, 0x53, 0xf1, 0x00]);
+        assert_eq!(expected, bytes);
+        assert_eq!(event, from_slice(&bytes).unwrap());
+
+        let created: DateTime<Utc> = polywrap_datetime::deserialize(
+            &mut crate::Deserializer::from_slice(
+                b"\xb92023-11-14T23:13:20+01:00",
+            ),
+        )
+        .unwrap();
+        assert_eq!(event.updated, created);
+    }
+}
@@ -1,4 +1,5 @@
-use std::fmt::{self};
+use alloc::{format, string::ToString};
+use core::fmt::{self};
 
 use serde_json::Value;
 use serde::{de::Visitor, Deserialize, Serialize, Serializer, Deserializer};
@@ -14,6 +15,27 @@ impl JSONString {
   pub fn to_json(&self) -> serde_json::Value {
     self.0.clone()
   }
+
+  /// Converts any serializable value, e.g. a typed struct, to its JSON.
+  pub fn from_serializable<T>(value: &T) -> serde_json::Result<Self>
+  where
+      T: ?Sized + Serialize,
+  {
+      serde_json::to_value(value).map(Self)
+  }
+
+  /// Decodes the JSON into a typed value without cloning it.
+  pub fn to_type<T>(&self) -> serde_json::Result<T>
+  where
+      T: serde::de::DeserializeOwned,
+  {
+      T::deserialize(&self.0)
+  }
+
+  /// Looks up a value by JSON Pointer, e.g. `"/items/0/name"`.
+  pub fn pointer(&self, path: &str) -> Option<&Value> {
+      self.0.pointer(path)
+  }
 }
 
 pub fn serialize<S>(x: &Value, s: S) -> Result<S::Ok, S::Error>
@@ -79,4 +101,220 @@ impl From<serde_json::Value> for JSONString {
     fn from(value: serde_json::Value) -> Self {
         JSONString::new(value)
     }
-}
\ No newline at end of file
+}
+
+/// `#[serde(with = "polywrap_json::option")]` helpers for
+/// `Option<serde_json::Value>` fields, written as nil when `None`.
+pub mod option {
+  use alloc::string::ToString;
+  use serde::{Deserialize, Deserializer, Serializer};
+  use serde_json::Value;
+
+  use super::JSONString;
+
+  pub fn serialize<S>(x: &Option<Value>, s: S) -> Result<S::Ok, S::Error>
+  where
+      S: Serializer,
+  {
+      match x {
+          Some(x) => s.serialize_some(&x.to_string()),
+          None => s.serialize_none(),
+      }
+  }
+
+  pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<Value>, D::Error>
+  where
+      D: Deserializer<'de>,
+  {
+      Ok(Option::<JSONString>::deserialize(deserializer)?.map(|x| x.0))
+  }
+}
+
+/// `#[serde(with = "polywrap_json::vec")]` helpers for
+/// `Vec<serde_json::Value>` fields, written as an array of JSON strings.
+pub mod vec {
+  use alloc::{string::ToString, vec::Vec};
+  use serde::{Deserialize, Deserializer, Serializer};
+  use serde_json::Value;
+
+  use super::JSONString;
+
+  pub fn serialize<S>(x: &[Value], s: S) -> Result<S::Ok, S::Error>
+  where
+      S: Serializer,
+  {
+      s.collect_seq(x.iter().map(|x| x.to_string()))
+  }
+
+  pub fn deserialize<'de, D>(deserializer: D) -> Result<Vec<Value>, D::Error>
+  where
+      D: Deserializer<'de>,
+  {
+      let wrappers = Vec::<JSONString>::deserialize(deserializer)?;
+      Ok(wrappers.into_iter().map(|x| x.0).collect())
+  }
+}
+
+/// `#[serde(with = "polywrap_json::map_values")]` helpers for maps of
+/// `serde_json::Value`s, written with JSON string values.
+pub mod map_values {
+  use alloc::string::ToString;
+  use serde::{Deserialize, Deserializer, Serialize, Serializer};
+  use serde_json::Value;
+
+  use super::JSONString;
+  use crate::wrappers::{deserialize_map_values, serialize_map_values};
+
+  pub fn serialize<'a, S, K, M>(x: &'a M, s: S) -> Result<S::Ok, S::Error>
+  where
+      S: Serializer,
+      &'a M: IntoIterator<Item = (&'a K, &'a Value)>,
+      K: Serialize + 'a,
+  {
+      serialize_map_values(x, s, Value::to_string)
+  }
+
+  pub fn deserialize<'de, D, K, M>(deserializer: D) -> Result<M, D::Error>
+  where
+      D: Deserializer<'de>,
+      K: Deserialize<'de>,
+      M: FromIterator<(K, Value)>,
+  {
+      deserialize_map_values(deserializer, |x: JSONString| x.0)
+  }
+}
+
+/// `#[serde(with = "polywrap_json::native")]` helpers writing a
+/// `serde_json::Value` as msgpack structures rather than a JSON string:
+/// objects become maps (following the serializer's map encoding), arrays
+/// become arrays and numbers become integers or floats. Decoding accepts
+/// any msgpack value with string map keys and no bins or exts.
+pub mod native {
+  use serde::{Deserialize, Deserializer, Serialize, Serializer};
+  use serde_json::Value;
+
+  pub fn serialize<S>(x: &Value, s: S) -> Result<S::Ok, S::Error>
+  where
+      S: Serializer,
+  {
+      x.serialize(s)
+  }
+
+  pub fn deserialize<'de, D>(deserializer: D) -> Result<Value, D::Error>
+  where
+      D: Deserializer<'de>,
+  {
+      Value::deserialize(deserializer)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use serde_derive::{Deserialize, Serialize};
+  use serde_json::{json, Value};
+
+  use crate::{from_slice, to_vec, wrappers::polywrap_json};
+
+  #[test]
+  fn test_optional_json_fields() {
+      #[derive(Debug, PartialEq, Serialize, Deserialize)]
+      struct Config {
+          #[serde(with = "polywrap_json::option")]
+          extra: Option<Value>,
+      }
+
+      for config in [
+          Config { extra: Some(json!({"a": [1, null]})) },
+          Config { extra: None },
+      ] {
+          let bytes = to_vec(&config).unwrap();
+          assert_eq!(config, from_slice(&bytes).unwrap());
+      }
+      // A JSON null is kept apart from a missing value
+      let null = Config { extra: Some(Value::Null) };
+      let bytes = to_vec(&null).unwrap();
+      assert!(bytes.ends_with(&[0xa4, b'n', b'u', b'l', b'l']));
+  }
+
+  #[test]
+  fn test_native_json_fields() {
+      use crate::{to_vec_with_config, MapEncoding, SerializerConfig};
+
+      #[derive(Debug, PartialEq, Serialize, Deserialize)]
+      struct Event {
+          #[serde(with = "polywrap_json::native")]
+          data: Value,
+      }
+
+      let event = Event {
+          data: json!({"ids": [1, -2, 3.5], "ok": true, "name": null}),
+      };
+      let config = SerializerConfig::default().map_encoding(MapEncoding::Plain);
+      let bytes = to_vec_with_config(&event, config).unwrap();
+      // {"data": {"ids": [1, -2, 3.5], "name": nil, "ok": true}}
+      let mut expected = vec![0x81, 0xa4];
+      expected.extend(b"data\x83\xa3ids\x93\x01\xfe");
+      expected.extend([0xcb, 0x40, 0x0c, 0, 0, 0, 0, 0, 0]);
+      expected.extend(b"\xa4name\xc0\xa2ok\xc3");
+      assert_eq!(expected, bytes);
+      assert_eq!(event, from_slice(&bytes).unwrap());
+
+      // The default, ext-wrapped map encoding decodes as well
+      let bytes = to_vec(&event).unwrap();
+      assert_eq!(event, from_slice(&bytes).unwrap());
+  }
+
+  #[test]
+  fn test_json_string_typed_helpers() {
+      use super::JSONString;
+
+      #[derive(Debug, PartialEq, Serialize, Deserialize)]
+      struct Item {
+          name: String,
+          tags: Vec<String>,
+      }
+
+      let item = Item {
+          name: "a".to_string(),
+          tags: vec!["x".to_string()],
+      };
+      let json = JSONString::from_serializable(&item).unwrap();
+      assert_eq!(json!({"name": "a", "tags": ["x"]}), json.to_json());
+      assert_eq!(Some(&json!("x")), json.pointer("/tags/0"));
+      assert_eq!(None, json.pointer("/missing"));
+      assert_eq!(item, json.to_type::<Item>().unwrap());
+      assert!(json.to_type::<Vec<u8>>().is_err());
+  }
+
+  #[test]
+  fn test_json_collection_fields() {
+      use crate::Map;
+
+      #[derive(Debug, PartialEq, Serialize, Deserialize)]
+      struct Batch {
+          #[serde(with = "polywrap_json::vec")]
+          items: Vec<Value>,
+          #[serde(with = "polywrap_json::map_values")]
+          by_name: Map<String, Value>,
+      }
+
+      #[derive(Serialize)]
+      struct Strings {
+          items: Vec<&'static str>,
+          by_name: Map<&'static str, &'static str>,
+      }
+
+      let batch = Batch {
+          items: vec![json!([1]), json!({"b": true})],
+          by_name: Map::from([("a".to_string(), json!("x"))]),
+      };
+      let bytes = to_vec(&batch).unwrap();
+      let expected = to_vec(&Strings {
+          items: vec!["[1]", r#"{"b":true}"#],
+          by_name: Map::from([("a", r#""x""#)]),
+      })
+      .unwrap();
+      assert_eq!(expected, bytes);
+      assert_eq!(batch, from_slice(&bytes).unwrap());
+  }
+}
@@ -0,0 +1,140 @@
+//! `#[serde(with = "polywrap_uuid")]` helpers writing a [`Uuid`] as its
+//! 36-character hyphenated string, the way wrap ABIs model IDs.
+//!
+//! [`bytes`] writes the 16 raw bytes as a bin instead. Both helpers decode
+//! either form.
+
+use alloc::format;
+use core::fmt;
+
+use serde::{de::Visitor, Deserialize, Deserializer, Serialize, Serializer};
+use uuid::Uuid;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub struct UuidWrapper(pub Uuid);
+
+pub fn serialize<S>(x: &Uuid, s: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    s.serialize_str(x.hyphenated().encode_lower(&mut Uuid::encode_buffer()))
+}
+
+pub fn deserialize<'de, D>(deserializer: D) -> Result<Uuid, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(deserializer.deserialize_any(UuidVisitor)?.0)
+}
+
+impl Serialize for UuidWrapper {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serialize(&self.0, serializer)
+    }
+}
+
+struct UuidVisitor;
+
+impl<'de> Visitor<'de> for UuidVisitor {
+    type Value = UuidWrapper;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a UUID string or 16 bytes")
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+    where
+        E: serde::de::Error,
+    {
+        let uuid = Uuid::parse_str(v).map_err(|e| {
+            serde::de::Error::custom(format!("Error parsing UUID: {e}"))
+        })?;
+
+        Ok(UuidWrapper(uuid))
+    }
+
+    fn visit_bytes<E>(self, v: &[u8]) -> Result<Self::Value, E>
+    where
+        E: serde::de::Error,
+    {
+        let uuid = Uuid::from_slice(v).map_err(|e| {
+            serde::de::Error::custom(format!("Error parsing UUID: {e}"))
+        })?;
+
+        Ok(UuidWrapper(uuid))
+    }
+}
+
+impl<'a> Deserialize<'a> for UuidWrapper {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'a>,
+    {
+        deserializer.deserialize_any(UuidVisitor)
+    }
+}
+
+/// `#[serde(with = "polywrap_uuid::bytes")]` helpers writing a [`Uuid`] as
+/// a 16-byte bin.
+pub mod bytes {
+    use serde::{Deserializer, Serializer};
+    use uuid::Uuid;
+
+    pub fn serialize<S>(x: &Uuid, s: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        s.serialize_bytes(x.as_bytes())
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Uuid, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        super::deserialize(deserializer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_derive::{Deserialize, Serialize};
+    use uuid::Uuid;
+
+    use super::UuidWrapper;
+    use crate::{from_slice, to_vec, wrappers::polywrap_uuid};
+
+    #[test]
+    fn test_uuid_fields() {
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Account {
+            #[serde(with = "polywrap_uuid")]
+            id: Uuid,
+            #[serde(with = "polywrap_uuid::bytes")]
+            owner: Uuid,
+        }
+
+        let id = "67e55044-10b1-426f-9247-bb680e5fe0c8";
+        let account = Account {
+            id: Uuid::parse_str(id).unwrap(),
+            owner: Uuid::from_bytes([7; 16]),
+        };
+        let bytes = to_vec(&account).unwrap();
+        let mut expected = vec![130, 162, b'i', b'd', 0xd9, 36];
+        expected.extend(id.as_bytes());
+        expected.extend([165, b'o', b'w', b'n', b'e', b'r', 0xc4, 16]);
+        expected.extend([7; 16]);
+        assert_eq!(expected, bytes);
+        assert_eq!(account, from_slice(&bytes).unwrap());
+
+        // Either form decodes with either helper
+        let wrapper: UuidWrapper = from_slice(&bytes[4..42]).unwrap();
+        assert_eq!(account.id, wrapper.0);
+        let mut bin = vec![0xc4, 16];
+        bin.extend(account.id.as_bytes());
+        assert_eq!(UuidWrapper(account.id), from_slice(&bin).unwrap());
+        assert!(from_slice::<UuidWrapper>(&[0xa3, b'a', b'b', b'c']).is_err());
+    }
+}
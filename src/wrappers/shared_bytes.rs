@@ -0,0 +1,167 @@
+//! `#[serde(with = "shared_bytes")]` helpers for `bytes::Bytes` fields,
+//! written as a bin. Decoded with [`from_bytes`], the field shares the
+//! input buffer rather than copying out of it. Without the `std` feature
+//! the bytes are always copied.
+//!
+//! [`from_bytes`]: crate::from_bytes
+
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use core::cell::RefCell;
+use core::fmt;
+
+use bytes::Bytes;
+use serde::{de::Visitor, Deserializer, Serializer};
+
+#[cfg(feature = "std")]
+thread_local! {
+    /// The buffer being decoded by [`from_bytes`](crate::from_bytes), which
+    /// borrowed slices of it can be shared from.
+    static SOURCE: RefCell<Option<Bytes>> = const { RefCell::new(None) };
+}
+
+/// Makes `source` available to the [`Bytes`] visitors run by `decode`.
+#[cfg(feature = "std")]
+pub(crate) fn with_source<T>(source: &Bytes, decode: impl FnOnce() -> T) -> T {
+    /// Puts the outer source back once `decode` returns or unwinds.
+    struct Restore(Option<Bytes>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            let outer = self.0.take();
+            SOURCE.with(|slot| *slot.borrow_mut() = outer);
+        }
+    }
+
+    let _restore =
+        Restore(SOURCE.with(|slot| slot.replace(Some(source.clone()))));
+    decode()
+}
+
+#[cfg(not(feature = "std"))]
+pub(crate) fn with_source<T>(_source: &Bytes, decode: impl FnOnce() -> T) -> T {
+    decode()
+}
+
+/// `v` as a slice of the buffer being decoded, if it points into it.
+#[cfg(feature = "std")]
+fn shared_slice(v: &[u8]) -> Option<Bytes> {
+    SOURCE.with(|slot| {
+        let source = slot.borrow();
+        let source = source.as_ref()?;
+        let range = source.as_ptr_range();
+        (range.start <= v.as_ptr() && v.as_ptr_range().end <= range.end)
+            .then(|| source.slice_ref(v))
+    })
+}
+
+#[cfg(not(feature = "std"))]
+fn shared_slice(_v: &[u8]) -> Option<Bytes> {
+    None
+}
+
+pub fn serialize<S>(bytes: &Bytes, s: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    s.serialize_bytes(bytes)
+}
+
+pub fn deserialize<'de, D>(deserializer: D) -> Result<Bytes, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserializer.deserialize_bytes(BytesVisitor)
+}
+
+struct BytesVisitor;
+
+impl<'de> Visitor<'de> for BytesVisitor {
+    type Value = Bytes;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("bytes")
+    }
+
+    fn visit_borrowed_bytes<E>(self, v: &'de [u8]) -> Result<Bytes, E> {
+        Ok(shared_slice(v).unwrap_or_else(|| Bytes::copy_from_slice(v)))
+    }
+
+    fn visit_bytes<E>(self, v: &[u8]) -> Result<Bytes, E> {
+        Ok(Bytes::copy_from_slice(v))
+    }
+
+    fn visit_byte_buf<E>(self, v: Vec<u8>) -> Result<Bytes, E> {
+        Ok(Bytes::from(v))
+    }
+}
+
+/// `#[serde(with = "shared_bytes::bytes_mut")]` helpers for
+/// `bytes::BytesMut` fields, written as a bin.
+pub mod bytes_mut {
+    use bytes::BytesMut;
+    use serde::{Deserializer, Serializer};
+
+    pub fn serialize<S>(bytes: &BytesMut, s: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        s.serialize_bytes(bytes)
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<BytesMut, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        // A `BytesMut` owns its buffer, so it's always a copy.
+        let bytes = super::deserialize(deserializer)?;
+        Ok(BytesMut::from(&bytes[..]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::{Bytes, BytesMut};
+    use serde_derive::{Deserialize, Serialize};
+
+    use crate::{from_bytes, from_slice, to_vec};
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Frame {
+        #[serde(with = "super")]
+        body: Bytes,
+        #[serde(with = "super::bytes_mut")]
+        scratch: BytesMut,
+    }
+
+    #[test]
+    fn test_shared_bytes_fields() {
+        let frame = Frame {
+            body: Bytes::from_static(&[1, 2, 3]),
+            scratch: BytesMut::from(&[4][..]),
+        };
+        let input = Bytes::from(to_vec(&frame).unwrap());
+
+        let shared: Frame = from_bytes(&input).unwrap();
+        assert_eq!(frame, shared);
+        let body = input.as_ptr_range();
+        assert!(body.contains(&shared.body.as_ptr()));
+
+        let copied: Frame = from_slice(&input).unwrap();
+        assert_eq!(frame, copied);
+        assert!(!body.contains(&copied.body.as_ptr()));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_source_restored_after_panic() {
+        use std::panic::{catch_unwind, AssertUnwindSafe};
+
+        let input = Bytes::from_static(&[1, 2, 3]);
+        let panicked = catch_unwind(AssertUnwindSafe(|| {
+            super::with_source(&input, || panic!("decode failed"))
+        }));
+        assert!(panicked.is_err());
+        assert_eq!(None, super::shared_slice(&input));
+    }
+}
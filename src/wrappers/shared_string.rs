@@ -0,0 +1,164 @@
+use alloc::{string::String, sync::Arc};
+#[cfg(feature = "std")]
+use core::cell::RefCell;
+use core::{fmt, ops::Deref};
+
+use serde::{de::Visitor, Deserialize, Deserializer, Serialize, Serializer};
+
+/// Newtype name through which [`SharedString`] asks the deserializer for an
+/// interned string.
+pub(crate) const SHARED_STRING_TOKEN: &str = "$polywrap_msgpack::SharedString";
+
+#[cfg(feature = "std")]
+thread_local! {
+    /// The interned string the deserializer is handing to the visitor that
+    /// is currently running, since a visitor can only be given a `&str`.
+    static INTERNED: RefCell<Option<Arc<str>>> = const { RefCell::new(None) };
+}
+
+/// Passes `interned` to the [`SharedString`] visitor run by `visit`.
+#[cfg(feature = "std")]
+pub(crate) fn with_interned<T>(
+    interned: Arc<str>,
+    visit: impl FnOnce(&str) -> T,
+) -> T {
+    /// Puts the outer string back once `visit` returns or unwinds.
+    struct Restore(Option<Arc<str>>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            let outer = self.0.take();
+            INTERNED.with(|slot| *slot.borrow_mut() = outer);
+        }
+    }
+
+    let _restore =
+        Restore(INTERNED.with(|slot| slot.replace(Some(interned.clone()))));
+    visit(&interned)
+}
+
+/// Without thread locals there's no way to hand `interned` to the visitor,
+/// which allocates its own copy instead.
+#[cfg(not(feature = "std"))]
+pub(crate) fn with_interned<T>(
+    interned: Arc<str>,
+    visit: impl FnOnce(&str) -> T,
+) -> T {
+    visit(&interned)
+}
+
+/// Takes the interned string handed over by [`with_interned`], if it's `v`.
+#[cfg(feature = "std")]
+fn take_interned(v: &str) -> Option<Arc<str>> {
+    INTERNED
+        .with(|slot| slot.borrow_mut().take())
+        .filter(|interned| &**interned == v)
+}
+
+#[cfg(not(feature = "std"))]
+fn take_interned(_v: &str) -> Option<Arc<str>> {
+    None
+}
+
+/// A reference-counted string.
+///
+/// Decoded with [`DeserializerConfig::intern_strings`] enabled, identical
+/// strings share a single allocation. Otherwise it decodes like a `String`.
+///
+/// [`DeserializerConfig::intern_strings`]:
+/// crate::DeserializerConfig::intern_strings
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SharedString(pub Arc<str>);
+
+impl Deref for SharedString {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for SharedString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl From<&str> for SharedString {
+    fn from(value: &str) -> Self {
+        SharedString(value.into())
+    }
+}
+
+impl From<String> for SharedString {
+    fn from(value: String) -> Self {
+        SharedString(value.into())
+    }
+}
+
+impl Serialize for SharedString {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+struct SharedStringVisitor;
+
+impl<'de> Visitor<'de> for SharedStringVisitor {
+    type Value = SharedString;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a string")
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+    where
+        E: serde::de::Error,
+    {
+        Ok(SharedString(take_interned(v).unwrap_or_else(|| v.into())))
+    }
+
+    fn visit_newtype_struct<D>(
+        self,
+        deserializer: D,
+    ) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for SharedString {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_newtype_struct(
+            SHARED_STRING_TOKEN,
+            SharedStringVisitor,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_interned_cleared_after_panic() {
+        use std::{
+            panic::{catch_unwind, AssertUnwindSafe},
+            sync::Arc,
+        };
+
+        let interned: Arc<str> = Arc::from("foo");
+        let panicked = catch_unwind(AssertUnwindSafe(|| {
+            super::with_interned(interned.clone(), |_| panic!("visit failed"))
+        }));
+        assert!(panicked.is_err());
+        assert_eq!(None, super::take_interned("foo"));
+    }
+}
@@ -0,0 +1,327 @@
+use alloc::{format, string::String, vec::Vec};
+use core::fmt;
+#[cfg(feature = "std")]
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{
+    de::{self, Visitor},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+use serde_bytes::ByteBuf;
+
+use crate::value::EXT_TOKEN;
+
+/// Ext type of the msgpack timestamp extension.
+pub const TIMESTAMP_EXT_TYPE: i8 = -1;
+
+const NANOS_PER_SEC: u32 = 1_000_000_000;
+
+/// A point in time encoded as the msgpack timestamp ext (type -1), which
+/// other msgpack libraries decode as their native time type.
+///
+/// The smallest of the spec's three forms that holds the value is written:
+/// 32-bit seconds, 34-bit seconds with 30-bit nanoseconds, or 64-bit signed
+/// seconds with 32-bit nanoseconds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Timestamp {
+    seconds: i64,
+    nanoseconds: u32,
+}
+
+impl Timestamp {
+    /// Returns `None` if `nanoseconds` is a second or more.
+    pub fn new(seconds: i64, nanoseconds: u32) -> Option<Self> {
+        (nanoseconds < NANOS_PER_SEC).then_some(Self {
+            seconds,
+            nanoseconds,
+        })
+    }
+
+    /// Seconds since the Unix epoch, negative before it.
+    pub fn seconds(&self) -> i64 {
+        self.seconds
+    }
+
+    /// Nanoseconds within the second, always counted forward in time.
+    pub fn nanoseconds(&self) -> u32 {
+        self.nanoseconds
+    }
+
+    /// The ext payload, in the smallest form that holds the value.
+    pub(crate) fn to_ext_data(self) -> Vec<u8> {
+        if self.seconds >> 34 == 0 {
+            let packed = (self.nanoseconds as u64) << 34 | self.seconds as u64;
+            if packed >> 32 == 0 {
+                return (packed as u32).to_be_bytes().to_vec();
+            }
+            return packed.to_be_bytes().to_vec();
+        }
+
+        let mut data = Vec::with_capacity(12);
+        data.extend(self.nanoseconds.to_be_bytes());
+        data.extend(self.seconds.to_be_bytes());
+        data
+    }
+
+    pub(crate) fn from_ext_data(data: &[u8]) -> Result<Self, String> {
+        let (seconds, nanoseconds) = match data.len() {
+            4 => (u32::from_be_bytes(data.try_into().unwrap()) as i64, 0),
+            8 => {
+                let packed = u64::from_be_bytes(data.try_into().unwrap());
+                ((packed & ((1 << 34) - 1)) as i64, (packed >> 34) as u32)
+            }
+            12 => (
+                i64::from_be_bytes(data[4..].try_into().unwrap()),
+                u32::from_be_bytes(data[..4].try_into().unwrap()),
+            ),
+            len => {
+                return Err(format!(
+                    "Invalid timestamp ext of {len} bytes, expected 4, 8 or 12"
+                ))
+            }
+        };
+        Self::new(seconds, nanoseconds).ok_or_else(|| {
+            format!("Invalid timestamp with {nanoseconds} nanoseconds")
+        })
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<SystemTime> for Timestamp {
+    /// Times too far from the epoch for 64-bit seconds saturate.
+    fn from(time: SystemTime) -> Self {
+        match time.duration_since(UNIX_EPOCH) {
+            Ok(after) => Self {
+                seconds: i64::try_from(after.as_secs()).unwrap_or(i64::MAX),
+                nanoseconds: after.subsec_nanos(),
+            },
+            Err(e) => {
+                let before = e.duration();
+                let seconds =
+                    i64::try_from(before.as_secs()).unwrap_or(i64::MAX);
+                match before.subsec_nanos() {
+                    0 => Self {
+                        seconds: -seconds,
+                        nanoseconds: 0,
+                    },
+                    nanos => Self {
+                        seconds: -seconds - 1,
+                        nanoseconds: NANOS_PER_SEC - nanos,
+                    },
+                }
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl TryFrom<Timestamp> for SystemTime {
+    type Error = String;
+
+    fn try_from(timestamp: Timestamp) -> Result<Self, Self::Error> {
+        let seconds = Duration::from_secs(timestamp.seconds.unsigned_abs());
+        let time = if timestamp.seconds >= 0 {
+            UNIX_EPOCH.checked_add(seconds)
+        } else {
+            UNIX_EPOCH.checked_sub(seconds)
+        };
+        time.and_then(|time| {
+            time.checked_add(Duration::from_nanos(timestamp.nanoseconds as u64))
+        })
+        .ok_or_else(|| format!("{timestamp:?} is out of range for SystemTime"))
+    }
+}
+
+impl Serialize for Timestamp {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let data = self.to_ext_data();
+        serializer.serialize_newtype_struct(
+            EXT_TOKEN,
+            &(TIMESTAMP_EXT_TYPE, serde_bytes::Bytes::new(&data)),
+        )
+    }
+}
+
+struct TimestampVisitor;
+
+impl<'de> Visitor<'de> for TimestampVisitor {
+    type Value = Timestamp;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a timestamp ext")
+    }
+
+    fn visit_newtype_struct<D>(
+        self,
+        deserializer: D,
+    ) -> Result<Timestamp, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let (ext_type, data) = <(i8, ByteBuf)>::deserialize(deserializer)?;
+        if ext_type != TIMESTAMP_EXT_TYPE {
+            return Err(de::Error::custom(format!(
+                "Expected timestamp ext type -1, found {ext_type}"
+            )));
+        }
+        Timestamp::from_ext_data(&data).map_err(de::Error::custom)
+    }
+}
+
+impl<'de> Deserialize<'de> for Timestamp {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_newtype_struct(EXT_TOKEN, TimestampVisitor)
+    }
+}
+
+/// `#[serde(with = "...")]` helpers encoding a [`SystemTime`] as a
+/// timestamp ext.
+#[cfg(feature = "std")]
+pub mod system_time {
+    use std::time::SystemTime;
+
+    use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::Timestamp;
+
+    pub fn serialize<S>(time: &SystemTime, s: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        Timestamp::from(*time).serialize(s)
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<SystemTime, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let timestamp = Timestamp::deserialize(deserializer)?;
+        SystemTime::try_from(timestamp).map_err(de::Error::custom)
+    }
+}
+
+/// `#[serde(with = "...")]` helpers encoding a `chrono::DateTime<Utc>` as
+/// a timestamp ext.
+#[cfg(feature = "chrono")]
+pub mod chrono_utc {
+    use alloc::format;
+
+    use chrono::{DateTime, Utc};
+    use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::Timestamp;
+
+    pub fn serialize<S>(time: &DateTime<Utc>, s: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        // Leap seconds, which chrono counts as extra nanoseconds, have no
+        // representation in the ext.
+        Timestamp {
+            seconds: time.timestamp(),
+            nanoseconds: time
+                .timestamp_subsec_nanos()
+                .min(super::NANOS_PER_SEC - 1),
+        }
+        .serialize(s)
+    }
+
+    pub fn deserialize<'de, D>(
+        deserializer: D,
+    ) -> Result<DateTime<Utc>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let timestamp = Timestamp::deserialize(deserializer)?;
+        DateTime::from_timestamp(timestamp.seconds, timestamp.nanoseconds)
+            .ok_or_else(|| {
+                de::Error::custom(format!(
+                    "{timestamp:?} is out of range for DateTime<Utc>"
+                ))
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Timestamp;
+    use crate::{from_slice, to_vec};
+
+    #[test]
+    fn test_timestamp_forms() {
+        // FixExt4, FixExt8 and Ext8 with 12 bytes
+        let cases = [
+            (Timestamp::new(60, 0).unwrap(), &[0xd6, 0xff][..]),
+            (Timestamp::new(1, 500).unwrap(), &[0xd7, 0xff]),
+            (Timestamp::new(1 << 34, 0).unwrap(), &[0xc7, 12, 0xff]),
+            (Timestamp::new(-1, 999_999_999).unwrap(), &[0xc7, 12, 0xff]),
+        ];
+        for (timestamp, header) in cases {
+            let bytes = to_vec(&timestamp).unwrap();
+            assert_eq!(header, &bytes[..header.len()]);
+            assert_eq!(timestamp, from_slice(&bytes).unwrap());
+        }
+        assert_eq!(
+            vec![0xd6, 0xff, 0, 0, 0, 60],
+            to_vec(&Timestamp::new(60, 0).unwrap()).unwrap()
+        );
+
+        // The 32-bit form written as an Ext8 still decodes
+        let timestamp: Timestamp =
+            from_slice(&[0xc7, 4, 0xff, 0, 0, 0, 60]).unwrap();
+        assert_eq!((60, 0), (timestamp.seconds(), timestamp.nanoseconds()));
+        assert!(from_slice::<Timestamp>(&[0xd6, 0x05, 0, 0, 0, 60]).is_err());
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn test_chrono_field() {
+        use chrono::{DateTime, Utc};
+        use serde_derive::{Deserialize, Serialize};
+
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Event {
+            #[serde(with = "super::chrono_utc")]
+            at: DateTime<Utc>,
+        }
+
+        let event = Event {
+            at: DateTime::from_timestamp(-86_400, 5).unwrap(),
+        };
+        let bytes = to_vec(&event).unwrap();
+        assert_eq!(event, from_slice(&bytes).unwrap());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_system_time_field() {
+        use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+        use serde_derive::{Deserialize, Serialize};
+
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Event {
+            #[serde(with = "super::system_time")]
+            at: SystemTime,
+        }
+
+        let events = [
+            Event {
+                at: UNIX_EPOCH + Duration::new(1_700_000_000, 123),
+            },
+            Event {
+                at: UNIX_EPOCH - Duration::new(10, 250),
+            },
+        ];
+        for event in events {
+            let bytes = to_vec(&event).unwrap();
+            assert_eq!(event, from_slice(&bytes).unwrap());
+        }
+    }
+}